@@ -0,0 +1,225 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::fmt;
+use std::time::Duration;
+
+/// Why a transcription request failed, with a short explanation meant for end users.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The API key was missing, malformed or revoked; holds the variable it came from.
+    Unauthorized {
+        key_var: &'static str,
+    },
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    PayloadTooLarge,
+    /// Any other 4xx, with the message the service returned.
+    InvalidRequest(String),
+    Server(StatusCode),
+    /// The request never got a response (DNS, TLS, proxy, timeout).
+    Network(String),
+}
+
+impl ApiError {
+    /// Classifies an error response; `message` is the service's own explanation,
+    /// already extracted from the body.
+    pub fn from_response(
+        status: StatusCode,
+        headers: &HeaderMap,
+        message: String,
+        key_var: &'static str,
+    ) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized { key_var },
+            StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+                retry_after: retry_after(headers).or_else(|| suggested_wait(&message)),
+            },
+            StatusCode::PAYLOAD_TOO_LARGE => ApiError::PayloadTooLarge,
+            status if status.is_server_error() => ApiError::Server(status),
+            _ => ApiError::InvalidRequest(message),
+        }
+    }
+
+    /// Rate limits, server errors and network failures may succeed on a later attempt.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ApiError::RateLimited { .. } | ApiError::Server(_) | ApiError::Network(_)
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Unauthorized { key_var } => {
+                write!(f, "API key rejected — check {}", key_var)
+            }
+            ApiError::RateLimited {
+                retry_after: Some(wait),
+            } => write!(f, "Rate limit reached — try again in {}s", wait.as_secs()),
+            ApiError::RateLimited { retry_after: None } => {
+                write!(f, "Rate limit reached — wait a moment and try again")
+            }
+            ApiError::PayloadTooLarge => write!(
+                f,
+                "Recording is too large to upload — keep it shorter or set upload_format = \"opus\""
+            ),
+            ApiError::InvalidRequest(message) => write!(f, "Request rejected: {}", message),
+            ApiError::Server(status) => write!(
+                f,
+                "The service is having problems ({}) — try again later",
+                status
+            ),
+            ApiError::Network(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Reads a `Retry-After` header given in seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Reads the wait OpenAI suggests in rate limit messages, e.g.
+/// "Please try again in 20s", "in 1m30s" or "in 850ms".
+fn suggested_wait(message: &str) -> Option<Duration> {
+    let start = message.find("try again in ")? + "try again in ".len();
+    let hint = message[start..].split_whitespace().next()?;
+    let hint = hint.trim_end_matches(['.', ',']);
+
+    let mut total = 0.0;
+    let mut rest = hint;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let value: f64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        total += value
+            * match &rest[..unit_len] {
+                "ms" => 0.001,
+                "s" => 1.0,
+                "m" => 60.0,
+                "h" => 3600.0,
+                _ => return None,
+            };
+        rest = &rest[unit_len..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn classify(status: u16, message: &str) -> ApiError {
+        ApiError::from_response(
+            StatusCode::from_u16(status).unwrap(),
+            &HeaderMap::new(),
+            message.to_string(),
+            "OPENAI_API_KEY",
+        )
+    }
+
+    #[test]
+    fn test_status_codes_map_to_friendly_messages() {
+        let error = classify(401, "Incorrect API key provided: sk-abc***");
+        assert!(matches!(error, ApiError::Unauthorized { .. }));
+        assert_eq!(error.to_string(), "API key rejected — check OPENAI_API_KEY");
+
+        let error = classify(413, "Maximum content size limit (26214400) exceeded");
+        assert!(matches!(error, ApiError::PayloadTooLarge));
+        assert!(error.to_string().contains("too large"));
+
+        let error = classify(
+            400,
+            "Audio file is too short. Minimum audio length is 0.1 seconds.",
+        );
+        assert_eq!(
+            error.to_string(),
+            "Request rejected: Audio file is too short. Minimum audio length is 0.1 seconds."
+        );
+
+        let error = classify(503, "upstream connect error");
+        assert!(matches!(
+            error,
+            ApiError::Server(StatusCode::SERVICE_UNAVAILABLE)
+        ));
+        assert!(error.to_string().contains("try again later"));
+    }
+
+    #[test]
+    fn test_rate_limit_reads_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("20"));
+        let error = ApiError::from_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers,
+            "Rate limit reached for requests".to_string(),
+            "OPENAI_API_KEY",
+        );
+        assert!(matches!(
+            error,
+            ApiError::RateLimited {
+                retry_after: Some(wait)
+            } if wait == Duration::from_secs(20)
+        ));
+        assert_eq!(error.to_string(), "Rate limit reached — try again in 20s");
+
+        let error = classify(429, "Rate limit reached for requests");
+        assert!(matches!(error, ApiError::RateLimited { retry_after: None }));
+    }
+
+    #[test]
+    fn test_rate_limit_falls_back_to_message_hint() {
+        let error = classify(
+            429,
+            "Rate limit reached for whisper-1 in organization org-abc on requests per min (RPM): Limit 3, Used 3, Requested 1. Please try again in 20s. Visit https://platform.openai.com/account/rate-limits to learn more.",
+        );
+        assert!(matches!(
+            error,
+            ApiError::RateLimited {
+                retry_after: Some(wait)
+            } if wait == Duration::from_secs(20)
+        ));
+
+        assert_eq!(
+            suggested_wait("Please try again in 1m30s."),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            suggested_wait("Please try again in 850ms."),
+            Some(Duration::from_millis(850))
+        );
+        assert_eq!(
+            suggested_wait("Please try again in 1.5s"),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(suggested_wait("Please try again later."), None);
+    }
+
+    #[test]
+    fn test_only_transient_errors_are_retryable() {
+        assert!(classify(429, "").is_retryable());
+        assert!(classify(500, "").is_retryable());
+        assert!(ApiError::Network("connection refused".to_string()).is_retryable());
+        assert!(!classify(401, "").is_retryable());
+        assert!(!classify(400, "").is_retryable());
+        assert!(!classify(413, "").is_retryable());
+    }
+}
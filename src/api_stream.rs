@@ -0,0 +1,355 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+use crate::config::AppConfig;
+use crate::encode;
+use crate::keys;
+
+const REALTIME_URL: &str = "wss://api.openai.com/v1/realtime?intent=transcription";
+
+/// The realtime API takes 24 kHz mono 16-bit PCM.
+const STREAM_SAMPLE_RATE: u32 = 24000;
+
+/// How long to wait for the final transcript after the key is released.
+const FINISH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Opens realtime transcription sessions against OpenAI.
+pub struct StreamingClient {
+    api_key: String,
+    url: String,
+}
+
+/// Creates the streaming client when `streaming = true`, or `None` if streaming is off or
+/// the backend has no realtime API.
+pub fn create_streamer(config: &AppConfig) -> Result<Option<StreamingClient>> {
+    if !config.streaming {
+        return Ok(None);
+    }
+    if config.backend != "openai" {
+        warn!(
+            "⚠️ streaming only works with the openai backend, '{}' uploads after release",
+            config.backend
+        );
+        return Ok(None);
+    }
+    let api_keys =
+        keys::resolve_api_keys("OPENAI_API_KEY", config, |name| std::env::var(name).ok())?;
+    Ok(Some(StreamingClient {
+        api_key: api_keys[0].clone(),
+        url: REALTIME_URL.to_string(),
+    }))
+}
+
+impl StreamingClient {
+    /// Connects in the background; audio pushed before the socket is up is queued.
+    pub fn start(&self, spec: hound::WavSpec, config: &AppConfig) -> StreamingSession {
+        let (audio_tx, audio_rx) = mpsc::unbounded_channel();
+        let (result_tx, result_rx) = oneshot::channel();
+        let url = self.url.clone();
+        let api_key = self.api_key.clone();
+        let update = session_update(config);
+        let task = tokio::spawn(async move {
+            let result = run_session(&url, &api_key, update, audio_rx).await;
+            let _ = result_tx.send(result);
+        });
+        StreamingSession {
+            audio: Some(audio_tx),
+            result: Some(result_rx),
+            spec,
+            task,
+        }
+    }
+}
+
+/// A realtime session fed while the key is held. Dropping it closes the socket.
+pub struct StreamingSession {
+    audio: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    result: Option<oneshot::Receiver<Result<String>>>,
+    spec: hound::WavSpec,
+    task: JoinHandle<()>,
+}
+
+impl StreamingSession {
+    /// Queues captured (interleaved, capture-rate) samples for sending.
+    pub fn push(&self, samples: &[i16]) {
+        if samples.is_empty() {
+            return;
+        }
+        if let Some(audio) = &self.audio {
+            let _ = audio.send(to_stream_pcm(samples, self.spec));
+        }
+    }
+
+    /// Commits the audio and waits for the final transcript.
+    pub async fn finish(mut self) -> Result<String> {
+        // Closing the channel tells the session task to commit the buffer
+        self.audio.take();
+        let result = self
+            .result
+            .take()
+            .context("Streaming session already finished")?;
+        match tokio::time::timeout(FINISH_TIMEOUT, result).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => anyhow::bail!("Streaming session ended without a transcript"),
+            Err(_) => anyhow::bail!(
+                "No transcript from the realtime API after {}s",
+                FINISH_TIMEOUT.as_secs()
+            ),
+        }
+    }
+}
+
+impl Drop for StreamingSession {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum ServerEvent {
+    #[serde(rename = "conversation.item.input_audio_transcription.delta")]
+    Delta { delta: String },
+    #[serde(rename = "conversation.item.input_audio_transcription.completed")]
+    Completed { transcript: String },
+    #[serde(rename = "error")]
+    Error { error: ServerError },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct ServerError {
+    message: String,
+}
+
+fn session_update(config: &AppConfig) -> String {
+    let mut transcription = serde_json::json!({ "model": config.streaming_model });
+    if let Some(lang) = &config.language {
+        transcription["language"] = lang.clone().into();
+    }
+    if let Some(prompt) = config.whisper_prompt() {
+        transcription["prompt"] = prompt.into();
+    }
+    serde_json::json!({
+        "type": "transcription_session.update",
+        "session": {
+            "input_audio_format": "pcm16",
+            "input_audio_transcription": transcription,
+            // The key release decides when the utterance ends, not server-side VAD
+            "turn_detection": null,
+        },
+    })
+    .to_string()
+}
+
+fn append_event(pcm: &[u8]) -> String {
+    serde_json::json!({
+        "type": "input_audio_buffer.append",
+        "audio": base64::engine::general_purpose::STANDARD.encode(pcm),
+    })
+    .to_string()
+}
+
+/// Converts captured samples to the 24 kHz mono little-endian PCM the API expects.
+fn to_stream_pcm(samples: &[i16], spec: hound::WavSpec) -> Vec<u8> {
+    let mono = encode::downmix_to_mono(samples, spec.channels);
+    encode::resample_linear(&mono, spec.sample_rate, STREAM_SAMPLE_RATE)
+        .iter()
+        .flat_map(|s| s.to_le_bytes())
+        .collect()
+}
+
+async fn run_session(
+    url: &str,
+    api_key: &str,
+    session_update: String,
+    mut audio: mpsc::UnboundedReceiver<Vec<u8>>,
+) -> Result<String> {
+    let mut request = url.into_client_request()?;
+    let headers = request.headers_mut();
+    headers.insert(
+        "Authorization",
+        HeaderValue::from_str(&format!("Bearer {}", api_key))?,
+    );
+    headers.insert("OpenAI-Beta", HeaderValue::from_static("realtime=v1"));
+    let (socket, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .context("Failed to connect to the realtime API")?;
+    let (mut sink, mut events) = socket.split();
+    sink.send(Message::Text(session_update)).await?;
+
+    let mut committed = false;
+    let mut partial = String::new();
+    // Partial transcripts are a live preview, kept out of redirected output and the journal
+    let preview = std::io::stdout().is_terminal();
+    loop {
+        tokio::select! {
+            chunk = audio.recv(), if !committed => match chunk {
+                Some(pcm) => sink.send(Message::Text(append_event(&pcm))).await?,
+                None => {
+                    let commit = serde_json::json!({ "type": "input_audio_buffer.commit" });
+                    sink.send(Message::Text(commit.to_string())).await?;
+                    committed = true;
+                }
+            },
+            message = events.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => {
+                        anyhow::bail!("Realtime API closed the connection")
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e).context("Realtime API connection failed"),
+                };
+                match serde_json::from_str::<ServerEvent>(&text) {
+                    Ok(ServerEvent::Delta { delta }) => {
+                        partial.push_str(&delta);
+                        if preview {
+                            print!("\r💬 {}", partial);
+                            let _ = std::io::stdout().flush();
+                        }
+                    }
+                    Ok(ServerEvent::Completed { transcript }) => {
+                        if preview && !partial.is_empty() {
+                            println!();
+                        }
+                        let _ = sink.send(Message::Close(None)).await;
+                        return Ok(transcript.trim().to_string());
+                    }
+                    Ok(ServerEvent::Error { error }) => {
+                        anyhow::bail!("Realtime API error: {}", error.message)
+                    }
+                    Ok(ServerEvent::Other) | Err(_) => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn spec(channels: u16, sample_rate: u32) -> hound::WavSpec {
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn test_session_update() {
+        let config = AppConfig {
+            language: Some("de".to_string()),
+            ..Default::default()
+        };
+        let update: serde_json::Value = serde_json::from_str(&session_update(&config)).unwrap();
+        assert_eq!(update["type"], "transcription_session.update");
+        assert_eq!(update["session"]["input_audio_format"], "pcm16");
+        let transcription = &update["session"]["input_audio_transcription"];
+        assert_eq!(transcription["model"], config.streaming_model.as_str());
+        assert_eq!(transcription["language"], "de");
+        assert!(update["session"]["turn_detection"].is_null());
+    }
+
+    #[test]
+    fn test_audio_is_converted_to_24khz_mono() {
+        // 10 ms of 48 kHz stereo becomes 10 ms of 24 kHz mono
+        let samples = vec![1000i16; 960];
+        let pcm = to_stream_pcm(&samples, spec(2, 48000));
+        assert_eq!(pcm.len(), 240 * 2);
+        assert_eq!(i16::from_le_bytes([pcm[0], pcm[1]]), 1000);
+
+        let event: serde_json::Value = serde_json::from_str(&append_event(&pcm)).unwrap();
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(event["audio"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(decoded, pcm);
+    }
+
+    #[test]
+    fn test_parse_server_events() {
+        let delta = r#"{"type": "conversation.item.input_audio_transcription.delta", "item_id": "item_1", "content_index": 0, "delta": "Hel"}"#;
+        assert!(matches!(
+            serde_json::from_str(delta).unwrap(),
+            ServerEvent::Delta { delta } if delta == "Hel"
+        ));
+        let done = r#"{"type": "conversation.item.input_audio_transcription.completed", "item_id": "item_1", "content_index": 0, "transcript": "Hello there."}"#;
+        assert!(matches!(
+            serde_json::from_str(done).unwrap(),
+            ServerEvent::Completed { transcript } if transcript == "Hello there."
+        ));
+        let other = r#"{"type": "input_audio_buffer.committed", "item_id": "item_1"}"#;
+        assert!(matches!(
+            serde_json::from_str(other).unwrap(),
+            ServerEvent::Other
+        ));
+    }
+
+    /// Plays the server side of one session: expects the session update, audio, then a commit.
+    async fn fake_realtime_server(listener: TcpListener) -> usize {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        let mut appended = 0;
+        while let Some(Ok(Message::Text(text))) = socket.next().await {
+            let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+            match event["type"].as_str().unwrap() {
+                "input_audio_buffer.append" => appended += 1,
+                "input_audio_buffer.commit" => {
+                    let delta = r#"{"type": "conversation.item.input_audio_transcription.delta", "delta": "streamed"}"#;
+                    let done = r#"{"type": "conversation.item.input_audio_transcription.completed", "transcript": " streamed text "}"#;
+                    socket.send(Message::Text(delta.to_string())).await.unwrap();
+                    socket.send(Message::Text(done.to_string())).await.unwrap();
+                }
+                _ => {}
+            }
+        }
+        appended
+    }
+
+    #[tokio::test]
+    async fn test_session_streams_audio_and_returns_transcript() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(fake_realtime_server(listener));
+
+        let client = StreamingClient {
+            api_key: "sk-test".to_string(),
+            url,
+        };
+        let session = client.start(spec(1, 16000), &AppConfig::default());
+        session.push(&[0; 1600]);
+        session.push(&[0; 1600]);
+        assert_eq!(session.finish().await.unwrap(), "streamed text");
+        assert_eq!(server.await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server_fails_finish() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = StreamingClient {
+            api_key: "sk-test".to_string(),
+            url,
+        };
+        let session = client.start(spec(1, 16000), &AppConfig::default());
+        let error = session.finish().await.unwrap_err();
+        assert!(error.to_string().contains("Failed to connect"));
+    }
+}
@@ -1,7 +1,213 @@
 use anyhow::{Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use ringbuf::traits::Producer;
+use ringbuf::HeapProd;
+use std::fs::File;
+use std::io::BufWriter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+
+/// A finished recording, either still in memory or already written to a WAV file.
+pub enum RecordedAudio {
+    Memory {
+        samples: Vec<i16>,
+        spec: hound::WavSpec,
+    },
+    File(PathBuf),
+}
+
+impl RecordedAudio {
+    /// Playback length, read from the WAV header for file recordings.
+    pub fn duration(&self) -> Duration {
+        match self {
+            RecordedAudio::Memory { samples, spec } => {
+                crate::encode::audio_duration(samples.len(), *spec)
+            }
+            RecordedAudio::File(path) => hound::WavReader::open(path)
+                .map(|reader| crate::encode::audio_duration(reader.len() as usize, reader.spec()))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Returns a unique, timestamped WAV path in the system temp directory.
+pub fn temp_wav_path(tag: &str) -> PathBuf {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    std::env::temp_dir().join(format!("voice-ptt-{}{}.wav", ts, tag))
+}
+
+/// Temp recordings untouched for this long are left over from a crash; a running instance
+/// keeps writing to its own or deletes them within minutes.
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Deletes the temp WAV files an earlier run left behind when it crashed; returns how many.
+pub fn sweep_temp_files() -> usize {
+    sweep_stale_recordings(&std::env::temp_dir(), STALE_TEMP_AGE)
+}
+
+/// Deletes the files in `dir` named like [`temp_wav_path`] that weren't modified for `max_age`.
+fn sweep_stale_recordings(dir: &Path, max_age: Duration) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.strip_prefix("voice-ptt-")
+                .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+                && name.ends_with(".wav")
+        })
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age >= max_age)
+        })
+        .filter(|entry| std::fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+struct SpillFile {
+    writer: hound::WavWriter<BufWriter<File>>,
+    path: PathBuf,
+}
+
+/// Accumulates captured samples on the main loop side of the capture ring buffer.
+///
+/// Short recordings stay in memory. Once `spill_threshold` samples have been captured,
+/// everything is moved into an incremental WAV file and only a short tail is kept in
+/// memory for the level meter.
+pub struct CaptureBuffer {
+    samples: Vec<i16>,
+    spec: hound::WavSpec,
+    spill_threshold: usize,
+    spill: Option<SpillFile>,
+    spill_failed: bool,
+}
+
+impl CaptureBuffer {
+    /// `spill_threshold` is a sample count; 0 keeps every recording in memory.
+    pub fn new(spec: hound::WavSpec, spill_threshold: usize) -> Self {
+        Self {
+            samples: Vec::new(),
+            spec,
+            spill_threshold,
+            spill: None,
+            spill_failed: false,
+        }
+    }
+
+    pub fn spec(&self) -> hound::WavSpec {
+        self.spec
+    }
+
+    /// Number of samples that make up one second of interleaved audio.
+    pub fn samples_per_sec(&self) -> usize {
+        self.spec.sample_rate as usize * self.spec.channels as usize
+    }
+
+    /// Drops any previous recording, including an unfinished spill file.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.spill_failed = false;
+        if let Some(spill) = self.spill.take() {
+            drop(spill.writer);
+            let _ = std::fs::remove_file(&spill.path);
+        }
+    }
+
+    /// Appends captured samples, spilling to disk once the threshold is crossed.
+    pub fn extend<I: IntoIterator<Item = i16>>(&mut self, data: I) {
+        let tail = self.samples_per_sec();
+        if let Some(spill) = &mut self.spill {
+            for sample in data {
+                if let Err(e) = spill.writer.write_sample(sample) {
+                    error!("❌ Failed to write spilled audio: {}", e);
+                    break;
+                }
+                self.samples.push(sample);
+            }
+            if self.samples.len() > tail * 2 {
+                self.samples.drain(..self.samples.len() - tail);
+            }
+            return;
+        }
+
+        self.samples.extend(data);
+        if self.spill_threshold > 0
+            && !self.spill_failed
+            && self.samples.len() >= self.spill_threshold
+        {
+            if let Err(e) = self.start_spill() {
+                warn!(
+                    "⚠️ Failed to spill recording to disk, keeping it in memory: {}",
+                    e
+                );
+                self.spill_failed = true;
+            }
+        }
+    }
+
+    fn start_spill(&mut self) -> Result<()> {
+        let path = temp_wav_path("-spill");
+        let mut writer = hound::WavWriter::create(&path, self.spec)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        for &sample in &self.samples {
+            writer.write_sample(sample)?;
+        }
+        let keep = self.samples_per_sec();
+        let len = self.samples.len();
+        self.samples.drain(..len.saturating_sub(keep));
+        self.spill = Some(SpillFile { writer, path });
+        Ok(())
+    }
+
+    /// The most recently captured samples (at most `max` of them).
+    pub fn recent(&self, max: usize) -> &[i16] {
+        &self.samples[self.samples.len().saturating_sub(max)..]
+    }
+
+    /// Total number of samples captured so far (in memory or on disk).
+    pub fn len(&self) -> usize {
+        match &self.spill {
+            Some(spill) => spill.writer.len() as usize,
+            None => self.samples.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Hands the finished recording over without copying, leaving the buffer empty.
+    pub fn take(&mut self) -> Result<RecordedAudio> {
+        match self.spill.take() {
+            Some(spill) => {
+                self.samples.clear();
+                spill
+                    .writer
+                    .finalize()
+                    .context("Failed to finalize spilled recording")?;
+                Ok(RecordedAudio::File(spill.path))
+            }
+            None => Ok(RecordedAudio::Memory {
+                samples: std::mem::take(&mut self.samples),
+                spec: self.spec,
+            }),
+        }
+    }
+}
 
 pub struct AudioSystem {
     pub device: cpal::Device,
@@ -9,16 +215,49 @@ pub struct AudioSystem {
 }
 
 impl AudioSystem {
-    /// Initializes the default audio input device and its configuration.
-    pub fn new() -> Result<Self> {
+    /// Initializes an audio input device: the default one, or the first whose name contains
+    /// `device_pattern` (case-insensitive).
+    ///
+    /// Picks the supported configuration closest to `preferred_sample_rate` mono 16-bit, so
+    /// no resampling is needed later. A rate of 0 keeps the device's default configuration.
+    pub fn new(preferred_sample_rate: u32, device_pattern: Option<&str>) -> Result<Self> {
         let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .context("No input device found. Please check your microphone connection.")?;
+        let device = match device_pattern {
+            None => host
+                .default_input_device()
+                .context("No input device found. Please check your microphone connection.")?,
+            Some(pattern) => {
+                let mut devices: Vec<(String, cpal::Device)> = host
+                    .input_devices()
+                    .context("Failed to list input devices")?
+                    .filter_map(|device| Some((device.name().ok()?, device)))
+                    .collect();
+                let names: Vec<&str> = devices.iter().map(|(name, _)| name.as_str()).collect();
+                let index = find_device(&names, pattern).with_context(|| {
+                    format!(
+                        "No input device matching '{}' found (available: {})",
+                        pattern,
+                        names.join(", ")
+                    )
+                })?;
+                devices.swap_remove(index).1
+            }
+        };
 
-        let config = device
-            .default_input_config()
-            .context("Failed to get default input configuration")?;
+        let preferred = if preferred_sample_rate == 0 {
+            None
+        } else {
+            device
+                .supported_input_configs()
+                .ok()
+                .and_then(|ranges| choose_input_config(ranges, preferred_sample_rate))
+        };
+        let config = match preferred {
+            Some(config) => config,
+            None => device
+                .default_input_config()
+                .context("Failed to get default input configuration")?,
+        };
 
         Ok(Self { device, config })
     }
@@ -33,41 +272,55 @@ impl AudioSystem {
         }
     }
 
-    /// Builds an input stream that captures audio into the provided buffer when `is_recording` is true.
+    /// Builds an input stream that pushes samples into the ring buffer when `is_recording` is true.
+    ///
+    /// The callback never blocks: samples that don't fit into the ring are counted in
+    /// `dropped_samples`. Any stream error (e.g. the device being unplugged) sets `stream_dead`
+    /// so the caller can tear the stream down and reconnect.
     pub fn build_stream(
         &self,
-        audio_buffer: Arc<Mutex<Vec<i16>>>,
+        mut producer: HeapProd<i16>,
         is_recording: Arc<AtomicBool>,
+        dropped_samples: Arc<AtomicUsize>,
+        stream_dead: Arc<AtomicBool>,
     ) -> Result<cpal::Stream> {
-        let writer_buffer = audio_buffer;
         let reader_is_recording = is_recording;
+        let error_callback = move |err: cpal::StreamError| {
+            error!("❌ Audio stream error: {}", err);
+            stream_dead.store(true, Ordering::Relaxed);
+        };
 
         let stream = match self.config.sample_format() {
             cpal::SampleFormat::F32 => self.device.build_input_stream(
                 &self.config.clone().into(),
                 move |data: &[f32], _: &cpal::InputCallbackInfo| {
                     if reader_is_recording.load(Ordering::Relaxed) {
-                        if let Ok(mut buffer) = writer_buffer.lock() {
-                            for &sample in data {
-                                let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-                                buffer.push(sample);
+                        let mut lost = 0;
+                        for &sample in data {
+                            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                            if producer.try_push(sample).is_err() {
+                                lost += 1;
                             }
                         }
+                        if lost > 0 {
+                            dropped_samples.fetch_add(lost, Ordering::Relaxed);
+                        }
                     }
                 },
-                |err| eprintln!("Audio stream error: {}", err),
+                error_callback,
                 None,
             ),
             cpal::SampleFormat::I16 => self.device.build_input_stream(
                 &self.config.clone().into(),
                 move |data: &[i16], _: &cpal::InputCallbackInfo| {
                     if reader_is_recording.load(Ordering::Relaxed) {
-                        if let Ok(mut buffer) = writer_buffer.lock() {
-                            buffer.extend_from_slice(data);
+                        let pushed = producer.push_slice(data);
+                        if pushed < data.len() {
+                            dropped_samples.fetch_add(data.len() - pushed, Ordering::Relaxed);
                         }
                     }
                 },
-                |err| eprintln!("Audio stream error: {}", err),
+                error_callback,
                 None,
             ),
             _ => anyhow::bail!(
@@ -78,3 +331,465 @@ impl AudioSystem {
         Ok(stream)
     }
 }
+
+/// Returns the index of the first device name containing `pattern`, ignoring case.
+fn find_device(names: &[&str], pattern: &str) -> Option<usize> {
+    let pattern = pattern.to_lowercase();
+    names
+        .iter()
+        .position(|name| name.to_lowercase().contains(&pattern))
+}
+
+/// Picks the supported config closest to `preferred_rate`, preferring mono and I16 samples.
+///
+/// Only sample formats the capture callback can handle (I16 and F32) are considered.
+fn choose_input_config<I>(ranges: I, preferred_rate: u32) -> Option<cpal::SupportedStreamConfig>
+where
+    I: IntoIterator<Item = cpal::SupportedStreamConfigRange>,
+{
+    ranges
+        .into_iter()
+        .filter(|range| {
+            matches!(
+                range.sample_format(),
+                cpal::SampleFormat::I16 | cpal::SampleFormat::F32
+            )
+        })
+        .map(|range| {
+            let rate = preferred_rate.clamp(range.min_sample_rate().0, range.max_sample_rate().0);
+            let score = (
+                rate.abs_diff(preferred_rate),
+                range.channels(),
+                range.sample_format() != cpal::SampleFormat::I16,
+            );
+            (score, range.with_sample_rate(cpal::SampleRate(rate)))
+        })
+        .min_by_key(|(score, _)| *score)
+        .map(|(_, config)| config)
+}
+
+/// RMS level below which a stretch of audio is considered silent (about -46 dBFS).
+pub const SILENCE_RMS: f32 = 0.005;
+
+/// Peak level below which a whole recording is treated as a muted microphone (about -70 dBFS).
+/// Even a quiet room has a noise floor well above this; a hardware mute produces digital zeros.
+const MUTED_PEAK: f32 = 0.0003;
+
+/// Returns true if `samples` never rise above `threshold` (a normalized peak level).
+pub fn is_silent(samples: &[i16], threshold: f32) -> bool {
+    peak_level(samples) <= threshold
+}
+
+/// Returns true if a recording is digital silence, as produced by a muted microphone.
+pub fn is_muted(samples: &[i16]) -> bool {
+    is_silent(samples, MUTED_PEAK)
+}
+
+/// Computes the RMS level of a sample slice, normalized to 0.0..=1.0.
+pub fn rms_level(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = samples
+        .iter()
+        .map(|&s| {
+            let v = s as f64 / i16::MAX as f64;
+            v * v
+        })
+        .sum();
+    (sum / samples.len() as f64).sqrt() as f32
+}
+
+/// Returns the peak absolute sample value, normalized to 0.0..=1.0.
+pub fn peak_level(samples: &[i16]) -> f32 {
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+    (peak as f32 / i16::MAX as f32).min(1.0)
+}
+
+/// Converts a normalized level to dBFS, floored at -120 dB.
+pub fn to_dbfs(level: f32) -> f32 {
+    20.0 * level.max(1e-6).log10()
+}
+
+/// Applies a single-pole high-pass filter in place to remove rumble and DC offset.
+///
+/// Interleaved channels are filtered independently. A cutoff of 0 leaves the samples untouched.
+pub fn highpass_in_place(samples: &mut [i16], spec: hound::WavSpec, cutoff_hz: f32) {
+    if cutoff_hz <= 0.0 || spec.sample_rate == 0 {
+        return;
+    }
+    let channels = spec.channels.max(1) as usize;
+    let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz);
+    let dt = 1.0 / spec.sample_rate as f32;
+    let alpha = rc / (rc + dt);
+
+    let mut prev_in = vec![0.0f32; channels];
+    let mut prev_out = vec![0.0f32; channels];
+    // Start from the first frame so a DC offset doesn't produce an initial click
+    for (ch, &sample) in samples.iter().take(channels).enumerate() {
+        prev_in[ch] = sample as f32;
+    }
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let ch = i % channels;
+        let x = *sample as f32;
+        let y = alpha * (prev_out[ch] + x - prev_in[ch]);
+        prev_in[ch] = x;
+        prev_out[ch] = y;
+        *sample = y.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    }
+}
+
+/// Runs `highpass_in_place` over a WAV file and rewrites it.
+pub fn highpass_wav_file(path: &Path, cutoff_hz: f32) -> Result<()> {
+    if cutoff_hz <= 0.0 {
+        return Ok(());
+    }
+    let (mut samples, spec) = crate::encode::read_wav(path)?;
+    highpass_in_place(&mut samples, spec, cutoff_hz);
+    let bytes = crate::encode::encode_wav(&samples, spec)?;
+    std::fs::write(path, bytes).with_context(|| format!("Failed to rewrite {}", path.display()))
+}
+
+/// Splits interleaved `samples` into consecutive ranges of at most `max_len` samples,
+/// cutting at the quietest 20 ms in the last quarter of each chunk so words aren't split.
+pub fn split_at_silence(
+    samples: &[i16],
+    spec: hound::WavSpec,
+    max_len: usize,
+) -> Vec<Range<usize>> {
+    let channels = spec.channels.max(1) as usize;
+    let window = (spec.sample_rate as usize / 50).max(1) * channels;
+    let max_len = max_len.max(window * 4);
+    let max_len = max_len - max_len % channels;
+
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > max_len {
+        let end = start + max_len;
+        let mut cut = end;
+        let mut quietest = f32::MAX;
+        let mut pos = start + (max_len * 3 / 4) / channels * channels;
+        while pos + window <= end {
+            let level = rms_level(&samples[pos..pos + window]);
+            if level < quietest {
+                quietest = level;
+                cut = pos + window / 2 / channels * channels;
+            }
+            pos += window;
+        }
+        ranges.push(start..cut);
+        start = cut;
+    }
+    ranges.push(start..samples.len());
+    ranges
+}
+
+/// Absolute sample value treated as clipped (within ~0.2% of full scale).
+const CLIP_LEVEL: i16 = 32700;
+
+/// Returns the fraction (0.0..=1.0) of samples at or near full scale.
+pub fn clipping_ratio(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let clipped = samples
+        .iter()
+        .filter(|&&s| s >= CLIP_LEVEL || s <= -CLIP_LEVEL)
+        .count();
+    clipped as f32 / samples.len() as f32
+}
+
+/// Renders a one-line VU meter for the given RMS level on a -60..0 dBFS scale.
+pub fn format_level_meter(level: f32, silent: bool) -> String {
+    const WIDTH: usize = 20;
+    let db = to_dbfs(level);
+    let filled = (((db + 60.0) / 60.0).clamp(0.0, 1.0) * WIDTH as f32).round() as usize;
+    format!(
+        "🎚️ [{}{}] {:>4.0} dB{}",
+        "#".repeat(filled),
+        ".".repeat(WIDTH - filled),
+        db.max(-60.0),
+        if silent { " ⚠️ silence" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_stale_recordings() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = [
+            "voice-ptt-1700000000000.wav",
+            "voice-ptt-1700000000001-spill.wav",
+            "voice-ptt-notes.wav",
+            "voice-ptt-beep-42.oga",
+            "meeting.wav",
+        ];
+        for name in names {
+            std::fs::write(dir.path().join(name), b"RIFF").unwrap();
+        }
+        // Fresh files belong to a running instance
+        assert_eq!(sweep_stale_recordings(dir.path(), STALE_TEMP_AGE), 0);
+        assert_eq!(sweep_stale_recordings(dir.path(), Duration::ZERO), 2);
+        let mut left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "meeting.wav",
+                "voice-ptt-beep-42.oga",
+                "voice-ptt-notes.wav"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rms_level() {
+        assert_eq!(rms_level(&[]), 0.0);
+        assert_eq!(rms_level(&[0; 100]), 0.0);
+        let full = rms_level(&[i16::MAX, -i16::MAX]);
+        assert!((full - 1.0).abs() < 1e-4);
+    }
+
+    fn sine(freq: f32, rate: u32, len: usize, amplitude: f32) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_highpass_removes_dc_offset() {
+        let spec = hound::WavSpec {
+            sample_rate: 16000,
+            ..test_spec()
+        };
+        let mut samples: Vec<i16> = sine(300.0, 16000, 16000, 2000.0)
+            .into_iter()
+            .map(|s| (s + 8000.0) as i16)
+            .collect();
+        highpass_in_place(&mut samples, spec, 80.0);
+
+        let tail = &samples[8000..];
+        let mean = tail.iter().map(|&s| s as f64).sum::<f64>() / tail.len() as f64;
+        assert!(mean.abs() < 20.0, "mean {} is not centered", mean);
+    }
+
+    #[test]
+    fn test_highpass_passes_1khz_tone() {
+        let spec = hound::WavSpec {
+            sample_rate: 16000,
+            ..test_spec()
+        };
+        let mut samples: Vec<i16> = sine(1000.0, 16000, 16000, 10000.0)
+            .into_iter()
+            .map(|s| s as i16)
+            .collect();
+        let before = rms_level(&samples[8000..]);
+        highpass_in_place(&mut samples, spec, 80.0);
+        let after = rms_level(&samples[8000..]);
+        assert!(
+            after / before > 0.98,
+            "tone attenuated to {}",
+            after / before
+        );
+
+        let mut untouched = samples.clone();
+        highpass_in_place(&mut untouched, spec, 0.0);
+        assert_eq!(untouched, samples);
+    }
+
+    #[test]
+    fn test_muted_detection() {
+        assert!(is_muted(&[0; 16000]));
+        assert!(is_muted(&[]));
+        // Dither-level noise still counts as muted
+        let dither: Vec<i16> = (0..16000).map(|i| (i % 5) as i16 - 2).collect();
+        assert!(is_muted(&dither));
+
+        let quiet_room: Vec<i16> = sine(200.0, 16000, 16000, 60.0)
+            .into_iter()
+            .map(|s| s as i16)
+            .collect();
+        assert!(!is_muted(&quiet_room));
+        assert!(is_silent(&quiet_room, SILENCE_RMS));
+    }
+
+    #[test]
+    fn test_peak_level() {
+        assert_eq!(peak_level(&[]), 0.0);
+        assert_eq!(
+            peak_level(&[0, 100, -16384, 200]),
+            16384.0 / i16::MAX as f32
+        );
+        assert_eq!(peak_level(&[i16::MIN]), 1.0);
+        assert!((to_dbfs(1.0)).abs() < 1e-6);
+        assert!((to_dbfs(0.1) + 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_split_cuts_in_pauses() {
+        let spec = hound::WavSpec {
+            sample_rate: 16000,
+            ..test_spec()
+        };
+        let tone: Vec<i16> = sine(440.0, 16000, 16000, 8000.0)
+            .into_iter()
+            .map(|s| s as i16)
+            .collect();
+        let pause = vec![0i16; 4800];
+        let samples = [&tone[..], &pause, &tone, &pause, &tone].concat();
+
+        let ranges = split_at_silence(&samples, spec, 24000);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[2].end, samples.len());
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            let cut = pair[0].end;
+            assert_eq!(samples[cut], 0, "cut at {} is not in a pause", cut);
+        }
+        assert!(ranges.iter().all(|r| r.len() <= 24000));
+    }
+
+    #[test]
+    fn test_split_without_pauses_respects_limit() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 16000,
+            ..test_spec()
+        };
+        let samples: Vec<i16> = sine(440.0, 16000, 50000, 8000.0)
+            .into_iter()
+            .map(|s| s as i16)
+            .collect();
+
+        let ranges = split_at_silence(&samples, spec, 16001);
+        assert!(ranges.len() >= 4);
+        assert!(ranges.iter().all(|r| r.len() <= 16000));
+        // Cuts never separate the channels of a frame
+        assert!(ranges.iter().all(|r| r.start % 2 == 0));
+        assert_eq!(ranges.last().unwrap().end, samples.len());
+
+        assert_eq!(split_at_silence(&samples, spec, 100000), vec![0..50000]);
+    }
+
+    fn test_spec() -> hound::WavSpec {
+        hound::WavSpec {
+            channels: 1,
+            sample_rate: 100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn test_find_device() {
+        let names = [
+            "Built-in Audio Analog Stereo",
+            "Monitor of Built-in Audio Analog Stereo",
+            "USB Headset",
+        ];
+        assert_eq!(find_device(&names, "Monitor"), Some(1));
+        assert_eq!(find_device(&names, "usb"), Some(2));
+        assert_eq!(find_device(&names, "HDMI"), None);
+    }
+
+    fn range(
+        channels: u16,
+        min: u32,
+        max: u32,
+        format: cpal::SampleFormat,
+    ) -> cpal::SupportedStreamConfigRange {
+        cpal::SupportedStreamConfigRange::new(
+            channels,
+            cpal::SampleRate(min),
+            cpal::SampleRate(max),
+            cpal::SupportedBufferSize::Unknown,
+            format,
+        )
+    }
+
+    #[test]
+    fn test_choose_input_config_prefers_16k_mono_i16() {
+        let ranges = vec![
+            range(2, 44100, 44100, cpal::SampleFormat::F32),
+            range(2, 8000, 48000, cpal::SampleFormat::I16),
+            range(1, 8000, 48000, cpal::SampleFormat::F32),
+            range(1, 8000, 48000, cpal::SampleFormat::I16),
+        ];
+        let config = choose_input_config(ranges, 16000).unwrap();
+        assert_eq!(config.sample_rate().0, 16000);
+        assert_eq!(config.channels(), 1);
+        assert_eq!(config.sample_format(), cpal::SampleFormat::I16);
+    }
+
+    #[test]
+    fn test_choose_input_config_falls_back_to_closest_rate() {
+        let ranges = vec![
+            range(1, 44100, 44100, cpal::SampleFormat::U8),
+            range(2, 44100, 44100, cpal::SampleFormat::F32),
+            range(2, 48000, 48000, cpal::SampleFormat::F32),
+        ];
+        let config = choose_input_config(ranges.clone(), 16000).unwrap();
+        assert_eq!(config.sample_rate().0, 44100);
+        assert_eq!(config.channels(), 2);
+
+        let config = choose_input_config(ranges, 48000).unwrap();
+        assert_eq!(config.sample_rate().0, 48000);
+        assert!(choose_input_config(Vec::new(), 16000).is_none());
+    }
+
+    #[test]
+    fn test_short_recording_stays_in_memory() {
+        let mut buffer = CaptureBuffer::new(test_spec(), 1000);
+        buffer.extend(vec![1i16; 500]);
+        match buffer.take().unwrap() {
+            RecordedAudio::Memory { samples, .. } => assert_eq!(samples.len(), 500),
+            RecordedAudio::File(_) => panic!("short recording should not spill"),
+        }
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_long_recording_spills_to_disk() {
+        let mut buffer = CaptureBuffer::new(test_spec(), 1000);
+        buffer.extend(vec![7i16; 800]);
+        buffer.extend(vec![7i16; 800]);
+        buffer.extend(vec![7i16; 800]);
+        assert_eq!(buffer.len(), 2400);
+        assert_eq!(buffer.recent(50).len(), 50);
+
+        let path = match buffer.take().unwrap() {
+            RecordedAudio::File(path) => path,
+            RecordedAudio::Memory { .. } => panic!("long recording should spill"),
+        };
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.len(), 2400);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_clipping_ratio() {
+        let clean: Vec<i16> = (0..1000).map(|i| ((i % 200) as i16 - 100) * 100).collect();
+        assert_eq!(clipping_ratio(&clean), 0.0);
+
+        let mut clipped = clean.clone();
+        for sample in clipped.iter_mut().step_by(4) {
+            *sample = if *sample >= 0 { i16::MAX } else { i16::MIN };
+        }
+        assert!((clipping_ratio(&clipped) - 0.25).abs() < 1e-6);
+        assert_eq!(clipping_ratio(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_level_meter_bounds() {
+        assert!(format_level_meter(0.0, true).contains("[....................]"));
+        assert!(format_level_meter(0.0, true).contains("silence"));
+        assert!(format_level_meter(1.0, false).contains("[####################]"));
+    }
+}
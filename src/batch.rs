@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::api::{self, TranscriptionBackend};
+use crate::audio::{self, RecordedAudio};
+use crate::config::AppConfig;
+
+/// Runs existing WAV files through the same pipeline as live recordings and prints the text,
+/// one blank-line-separated block (or one JSON line with `json`) per file.
+///
+/// With `out_dir`, each transcript is also written to `<out_dir>/<file stem>.txt`.
+/// Returns `false` if any file failed.
+pub async fn run(
+    app_config: &AppConfig,
+    paths: &[PathBuf],
+    json: bool,
+    out_dir: Option<&Path>,
+) -> Result<bool> {
+    let backend = api::create_backend(app_config)?;
+    if let Some(dir) = out_dir {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+
+    let mut all_ok = true;
+    let mut printed = 0;
+    for (i, path) in paths.iter().enumerate() {
+        eprintln!("⏳ [{}/{}] {}", i + 1, paths.len(), path.display());
+        let text = match transcribe_file(backend.as_ref(), path, app_config).await {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("❌ {}: {}", path.display(), e);
+                all_ok = false;
+                continue;
+            }
+        };
+
+        if json {
+            println!("{}", json_line(path, &text));
+        } else {
+            if printed > 0 {
+                println!();
+            }
+            println!("{}", text);
+        }
+        printed += 1;
+
+        if let Some(dir) = out_dir {
+            let out_path = text_path(dir, path);
+            match fs::write(&out_path, format!("{}\n", text)) {
+                Ok(()) => eprintln!("💾 Wrote {}", out_path.display()),
+                Err(e) => {
+                    eprintln!("❌ Failed to write {}: {}", out_path.display(), e);
+                    all_ok = false;
+                }
+            }
+        }
+    }
+    Ok(all_ok)
+}
+
+/// Transcribes one WAV file, leaving it untouched. Failures are never added to the offline
+/// queue, the file is still there to retry.
+pub async fn transcribe_file(
+    backend: &dyn TranscriptionBackend,
+    path: &Path,
+    config: &AppConfig,
+) -> Result<String> {
+    check_wav(path)?;
+    let config = &AppConfig {
+        offline_queue_enabled: false,
+        ..config.clone()
+    };
+    // The pipeline filters recordings in place, so leave the user's file alone
+    let copy = audio::temp_wav_path("-transcribe");
+    fs::copy(path, &copy).with_context(|| format!("Failed to copy {}", path.display()))?;
+    let result = api::transcribe_recording(
+        backend,
+        RecordedAudio::File(copy.clone()),
+        config.mode,
+        config,
+    )
+    .await;
+    let _ = fs::remove_file(&copy);
+    Ok(api::postprocess_transcript(backend, result?, config).await)
+}
+
+/// Rejects files the pipeline can't read before anything is uploaded.
+fn check_wav(path: &Path) -> Result<()> {
+    let spec = match hound::WavReader::open(path) {
+        Ok(reader) => reader.spec(),
+        Err(hound::Error::IoError(e)) => {
+            return Err(e).with_context(|| format!("Failed to open {}", path.display()))
+        }
+        Err(_) => anyhow::bail!(
+            "Not a WAV file. Convert it first, e.g. `ffmpeg -i input.mp3 -ac 1 -ar 16000 output.wav`"
+        ),
+    };
+    if spec.bits_per_sample != 16 || spec.sample_format != hound::SampleFormat::Int {
+        anyhow::bail!(
+            "Only 16-bit PCM WAV files are supported, this one is {}-bit {:?}. Convert it with `ffmpeg -i input.wav -c:a pcm_s16le output.wav`",
+            spec.bits_per_sample,
+            spec.sample_format
+        );
+    }
+    Ok(())
+}
+
+fn json_line(path: &Path, text: &str) -> String {
+    serde_json::json!({ "file": path.display().to_string(), "text": text }).to_string()
+}
+
+fn text_path(out_dir: &Path, source: &Path) -> PathBuf {
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "transcript".to_string());
+    out_dir.join(format!("{}.txt", stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TranscriptionMode;
+    use crate::encode::{self, EncodedAudio};
+    use async_trait::async_trait;
+
+    struct FixedBackend;
+
+    #[async_trait]
+    impl TranscriptionBackend for FixedBackend {
+        async fn transcribe_audio(
+            &self,
+            _audio: EncodedAudio,
+            _mode: TranscriptionMode,
+            _config: &AppConfig,
+        ) -> Result<String> {
+            Ok("meeting notes".to_string())
+        }
+    }
+
+    fn spec(bits_per_sample: u16, sample_format: hound::SampleFormat) -> hound::WavSpec {
+        hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_file_leaves_input_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("standup.wav");
+        let samples: Vec<i16> = (0..16000).map(|i| 4000 + (i % 100) as i16).collect();
+        let bytes = encode::encode_wav(&samples, spec(16, hound::SampleFormat::Int)).unwrap();
+        fs::write(&path, &bytes).unwrap();
+
+        let text = transcribe_file(&FixedBackend, &path, &AppConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(text, "meeting notes");
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_non_wav_input_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mp3 = dir.path().join("call.mp3");
+        fs::write(&mp3, b"ID3\x04\x00\x00\x00\x00\x00\x00").unwrap();
+        let message = check_wav(&mp3).unwrap_err().to_string();
+        assert!(message.contains("Not a WAV file"), "{}", message);
+
+        let float = dir.path().join("float.wav");
+        let mut writer =
+            hound::WavWriter::create(&float, spec(32, hound::SampleFormat::Float)).unwrap();
+        writer.write_sample(0.5f32).unwrap();
+        writer.finalize().unwrap();
+        let message = check_wav(&float).unwrap_err().to_string();
+        assert!(message.contains("32-bit Float"), "{}", message);
+
+        let missing = check_wav(&dir.path().join("missing.wav")).unwrap_err();
+        assert!(missing.to_string().contains("Failed to open"));
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let path = Path::new("/recordings/standup \"monday\".wav");
+        let value: serde_json::Value =
+            serde_json::from_str(&json_line(path, "Hello, team.")).unwrap();
+        assert_eq!(value["file"], "/recordings/standup \"monday\".wav");
+        assert_eq!(value["text"], "Hello, team.");
+
+        assert_eq!(
+            text_path(Path::new("/tmp/out"), path),
+            PathBuf::from("/tmp/out/standup \"monday\".txt")
+        );
+    }
+}
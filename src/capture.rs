@@ -0,0 +1,635 @@
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use ringbuf::traits::{Consumer, Split};
+use ringbuf::{HeapCons, HeapProd, HeapRb};
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::audio::{self, AudioSystem, CaptureBuffer};
+use crate::config::{AppConfig, CaptureBackend, CaptureSource};
+use crate::injector::SystemInjector;
+
+pub enum CaptureMode {
+    Cpal {
+        audio_buffer: CaptureBuffer,
+        /// Receiving end of the lock-free ring the capture callback writes into.
+        consumer: HeapCons<i16>,
+        is_recording: Arc<AtomicBool>,
+        dropped_samples: Arc<AtomicUsize>,
+        stream_dead: Arc<AtomicBool>,
+        /// `None` when the samples are pushed by the caller, see [`CaptureMode::fed`].
+        _stream: Option<cpal::Stream>,
+    },
+    Cli {
+        tool: CliRecorder,
+        recorder: Option<RecorderProcess>,
+        current_file: Option<PathBuf>,
+    },
+}
+
+impl CaptureMode {
+    /// Creates the capture mode for the configured backend.
+    pub fn init(app_config: &AppConfig) -> Result<Self> {
+        match app_config.capture_source {
+            CaptureSource::Mic => info!("🎙️ Capture source: microphone"),
+            CaptureSource::Monitor => info!(
+                "🔊 Capture source: system audio monitor (everything played on your speakers is transcribed)"
+            ),
+        }
+        select_capture(
+            app_config.capture_backend,
+            || init_cpal_capture(app_config),
+            CliRecorder::detect,
+        )
+    }
+
+    /// A capture without an input device: samples pushed into the returned producer are
+    /// recorded while a key is held, like the ones the cpal callback delivers.
+    pub fn fed(spec: hound::WavSpec) -> (Self, HeapProd<i16>) {
+        let audio_buffer = CaptureBuffer::new(spec, 0);
+        let ring_capacity = audio_buffer.samples_per_sec() * RING_SECONDS;
+        let (producer, consumer) = HeapRb::<i16>::new(ring_capacity).split();
+        let mode = CaptureMode::Cpal {
+            audio_buffer,
+            consumer,
+            is_recording: Arc::new(AtomicBool::new(false)),
+            dropped_samples: Arc::new(AtomicUsize::new(0)),
+            stream_dead: Arc::new(AtomicBool::new(false)),
+            _stream: None,
+        };
+        (mode, producer)
+    }
+
+    fn cli(tool: CliRecorder) -> Self {
+        info!("Using command-line recorder: {}", tool.binary());
+        CaptureMode::Cli {
+            tool,
+            recorder: None,
+            current_file: None,
+        }
+    }
+}
+
+/// External recording tools usable when cpal capture is unavailable, in order of preference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CliRecorder {
+    PwRecord,
+    Parec,
+    Arecord,
+}
+
+impl CliRecorder {
+    pub const ALL: [CliRecorder; 3] = [
+        CliRecorder::PwRecord,
+        CliRecorder::Parec,
+        CliRecorder::Arecord,
+    ];
+
+    pub fn binary(self) -> &'static str {
+        match self {
+            CliRecorder::PwRecord => "pw-record",
+            CliRecorder::Parec => "parec",
+            CliRecorder::Arecord => "arecord",
+        }
+    }
+
+    fn is_available(self) -> bool {
+        Command::new(self.binary())
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok()
+    }
+
+    /// Returns the first candidate that is installed.
+    pub fn detect(candidates: &[CliRecorder]) -> Option<CliRecorder> {
+        candidates.iter().copied().find(|tool| tool.is_available())
+    }
+
+    /// Builds the argument list for recording 16-bit PCM into the WAV file `out_file`.
+    fn args(self, app_config: &AppConfig, out_file: &Path) -> Vec<OsString> {
+        let rate = app_config.pw_rate.to_string();
+        let channels = app_config.pw_channels.to_string();
+        let mut args: Vec<OsString> = match self {
+            CliRecorder::PwRecord => vec![
+                "--rate".into(),
+                rate.into(),
+                "--channels".into(),
+                channels.into(),
+                "--format".into(),
+                "s16".into(),
+            ],
+            // parec writes a proper WAV header itself when given --file-format
+            CliRecorder::Parec => vec![
+                format!("--rate={}", rate).into(),
+                format!("--channels={}", channels).into(),
+                "--format=s16le".into(),
+                "--file-format=wav".into(),
+            ],
+            CliRecorder::Arecord => vec![
+                "-q".into(),
+                "-f".into(),
+                "S16_LE".into(),
+                "-r".into(),
+                rate.into(),
+                "-c".into(),
+                channels.into(),
+                "-t".into(),
+                "wav".into(),
+            ],
+        };
+        let monitor = app_config.capture_source == CaptureSource::Monitor;
+        let target = if monitor {
+            app_config
+                .monitor_device
+                .as_ref()
+                .or(app_config.pw_target.as_ref())
+        } else {
+            app_config.pw_target.as_ref()
+        };
+        if monitor {
+            match (self, target) {
+                // With capture.sink set, --target names a sink and pw-record records its monitor
+                (CliRecorder::PwRecord, _) => {
+                    args.push("-P".into());
+                    args.push("{ stream.capture.sink=true }".into());
+                }
+                (CliRecorder::Parec, None) => args.push("--device=@DEFAULT_MONITOR@".into()),
+                (CliRecorder::Parec, Some(_)) | (CliRecorder::Arecord, _) => {}
+            }
+        }
+        if let Some(target) = target {
+            match self {
+                CliRecorder::PwRecord => {
+                    args.push("--target".into());
+                    args.push(target.into());
+                }
+                CliRecorder::Parec => args.push(format!("--device={}", target).into()),
+                CliRecorder::Arecord => {
+                    args.push("-D".into());
+                    args.push(target.into());
+                }
+            }
+        }
+        args.push(out_file.into());
+        args
+    }
+
+    /// How long to wait for the tool to finalize its WAV header after SIGINT.
+    fn stop_grace(self) -> Duration {
+        match self {
+            // parec flushes libsndfile buffers on exit, which can take a moment
+            CliRecorder::Parec => Duration::from_millis(1500),
+            CliRecorder::PwRecord | CliRecorder::Arecord => Duration::from_millis(500),
+        }
+    }
+}
+
+/// Picks the capture mode for `backend`.
+///
+/// `init_cpal` and `detect` are injected so the selection rules can be tested
+/// without real audio hardware.
+fn select_capture<C, D>(backend: CaptureBackend, init_cpal: C, detect: D) -> Result<CaptureMode>
+where
+    C: FnOnce() -> Result<CaptureMode>,
+    D: FnOnce(&[CliRecorder]) -> Option<CliRecorder>,
+{
+    match backend {
+        CaptureBackend::Auto => match init_cpal() {
+            Ok(mode) => Ok(mode),
+            Err(e) => {
+                warn!("⚠️ cpal capture init failed: {}", e);
+                let tool = detect(&CliRecorder::ALL).with_context(|| {
+                    format!(
+                        "No fallback recorder found in PATH (tried {})",
+                        recorder_names(&CliRecorder::ALL)
+                    )
+                })?;
+                warn!("⚠️ Falling back to {}.", tool.binary());
+                Ok(CaptureMode::cli(tool))
+            }
+        },
+        CaptureBackend::Cpal => init_cpal().context("cpal capture init failed"),
+        CaptureBackend::PwRecord => {
+            if detect(&[CliRecorder::PwRecord]).is_none() {
+                anyhow::bail!(
+                    "capture_backend is \"pw-record\" but 'pw-record' was not found in PATH. Install the PipeWire tools (e.g. pipewire-bin or pipewire-audio)."
+                );
+            }
+            Ok(CaptureMode::cli(CliRecorder::PwRecord))
+        }
+    }
+}
+
+fn recorder_names(tools: &[CliRecorder]) -> String {
+    tools
+        .iter()
+        .map(|tool| tool.binary())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Capacity of the capture ring buffer, in seconds of audio.
+const RING_SECONDS: usize = 2;
+
+/// Opens the default input device and starts a cpal capture stream.
+fn init_cpal_capture(app_config: &AppConfig) -> Result<CaptureMode> {
+    let audio_system = AudioSystem::new(
+        app_config.preferred_sample_rate,
+        app_config.input_device_pattern(),
+    )?;
+    let device_name = audio_system
+        .device
+        .name()
+        .unwrap_or_else(|_| "default".to_string());
+    let wav_spec = audio_system.get_wav_spec();
+    info!(
+        "Using input device: {} ({} Hz, {} ch, {:?})",
+        device_name,
+        wav_spec.sample_rate,
+        wav_spec.channels,
+        audio_system.config.sample_format()
+    );
+
+    let spill_threshold = app_config.spill_after_secs as usize
+        * wav_spec.sample_rate as usize
+        * wav_spec.channels as usize;
+    let audio_buffer = CaptureBuffer::new(wav_spec, spill_threshold);
+    // Two seconds of headroom; the main loop drains the ring every few milliseconds
+    let ring_capacity = audio_buffer.samples_per_sec() * RING_SECONDS;
+    let (producer, consumer) = HeapRb::<i16>::new(ring_capacity).split();
+    let is_recording = Arc::new(AtomicBool::new(false));
+    let dropped_samples = Arc::new(AtomicUsize::new(0));
+    let stream_dead = Arc::new(AtomicBool::new(false));
+    let stream = audio_system.build_stream(
+        producer,
+        is_recording.clone(),
+        dropped_samples.clone(),
+        stream_dead.clone(),
+    )?;
+    stream.play()?;
+
+    Ok(CaptureMode::Cpal {
+        audio_buffer,
+        consumer,
+        is_recording,
+        dropped_samples,
+        stream_dead,
+        _stream: Some(stream),
+    })
+}
+
+/// How long `probe_microphone` listens.
+const PROBE_DURATION: Duration = Duration::from_millis(300);
+
+/// Opens the input device briefly and reports whether it delivered more than a muted
+/// microphone would, see [`audio::is_muted`]. Without Microphone permission macOS hands out
+/// silence instead of failing.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn probe_microphone(app_config: &AppConfig) -> Result<bool> {
+    let CaptureMode::Cpal {
+        mut consumer,
+        is_recording,
+        ..
+    } = init_cpal_capture(app_config)?
+    else {
+        return Ok(true);
+    };
+    is_recording.store(true, Ordering::Relaxed);
+    std::thread::sleep(PROBE_DURATION);
+    let samples: Vec<i16> = consumer.pop_iter().collect();
+    Ok(!audio::is_muted(&samples))
+}
+
+/// How many times a lost input device is re-opened before falling back to pw-record.
+const DEVICE_RETRY_ATTEMPTS: u32 = 5;
+
+/// Re-initializes cpal capture after the stream died, backing off between attempts. A cpal
+/// stream can't move between threads, so the main loop makes the attempts as they come due
+/// instead of a background task.
+///
+/// With `capture_backend = "cpal"`, or when no command-line recorder exists to fall back to,
+/// this keeps retrying.
+pub struct Reconnect {
+    attempt: u32,
+    next: tokio::time::Instant,
+}
+
+impl Reconnect {
+    pub fn new(app_config: &AppConfig) -> Self {
+        Self {
+            attempt: 0,
+            next: tokio::time::Instant::now()
+                + Duration::from_millis(app_config.device_retry_interval_ms),
+        }
+    }
+
+    /// Waits until the next attempt is due and makes it; the capture to use once the device
+    /// is back or a command-line recorder takes over. Cancelling the wait skips nothing.
+    pub async fn attempt(&mut self, app_config: &AppConfig) -> Option<CaptureMode> {
+        tokio::time::sleep_until(self.next).await;
+        self.attempt += 1;
+        let interval = Duration::from_millis(app_config.device_retry_interval_ms);
+        self.next =
+            tokio::time::Instant::now() + interval * (self.attempt + 1).min(DEVICE_RETRY_ATTEMPTS);
+        info!("🔌 Reconnecting input device (attempt {})...", self.attempt);
+        match init_cpal_capture(app_config) {
+            Ok(mode) => {
+                SystemInjector::notify("Voice PTT", "Microphone reconnected");
+                return Some(mode);
+            }
+            Err(e) => warn!("⚠️ Reconnect failed: {}", e),
+        }
+        if self.attempt < DEVICE_RETRY_ATTEMPTS
+            || app_config.capture_backend == CaptureBackend::Cpal
+        {
+            return None;
+        }
+        match CliRecorder::detect(&CliRecorder::ALL) {
+            Some(tool) => {
+                warn!("⚠️ Falling back to {}.", tool.binary());
+                SystemInjector::notify(
+                    "Voice PTT",
+                    &format!(
+                        "Microphone could not be reopened, switched to {}",
+                        tool.binary()
+                    ),
+                );
+                Some(CaptureMode::cli(tool))
+            }
+            // Keep waiting for the device if no command-line recorder exists either
+            None => {
+                if self.attempt == DEVICE_RETRY_ATTEMPTS {
+                    warn!("⚠️ No fallback recorder found, retrying the input device.");
+                }
+                None
+            }
+        }
+    }
+}
+
+/// A running command-line recorder. Its stderr is read on a thread while it records, so a
+/// chatty tool can't fill the pipe and stall.
+pub struct RecorderProcess {
+    child: Child,
+    stderr: Option<std::thread::JoinHandle<String>>,
+}
+
+impl RecorderProcess {
+    fn spawn(mut command: Command) -> std::io::Result<Self> {
+        let mut child = command.stderr(Stdio::piped()).spawn()?;
+        let stderr = child.stderr.take().map(|mut pipe| {
+            std::thread::spawn(move || {
+                let mut output = Vec::new();
+                let _ = pipe.read_to_end(&mut output);
+                String::from_utf8_lossy(&output).into_owned()
+            })
+        });
+        Ok(Self { child, stderr })
+    }
+
+    /// Kills and reaps the recorder, e.g. to throw its recording away.
+    pub fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Starts recording with an external tool into a fresh temp WAV file.
+pub fn start_cli_recording(
+    tool: CliRecorder,
+    app_config: &AppConfig,
+) -> Result<(RecorderProcess, PathBuf)> {
+    let out_file = audio::temp_wav_path("");
+
+    let mut command = Command::new(tool.binary());
+    command.args(tool.args(app_config, &out_file));
+    let process = RecorderProcess::spawn(command).with_context(|| {
+        format!(
+            "Failed to start {}. Make sure it is installed and the audio server is running.",
+            tool.binary()
+        )
+    })?;
+
+    Ok((process, out_file))
+}
+
+/// Stops a recorder and returns whatever it printed to stderr. This blocks until the
+/// recorder exits, so async code runs it on `spawn_blocking`.
+///
+/// The tool gets SIGINT first so it can finalize the WAV header; it is only killed if it
+/// doesn't exit within its grace period.
+pub fn stop_cli_recording(tool: CliRecorder, mut process: RecorderProcess) -> String {
+    let child = &mut process.child;
+    #[cfg(unix)]
+    {
+        // SAFETY: kill(2) with a PID we spawned and still own (not yet reaped)
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+        }
+        let deadline = std::time::Instant::now() + tool.stop_grace();
+        while std::time::Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(Some(_)) | Err(_) => break,
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tool;
+
+    let _ = child.kill();
+    let _ = child.wait();
+    let stderr = process
+        .stderr
+        .take()
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    stderr.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpal_unavailable() -> Result<CaptureMode> {
+        anyhow::bail!("no input device")
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_returns_all_of_stderr() {
+        // Far more than a pipe buffer holds
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "head -c 200000 /dev/zero | tr '\\0' x >&2; exec sleep 10",
+        ]);
+        let process = RecorderProcess::spawn(command).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let started = std::time::Instant::now();
+        let stderr = stop_cli_recording(CliRecorder::Arecord, process);
+        assert_eq!(stderr.len(), 200000);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_pw_record_args() {
+        let mut config = AppConfig::default();
+        let args = CliRecorder::PwRecord.args(&config, Path::new("/tmp/out.wav"));
+        assert_eq!(
+            args,
+            [
+                "--rate",
+                "16000",
+                "--channels",
+                "1",
+                "--format",
+                "s16",
+                "/tmp/out.wav"
+            ]
+        );
+
+        config.pw_target = Some("alsa_input.usb-headset".to_string());
+        config.pw_rate = 48000;
+        config.pw_channels = 2;
+        let args = CliRecorder::PwRecord.args(&config, Path::new("/tmp/out.wav"));
+        assert_eq!(
+            args,
+            [
+                "--rate",
+                "48000",
+                "--channels",
+                "2",
+                "--format",
+                "s16",
+                "--target",
+                "alsa_input.usb-headset",
+                "/tmp/out.wav"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parec_and_arecord_args() {
+        let config = AppConfig::default();
+        let args = CliRecorder::Parec.args(&config, Path::new("/tmp/out.wav"));
+        assert_eq!(
+            args,
+            [
+                "--rate=16000",
+                "--channels=1",
+                "--format=s16le",
+                "--file-format=wav",
+                "/tmp/out.wav"
+            ]
+        );
+        let args = CliRecorder::Arecord.args(&config, Path::new("/tmp/out.wav"));
+        assert_eq!(
+            args,
+            [
+                "-q",
+                "-f",
+                "S16_LE",
+                "-r",
+                "16000",
+                "-c",
+                "1",
+                "-t",
+                "wav",
+                "/tmp/out.wav"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_monitor_source_args() {
+        let mut config = AppConfig {
+            capture_source: CaptureSource::Monitor,
+            ..Default::default()
+        };
+        let args = CliRecorder::PwRecord.args(&config, Path::new("/tmp/out.wav"));
+        assert_eq!(
+            args[6..],
+            ["-P", "{ stream.capture.sink=true }", "/tmp/out.wav"]
+        );
+        let args = CliRecorder::Parec.args(&config, Path::new("/tmp/out.wav"));
+        assert_eq!(args[4..], ["--device=@DEFAULT_MONITOR@", "/tmp/out.wav"]);
+
+        config.pw_target = Some("alsa_input.usb-headset".to_string());
+        config.monitor_device = Some("alsa_output.pci-0000_00_1f.3.analog-stereo".to_string());
+        let args = CliRecorder::PwRecord.args(&config, Path::new("/tmp/out.wav"));
+        assert_eq!(
+            args[6..],
+            [
+                "-P",
+                "{ stream.capture.sink=true }",
+                "--target",
+                "alsa_output.pci-0000_00_1f.3.analog-stereo",
+                "/tmp/out.wav"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_falls_back_to_first_available_recorder() {
+        let mode = select_capture(CaptureBackend::Auto, cpal_unavailable, |candidates| {
+            assert_eq!(candidates, CliRecorder::ALL);
+            Some(CliRecorder::Arecord)
+        })
+        .unwrap();
+        assert!(matches!(
+            mode,
+            CaptureMode::Cli {
+                tool: CliRecorder::Arecord,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_auto_without_any_recorder() {
+        let result = select_capture(CaptureBackend::Auto, cpal_unavailable, |_| None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_cpal_never_falls_back() {
+        let result = select_capture(CaptureBackend::Cpal, cpal_unavailable, |_| {
+            Some(CliRecorder::PwRecord)
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_pw_record_skips_cpal() {
+        let mode = select_capture(
+            CaptureBackend::PwRecord,
+            || panic!("cpal must not be initialized"),
+            |candidates| candidates.first().copied(),
+        )
+        .unwrap();
+        assert!(matches!(
+            mode,
+            CaptureMode::Cli {
+                tool: CliRecorder::PwRecord,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_pw_record_missing_binary() {
+        match select_capture(CaptureBackend::PwRecord, cpal_unavailable, |_| None) {
+            Err(e) => assert!(e.to_string().contains("pw-record")),
+            Ok(_) => panic!("missing pw-record must be an error"),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::control::Request;
+
+/// Push-to-Talk voice dictation: hold a key, speak, and the transcription is typed for you.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Config file to load instead of searching $VOICE_PTT_CONFIG, ~/.config/voice-ptt and
+    /// the executable's directory.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Key or combination to hold while speaking, e.g. LAlt, LControl+Space or KEY_COMPOSE (config: ptt_key).
+    #[arg(long, global = true, value_name = "KEY")]
+    pub ptt_key: Option<String>,
+
+    /// Transcription language as an ISO-639-1 code, e.g. de (config: language).
+    #[arg(long, global = true, value_name = "CODE")]
+    pub language: Option<String>,
+
+    /// Transcription model (config: model).
+    #[arg(long, global = true)]
+    pub model: Option<String>,
+
+    /// Starts with the settings of a [profiles.<name>] table on top of the config.
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Part of the name of the microphone to record from (config: input_device).
+    #[arg(long, global = true, value_name = "NAME")]
+    pub device: Option<String>,
+
+    /// Plays no feedback sounds (config: sound_enabled = false).
+    #[arg(long, global = true)]
+    pub no_sound: bool,
+
+    /// Logs debug details like window classes, paste shortcuts and transcripts
+    /// (config: verbose = true).
+    #[arg(long, global = true)]
+    pub verbose: bool,
+
+    /// Only logs warnings and errors; RUST_LOG overrides both this and --verbose.
+    #[arg(long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Prints the resolved configuration (defaults, file, VOICE_PTT_* variables and flags)
+    /// as TOML and exits.
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Asks an already running instance to exit (SIGTERM) and takes its place.
+    #[arg(long, conflicts_with = "allow_multiple")]
+    pub replace: bool,
+
+    /// Starts even when another instance is running, e.g. a second one on another --ptt-key
+    /// for testing.
+    #[arg(long)]
+    pub allow_multiple: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum Command {
+    #[command(flatten)]
+    Standalone(StandaloneCommand),
+    #[command(flatten)]
+    Configured(ConfiguredCommand),
+}
+
+/// Commands that run before a config is loaded.
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum StandaloneCommand {
+    /// Writes a commented config.toml with every setting at its default to
+    /// ~/.config/voice-ptt (or the --config path).
+    Init {
+        /// Replaces an existing file.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Prints every key name accepted by ptt_key, [bindings] and profile_key.
+    ListKeys,
+    /// Waits for a key or combination and prints its name for ptt_key.
+    DetectKey {
+        /// Seconds to wait for a key before giving up.
+        #[arg(long, default_value_t = 10, value_name = "SECS")]
+        timeout: u64,
+    },
+    /// Sends a command to the running instance over its control socket and prints the JSON
+    /// response; the exit status is nonzero if the command failed.
+    Ctl {
+        #[arg(value_enum)]
+        command: Request,
+    },
+    /// Prints the state of the running instance for a status bar, one line now and one after
+    /// every change: idle, recording, processing, paused, or stopped while none is running.
+    #[command(after_help = BAR_STATUS_EXAMPLES)]
+    BarStatus {
+        /// Prints just the state instead of waybar's JSON, e.g. for polybar.
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Writes a systemd user unit running voice-ptt (with --config, if given) to
+    /// ~/.config/systemd/user, without enabling it.
+    InstallService {
+        /// Replaces an existing unit file.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Commands that run with the loaded config.
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum ConfiguredCommand {
+    /// Records a few seconds, prints the input level and plays the recording back.
+    TestMic,
+    /// Stores the API key for the configured backend in the desktop keyring.
+    SetKey,
+    /// Transcribes WAV files with the configured backend and prints the text.
+    Transcribe {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Prints one {"file": ..., "text": ...} JSON object per line.
+        #[arg(long)]
+        json: bool,
+        /// Also writes each transcript to <DIR>/<name>.txt.
+        #[arg(long, value_name = "DIR")]
+        out: Option<PathBuf>,
+    },
+    /// Prints totals from the transcription stats file.
+    Stats,
+    /// Retries recordings saved while offline and copies the recovered text to the clipboard.
+    Flush,
+}
+
+const BAR_STATUS_EXAMPLES: &str = r#"Waybar (~/.config/waybar/config), with "custom/voice-ptt" in a modules list:
+  "custom/voice-ptt": {
+      "exec": "voice-ptt bar-status",
+      "return-type": "json",
+      "format": "{icon}",
+      "format-icons": {
+          "idle": "🎙", "recording": "🔴", "processing": "⏳",
+          "paused": "⏸", "error": "⚠", "stopped": ""
+      }
+  }
+The class (#custom-voice-ptt.recording, ...) can be styled in style.css.
+
+Polybar:
+  [module/voice-ptt]
+  type = custom/script
+  exec = voice-ptt bar-status --plain
+  tail = true"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = Cli::try_parse_from(["voice-ptt"]).unwrap();
+        assert_eq!(cli.command, None);
+
+        let cli = Cli::try_parse_from(["voice-ptt", "test-mic"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Configured(ConfiguredCommand::TestMic))
+        );
+
+        let cli = Cli::try_parse_from(["voice-ptt", "list-keys"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Standalone(StandaloneCommand::ListKeys))
+        );
+
+        let cli = Cli::try_parse_from(["voice-ptt", "detect-key"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Standalone(StandaloneCommand::DetectKey {
+                timeout: 10
+            }))
+        );
+        let cli = Cli::try_parse_from(["voice-ptt", "detect-key", "--timeout", "3"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Standalone(StandaloneCommand::DetectKey {
+                timeout: 3
+            }))
+        );
+
+        let cli = Cli::try_parse_from(["voice-ptt", "set-key"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Configured(ConfiguredCommand::SetKey))
+        );
+
+        let cli = Cli::try_parse_from(["voice-ptt", "init", "--force"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Standalone(StandaloneCommand::Init { force: true }))
+        );
+
+        let cli = Cli::try_parse_from(["voice-ptt", "stats"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Configured(ConfiguredCommand::Stats))
+        );
+
+        let cli = Cli::try_parse_from(["voice-ptt", "flush"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Configured(ConfiguredCommand::Flush))
+        );
+
+        let cli = Cli::try_parse_from(["voice-ptt", "ctl", "reload-config"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Standalone(StandaloneCommand::Ctl {
+                command: Request::ReloadConfig
+            }))
+        );
+        assert!(Cli::try_parse_from(["voice-ptt", "ctl", "dance"]).is_err());
+
+        let cli = Cli::try_parse_from(["voice-ptt", "bar-status", "--plain"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Standalone(StandaloneCommand::BarStatus {
+                plain: true
+            }))
+        );
+        // Subscribing is for bar-status, not a command to send
+        assert!(Cli::try_parse_from(["voice-ptt", "ctl", "subscribe"]).is_err());
+
+        let cli = Cli::try_parse_from(["voice-ptt", "install-service"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Standalone(StandaloneCommand::InstallService {
+                force: false
+            }))
+        );
+
+        let cli = Cli::try_parse_from([
+            "voice-ptt",
+            "transcribe",
+            "a.wav",
+            "b.wav",
+            "--json",
+            "--out",
+            "txt",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Configured(ConfiguredCommand::Transcribe {
+                paths: vec![PathBuf::from("a.wav"), PathBuf::from("b.wav")],
+                json: true,
+                out: Some(PathBuf::from("txt")),
+            }))
+        );
+        assert!(Cli::try_parse_from(["voice-ptt", "transcribe"]).is_err());
+
+        assert!(Cli::try_parse_from(["voice-ptt", "test-speaker"]).is_err());
+
+        let cli = Cli::try_parse_from(["voice-ptt", "stats", "--config", "alt.toml"]).unwrap();
+        assert_eq!(cli.config, Some(PathBuf::from("alt.toml")));
+    }
+
+    #[test]
+    fn test_parse_instance_flags() {
+        let cli = Cli::try_parse_from(["voice-ptt"]).unwrap();
+        assert!(!cli.replace && !cli.allow_multiple);
+
+        let cli = Cli::try_parse_from(["voice-ptt", "--replace"]).unwrap();
+        assert!(cli.replace);
+        let cli = Cli::try_parse_from(["voice-ptt", "--allow-multiple"]).unwrap();
+        assert!(cli.allow_multiple);
+        assert!(Cli::try_parse_from(["voice-ptt", "--replace", "--allow-multiple"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_log_flags() {
+        let cli = Cli::try_parse_from(["voice-ptt", "--quiet"]).unwrap();
+        assert!(cli.quiet && !cli.verbose);
+        let cli = Cli::try_parse_from(["voice-ptt", "stats", "--verbose"]).unwrap();
+        assert!(cli.verbose && !cli.quiet);
+        assert!(Cli::try_parse_from(["voice-ptt", "--quiet", "--verbose"]).is_err());
+    }
+
+    #[test]
+    fn test_help_names_config_keys() {
+        let help = <Cli as clap::CommandFactory>::command()
+            .render_long_help()
+            .to_string();
+        for key in [
+            "ptt_key",
+            "language",
+            "model",
+            "input_device",
+            "sound_enabled",
+            "verbose",
+        ] {
+            assert!(help.contains(&format!("config: {}", key)), "{}", key);
+        }
+    }
+}
@@ -1,31 +1,1323 @@
 use anyhow::{Context, Result};
 use device_query::Keycode;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-#[derive(Deserialize, Clone)]
+use crate::api::BACKENDS;
+use crate::cli::Cli;
+use crate::evdev;
+use crate::injector;
+use crate::sound;
+use crate::text::{self, DEFAULT_HALLUCINATION_FILTERS};
+
+/// Which audio capture path to use.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureBackend {
+    /// Try cpal first and fall back to pw-record if it fails.
+    #[default]
+    Auto,
+    /// Always use cpal, never fall back.
+    Cpal,
+    /// Skip cpal and record through PipeWire's pw-record.
+    PwRecord,
+}
+
+/// What gets recorded while the PTT key is held.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureSource {
+    /// The microphone (default input device).
+    #[default]
+    Mic,
+    /// The monitor of the output device, i.e. whatever is currently playing.
+    Monitor,
+}
+
+/// Longest `hold_threshold_ms`; the capture ring buffer keeps its audio until recording starts.
+const MAX_HOLD_THRESHOLD_MS: u64 = 1000;
+
+/// Whisper only looks at the last 224 tokens of the prompt.
+const PROMPT_TOKEN_LIMIT: usize = 224;
+
+/// Settings only read at startup, by the audio capture, the backend, the key reader, the
+/// offline queue flusher or the logging. Reloading the config can't change them and profiles
+/// can't set them.
+pub const STARTUP_KEYS: &[&str] = &[
+    "backend",
+    "api_base_url",
+    "api_key_source",
+    "api_keys",
+    "api_key_file",
+    "api_key_command",
+    "model_path",
+    "vosk_model_path",
+    "api_connect_timeout_secs",
+    "proxy_url",
+    "no_proxy",
+    "warmup_on_start",
+    "keepalive_secs",
+    "streaming",
+    "offline_queue_enabled",
+    "queue_dir",
+    "queue_retry_secs",
+    "verbose",
+    "log_format",
+    "log_file",
+    "shutdown_grace_secs",
+    "input_backend",
+    "status_notifications",
+    "spill_after_secs",
+    "capture_backend",
+    "capture_source",
+    "input_device",
+    "monitor_device",
+    "preferred_sample_rate",
+    "pw_target",
+    "pw_rate",
+    "pw_channels",
+];
+
+/// Rough token count for prompt text (about four characters per token).
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// What Whisper does with the recording.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptionMode {
+    /// Text in the spoken language.
+    #[default]
+    Transcribe,
+    /// English text, whatever the spoken language.
+    Translate,
+}
+
+/// Where the API key is looked up first.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeySource {
+    /// Environment variable, then `api_key_file`, then `api_key_command`.
+    #[default]
+    Env,
+    /// The desktop keyring (Secret Service / macOS Keychain), falling back to `Env`.
+    Keyring,
+}
+
+/// Transcript format requested from the API.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    /// Plain text only.
+    #[default]
+    Text,
+    /// SubRip subtitles, saved to `subtitles_dir` while the plain text is typed.
+    Srt,
+    /// WebVTT subtitles, saved to `subtitles_dir` while the plain text is typed.
+    Vtt,
+}
+
+impl TranscriptFormat {
+    /// The `response_format` value and file extension, or `None` for plain text.
+    pub fn subtitle_extension(self) -> Option<&'static str> {
+        match self {
+            TranscriptFormat::Text => None,
+            TranscriptFormat::Srt => Some("srt"),
+            TranscriptFormat::Vtt => Some("vtt"),
+        }
+    }
+}
+
+/// What to do when a recording is identical to the one just transcribed.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeAction {
+    /// Type the cached transcript again without a request.
+    #[default]
+    Reuse,
+    /// Don't type anything.
+    Skip,
+}
+
+/// How text is pasted into the focused window.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InjectionBackend {
+    /// Wayland if `XDG_SESSION_TYPE`/`WAYLAND_DISPLAY` say so, X11 otherwise (native if
+    /// xsel/xdotool are missing). osascript on macOS.
+    #[default]
+    Auto,
+    /// xsel and xdotool.
+    X11,
+    /// wl-copy, and wtype (or ydotool) for the paste shortcut.
+    Wayland,
+    /// In-process clipboard and key events (arboard + enigo), no external tools; X11 and macOS.
+    Native,
+}
+
+/// Where the class and title of the focused window come from, for `paste_overrides`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowInfo {
+    /// Hyprland or Sway when `HYPRLAND_INSTANCE_SIGNATURE`/`SWAYSOCK` is set, xdotool outside
+    /// Wayland; other Wayland compositors don't expose the focused window.
+    #[default]
+    Auto,
+    /// `xdotool getactivewindow`.
+    X11,
+    /// `hyprctl activewindow -j`.
+    Hyprland,
+    /// `swaymsg -t get_tree`.
+    Sway,
+}
+
+/// What happens with a finished transcript.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Paste or type it into the focused window.
+    #[default]
+    Inject,
+    /// Only put it on the clipboard and show a notification.
+    Clipboard,
+    /// Only print it to standard output, one line per transcript.
+    Stdout,
+}
+
+/// A modifier of a [`Shortcut`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    /// Super on Linux, Command on macOS.
+    Super,
+}
+
+/// Names a [`Shortcut`] accepts for each modifier, compared case-insensitively.
+const MODIFIER_NAMES: [(&str, Modifier); 9] = [
+    ("ctrl", Modifier::Ctrl),
+    ("control", Modifier::Ctrl),
+    ("shift", Modifier::Shift),
+    ("alt", Modifier::Alt),
+    ("option", Modifier::Alt),
+    ("super", Modifier::Super),
+    ("cmd", Modifier::Super),
+    ("command", Modifier::Super),
+    ("meta", Modifier::Super),
+];
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Modifier::Ctrl => write!(f, "ctrl"),
+            Modifier::Shift => write!(f, "shift"),
+            Modifier::Alt => write!(f, "alt"),
+            Modifier::Super if cfg!(target_os = "macos") => write!(f, "cmd"),
+            Modifier::Super => write!(f, "super"),
+        }
+    }
+}
+
+/// The key a [`Shortcut`] presses after its modifiers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShortcutKey {
+    /// A character key, lowercase for letters.
+    Char(char),
+    Return,
+    Tab,
+    Space,
+    Escape,
+    Insert,
+    Delete,
+    BackSpace,
+}
+
+/// Names a [`Shortcut`] accepts for keys other than characters, compared case-insensitively.
+const SHORTCUT_KEY_NAMES: [(&str, ShortcutKey); 9] = [
+    ("return", ShortcutKey::Return),
+    ("enter", ShortcutKey::Return),
+    ("tab", ShortcutKey::Tab),
+    ("space", ShortcutKey::Space),
+    ("escape", ShortcutKey::Escape),
+    ("esc", ShortcutKey::Escape),
+    ("insert", ShortcutKey::Insert),
+    ("delete", ShortcutKey::Delete),
+    ("backspace", ShortcutKey::BackSpace),
+];
+
+impl fmt::Display for ShortcutKey {
+    /// The X keysym, as xdotool and wtype take it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShortcutKey::Char(c) => write!(f, "{}", c),
+            ShortcutKey::Return => write!(f, "Return"),
+            ShortcutKey::Tab => write!(f, "Tab"),
+            ShortcutKey::Space => write!(f, "space"),
+            ShortcutKey::Escape => write!(f, "Escape"),
+            ShortcutKey::Insert => write!(f, "Insert"),
+            ShortcutKey::Delete => write!(f, "Delete"),
+            ShortcutKey::BackSpace => write!(f, "BackSpace"),
+        }
+    }
+}
+
+/// A key chord like `ctrl+shift+v` or `Return`: modifiers joined by "+", ending in a key.
+/// Parsed once when the config is loaded; each injection backend translates it to its own
+/// syntax. Displays in xdotool syntax.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shortcut {
+    pub modifiers: Vec<Modifier>,
+    pub key: ShortcutKey,
+}
+
+impl Shortcut {
+    /// A modifier chord ending in a character key, like ctrl+v.
+    pub fn new(modifiers: &[Modifier], key: char) -> Self {
+        Self {
+            modifiers: modifiers.to_vec(),
+            key: ShortcutKey::Char(key),
+        }
+    }
+
+    /// Parses a chord like `ctrl+shift+v`; names are case-insensitive and letters are
+    /// lowercased, so `Ctrl+V` is the same shortcut.
+    pub fn parse(chord: &str) -> Result<Self> {
+        if chord.trim().is_empty() {
+            anyhow::bail!("empty shortcut");
+        }
+        let mut parts: Vec<String> = chord
+            .split('+')
+            .map(|part| part.trim().to_lowercase())
+            .collect();
+        if parts.iter().any(String::is_empty) {
+            anyhow::bail!("missing key or modifier around \"+\"");
+        }
+        let key = parts.pop().unwrap_or_default();
+        let modifier_names: Vec<&str> = MODIFIER_NAMES.iter().map(|(name, _)| *name).collect();
+        let modifier = |name: &str| {
+            MODIFIER_NAMES
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, modifier)| *modifier)
+        };
+        let modifiers = parts
+            .iter()
+            .map(|name| {
+                modifier(name)
+                    .ok_or_else(|| anyhow::anyhow!(unknown("modifier", name, &modifier_names)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if modifier(&key).is_some() {
+            anyhow::bail!("no key after the modifiers, e.g. {}+v", chord.trim());
+        }
+        let mut chars = key.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => ShortcutKey::Char(c),
+            _ => {
+                let key_names: Vec<&str> =
+                    SHORTCUT_KEY_NAMES.iter().map(|(name, _)| *name).collect();
+                SHORTCUT_KEY_NAMES
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, key)| *key)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "{} (use a single character or Return, Tab, space, Escape, Insert, Delete or BackSpace)",
+                            unknown("key", &key, &key_names)
+                        )
+                    })?
+            }
+        };
+        Ok(Self { modifiers, key })
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier)?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// How a `paste_overrides` entry inserts the text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PasteAction {
+    /// Type it key by key.
+    Type,
+    /// Paste it with this shortcut.
+    Shortcut(Shortcut),
+}
+
+impl fmt::Display for PasteAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasteAction::Type => write!(f, "type"),
+            PasteAction::Shortcut(shortcut) => write!(f, "{}", shortcut),
+        }
+    }
+}
+
+/// A `paste_overrides` entry: a paste shortcut ("type" to type, "paste" for the window's
+/// default shortcut), or a table that can also send keys after the text, e.g.
+/// `{ paste = "ctrl+v", after = "Return" }`. Shortcuts are checked when the config loads.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(try_from = "RawPasteOverride", into = "RawPasteOverride")]
+pub struct PasteOverride {
+    /// `None` pastes with the window's default shortcut.
+    pub paste: Option<PasteAction>,
+    /// Keys sent after the text.
+    pub after: Vec<Shortcut>,
+}
+
+/// A `paste_overrides` entry as written in the config.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RawPasteOverride {
+    Shortcut(String),
+    Table {
+        paste: Option<String>,
+        /// xdotool-style keys sent after the text, separated by spaces.
+        after: Option<String>,
+    },
+}
+
+impl TryFrom<RawPasteOverride> for PasteOverride {
+    type Error = String;
+
+    fn try_from(raw: RawPasteOverride) -> std::result::Result<Self, Self::Error> {
+        let (paste, after) = match raw {
+            RawPasteOverride::Shortcut(paste) => (Some(paste), None),
+            RawPasteOverride::Table { paste, after } => (paste, after),
+        };
+        let paste = match paste {
+            Some(value) if value.eq_ignore_ascii_case("type") => Some(PasteAction::Type),
+            Some(value) if !value.eq_ignore_ascii_case("paste") => Some(PasteAction::Shortcut(
+                Shortcut::parse(&value)
+                    .map_err(|e| format!("invalid paste shortcut \"{}\": {}", value, e))?,
+            )),
+            _ => None,
+        };
+        let after = after
+            .iter()
+            .flat_map(|after| after.split_whitespace())
+            .map(|chord| {
+                Shortcut::parse(chord)
+                    .map_err(|e| format!("invalid after key \"{}\": {}", chord, e))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Self { paste, after })
+    }
+}
+
+impl From<PasteOverride> for RawPasteOverride {
+    fn from(entry: PasteOverride) -> Self {
+        let paste = entry.paste.map(|paste| paste.to_string());
+        if entry.after.is_empty() {
+            return RawPasteOverride::Shortcut(paste.unwrap_or_else(|| "paste".to_string()));
+        }
+        let after: Vec<String> = entry.after.iter().map(ToString::to_string).collect();
+        RawPasteOverride::Table {
+            paste,
+            after: Some(after.join(" ")),
+        }
+    }
+}
+
+/// What the macOS paste restores on the clipboard afterwards.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardRestore {
+    /// Whatever was there, images included.
+    #[default]
+    All,
+    /// Only plain text; non-text contents are left replaced by the transcript.
+    Text,
+    /// Leave the transcript on the clipboard.
+    Never,
+}
+
+/// How text gets into the focused window on Linux.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InjectionMethod {
+    /// Set the clipboard and send the paste shortcut.
+    #[default]
+    Paste,
+    /// Type the text key by key, `typing_delay_ms` apart; for apps that ignore synthetic pastes.
+    Type,
+}
+
+/// How the PTT keys start and stop a recording.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Activation {
+    /// Record while the keys are held.
+    #[default]
+    Hold,
+    /// A tap (press and release) starts recording, the next tap stops it.
+    Toggle,
+}
+
+/// How the PTT keys are read.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputBackend {
+    /// Wait for key events from /dev/input (Linux), polling when it can't be read.
+    #[default]
+    Events,
+    /// Ask for the held keys every 20 ms.
+    Poll,
+}
+
+/// How log lines look on the console.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Only the message, with its emoji.
+    #[default]
+    Emoji,
+    /// Timestamp, level and module before every message, like the log file.
+    Full,
+}
+
+/// How the feedback sounds are played.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SoundBackend {
+    /// In-process playback of the configured sound files.
+    #[default]
+    Rodio,
+    /// Short tones bundled into the binary, ignores the sound paths.
+    Builtin,
+    /// Spawn pw-play, paplay, ffplay or aplay (Linux) or afplay (macOS) for every sound.
+    External,
+}
+
+/// A key `ptt_key`, `[bindings]`, `profile_key` and `repeat_key` can use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PttKey {
+    /// A key device_query reports.
+    Key(Keycode),
+    /// A Linux input event code device_query has no name for, read from /dev/input.
+    Code(u16),
+}
+
+impl PttKey {
+    /// Parses a device_query name like "RControl", an evdev name like "KEY_MENU" or a raw
+    /// code like "code:183"; evdev keys that device_query knows become its key.
+    fn parse(name: &str) -> std::result::Result<Self, String> {
+        if let Ok(key) = Keycode::from_str(name) {
+            return Ok(PttKey::Key(key));
+        }
+        let code = match name.strip_prefix("code:") {
+            Some(number) => number
+                .trim()
+                .parse()
+                .ok()
+                .filter(|code| *code <= evdev::KEY_MAX)
+                .ok_or_else(|| format!("invalid key code '{}' (0 to {})", name, evdev::KEY_MAX))?,
+            None => evdev::code(name).ok_or_else(|| format!("unknown key '{}'", name))?,
+        };
+        match PttKey::from_code(code) {
+            PttKey::Code(_) if !cfg!(target_os = "linux") => {
+                Err(format!("'{}' can only be read on Linux", name))
+            }
+            key => Ok(key),
+        }
+    }
+
+    /// The key for a Linux input event code: device_query's when it knows the key.
+    pub fn from_code(code: u16) -> Self {
+        evdev::keycode(code).map_or(PttKey::Code(code), PttKey::Key)
+    }
+}
+
+impl fmt::Display for PttKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PttKey::Key(key) => write!(f, "{}", key),
+            PttKey::Code(code) => match evdev::name(*code) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "code:{}", code),
+            },
+        }
+    }
+}
+
+/// Parses a PTT key or a "+"-separated combination like "LControl+Space" into its keys.
+fn parse_ptt_keys(spec: &str) -> Result<Vec<PttKey>> {
+    parse_keys("ptt_key", spec)
+}
+
+/// Parses the keys of `setting`, reporting every name that isn't a key.
+fn parse_keys(setting: &str, spec: &str) -> Result<Vec<PttKey>> {
+    let mut keys = Vec::new();
+    let mut invalid = Vec::new();
+    for name in spec.split('+').map(str::trim) {
+        if name.is_empty() {
+            invalid.push("empty key name".to_string());
+            continue;
+        }
+        match PttKey::parse(name) {
+            Ok(key) if !keys.contains(&key) => keys.push(key),
+            Ok(_) => {}
+            Err(e) => invalid.push(e),
+        }
+    }
+    if !invalid.is_empty() {
+        anyhow::bail!(
+            "Invalid {} '{}' ({}), expected key names like RControl, LControl+Space, KEY_MENU or code:183 (see voice-ptt list-keys)",
+            setting,
+            spec,
+            invalid.join(", ")
+        );
+    }
+    Ok(keys)
+}
+
+/// Key names joined with "+", as shown in the "Hold [...] to speak" messages.
+pub fn keys_label(keys: &[PttKey]) -> String {
+    let names: Vec<String> = keys.iter().map(ToString::to_string).collect();
+    names.join("+")
+}
+
+/// Settings a `[bindings]` key records with instead of the base config.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Binding {
+    /// Spoken language; "" detects it even when the base config sets one.
+    pub language: Option<String>,
+    /// `true` translates into English, `false` keeps the spoken language.
+    pub translate: Option<bool>,
+    pub model: Option<String>,
+    pub prompt: Option<String>,
+}
+
+impl Binding {
+    /// `config` with this binding's settings applied.
+    fn apply(&self, config: &AppConfig) -> AppConfig {
+        let mut config = config.clone();
+        if let Some(language) = &self.language {
+            config.language = (!language.is_empty()).then(|| language.clone());
+        }
+        if let Some(translate) = self.translate {
+            config.mode = if translate {
+                TranscriptionMode::Translate
+            } else {
+                TranscriptionMode::Transcribe
+            };
+        }
+        if let Some(model) = &self.model {
+            config.model = model.clone();
+        }
+        if let Some(prompt) = &self.prompt {
+            config.prompt = Some(prompt.clone());
+        }
+        config
+    }
+
+    /// What the binding changes, e.g. "language ru, translate".
+    fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match self.language.as_deref() {
+            Some("") => parts.push("detected language".to_string()),
+            Some(language) => parts.push(format!("language {}", language)),
+            None => {}
+        }
+        match self.translate {
+            Some(true) => parts.push("translate".to_string()),
+            Some(false) => parts.push("transcribe".to_string()),
+            None => {}
+        }
+        if let Some(model) = &self.model {
+            parts.push(format!("model {}", model));
+        }
+        if self.prompt.is_some() {
+            parts.push("own prompt".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// A key or combination that records, with the config its recordings are transcribed with.
+pub struct PttBinding {
+    pub keys: Vec<PttKey>,
+    pub config: AppConfig,
+    /// What the binding changes compared to the base config; empty for `ptt_key`.
+    pub summary: String,
+    /// Whether the binding sets its own language, which `language_overrides` then leave alone.
+    pub sets_language: bool,
+}
+
+impl PttBinding {
+    pub fn label(&self) -> String {
+        keys_label(&self.keys)
+    }
+
+    /// Whether all of the binding's keys are in `held`.
+    pub fn is_held(&self, held: &[PttKey]) -> bool {
+        self.keys.iter().all(|key| held.contains(key))
+    }
+}
+
+/// The binding whose keys are all held. The one with the most keys wins, so a held
+/// "LControl+Space" doesn't also trigger "LControl".
+pub fn held_binding(bindings: &[PttBinding], held: &[PttKey]) -> Option<usize> {
+    (0..bindings.len())
+        .filter(|&i| bindings[i].is_held(held))
+        .max_by_key(|&i| bindings[i].keys.len())
+}
+
+/// Keys of a `paste_overrides` entry written as a table.
+const PASTE_OVERRIDE_KEYS: &[&str] = &["paste", "after"];
+
+/// Something wrong with a config file. Loading collects all of them into one report.
+#[derive(Debug, PartialEq)]
+struct ConfigProblem {
+    /// Top-level key the problem is about, used to find its line.
+    key: Option<String>,
+    line: Option<usize>,
+    message: String,
+}
+
+impl ConfigProblem {
+    fn new(key: &str, message: String) -> Self {
+        Self {
+            key: Some(key.to_string()),
+            line: None,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Names of the fields `T` reads, taken from what its derived `Deserialize` asks for.
+fn struct_fields<T: serde::de::DeserializeOwned>() -> &'static [&'static str] {
+    let mut probe = FieldProbe(&[]);
+    let _ = T::deserialize(&mut probe);
+    probe.0
+}
+
+/// Deserializer that only records the field list of the struct requested from it.
+struct FieldProbe(&'static [&'static str]);
+
+impl<'de> serde::Deserializer<'de> for &mut FieldProbe {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: serde::de::Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        Err(serde::de::Error::custom("only the field names are read"))
+    }
+
+    fn deserialize_struct<V: serde::de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.0 = fields;
+        self.deserialize_any(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if a == *b {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The entry of `known` that `name` is most likely a typo of.
+fn suggest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 4).max(2);
+    known
+        .iter()
+        .map(|candidate| (edit_distance(&name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// "unknown <what> <name>", with the closest entry of `known` as a suggestion.
+fn unknown(what: &str, name: &str, known: &[&str]) -> String {
+    match suggest(name, known) {
+        Some(candidate) => format!("unknown {} {}, did you mean {}?", what, name, candidate),
+        None => format!("unknown {} {}", what, name),
+    }
+}
+
+/// Parses and checks a config file, collecting every problem instead of stopping at the first.
+fn parse_config(content: &str) -> std::result::Result<AppConfig, Vec<ConfigProblem>> {
+    let line_of = |offset: usize| content[..offset.min(content.len())].matches('\n').count() + 1;
+    let table = toml::de::DeTable::parse(content).map_err(|e| {
+        vec![ConfigProblem {
+            key: None,
+            line: e.span().map(|span| line_of(span.start)),
+            message: e.message().trim().to_string(),
+        }]
+    })?;
+    let table = table.get_ref();
+
+    let fields = struct_fields::<AppConfig>();
+    let mut problems = Vec::new();
+    for (key, value) in table {
+        if !fields.contains(&key.get_ref().as_ref()) {
+            problems.push(ConfigProblem {
+                key: None,
+                line: Some(line_of(key.span().start)),
+                message: unknown("key", key.get_ref(), fields),
+            });
+        } else {
+            // Tables of tables whose entries serde would read without complaining about typos
+            let known = match key.get_ref().as_ref() {
+                "paste_overrides" => PASTE_OVERRIDE_KEYS,
+                "bindings" => struct_fields::<Binding>(),
+                "profiles" => struct_fields::<AppConfig>(),
+                _ => continue,
+            };
+            let toml::de::DeValue::Table(section) = value.get_ref() else {
+                continue;
+            };
+            for (entry_name, entry) in section {
+                let toml::de::DeValue::Table(entry) = entry.get_ref() else {
+                    continue;
+                };
+                for name in entry.keys() {
+                    if !known.contains(&name.get_ref().as_ref()) {
+                        problems.push(ConfigProblem {
+                            key: None,
+                            line: Some(line_of(name.span().start)),
+                            message: unknown(
+                                &format!("key in {}.{}:", key.get_ref(), entry_name.get_ref()),
+                                name.get_ref(),
+                                known,
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    let config = match toml::from_str::<AppConfig>(content) {
+        Ok(config) => {
+            problems.extend(config.validate());
+            Some(config)
+        }
+        Err(e) => {
+            let span = e.span().unwrap_or_default();
+            let within = |start: usize, end: usize| (start..end).contains(&span.start);
+            // The entry too for tables, whose [section] entries lie outside the header's span
+            let found = table.iter().find_map(|(key, value)| {
+                let entry = match value.get_ref() {
+                    toml::de::DeValue::Table(section) => section
+                        .iter()
+                        .find(|(entry, value)| within(entry.span().start, value.span().end))
+                        .map(|(entry, _)| entry.get_ref().to_string()),
+                    _ => None,
+                };
+                (entry.is_some() || within(key.span().start, value.span().end))
+                    .then(|| (key.get_ref().to_string(), entry))
+            });
+            let message = e.message().trim();
+            problems.push(ConfigProblem {
+                line: Some(line_of(span.start)),
+                message: match &found {
+                    Some((key, Some(entry))) => format!("{}.{}: {}", key, entry, message),
+                    Some((key, None)) => format!("{}: {}", key, message),
+                    None => message.to_string(),
+                },
+                key: found.map(|(key, _)| key),
+            });
+            None
+        }
+    };
+    for problem in &mut problems {
+        if problem.line.is_none() {
+            problem.line = table
+                .keys()
+                .find(|key| Some(key.get_ref().as_ref()) == problem.key.as_deref())
+                .map(|key| line_of(key.span().start));
+        }
+    }
+    match config {
+        Some(config) if problems.is_empty() => Ok(config),
+        _ => {
+            problems.sort_by_key(|problem| problem.line);
+            Err(problems)
+        }
+    }
+}
+
+/// Environment variable that points at the config file.
+pub const CONFIG_ENV: &str = "VOICE_PTT_CONFIG";
+
+/// Prefix of the environment variables that override config keys, e.g. `VOICE_PTT_MODEL`.
+pub const ENV_PREFIX: &str = "VOICE_PTT_";
+
+/// Reads the `VOICE_PTT_*` override of `key`: as a TOML value (`true`, `250`, `["a", "b"]`,
+/// `{ Alacritty = "type" }`) when the key takes one, else as a plain string.
+fn env_value(key: &str, raw: &str) -> std::result::Result<toml::Value, String> {
+    let literal = format!("value = {}", raw)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"));
+    let mut error = String::new();
+    for value in literal
+        .into_iter()
+        .chain([toml::Value::String(raw.to_string())])
+    {
+        let table = toml::Table::from_iter([(key.to_string(), value.clone())]);
+        match table.try_into::<AppConfig>() {
+            Ok(_) => return Ok(value),
+            Err(e) => error = e.message().trim().to_string(),
+        }
+    }
+    Err(error)
+}
+
+/// Where the config file came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigOrigin {
+    Flag,
+    Env,
+    /// `~/.config/voice-ptt/config.toml`.
+    Xdg,
+    /// Next to the executable, for portable installs.
+    ExeDir,
+    /// No config file was found.
+    Defaults,
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::Flag => write!(f, "--config"),
+            ConfigOrigin::Env => write!(f, "${}", CONFIG_ENV),
+            ConfigOrigin::Xdg => write!(f, "user config dir"),
+            ConfigOrigin::ExeDir => write!(f, "next to the executable"),
+            ConfigOrigin::Defaults => write!(f, "no config file, using defaults"),
+        }
+    }
+}
+
+/// Per-user directories of the app, and the executable's directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppDirs {
+    /// `~/.config/voice-ptt`.
+    pub config: Option<PathBuf>,
+    /// `~/.local/share/voice-ptt`.
+    pub data: Option<PathBuf>,
+    pub exe: PathBuf,
+}
+
+impl AppDirs {
+    pub fn detect() -> Result<Self> {
+        let exe_path = std::env::current_exe()?;
+        let exe = exe_path
+            .parent()
+            .context("Failed to get executable directory")?
+            .to_path_buf();
+        let dirs = directories::ProjectDirs::from("", "", "voice-ptt");
+        Ok(Self {
+            config: dirs.as_ref().map(|dirs| dirs.config_dir().to_path_buf()),
+            data: dirs.as_ref().map(|dirs| dirs.data_dir().to_path_buf()),
+            exe,
+        })
+    }
+
+    /// Directories searched for `.env`, most specific first: the config file's own
+    /// directory, the user config dir and the executable's directory.
+    pub fn env_dirs(&self, location: &ConfigLocation) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = location
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .into_iter()
+            .chain(self.config.clone())
+            .chain([self.exe.clone()])
+            .collect();
+        dirs.dedup();
+        dirs
+    }
+}
+
+/// The config file to load and where data files go.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigLocation {
+    pub path: Option<PathBuf>,
+    pub origin: ConfigOrigin,
+    /// The user data dir, or the executable's directory for portable installs.
+    pub data_dir: PathBuf,
+}
+
+/// Picks the config file: `--config`, then `$VOICE_PTT_CONFIG`, then the user config dir,
+/// then next to the executable. Paths given explicitly must exist.
+pub fn locate_config<F>(
+    flag: Option<&Path>,
+    env: Option<&Path>,
+    dirs: &AppDirs,
+    exists: F,
+) -> Result<ConfigLocation>
+where
+    F: Fn(&Path) -> bool,
+{
+    let user_data = || dirs.data.clone().unwrap_or_else(|| dirs.exe.clone());
+    let explicit = [(flag, ConfigOrigin::Flag), (env, ConfigOrigin::Env)];
+    if let Some((path, origin)) = explicit
+        .into_iter()
+        .find_map(|(path, origin)| Some((path?, origin)))
+    {
+        if !exists(path) {
+            anyhow::bail!(
+                "Config file {} (from {}) doesn't exist",
+                path.display(),
+                origin
+            );
+        }
+        return Ok(ConfigLocation {
+            path: Some(path.to_path_buf()),
+            origin,
+            data_dir: user_data(),
+        });
+    }
+    if let Some(path) = dirs.config.as_ref().map(|dir| dir.join("config.toml")) {
+        if exists(&path) {
+            return Ok(ConfigLocation {
+                path: Some(path),
+                origin: ConfigOrigin::Xdg,
+                data_dir: user_data(),
+            });
+        }
+    }
+    let path = dirs.exe.join("config.toml");
+    if exists(&path) {
+        return Ok(ConfigLocation {
+            path: Some(path),
+            origin: ConfigOrigin::ExeDir,
+            data_dir: dirs.exe.clone(),
+        });
+    }
+    Ok(ConfigLocation {
+        path: None,
+        origin: ConfigOrigin::Defaults,
+        data_dir: user_data(),
+    })
+}
+
+/// Feedback sound files for the current OS, see [`AppConfig::get_sound_paths`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoundPaths {
+    pub start: String,
+    pub end: String,
+    pub error: String,
+    pub cancel: String,
+    pub done: Option<String>,
+}
+
+/// Audio container/codec used when uploading recordings.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadFormat {
+    #[default]
+    Wav,
+    /// Mono 16 kHz Ogg/Opus, roughly 20x smaller than WAV.
+    Opus,
+    /// Lossless FLAC, roughly half the size of WAV.
+    Flac,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct AppConfig {
     pub ptt_key: String,
     pub typing_delay_ms: u64,
     pub initial_delay_ms: u64,
+    /// Transcription service: "openai", "groq", "deepgram", "local" or "vosk".
+    pub backend: String,
     pub model: String,
+    /// Overrides the OpenAI/Groq endpoint, e.g. for a self-hosted OpenAI-compatible server.
+    pub api_base_url: Option<String>,
+    /// "keyring" reads the key from the desktop keyring before the sources below.
+    pub api_key_source: ApiKeySource,
+    /// Several keys for the backend, tried in turn when one is rate limited or out of quota.
+    /// Used instead of the sources below; a comma-separated `OPENAI_API_KEY` works too.
+    pub api_keys: Vec<String>,
+    /// File holding the API key, used when the backend's environment variable isn't set.
+    pub api_key_file: Option<PathBuf>,
+    /// Shell command printing the API key (e.g. `pass show openai`), tried after `api_key_file`.
+    pub api_key_command: Option<String>,
+    /// GGML/GGUF model file for `backend = "local"`.
+    pub model_path: Option<PathBuf>,
+    /// Unpacked model directory for `backend = "vosk"`.
+    pub vosk_model_path: Option<PathBuf>,
+    pub mode: TranscriptionMode,
+    /// How often a failed upload is retried on network errors, 429 and 5xx responses.
+    pub api_retries: u32,
+    /// Delay before the first retry; doubles with every further attempt.
+    pub api_retry_base_ms: u64,
+    /// Longest wait honored from a rate limit's `Retry-After` before retrying.
+    pub rate_limit_max_wait_secs: u64,
+    /// Largest payload sent in one request (0 = no limit).
+    pub max_upload_bytes: usize,
+    /// Split larger recordings at pauses and transcribe the parts one after another.
+    pub split_long_uploads: bool,
+    pub api_connect_timeout_secs: u64,
+    /// Overall request timeout for a short recording.
+    pub api_timeout_secs: u64,
+    /// Extra request time allowed per minute of recorded audio.
+    pub api_timeout_per_minute_secs: u64,
+    /// Opens the API connection at startup so the first transcription isn't slowed by the handshake.
+    pub warmup_on_start: bool,
+    /// Repeats the warm-up request this often to keep the connection open (0 = disabled).
+    pub keepalive_secs: u64,
+    /// HTTP or SOCKS proxy for API requests; `HTTPS_PROXY`/`ALL_PROXY` are used when unset.
+    pub proxy_url: Option<String>,
+    /// Hosts that bypass the proxy (`NO_PROXY` syntax); defaults to `NO_PROXY`.
+    pub no_proxy: Option<String>,
     pub language: Option<String>,
+    /// Language per window class (or `title:<regex>` on the window title) focused when a
+    /// recording stops; "" detects it.
+    pub language_overrides: HashMap<String, String>,
+    /// Text passed as Whisper's `prompt`, e.g. to teach it product names.
+    pub prompt: Option<String>,
+    /// Terms joined into the prompt when no explicit `prompt` is set.
+    pub vocabulary: Vec<String>,
+    /// Sampling temperature (0.0-1.0); omitted from the request when unset.
+    pub temperature: Option<f32>,
+    /// Logs at debug level (window classes, paste shortcuts, transcripts, request details)
+    /// and requests `verbose_json` for the detected language, duration and segment count.
+    pub verbose: bool,
+    /// "emoji" prints only the messages; "full" adds a timestamp, level and module.
+    pub log_format: LogFormat,
+    /// Also appends every log line, with timestamps, to `<log_file>.<date>`, a new file every
+    /// day; the last 7 are kept.
+    pub log_file: Option<PathBuf>,
+    /// "srt" or "vtt" also saves the timestamped transcript to `subtitles_dir` (openai/groq).
+    pub transcript_format: TranscriptFormat,
+    /// Defaults to a `subtitles` folder next to the config file.
+    pub subtitles_dir: Option<PathBuf>,
+    /// Transcripts matching one of these are dropped instead of typed: a phrase compared
+    /// case-insensitively with the whole transcript, or a `/regex/`.
+    pub hallucination_filters: Vec<String>,
+    /// Words or phrases (whole words, any case) or `re:<regex>` keys replaced in every
+    /// transcript before it is typed.
+    pub replacements: BTreeMap<String, String>,
+    /// Removes filler words like "um" or "эм" from transcripts.
+    pub strip_fillers: bool,
+    /// Filler words and phrases for `strip_fillers`; empty uses the defaults for English and
+    /// `language`.
+    pub filler_words: Vec<String>,
+    /// Uppercases the first letter of the transcript and of every sentence.
+    pub auto_capitalize: bool,
+    /// Punctuation mark added when a transcript ends with a word, like "."; empty adds none.
+    pub ensure_final_punctuation: String,
+    /// Types spoken punctuation and formatting ("comma", "new line", ...) as characters.
+    pub spoken_commands: bool,
+    /// More spoken commands, or other text for the built-in ones ("" turns one off).
+    pub spoken_command_phrases: BTreeMap<String, String>,
+    /// With `verbose`, transcripts scoring below this confidence (0.0-1.0) aren't typed (0 = off).
+    pub min_confidence: f64,
+    /// Copies a discarded low-confidence transcript to the clipboard instead of dropping it.
+    pub low_confidence_to_clipboard: bool,
+    /// Streams audio to OpenAI's realtime API while the key is held (cpal capture only).
+    pub streaming: bool,
+    pub streaming_model: String,
+    /// Aborts a transcription that hasn't started typing when a new recording begins.
+    pub cancel_previous: bool,
+    /// On Ctrl+C or SIGTERM, how many seconds a transcription in flight gets to be typed
+    /// before it is dropped (0 = quit right away).
+    pub shutdown_grace_secs: u64,
+    /// Identical recordings within this many seconds aren't uploaded again (0 = disabled).
+    pub dedupe_window_secs: u64,
+    pub dedupe_action: DedupeAction,
+    /// Sends every transcript through a chat model before typing it.
+    pub postprocess_enabled: bool,
+    pub postprocess_model: String,
+    /// System prompt telling the chat model how to clean up the transcript.
+    pub postprocess_prompt: String,
+    /// Timeout for one post-processing request, separate from the transcription timeouts.
+    pub postprocess_timeout_secs: u64,
+    /// Appends the timing of every transcription to `stats_file`.
+    pub stats_enabled: bool,
+    /// Defaults to `stats.jsonl` next to the config file.
+    pub stats_file: Option<PathBuf>,
+    /// Price per minute of audio used for `voice-ptt stats` cost estimates.
+    pub price_per_minute: f64,
+    /// Recordings that failed to upload because the network was down are kept in `queue_dir`
+    /// and retried every `queue_retry_secs` (0 = only with `voice-ptt flush`).
+    pub offline_queue_enabled: bool,
+    /// Defaults to a `queue` folder next to the config file.
+    pub queue_dir: Option<PathBuf>,
+    pub queue_retry_secs: u64,
+    /// The oldest queued recordings are dropped beyond this many.
+    pub queue_max_items: usize,
+    /// Where stats, the offline queue, subtitles and recordings go by default.
+    #[serde(skip)]
+    pub data_dir: PathBuf,
     pub sound_enabled: bool,
+    pub sound_backend: SoundBackend,
     pub sound_start_path: String,
     pub sound_end_path: String,
+    /// Played when transcribing or injecting fails.
+    pub sound_error_path: String,
+    /// Played when `cancel_key` throws a recording away.
+    pub sound_cancel_path: String,
+    /// Played after a transcript was injected.
+    pub sound_done_path: Option<String>,
     #[allow(dead_code)]
     pub macos_sound_start_path: Option<String>,
     #[allow(dead_code)]
     pub macos_sound_end_path: Option<String>,
     #[allow(dead_code)]
+    pub macos_sound_error_path: Option<String>,
+    #[allow(dead_code)]
+    pub macos_sound_cancel_path: Option<String>,
+    #[allow(dead_code)]
+    pub macos_sound_done_path: Option<String>,
+    #[allow(dead_code)]
     pub linux_sound_start_path: Option<String>,
     #[allow(dead_code)]
     pub linux_sound_end_path: Option<String>,
-    #[serde(default)]
-    pub paste_overrides: HashMap<String, String>,
+    #[allow(dead_code)]
+    pub linux_sound_error_path: Option<String>,
+    #[allow(dead_code)]
+    pub linux_sound_cancel_path: Option<String>,
+    #[allow(dead_code)]
+    pub linux_sound_done_path: Option<String>,
+    /// How long the macOS paste waits before restoring the clipboard.
+    #[allow(dead_code)]
+    pub macos_paste_delay_ms: u64,
+    #[allow(dead_code)]
+    pub macos_restore_clipboard: ClipboardRestore,
+    /// Opens System Settings at the right pane when a permission is missing at startup.
+    #[allow(dead_code)]
+    pub macos_open_privacy_settings: bool,
+    pub output_mode: OutputMode,
+    /// Appended to every transcript before it is injected, e.g. " " or "\n".
+    pub append_suffix: String,
+    /// Prepends a space when the previous injection didn't end in whitespace.
+    pub auto_space: bool,
+    pub injection_backend: InjectionBackend,
+    pub injection_method: InjectionMethod,
+    /// Marks clipboard contents so clipboard managers keep transcripts out of their history
+    /// (X11; not possible with wl-copy).
+    pub clipboard_private: bool,
+    /// Paste shortcut per window class (or `title:<regex>` on the window title), or "type" to
+    /// type into that window instead.
+    pub paste_overrides: HashMap<String, PasteOverride>,
+    pub activation: Activation,
+    /// In hold mode, recording starts once the keys have been held this long; shorter presses
+    /// are ignored (0 = right away).
+    pub hold_threshold_ms: u64,
+    /// In hold mode, a second press within this many milliseconds of the first locks the
+    /// recording on until the next tap (0 = off).
+    pub double_tap_ms: u64,
+    /// Recordings shorter than this are dropped without a request (0 = keep all).
+    pub min_recording_ms: u64,
+    /// A "still recording" notification every this many seconds of a toggled or locked
+    /// recording (0 = never).
+    pub toggle_reminder_secs: u64,
+    /// Key events from /dev/input wake the main loop, so it sleeps between recordings;
+    /// polling checks the keyboard every 20 ms.
+    pub input_backend: InputBackend,
+    /// More PTT keys, each recording with its own language, mode, model or prompt. `ptt_key`
+    /// records with the base settings unless it is listed here too.
+    pub bindings: BTreeMap<String, Binding>,
+    /// `[profiles.<name>]` tables of settings that replace the base ones while the profile is
+    /// active (`--profile` or `profile_key`); tables like `paste_overrides` are merged.
+    /// [`STARTUP_KEYS`] can't be set here.
+    pub profiles: BTreeMap<String, toml::Table>,
+    /// Key or combination that switches to the next profile, and back to the base settings
+    /// after the last one.
+    pub profile_key: Option<String>,
+    /// Key or combination that types the last transcript again when tapped between
+    /// recordings, pasted for the window focused now.
+    pub repeat_key: Option<String>,
+    /// Key or combination that throws the recording in progress away, without uploading or
+    /// typing it; empty for none. It does nothing between recordings.
+    pub cancel_key: String,
+    /// Key or combination that sends the last recording whose transcription failed again when
+    /// tapped between recordings, and types the result.
+    pub retry_key: Option<String>,
+    /// How many recordings whose transcription failed are kept in memory for `retry_key`
+    /// (0 = none).
+    pub keep_failed_recordings: usize,
+    /// Key or combination that pauses listening to the other keys, e.g. during a screen
+    /// share, and resumes it when tapped again.
+    pub pause_key: Option<String>,
+    /// Pause between the text and the `after` keys of a paste override.
+    pub after_keys_delay_ms: u64,
+    pub window_info: WindowInfo,
+    /// Shows a persistent "Recording…"/"Processing…" notification (Linux).
+    pub status_notifications: bool,
+    /// Shows a live input level meter on stderr while recording, when run in a terminal
+    /// without `--quiet`.
+    pub meter_enabled: bool,
+    /// Recordings longer than this many seconds are streamed to a temp file instead of RAM (0 = never).
+    pub spill_after_secs: u64,
+    /// Base delay between attempts to reopen a disconnected input device.
+    pub device_retry_interval_ms: u64,
+    pub capture_backend: CaptureBackend,
+    pub capture_source: CaptureSource,
+    /// Name fragment of the cpal input device to record the mic from (default device if unset).
+    pub input_device: Option<String>,
+    /// Device (cpal) or node (command-line recorders) to use when `capture_source = "monitor"`.
+    pub monitor_device: Option<String>,
+    /// Input sample rate to request from the device (0 = device default).
+    pub preferred_sample_rate: u32,
+    /// Source for the command-line recorders: `pw-record --target`, `parec --device` or `arecord -D`.
+    pub pw_target: Option<String>,
+    pub pw_rate: u32,
+    pub pw_channels: u16,
+    pub upload_format: UploadFormat,
+    /// Keeps a copy of every uploaded recording in `recordings_dir`.
+    pub keep_last_recording: bool,
+    pub recordings_dir: Option<PathBuf>,
+    /// How many archived recordings to keep before the oldest are deleted.
+    pub keep_last_n: usize,
+    /// Cutoff of the high-pass filter applied before upload, removes rumble (0 = disabled).
+    pub highpass_hz: f32,
+    /// Warn when more than this percentage of samples is clipped (0 = never warn).
+    pub clipping_warn_percent: f32,
 }
 
 impl Default for AppConfig {
@@ -33,73 +1325,769 @@ impl Default for AppConfig {
         Self {
             ptt_key: "RControl".to_string(),
             typing_delay_ms: 50,
-            initial_delay_ms: 150,
+            initial_delay_ms: 200,
+            backend: "openai".to_string(),
             model: "whisper-1".to_string(),
+            api_base_url: None,
+            api_key_source: ApiKeySource::Env,
+            api_keys: Vec::new(),
+            api_key_file: None,
+            api_key_command: None,
+            model_path: None,
+            vosk_model_path: None,
+            mode: TranscriptionMode::Transcribe,
+            api_retries: 3,
+            api_retry_base_ms: 500,
+            rate_limit_max_wait_secs: 30,
+            max_upload_bytes: 25 * 1024 * 1024,
+            split_long_uploads: true,
+            api_connect_timeout_secs: 10,
+            api_timeout_secs: 60,
+            api_timeout_per_minute_secs: 30,
+            warmup_on_start: true,
+            keepalive_secs: 0,
+            proxy_url: None,
+            no_proxy: None,
             language: None,
+            language_overrides: HashMap::new(),
+            prompt: None,
+            vocabulary: Vec::new(),
+            temperature: None,
+            verbose: false,
+            log_format: LogFormat::Emoji,
+            log_file: None,
+            transcript_format: TranscriptFormat::Text,
+            subtitles_dir: None,
+            min_confidence: 0.0,
+            low_confidence_to_clipboard: false,
+            replacements: BTreeMap::new(),
+            strip_fillers: false,
+            filler_words: Vec::new(),
+            auto_capitalize: false,
+            ensure_final_punctuation: String::new(),
+            spoken_commands: false,
+            spoken_command_phrases: BTreeMap::new(),
+            hallucination_filters: DEFAULT_HALLUCINATION_FILTERS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            streaming: false,
+            streaming_model: "gpt-4o-mini-transcribe".to_string(),
+            cancel_previous: true,
+            shutdown_grace_secs: 5,
+            dedupe_window_secs: 10,
+            dedupe_action: DedupeAction::Reuse,
+            postprocess_enabled: false,
+            postprocess_model: "gpt-4o-mini".to_string(),
+            postprocess_prompt: "Fix punctuation and capitalization and remove filler words. Output only the corrected text.".to_string(),
+            postprocess_timeout_secs: 15,
+            stats_enabled: false,
+            stats_file: None,
+            price_per_minute: 0.006,
+            offline_queue_enabled: true,
+            queue_dir: None,
+            queue_retry_secs: 60,
+            queue_max_items: 50,
+            data_dir: PathBuf::new(),
             sound_enabled: true,
+            sound_backend: SoundBackend::Rodio,
             sound_start_path: "/usr/share/sounds/freedesktop/stereo/audio-volume-change.oga"
                 .to_string(),
             sound_end_path: "/usr/share/sounds/freedesktop/stereo/screen-capture.oga".to_string(),
+            sound_error_path: "/usr/share/sounds/freedesktop/stereo/dialog-error.oga".to_string(),
+            sound_cancel_path: "/usr/share/sounds/freedesktop/stereo/trash-empty.oga".to_string(),
+            sound_done_path: None,
             macos_sound_start_path: Some("/System/Library/Sounds/Tink.aiff".to_string()),
             macos_sound_end_path: Some("/System/Library/Sounds/Morse.aiff".to_string()),
+            macos_sound_error_path: Some("/System/Library/Sounds/Basso.aiff".to_string()),
+            macos_sound_cancel_path: Some("/System/Library/Sounds/Funk.aiff".to_string()),
+            macos_sound_done_path: None,
             linux_sound_start_path: Some(
                 "/usr/share/sounds/freedesktop/stereo/audio-volume-change.oga".to_string(),
             ),
             linux_sound_end_path: Some(
                 "/usr/share/sounds/freedesktop/stereo/screen-capture.oga".to_string(),
             ),
+            linux_sound_error_path: Some(
+                "/usr/share/sounds/freedesktop/stereo/dialog-error.oga".to_string(),
+            ),
+            linux_sound_cancel_path: Some(
+                "/usr/share/sounds/freedesktop/stereo/trash-empty.oga".to_string(),
+            ),
+            linux_sound_done_path: None,
+            macos_paste_delay_ms: 250,
+            macos_restore_clipboard: ClipboardRestore::All,
+            macos_open_privacy_settings: true,
+            output_mode: OutputMode::Inject,
+            append_suffix: String::new(),
+            auto_space: false,
+            injection_backend: InjectionBackend::Auto,
+            injection_method: InjectionMethod::Paste,
+            clipboard_private: false,
             paste_overrides: HashMap::new(),
+            activation: Activation::Hold,
+            hold_threshold_ms: 0,
+            double_tap_ms: 350,
+            min_recording_ms: 0,
+            toggle_reminder_secs: 120,
+            input_backend: InputBackend::Events,
+            bindings: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+            profile_key: None,
+            repeat_key: None,
+            cancel_key: "Escape".to_string(),
+            retry_key: None,
+            keep_failed_recordings: 1,
+            pause_key: None,
+            after_keys_delay_ms: 100,
+            window_info: WindowInfo::Auto,
+            status_notifications: false,
+            meter_enabled: true,
+            spill_after_secs: 30,
+            device_retry_interval_ms: 1000,
+            capture_backend: CaptureBackend::Auto,
+            capture_source: CaptureSource::Mic,
+            input_device: None,
+            monitor_device: None,
+            preferred_sample_rate: 16000,
+            pw_target: None,
+            pw_rate: 16000,
+            pw_channels: 1,
+            upload_format: UploadFormat::Wav,
+            keep_last_recording: false,
+            recordings_dir: None,
+            keep_last_n: 10,
+            clipping_warn_percent: 1.0,
+            highpass_hz: 80.0,
         }
     }
 }
 
 impl AppConfig {
+    /// Loads the config file `location` found, or the defaults when there is none, with its
+    /// data directory.
+    pub fn load_located(location: &ConfigLocation) -> Result<Self> {
+        let config = match &location.path {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+        Ok(Self {
+            data_dir: location.data_dir.clone(),
+            ..config
+        })
+    }
+
     /// Loads configuration from a TOML file. Falls back to defaults if file is missing.
     pub fn load(path: &Path) -> Result<Self> {
+        let data_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
         if path.exists() {
             let content = fs::read_to_string(path)
                 .with_context(|| format!("Failed to read config file at {:?}", path))?;
-            let config: AppConfig =
-                toml::from_str(&content).with_context(|| "Failed to parse config.toml")?;
+            let mut config = parse_config(&content).map_err(|problems| {
+                let report: Vec<String> = problems
+                    .iter()
+                    .map(|problem| format!("  {}", problem))
+                    .collect();
+                anyhow::anyhow!("Invalid config file at {:?}:\n{}", path, report.join("\n"))
+            })?;
+            config.data_dir = data_dir;
+            for path in config.missing_sound_files() {
+                eprintln!(
+                    "⚠️ Sound file not found: {} (check config.toml), using the builtin tone.",
+                    path
+                );
+            }
+            let (_, dropped) = config.build_prompt();
+            if dropped > 0 {
+                eprintln!(
+                    "⚠️ vocabulary exceeds Whisper's prompt limit (~{} tokens), ignoring the last {} terms.",
+                    PROMPT_TOKEN_LIMIT, dropped
+                );
+            }
             Ok(config)
         } else {
-            Ok(Self::default())
+            Ok(Self {
+                data_dir,
+                ..Self::default()
+            })
+        }
+    }
+
+    pub fn stats_path(&self) -> PathBuf {
+        self.stats_file
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("stats.jsonl"))
+    }
+
+    /// Directory of the offline queue.
+    pub fn queue_path(&self) -> PathBuf {
+        self.queue_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("queue"))
+    }
+
+    /// Configured sound files that don't exist; the builtin backend doesn't read any, and
+    /// "builtin" selects the bundled tone.
+    pub fn missing_sound_files(&self) -> Vec<String> {
+        if !self.sound_enabled || self.sound_backend == SoundBackend::Builtin {
+            return Vec::new();
         }
+        let paths = self.get_sound_paths();
+        let mut missing: Vec<String> = [
+            Some(paths.start),
+            Some(paths.end),
+            Some(paths.error),
+            Some(paths.cancel),
+        ]
+        .into_iter()
+        .chain([paths.done])
+        .flatten()
+        .filter(|path| path != sound::BUILTIN_PATH && !Path::new(path).exists())
+        .collect();
+        missing.dedup();
+        missing
     }
 
-    /// Returns (start_path, end_path) based on current OS
-    pub fn get_sound_paths(&self) -> (String, String) {
+    /// Returns the sound files for the current OS: the OS-specific path when set, the generic
+    /// one otherwise.
+    pub fn get_sound_paths(&self) -> SoundPaths {
         #[cfg(target_os = "macos")]
+        let (start, end, error, cancel, done) = (
+            &self.macos_sound_start_path,
+            &self.macos_sound_end_path,
+            &self.macos_sound_error_path,
+            &self.macos_sound_cancel_path,
+            &self.macos_sound_done_path,
+        );
+        #[cfg(not(target_os = "macos"))]
+        let (start, end, error, cancel, done) = (
+            &self.linux_sound_start_path,
+            &self.linux_sound_end_path,
+            &self.linux_sound_error_path,
+            &self.linux_sound_cancel_path,
+            &self.linux_sound_done_path,
+        );
+        SoundPaths {
+            start: start.as_ref().unwrap_or(&self.sound_start_path).clone(),
+            end: end.as_ref().unwrap_or(&self.sound_end_path).clone(),
+            error: error.as_ref().unwrap_or(&self.sound_error_path).clone(),
+            cancel: cancel.as_ref().unwrap_or(&self.sound_cancel_path).clone(),
+            done: done.clone().or_else(|| self.sound_done_path.clone()),
+        }
+    }
+
+    /// Checks value ranges that the TOML types alone can't express and returns every problem.
+    fn validate(&self) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, key: &str, message: String| {
+            if !ok {
+                problems.push(ConfigProblem::new(key, message));
+            }
+        };
+        if let Err(e) = parse_ptt_keys(&self.ptt_key) {
+            check(false, "ptt_key", e.to_string());
+        }
+        for spec in self.bindings.keys() {
+            if let Err(e) = parse_keys("[bindings] key", spec) {
+                check(false, "bindings", e.to_string());
+            }
+        }
+        if let Some(spec) = &self.profile_key {
+            if let Err(e) = parse_keys("profile_key", spec) {
+                check(false, "profile_key", e.to_string());
+            }
+        }
+        // Every PTT press would also repeat, retry, pause, or cancel itself
+        let optional_keys = [
+            ("repeat_key", self.repeat_key.as_deref()),
+            ("retry_key", self.retry_key.as_deref()),
+            ("pause_key", self.pause_key.as_deref()),
+            (
+                "cancel_key",
+                Some(self.cancel_key.as_str()).filter(|spec| !spec.is_empty()),
+            ),
+        ];
+        for (name, spec) in optional_keys {
+            let Some(spec) = spec else {
+                continue;
+            };
+            match parse_keys(name, spec) {
+                Ok(extra) => {
+                    for ptt in std::iter::once(&self.ptt_key).chain(self.bindings.keys()) {
+                        let Ok(keys) = parse_keys("ptt_key", ptt) else {
+                            continue;
+                        };
+                        check(
+                            !extra.iter().all(|key| keys.contains(key)),
+                            name,
+                            format!(
+                                "{} {} is part of the PTT key {}, pick keys it doesn't hold",
+                                name, spec, ptt
+                            ),
+                        );
+                    }
+                }
+                Err(e) => check(false, name, e.to_string()),
+            }
+        }
+        check(
+            BACKENDS.contains(&self.backend.as_str()),
+            "backend",
+            format!(
+                "{} (available: {})",
+                unknown("backend", &self.backend, &BACKENDS),
+                BACKENDS.join(", ")
+            ),
+        );
+        check(
+            !self.model.trim().is_empty(),
+            "model",
+            "model must not be empty".to_string(),
+        );
+        check(
+            !self.streaming || !self.streaming_model.trim().is_empty(),
+            "streaming_model",
+            "streaming_model must not be empty with streaming = true".to_string(),
+        );
+        check(
+            !self.postprocess_enabled || !self.postprocess_model.trim().is_empty(),
+            "postprocess_model",
+            "postprocess_model must not be empty with postprocess_enabled = true".to_string(),
+        );
+        if let Some(temperature) = self.temperature {
+            check(
+                (0.0..=1.0).contains(&temperature),
+                "temperature",
+                format!(
+                    "temperature must be between 0.0 and 1.0, got {}",
+                    temperature
+                ),
+            );
+        }
+        check(
+            (0.0..=1.0).contains(&self.min_confidence),
+            "min_confidence",
+            format!(
+                "min_confidence must be between 0.0 and 1.0, got {}",
+                self.min_confidence
+            ),
+        );
+        let mut marks = self.ensure_final_punctuation.chars();
+        check(
+            self.ensure_final_punctuation.is_empty()
+                || (marks.next().is_some_and(|c| c.is_ascii_punctuation() || c == '。')
+                    && marks.next().is_none()),
+            "ensure_final_punctuation",
+            format!(
+                "ensure_final_punctuation must be \"\" or one punctuation mark like \".\", got {:?}",
+                self.ensure_final_punctuation
+            ),
+        );
+        for (key, value) in [
+            ("price_per_minute", self.price_per_minute),
+            ("highpass_hz", self.highpass_hz as f64),
+        ] {
+            check(
+                (0.0..).contains(&value),
+                key,
+                format!("{} must not be negative, got {}", key, value),
+            );
+        }
+        check(
+            self.hold_threshold_ms <= MAX_HOLD_THRESHOLD_MS,
+            "hold_threshold_ms",
+            format!(
+                "hold_threshold_ms must be at most {}, got {}",
+                MAX_HOLD_THRESHOLD_MS, self.hold_threshold_ms
+            ),
+        );
+        check(
+            (0.0..=100.0).contains(&self.clipping_warn_percent),
+            "clipping_warn_percent",
+            format!(
+                "clipping_warn_percent must be between 0 and 100, got {}",
+                self.clipping_warn_percent
+            ),
+        );
+        for (key, value) in [
+            ("api_timeout_secs", self.api_timeout_secs),
+            ("api_connect_timeout_secs", self.api_connect_timeout_secs),
+            ("postprocess_timeout_secs", self.postprocess_timeout_secs),
+            ("pw_rate", self.pw_rate.into()),
+            ("pw_channels", self.pw_channels.into()),
+        ] {
+            check(value > 0, key, format!("{} must be at least 1", key));
+        }
+        if let Err(e) = text::validate_filters(&self.hallucination_filters) {
+            check(false, "hallucination_filters", format!("{:#}", e));
+        }
+        if let Err(e) = text::validate_replacements(&self.replacements) {
+            check(false, "replacements", format!("{:#}", e));
+        }
+        if let Err(e) =
+            injector::validate_window_overrides("paste_overrides", &self.paste_overrides)
         {
-            let start = self
-                .macos_sound_start_path
-                .as_ref()
-                .unwrap_or(&self.sound_start_path);
-            let end = self
-                .macos_sound_end_path
-                .as_ref()
-                .unwrap_or(&self.sound_end_path);
-            (start.clone(), end.clone())
+            check(false, "paste_overrides", format!("{:#}", e));
         }
-        #[cfg(not(target_os = "macos"))]
+        if let Err(e) =
+            injector::validate_window_overrides("language_overrides", &self.language_overrides)
         {
-            let start = self
-                .linux_sound_start_path
-                .as_ref()
-                .unwrap_or(&self.sound_start_path);
-            let end = self
-                .linux_sound_end_path
-                .as_ref()
-                .unwrap_or(&self.sound_end_path);
-            (start.clone(), end.clone())
+            check(false, "language_overrides", format!("{:#}", e));
+        }
+        for (name, overrides) in &self.profiles {
+            let fixed: Vec<&str> = STARTUP_KEYS
+                .iter()
+                .copied()
+                .filter(|key| overrides.contains_key(*key))
+                .collect();
+            if !fixed.is_empty() {
+                problems.push(ConfigProblem::new(
+                    "profiles",
+                    format!(
+                        "profiles.{}: {} {} only read at startup and can't be set in a profile",
+                        name,
+                        fixed.join(", "),
+                        if fixed.len() == 1 { "is" } else { "are" }
+                    ),
+                ));
+            }
+            let profile = self.with_profile(name).map(|config| AppConfig {
+                profiles: BTreeMap::new(),
+                ..config
+            });
+            match profile {
+                Ok(profile) => problems.extend(
+                    profile
+                        .validate()
+                        .into_iter()
+                        .filter(|problem| {
+                            problem
+                                .key
+                                .as_ref()
+                                .is_some_and(|key| overrides.contains_key(key))
+                        })
+                        .map(|problem| {
+                            ConfigProblem::new(
+                                "profiles",
+                                format!("profiles.{}: {}", name, problem.message),
+                            )
+                        }),
+                ),
+                Err(e) => problems.push(ConfigProblem::new("profiles", format!("{:#}", e))),
+            }
+        }
+        problems
+    }
+
+    /// Prompt sent with every transcription: the explicit `prompt`, or the vocabulary list.
+    pub fn whisper_prompt(&self) -> Option<String> {
+        self.build_prompt().0
+    }
+
+    /// Builds the prompt and returns how many vocabulary terms didn't fit the token limit.
+    fn build_prompt(&self) -> (Option<String>, usize) {
+        if let Some(prompt) = &self.prompt {
+            return (Some(prompt.clone()), 0);
+        }
+        let mut prompt = String::new();
+        let mut used = 0;
+        for term in &self.vocabulary {
+            let candidate = if prompt.is_empty() {
+                term.clone()
+            } else {
+                format!("{}, {}", prompt, term)
+            };
+            if estimate_tokens(&candidate) > PROMPT_TOKEN_LIMIT {
+                break;
+            }
+            prompt = candidate;
+            used += 1;
+        }
+        let dropped = self.vocabulary.len() - used;
+        ((!prompt.is_empty()).then_some(prompt), dropped)
+    }
+
+    /// Directory for archived recordings.
+    pub fn recordings_path(&self) -> PathBuf {
+        self.recordings_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("recordings"))
+    }
+
+    /// Directory for saved subtitle files.
+    pub fn subtitles_path(&self) -> PathBuf {
+        self.subtitles_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("subtitles"))
+    }
+
+    /// Name fragment of the cpal input device to capture from, or `None` for the default mic.
+    pub fn input_device_pattern(&self) -> Option<&str> {
+        match self.capture_source {
+            CaptureSource::Mic => self.input_device.as_deref(),
+            CaptureSource::Monitor => Some(self.monitor_device.as_deref().unwrap_or("Monitor")),
+        }
+    }
+
+    /// Applies the command-line flags on top of the loaded file.
+    pub fn apply_overrides(&mut self, cli: &Cli) -> Result<()> {
+        if let Some(key) = &cli.ptt_key {
+            self.ptt_key = key.clone();
+        }
+        if let Some(language) = &cli.language {
+            self.language = Some(language.clone());
+        }
+        if let Some(model) = &cli.model {
+            self.model = model.clone();
+        }
+        if let Some(device) = &cli.device {
+            self.input_device = Some(device.clone());
+        }
+        if cli.no_sound {
+            self.sound_enabled = false;
+        }
+        if cli.verbose {
+            self.verbose = true;
+        }
+        parse_ptt_keys(&self.ptt_key).map(|_| ())
+    }
+
+    /// Applies `VOICE_PTT_<KEY>` variables from `vars` on top of the config file, so that
+    /// environment beats file beats defaults. Unknown `VOICE_PTT_*` names are warned about.
+    pub fn apply_env<I>(&mut self, vars: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let fields = struct_fields::<AppConfig>();
+        let vars: BTreeMap<String, String> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && name != CONFIG_ENV)
+            .collect();
+        let mut table = toml::Table::try_from(&*self).context("Failed to serialize the config")?;
+        let mut applied = Vec::new();
+        let mut errors = Vec::new();
+        for (name, raw) in &vars {
+            let key = name[ENV_PREFIX.len()..].to_lowercase();
+            if !fields.contains(&key.as_str()) {
+                match suggest(&key, fields) {
+                    Some(field) => eprintln!(
+                        "⚠️ Ignoring {}: no config key {}, did you mean {}{}?",
+                        name,
+                        key,
+                        ENV_PREFIX,
+                        field.to_uppercase()
+                    ),
+                    None => eprintln!("⚠️ Ignoring {}: no config key {}", name, key),
+                }
+                continue;
+            }
+            match env_value(&key, raw) {
+                Ok(value) => {
+                    table.insert(key.clone(), value);
+                    applied.push((name, key));
+                }
+                Err(message) => errors.push(format!("  {}={}: {}", name, raw, message)),
+            }
+        }
+        if applied.is_empty() && errors.is_empty() {
+            return Ok(());
+        }
+
+        let mut config: AppConfig = match table.try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                errors.push(format!("  {}", e.message().trim()));
+                AppConfig::default()
+            }
+        };
+        for problem in config.validate() {
+            if let Some((name, _)) = applied
+                .iter()
+                .find(|(_, key)| problem.key.as_deref() == Some(key.as_str()))
+            {
+                errors.push(format!("  {}: {}", name, problem.message));
+            }
+        }
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "Invalid config environment variables:\n{}",
+                errors.join("\n")
+            );
+        }
+        config.data_dir = std::mem::take(&mut self.data_dir);
+        *self = config;
+        Ok(())
+    }
+
+    /// The config with the `[profiles.<name>]` settings on top. Tables such as
+    /// `paste_overrides` or `bindings` gain the profile's entries instead of being replaced,
+    /// and `language = ""` detects the language.
+    pub fn with_profile(&self, name: &str) -> Result<AppConfig> {
+        let Some(overrides) = self.profiles.get(name) else {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "{} (defined: {})",
+                unknown("profile", name, &names),
+                if names.is_empty() {
+                    "none".to_string()
+                } else {
+                    names.join(", ")
+                }
+            );
+        };
+        let mut table = toml::Table::try_from(self).context("Failed to serialize the config")?;
+        for (key, value) in overrides {
+            match (table.get_mut(key), value) {
+                (Some(toml::Value::Table(base)), toml::Value::Table(entries)) => {
+                    base.extend(entries.clone())
+                }
+                _ => {
+                    table.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        let mut config: AppConfig = table
+            .try_into()
+            .map_err(|e: toml::de::Error| anyhow::anyhow!("{}", e.message().trim()))
+            .with_context(|| format!("Invalid profile '{}'", name))?;
+        if config.language.as_deref() == Some("") {
+            config.language = None;
+        }
+        config.data_dir = self.data_dir.clone();
+        Ok(config)
+    }
+
+    /// The [`STARTUP_KEYS`] whose values differ from `running`, which a reload can't change.
+    pub fn changed_startup_keys(&self, running: &AppConfig) -> Result<Vec<&'static str>> {
+        let table = toml::Table::try_from(self).context("Failed to serialize the config")?;
+        let running = toml::Table::try_from(running).context("Failed to serialize the config")?;
+        Ok(STARTUP_KEYS
+            .iter()
+            .copied()
+            .filter(|key| table.get(*key) != running.get(*key))
+            .collect())
+    }
+
+    /// The profile `profile_key` switches to from `current`: the next one by name, or `None`
+    /// (the base settings) after the last.
+    pub fn next_profile(&self, current: Option<&str>) -> Option<&str> {
+        let mut names = self.profiles.keys().map(String::as_str);
+        match current {
+            None => names.next(),
+            Some(current) => names.skip_while(|name| *name != current).nth(1),
+        }
+    }
+
+    /// The config as TOML, for `--print-config`, with `api_keys` masked.
+    pub fn to_toml(&self) -> Result<String> {
+        let config = Self {
+            api_keys: vec!["<hidden>".to_string(); self.api_keys.len()],
+            ..self.clone()
+        };
+        toml::to_string(&config).context("Failed to serialize the config")
+    }
+
+    /// The `[bindings]` keys and `ptt_key`, each with the config its recordings use.
+    pub fn ptt_bindings(&self) -> Vec<PttBinding> {
+        let mut bindings: Vec<PttBinding> = self
+            .bindings
+            .iter()
+            .filter_map(|(spec, binding)| match parse_keys("[bindings] key", spec) {
+                Ok(keys) => Some(PttBinding {
+                    keys,
+                    config: binding.apply(self),
+                    summary: binding.summary(),
+                    sets_language: binding.language.is_some(),
+                }),
+                Err(e) => {
+                    eprintln!("⚠️ {}, skipping it", e);
+                    None
+                }
+            })
+            .collect();
+        let keys = self.get_ptt_keycodes();
+        let listed = bindings.iter().any(|binding| {
+            binding.keys.len() == keys.len() && binding.keys.iter().all(|key| keys.contains(key))
+        });
+        if !listed {
+            bindings.insert(
+                0,
+                PttBinding {
+                    keys,
+                    config: self.clone(),
+                    summary: String::new(),
+                    sets_language: false,
+                },
+            );
+        }
+        bindings
+    }
+
+    /// Parses the configured PTT key or combination into its keys, all of which have to be
+    /// held to record. Loading already rejected an invalid `ptt_key`, so the fallback only
+    /// covers configs built in code.
+    pub fn get_ptt_keycodes(&self) -> Vec<PttKey> {
+        parse_ptt_keys(&self.ptt_key).unwrap_or_else(|e| {
+            eprintln!("{}, defaulting to RControl", e);
+            vec![PttKey::Key(Keycode::RControl)]
+        })
+    }
+
+    /// The keys of `repeat_key`, empty when it isn't set.
+    pub fn get_repeat_keycodes(&self) -> Vec<PttKey> {
+        let Some(spec) = &self.repeat_key else {
+            return Vec::new();
+        };
+        parse_keys("repeat_key", spec).unwrap_or_else(|e| {
+            eprintln!("{}, transcripts can't be repeated", e);
+            Vec::new()
+        })
+    }
+
+    /// The keys of `retry_key`, empty when it isn't set.
+    pub fn get_retry_keycodes(&self) -> Vec<PttKey> {
+        let Some(spec) = &self.retry_key else {
+            return Vec::new();
+        };
+        parse_keys("retry_key", spec).unwrap_or_else(|e| {
+            eprintln!(
+                "{}, failed recordings can only be retried with voice-ptt ctl",
+                e
+            );
+            Vec::new()
+        })
+    }
+
+    /// The keys of `pause_key`, empty when it isn't set.
+    pub fn get_pause_keycodes(&self) -> Vec<PttKey> {
+        let Some(spec) = &self.pause_key else {
+            return Vec::new();
+        };
+        parse_keys("pause_key", spec).unwrap_or_else(|e| {
+            eprintln!("{}, pausing only works with voice-ptt ctl", e);
+            Vec::new()
+        })
+    }
+
+    /// The keys of `cancel_key`, empty when it is off.
+    pub fn get_cancel_keycodes(&self) -> Vec<PttKey> {
+        if self.cancel_key.is_empty() {
+            return Vec::new();
         }
+        parse_keys("cancel_key", &self.cancel_key).unwrap_or_else(|e| {
+            eprintln!("{}, recordings can't be cancelled", e);
+            Vec::new()
+        })
     }
 
-    /// Parses the configured PTT key string into a DeviceQuery Keycode.
-    pub fn get_ptt_keycode(&self) -> Keycode {
-        Keycode::from_str(&self.ptt_key).unwrap_or_else(|_| {
-            eprintln!("Invalid key '{}', defaulting to RControl", self.ptt_key);
-            Keycode::RControl
+    /// The keys of `profile_key`, empty when it isn't set.
+    pub fn get_profile_keycodes(&self) -> Vec<PttKey> {
+        let Some(spec) = &self.profile_key else {
+            return Vec::new();
+        };
+        parse_keys("profile_key", spec).unwrap_or_else(|e| {
+            eprintln!("{}, profiles can't be switched", e);
+            Vec::new()
         })
     }
 }
@@ -107,6 +2095,8 @@ impl AppConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::Parser;
+    use std::io::Write;
 
     #[test]
     fn test_default_config() {
@@ -114,17 +2104,986 @@ mod tests {
         assert_eq!(config.ptt_key, "RControl");
         assert_eq!(config.model, "whisper-1");
         assert!(config.sound_enabled);
+        assert!(config.meter_enabled);
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_keycode_parsing() {
         let mut config = AppConfig::default();
         config.ptt_key = "LAlt".to_string();
-        assert_eq!(config.get_ptt_keycode(), Keycode::LAlt);
+        assert_eq!(config.get_ptt_keycodes(), [PttKey::Key(Keycode::LAlt)]);
 
         config.ptt_key = "InvalidKeyName".to_string();
         // Should fallback to RControl on invalid input
-        assert_eq!(config.get_ptt_keycode(), Keycode::RControl);
+        assert_eq!(config.get_ptt_keycodes(), [PttKey::Key(Keycode::RControl)]);
+    }
+
+    #[test]
+    fn test_ptt_key_combination() {
+        let config = AppConfig {
+            ptt_key: "LControl + Space".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.get_ptt_keycodes(),
+            [PttKey::Key(Keycode::LControl), PttKey::Key(Keycode::Space)]
+        );
+        assert_eq!(keys_label(&config.get_ptt_keycodes()), "LControl+Space");
+    }
+
+    #[test]
+    fn test_ptt_combination_errors() {
+        assert_eq!(
+            parse_ptt_keys("LMeta+Z").unwrap(),
+            [PttKey::Key(Keycode::LMeta), PttKey::Key(Keycode::Z)]
+        );
+        assert_eq!(
+            parse_ptt_keys("LAlt+LAlt").unwrap(),
+            [PttKey::Key(Keycode::LAlt)]
+        );
+
+        let error = parse_ptt_keys("Ctrl+Space+Spc").unwrap_err().to_string();
+        assert!(
+            error.contains("(unknown key 'Ctrl', unknown key 'Spc')"),
+            "{}",
+            error
+        );
+        let error = parse_ptt_keys("LControl+").unwrap_err().to_string();
+        assert!(error.contains("(empty key name)"), "{}", error);
+    }
+
+    #[test]
+    fn test_evdev_ptt_keys() {
+        // Keys device_query knows stay device_query keys, however they're named
+        assert_eq!(
+            parse_ptt_keys("key_leftctrl+code:183").unwrap(),
+            [PttKey::Key(Keycode::LControl), PttKey::Key(Keycode::F13)]
+        );
+        assert_eq!(PttKey::from_code(191).to_string(), "KEY_F21");
+        assert_eq!(PttKey::Code(700).to_string(), "code:700");
+
+        let menu = parse_ptt_keys("KEY_MENU");
+        if cfg!(target_os = "linux") {
+            assert_eq!(menu.unwrap(), [PttKey::Code(139)]);
+            assert_eq!(
+                keys_label(&parse_ptt_keys("RShift+code:139").unwrap()),
+                "RShift+KEY_MENU"
+            );
+        } else {
+            let error = menu.unwrap_err().to_string();
+            assert!(error.contains("can only be read on Linux"), "{}", error);
+        }
+
+        let error = parse_ptt_keys("code:900+KEY_NOPE").unwrap_err().to_string();
+        assert!(
+            error.contains("(invalid key code 'code:900' (0 to 767), unknown key 'KEY_NOPE')"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_ptt_bindings() {
+        let config: AppConfig = toml::from_str(
+            r#"
+language = "en"
+model = "whisper-1"
+
+[bindings]
+RShift = { language = "ru", translate = true }
+"LControl+Space" = { language = "", model = "gpt-4o-transcribe" }
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_empty());
+
+        let bindings = config.ptt_bindings();
+        let labels: Vec<String> = bindings.iter().map(PttBinding::label).collect();
+        assert_eq!(labels, ["RControl", "LControl+Space", "RShift"]);
+
+        // ptt_key records with the base settings
+        assert_eq!(bindings[0].config.language.as_deref(), Some("en"));
+        assert_eq!(bindings[0].config.mode, TranscriptionMode::Transcribe);
+        assert_eq!(bindings[0].summary, "");
+
+        assert_eq!(bindings[1].config.language, None);
+        assert_eq!(bindings[1].config.model, "gpt-4o-transcribe");
+        assert_eq!(
+            bindings[1].summary,
+            "detected language, model gpt-4o-transcribe"
+        );
+
+        assert_eq!(bindings[2].config.language.as_deref(), Some("ru"));
+        assert_eq!(bindings[2].config.mode, TranscriptionMode::Translate);
+        assert_eq!(bindings[2].config.model, "whisper-1");
+        assert_eq!(bindings[2].summary, "language ru, translate");
+        assert!(!bindings[0].sets_language);
+        assert!(bindings[1].sets_language && bindings[2].sets_language);
+    }
+
+    #[test]
+    fn test_ptt_key_listed_in_bindings() {
+        let mut config = AppConfig {
+            ptt_key: "LAlt".to_string(),
+            ..Default::default()
+        };
+        config.bindings.insert(
+            "LAlt".to_string(),
+            Binding {
+                language: Some("fr".to_string()),
+                ..Default::default()
+            },
+        );
+        let bindings = config.ptt_bindings();
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].config.language.as_deref(), Some("fr"));
+
+        // Without bindings, ptt_key is the only one
+        let bindings = AppConfig::default().ptt_bindings();
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].keys, [PttKey::Key(Keycode::RControl)]);
+    }
+
+    #[test]
+    fn test_held_binding() {
+        let mut config = AppConfig {
+            ptt_key: "LControl".to_string(),
+            ..Default::default()
+        };
+        for spec in ["LControl+Space", "RShift"] {
+            config.bindings.insert(spec.to_string(), Binding::default());
+        }
+        let bindings = config.ptt_bindings();
+        let held = |keys: &[Keycode]| {
+            let keys: Vec<PttKey> = keys.iter().copied().map(PttKey::Key).collect();
+            held_binding(&bindings, &keys).map(|i| bindings[i].label())
+        };
+
+        assert_eq!(held(&[]), None);
+        assert_eq!(held(&[Keycode::Space]), None);
+        assert_eq!(held(&[Keycode::LControl]).as_deref(), Some("LControl"));
+        assert_eq!(
+            held(&[Keycode::Space, Keycode::LControl]).as_deref(),
+            Some("LControl+Space")
+        );
+        assert_eq!(
+            held(&[Keycode::RShift, Keycode::A]).as_deref(),
+            Some("RShift")
+        );
+    }
+
+    #[test]
+    fn test_binding_problems() {
+        let content = r#"[bindings]
+RShift = { langauge = "ru" }
+"Ctrl+Space" = { language = "de" }
+"#;
+        assert_eq!(
+            problems(content),
+            [
+                "line 1: Invalid [bindings] key 'Ctrl+Space' (unknown key 'Ctrl'), expected key names like RControl, LControl+Space, KEY_MENU or code:183 (see voice-ptt list-keys)",
+                "line 2: unknown key in bindings.RShift: langauge, did you mean language?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config: AppConfig = toml::from_str(
+            r#"
+language = "en"
+vocabulary = ["Rust"]
+paste_overrides = { Alacritty = "ctrl+shift+v", Slack = "type" }
+
+[profiles.meetings]
+mode = "translate"
+output_mode = "clipboard"
+language = ""
+
+[profiles.coding]
+vocabulary = ["tokio", "serde"]
+paste_overrides = { Slack = "ctrl+v", kitty = "type" }
+"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_empty());
+
+        // Keys the profile doesn't list keep their base values
+        let meetings = config.with_profile("meetings").unwrap();
+        assert_eq!(meetings.mode, TranscriptionMode::Translate);
+        assert_eq!(meetings.output_mode, OutputMode::Clipboard);
+        assert_eq!(meetings.language, None);
+        assert_eq!(meetings.vocabulary, ["Rust"]);
+
+        // Lists are replaced, tables gain the profile's entries
+        let coding = config.with_profile("coding").unwrap();
+        assert_eq!(coding.language.as_deref(), Some("en"));
+        assert_eq!(coding.vocabulary, ["tokio", "serde"]);
+        let paste = |name: &str| {
+            coding.paste_overrides[name]
+                .paste
+                .as_ref()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(paste("Alacritty"), "ctrl+shift+v");
+        assert_eq!(paste("Slack"), "ctrl+v");
+        assert_eq!(paste("kitty"), "type");
+
+        let error = config.with_profile("meeting").err().unwrap().to_string();
+        assert_eq!(
+            error,
+            "unknown profile meeting, did you mean meetings? (defined: coding, meetings)"
+        );
+        let error = AppConfig::default().with_profile("coding").err().unwrap();
+        assert!(error.to_string().ends_with("(defined: none)"));
+
+        assert_eq!(config.next_profile(None), Some("coding"));
+        assert_eq!(config.next_profile(Some("coding")), Some("meetings"));
+        assert_eq!(config.next_profile(Some("meetings")), None);
+    }
+
+    #[test]
+    fn test_profile_problems() {
+        let content = r#"temperature = 0.5
+profile_key = "Hyper"
+
+[profiles.quiet]
+sound_enabld = false
+temperature = 2.0
+
+[profiles.broken]
+sound_enabled = "no"
+"#;
+        assert_eq!(
+            problems(content),
+            [
+                "line 2: Invalid profile_key 'Hyper' (unknown key 'Hyper'), expected key names like RControl, LControl+Space, KEY_MENU or code:183 (see voice-ptt list-keys)",
+                "line 4: Invalid profile 'broken': invalid type: string \"no\", expected a boolean",
+                "line 4: profiles.quiet: temperature must be between 0.0 and 1.0, got 2",
+                "line 5: unknown key in profiles.quiet: sound_enabld, did you mean sound_enabled?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_startup_keys() {
+        let fields = struct_fields::<AppConfig>();
+        assert!(STARTUP_KEYS.iter().all(|key| fields.contains(key)));
+
+        assert_eq!(
+            problems("[profiles.remote]\nbackend = \"groq\"\nproxy_url = \"http://proxy:3128\"\nlanguage = \"en\"\n"),
+            ["line 1: profiles.remote: backend, proxy_url are only read at startup and can't be set in a profile"]
+        );
+
+        let running = AppConfig::default();
+        let reloaded = AppConfig {
+            backend: "groq".to_string(),
+            input_device: Some("USB".to_string()),
+            language: Some("en".to_string()),
+            activation: Activation::Toggle,
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            reloaded.changed_startup_keys(&running).unwrap(),
+            ["backend", "input_device"]
+        );
+        assert!(running.changed_startup_keys(&running).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_repeat_key() {
+        let content = r#"ptt_key = "LControl+Space"
+repeat_key = "LControl"
+"#;
+        assert_eq!(
+            problems(content),
+            ["line 2: repeat_key LControl is part of the PTT key LControl+Space, pick keys it doesn't hold"]
+        );
+        assert!(
+            problems("repeat_key = \"RShift\"\n[bindings]\nRShift = { language = \"ru\" }\n")
+                .iter()
+                .any(|problem| problem.contains("part of the PTT key RShift"))
+        );
+
+        // Containing a PTT key is fine, it is held off while the repeat key is down
+        let content = "ptt_key = \"RControl\"\nrepeat_key = \"RControl+R\"\n";
+        assert!(problems(content).is_empty());
+        let config: AppConfig = toml::from_str(content).unwrap();
+        assert_eq!(config.get_repeat_keycodes().len(), 2);
+        assert!(AppConfig::default().get_repeat_keycodes().is_empty());
+
+        assert_eq!(
+            problems("ptt_key = \"RControl+F7\"\nretry_key = \"F7\"\n"),
+            ["line 2: retry_key F7 is part of the PTT key RControl+F7, pick keys it doesn't hold"]
+        );
+        let content = "pause_key = \"F10\"\n";
+        assert!(problems(content).is_empty());
+        let config: AppConfig = toml::from_str(content).unwrap();
+        assert_eq!(config.get_pause_keycodes().len(), 1);
+    }
+
+    #[test]
+    fn test_cancel_key() {
+        assert_eq!(AppConfig::default().get_cancel_keycodes().len(), 1);
+        assert_eq!(
+            problems("ptt_key = \"LControl+Escape\"\n"),
+            ["cancel_key Escape is part of the PTT key LControl+Escape, pick keys it doesn't hold"]
+        );
+        let content = "ptt_key = \"LControl+Escape\"\ncancel_key = \"\"\n";
+        assert!(problems(content).is_empty());
+        let config: AppConfig = toml::from_str(content).unwrap();
+        assert!(config.get_cancel_keycodes().is_empty());
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let cli = |args: &[&str]| Cli::try_parse_from(["voice-ptt"].iter().chain(args)).unwrap();
+        let mut config = AppConfig::default();
+        config.apply_overrides(&cli(&[])).unwrap();
+        assert_eq!(config.ptt_key, AppConfig::default().ptt_key);
+        assert!(config.sound_enabled);
+
+        config
+            .apply_overrides(&cli(&[
+                "--ptt-key",
+                "LAlt",
+                "--language",
+                "de",
+                "--model",
+                "whisper-large-v3",
+                "--device",
+                "USB",
+                "--no-sound",
+                "--verbose",
+            ]))
+            .unwrap();
+        assert_eq!(config.get_ptt_keycodes(), [PttKey::Key(Keycode::LAlt)]);
+        assert_eq!(config.language.as_deref(), Some("de"));
+        assert_eq!(config.model, "whisper-large-v3");
+        assert_eq!(config.input_device.as_deref(), Some("USB"));
+        assert!(!config.sound_enabled);
+        assert!(config.verbose);
+
+        let error = config
+            .apply_overrides(&cli(&["--ptt-key", "Hyper"]))
+            .unwrap_err();
+        assert!(error.to_string().contains("Invalid ptt_key 'Hyper'"));
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let vars = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let mut config: AppConfig =
+            toml::from_str("model = \"whisper-1\"\nverbose = true").unwrap();
+        config.data_dir = PathBuf::from("/data");
+        config
+            .apply_env(vars(&[
+                ("VOICE_PTT_PTT_KEY", "LAlt"),
+                ("VOICE_PTT_MODEL", "whisper-large-v3"),
+                ("VOICE_PTT_LANGUAGE", "de"),
+                ("VOICE_PTT_SOUND_ENABLED", "false"),
+                ("VOICE_PTT_TYPING_DELAY_MS", "25"),
+                ("VOICE_PTT_VOCABULARY", r#"["Kubernetes", "Grafana"]"#),
+                (
+                    "VOICE_PTT_PASTE_OVERRIDES",
+                    r#"{ Alacritty = "ctrl+shift+v" }"#,
+                ),
+                ("VOICE_PTT_CONFIG", "/etc/voice-ptt.toml"),
+                ("HOME", "/root"),
+            ]))
+            .unwrap();
+        assert_eq!(config.get_ptt_keycodes(), [PttKey::Key(Keycode::LAlt)]);
+        assert_eq!(config.model, "whisper-large-v3");
+        assert_eq!(config.language.as_deref(), Some("de"));
+        assert!(!config.sound_enabled);
+        assert_eq!(config.typing_delay_ms, 25);
+        assert_eq!(config.vocabulary, ["Kubernetes", "Grafana"]);
+        assert_eq!(
+            config.paste_overrides["Alacritty"].paste,
+            Some(PasteAction::Shortcut(Shortcut::new(
+                &[Modifier::Ctrl, Modifier::Shift],
+                'v'
+            )))
+        );
+        // Keys without a variable keep the file's value
+        assert!(config.verbose);
+        assert_eq!(config.data_dir, PathBuf::from("/data"));
+
+        // A numeric-looking value still works for string keys
+        config
+            .apply_env(vars(&[("VOICE_PTT_PW_TARGET", "42")]))
+            .unwrap();
+        assert_eq!(config.pw_target.as_deref(), Some("42"));
+
+        let error = config
+            .apply_env(vars(&[
+                ("VOICE_PTT_SOUND_ENABLED", "yes"),
+                ("VOICE_PTT_TYPING_DELAY_MS", "fast"),
+                ("VOICE_PTT_TEMPERATURE", "3"),
+            ]))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("VOICE_PTT_SOUND_ENABLED=yes: invalid type"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("VOICE_PTT_TYPING_DELAY_MS=fast"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("VOICE_PTT_TEMPERATURE: temperature"),
+            "{}",
+            error
+        );
+        // A failed override leaves the config untouched
+        assert!(!config.sound_enabled);
+    }
+
+    #[test]
+    fn test_to_toml_round_trips() {
+        let mut config: AppConfig = toml::from_str(
+            "ptt_key = \"LAlt\"\napi_keys = [\"sk-1\"]\n[bindings]\nRShift = { language = \"ru\" }",
+        )
+        .unwrap();
+        config.language = Some("de".to_string());
+        let printed = config.to_toml().unwrap();
+        assert!(!printed.contains("sk-1"));
+
+        let parsed = parse_config(&printed).unwrap();
+        assert_eq!(parsed.ptt_key, "LAlt");
+        assert_eq!(parsed.language.as_deref(), Some("de"));
+        assert_eq!(parsed.bindings.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_ptt_key_in_file_is_rejected() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "ptt_key = \"RCtrl\"").unwrap();
+        match AppConfig::load(file.path()) {
+            Err(e) => assert!(format!("{:#}", e).contains("Invalid ptt_key 'RCtrl'")),
+            Ok(_) => panic!("an unknown ptt_key must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config: AppConfig =
+            toml::from_str("ptt_key = \"LAlt\"\nmeter_enabled = false").unwrap();
+        assert_eq!(config.ptt_key, "LAlt");
+        assert_eq!(config.model, "whisper-1");
+        assert!(!config.meter_enabled);
+    }
+
+    #[test]
+    fn test_capture_backend_parsing() {
+        let config: AppConfig = toml::from_str("capture_backend = \"pw-record\"").unwrap();
+        assert_eq!(config.capture_backend, CaptureBackend::PwRecord);
+        assert!(toml::from_str::<AppConfig>("capture_backend = \"alsa\"").is_err());
+    }
+
+    #[test]
+    fn test_log_settings_parsing() {
+        let config = AppConfig::default();
+        assert_eq!(config.log_format, LogFormat::Emoji);
+        assert_eq!(config.log_file, None);
+        let config: AppConfig =
+            toml::from_str("log_format = \"full\"\nlog_file = \"/tmp/voice-ptt.log\"").unwrap();
+        assert_eq!(config.log_format, LogFormat::Full);
+        assert_eq!(config.log_file, Some(PathBuf::from("/tmp/voice-ptt.log")));
+        assert!(toml::from_str::<AppConfig>("log_format = \"json\"").is_err());
+    }
+
+    #[test]
+    fn test_input_backend_parsing() {
+        assert_eq!(AppConfig::default().input_backend, InputBackend::Events);
+        let config: AppConfig = toml::from_str("input_backend = \"poll\"").unwrap();
+        assert_eq!(config.input_backend, InputBackend::Poll);
+        assert!(toml::from_str::<AppConfig>("input_backend = \"rdev\"").is_err());
+    }
+
+    #[test]
+    fn test_missing_sound_files() {
+        let dir = std::env::temp_dir().join(format!("voice-ptt-sounds-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let start = dir.join("start.oga");
+        fs::write(&start, b"").unwrap();
+        let start = start.to_string_lossy().into_owned();
+        let end = dir.join("missing.oga").to_string_lossy().into_owned();
+        let config = AppConfig {
+            sound_start_path: start.clone(),
+            sound_end_path: end.clone(),
+            sound_error_path: start.clone(),
+            sound_cancel_path: start.clone(),
+            linux_sound_start_path: None,
+            linux_sound_end_path: None,
+            linux_sound_error_path: None,
+            linux_sound_cancel_path: None,
+            macos_sound_start_path: None,
+            macos_sound_end_path: None,
+            macos_sound_error_path: None,
+            macos_sound_cancel_path: None,
+            ..Default::default()
+        };
+        assert_eq!(config.missing_sound_files(), std::slice::from_ref(&end));
+
+        let builtin = AppConfig {
+            sound_backend: SoundBackend::Builtin,
+            ..config.clone()
+        };
+        assert!(builtin.missing_sound_files().is_empty());
+        let same = AppConfig {
+            sound_start_path: end.clone(),
+            ..config.clone()
+        };
+        assert_eq!(same.missing_sound_files(), [end]);
+        let bundled = AppConfig {
+            sound_end_path: sound::BUILTIN_PATH.to_string(),
+            ..config
+        };
+        assert!(bundled.missing_sound_files().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sound_paths() {
+        let config = AppConfig {
+            sound_error_path: "generic-error.oga".to_string(),
+            linux_sound_error_path: Some("os-error.oga".to_string()),
+            macos_sound_error_path: Some("os-error.oga".to_string()),
+            ..Default::default()
+        };
+        let paths = config.get_sound_paths();
+        assert_eq!(paths.error, "os-error.oga");
+        assert_eq!(paths.done, None);
+
+        let config = AppConfig {
+            sound_done_path: Some("done.oga".to_string()),
+            ..config
+        };
+        assert_eq!(config.get_sound_paths().done.as_deref(), Some("done.oga"));
+    }
+
+    #[test]
+    fn test_paste_overrides_parsing() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [paste_overrides]
+            Alacritty = "ctrl+shift+v"
+            telegram-desktop = { paste = "ctrl+v", after = "Return" }
+            Slack = { after = "Return" }
+            "#,
+        )
+        .unwrap();
+        let entry = |class: &str| &config.paste_overrides[class];
+        let ctrl_v = Shortcut::new(&[Modifier::Ctrl], 'v');
+        let enter = Shortcut {
+            modifiers: Vec::new(),
+            key: ShortcutKey::Return,
+        };
+        assert_eq!(
+            entry("Alacritty"),
+            &PasteOverride {
+                paste: Some(PasteAction::Shortcut(Shortcut::new(
+                    &[Modifier::Ctrl, Modifier::Shift],
+                    'v'
+                ))),
+                after: Vec::new(),
+            }
+        );
+        assert_eq!(
+            entry("telegram-desktop").paste,
+            Some(PasteAction::Shortcut(ctrl_v))
+        );
+        assert_eq!(
+            entry("telegram-desktop").after,
+            std::slice::from_ref(&enter)
+        );
+        assert_eq!(entry("Slack").paste, None);
+        assert_eq!(entry("Slack").after, [enter]);
+
+        assert!(toml::from_str::<AppConfig>("[paste_overrides]\nSlack = 1").is_err());
+
+        // Entries are written back the way they were read
+        let table = toml::Table::try_from(&config).unwrap();
+        let written = &table["paste_overrides"];
+        assert_eq!(written["Alacritty"].as_str(), Some("ctrl+shift+v"));
+        assert_eq!(written["Slack"]["after"].as_str(), Some("Return"));
+        assert_eq!(
+            written["telegram-desktop"]["paste"].as_str(),
+            Some("ctrl+v")
+        );
+    }
+
+    #[test]
+    fn test_shortcut_parsing() {
+        for (chord, expected) in [
+            ("ctrl+v", "ctrl+v"),
+            ("Ctrl + Shift + V", "ctrl+shift+v"),
+            ("control+option+y", "ctrl+alt+y"),
+            ("shift+Insert", "shift+Insert"),
+            ("Return", "Return"),
+            ("ctrl+enter", "ctrl+Return"),
+            ("esc", "Escape"),
+            ("alt+,", "alt+,"),
+        ] {
+            assert_eq!(Shortcut::parse(chord).unwrap().to_string(), expected);
+        }
+        let super_v = Shortcut::parse("cmd+v").unwrap();
+        assert_eq!(super_v, Shortcut::new(&[Modifier::Super], 'v'));
+        assert_eq!(Shortcut::parse("meta+v").unwrap(), super_v);
+
+        for (chord, error) in [
+            ("", "empty shortcut"),
+            ("  ", "empty shortcut"),
+            ("ctr+v", "unknown modifier ctr, did you mean ctrl?"),
+            ("hyper+v", "unknown modifier hyper, did you mean super?"),
+            ("win+v", "unknown modifier win"),
+            ("ctrl+", "missing key or modifier around \"+\""),
+            ("ctrl++v", "missing key or modifier around \"+\""),
+            ("ctrl+shift", "no key after the modifiers, e.g. ctrl+shift+v"),
+            (
+                "ctrl+Retrun",
+                "unknown key retrun, did you mean return? (use a single character or Return, Tab, space, Escape, Insert, Delete or BackSpace)",
+            ),
+        ] {
+            assert_eq!(
+                Shortcut::parse(chord).err().unwrap().to_string(),
+                error,
+                "{:?}",
+                chord
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_paste_overrides_are_reported() {
+        let content = r#"ptt_key = "LAlt"
+
+[paste_overrides]
+Alacritty = "ctrl+shift+v"
+kitty = "ctr+shift+v"
+"#;
+        assert_eq!(
+            problems(content),
+            ["line 5: paste_overrides.kitty: invalid paste shortcut \"ctr+shift+v\": unknown modifier ctr, did you mean ctrl?"]
+        );
+        let problems = problems("[paste_overrides]\nSlack = { after = \"Return F13\" }\n");
+        assert_eq!(problems.len(), 1);
+        assert!(
+            problems[0].starts_with(
+                "line 2: paste_overrides.Slack: invalid after key \"F13\": unknown key f13"
+            ),
+            "{}",
+            problems[0]
+        );
+        assert!(!self::problems("[paste_overrides]\nSlack = \"\"\n").is_empty());
+    }
+
+    #[test]
+    fn test_input_device_pattern() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.input_device_pattern(), None);
+        config.input_device = Some("USB".to_string());
+        assert_eq!(config.input_device_pattern(), Some("USB"));
+
+        config.capture_source = CaptureSource::Monitor;
+        assert_eq!(config.input_device_pattern(), Some("Monitor"));
+
+        config.monitor_device = Some("Monitor of Built-in Audio".to_string());
+        assert_eq!(
+            config.input_device_pattern(),
+            Some("Monitor of Built-in Audio")
+        );
+    }
+
+    #[test]
+    fn test_whisper_prompt_from_vocabulary() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.whisper_prompt(), None);
+
+        config.vocabulary = vec!["Kubernetes".to_string(), "PostgreSQL".to_string()];
+        assert_eq!(
+            config.whisper_prompt().as_deref(),
+            Some("Kubernetes, PostgreSQL")
+        );
+
+        config.prompt = Some("Meeting notes about voice-ptt.".to_string());
+        assert_eq!(
+            config.whisper_prompt().as_deref(),
+            Some("Meeting notes about voice-ptt.")
+        );
+    }
+
+    #[test]
+    fn test_vocabulary_is_truncated_to_token_limit() {
+        let config = AppConfig {
+            vocabulary: (0..500).map(|i| format!("Term{}", i)).collect(),
+            ..Default::default()
+        };
+        let (prompt, dropped) = config.build_prompt();
+        let prompt = prompt.unwrap();
+        assert!(estimate_tokens(&prompt) <= PROMPT_TOKEN_LIMIT);
+        assert!(prompt.starts_with("Term0, Term1"));
+        assert!(dropped > 0);
+        assert_eq!(prompt.split(", ").count() + dropped, 500);
+    }
+
+    /// The problems `parse_config` reports for `content`, as printed.
+    fn problems(content: &str) -> Vec<String> {
+        match parse_config(content) {
+            Ok(_) => Vec::new(),
+            Err(problems) => problems.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(AppConfig::default().validate().is_empty());
+        assert!(problems("").is_empty());
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        let fields = struct_fields::<AppConfig>();
+        assert!(fields.contains(&"ptt_key"));
+        assert!(fields.contains(&"paste_overrides"));
+        assert!(fields.contains(&"linux_sound_done_path"));
+        assert!(!fields.contains(&"data_dir"));
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("past_overrides", "paste_overrides"), 1);
+
+        let fields = struct_fields::<AppConfig>();
+        assert_eq!(suggest("past_overrides", fields), Some("paste_overrides"));
+        assert_eq!(suggest("ptt-key", fields), Some("ptt_key"));
+        assert_eq!(suggest("Sound_Enable", fields), Some("sound_enabled"));
+        assert_eq!(suggest("initial_delay", fields), Some("initial_delay_ms"));
+        assert_eq!(suggest("colour_scheme", fields), None);
+        assert_eq!(suggest("aftr", PASTE_OVERRIDE_KEYS), Some("after"));
+    }
+
+    #[test]
+    fn test_unknown_keys_are_reported_with_lines() {
+        let content = r#"ptt_key = "LAlt"
+sound_enable = false
+colour_scheme = "dark"
+
+[past_overrides]
+Alacritty = "ctrl+shift+v"
+"#;
+        assert_eq!(
+            problems(content),
+            [
+                "line 2: unknown key sound_enable, did you mean sound_enabled?",
+                "line 3: unknown key colour_scheme",
+                "line 5: unknown key past_overrides, did you mean paste_overrides?",
+            ]
+        );
+
+        let content = r#"[paste_overrides]
+Alacritty = "ctrl+shift+v"
+telegram-desktop = { paste = "ctrl+v", aftr = "Return" }
+"#;
+        assert_eq!(
+            problems(content),
+            ["line 3: unknown key in paste_overrides.telegram-desktop: aftr, did you mean after?"]
+        );
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let content = r#"ptt_key = "RCtrl"
+backend = "opanai"
+model = ""
+temperature = 1.5
+pw_channels = 0
+clipping_warn_percent = 120.0
+hallucination_filters = ["/(/"]
+typing_dlay_ms = 10
+
+[replacements]
+"re:(unclosed" = "x"
+"#;
+        let problems = problems(content);
+        assert_eq!(problems.len(), 9, "{:#?}", problems);
+        assert!(problems[0].starts_with("line 1: Invalid ptt_key 'RCtrl'"));
+        assert_eq!(
+            problems[1],
+            "line 2: unknown backend opanai, did you mean openai? (available: openai, groq, deepgram, local, vosk)"
+        );
+        assert_eq!(problems[2], "line 3: model must not be empty");
+        assert_eq!(
+            problems[3],
+            "line 4: temperature must be between 0.0 and 1.0, got 1.5"
+        );
+        assert_eq!(problems[4], "line 5: pw_channels must be at least 1");
+        assert_eq!(
+            problems[5],
+            "line 6: clipping_warn_percent must be between 0 and 100, got 120"
+        );
+        assert!(problems[6].starts_with("line 7: Invalid hallucination filter /(/"));
+        assert_eq!(
+            problems[7],
+            "line 8: unknown key typing_dlay_ms, did you mean typing_delay_ms?"
+        );
+        assert!(problems[8].starts_with("line 10: Invalid replacements key \"re:(unclosed\""));
+    }
+
+    #[test]
+    fn test_range_checks() {
+        let config = AppConfig {
+            price_per_minute: -0.01,
+            highpass_hz: f32::NAN,
+            api_timeout_secs: 0,
+            hold_threshold_ms: 5000,
+            streaming: true,
+            streaming_model: " ".to_string(),
+            ..Default::default()
+        };
+        let keys: Vec<_> = config
+            .validate()
+            .into_iter()
+            .filter_map(|problem| problem.key)
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "streaming_model",
+                "price_per_minute",
+                "highpass_hz",
+                "hold_threshold_ms",
+                "api_timeout_secs"
+            ]
+        );
+
+        for (mark, valid) in [
+            ("", true),
+            ("!", true),
+            ("。", true),
+            ("..", false),
+            ("x", false),
+        ] {
+            let config = AppConfig {
+                ensure_final_punctuation: mark.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(config.validate().is_empty(), valid, "{:?}", mark);
+        }
+
+        // Optional models only matter when their feature is on
+        let config = AppConfig {
+            postprocess_model: String::new(),
+            streaming_model: String::new(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn test_type_errors_name_key_and_line() {
+        let problems = problems("ptt_key = \"LAlt\"\ntyping_delay_ms = -5\nbogus = 1\n");
+        assert_eq!(problems.len(), 2, "{:#?}", problems);
+        assert!(problems[0].starts_with("line 2: typing_delay_ms: "));
+        assert_eq!(problems[1], "line 3: unknown key bogus");
+
+        let problems = self::problems("ptt_key = \"LAlt\"\nmodel = \n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("line 2: "));
+    }
+
+    #[test]
+    fn test_load_reports_every_problem() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "past_overrides = {{}}\ntemperature = 2.0").unwrap();
+        match AppConfig::load(file.path()) {
+            Err(e) => {
+                let message = e.to_string();
+                assert!(message.contains("Invalid config file at"));
+                assert!(message.contains("\n  line 1: unknown key past_overrides"));
+                assert!(message.contains("\n  line 2: temperature must be"));
+            }
+            Ok(_) => panic!("an invalid config must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_temperature_range_is_validated() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "temperature = 1.5").unwrap();
+        match AppConfig::load(file.path()) {
+            Err(e) => {
+                assert!(format!("{:#}", e).contains("temperature must be between 0.0 and 1.0"))
+            }
+            Ok(_) => panic!("out-of-range temperature must be rejected"),
+        }
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "temperature = 0.0").unwrap();
+        assert_eq!(AppConfig::load(file.path()).unwrap().temperature, Some(0.0));
+    }
+
+    #[test]
+    fn test_locate_config() {
+        let dirs = AppDirs {
+            config: Some(PathBuf::from("/home/me/.config/voice-ptt")),
+            data: Some(PathBuf::from("/home/me/.local/share/voice-ptt")),
+            exe: PathBuf::from("/opt/voice-ptt"),
+        };
+        let all = [
+            "/tmp/test.toml",
+            "/etc/voice-ptt.toml",
+            "/home/me/.config/voice-ptt/config.toml",
+            "/opt/voice-ptt/config.toml",
+        ];
+        let locate = |flag: Option<&str>, env: Option<&str>, existing: &[&str]| {
+            let exists = |path: &Path| existing.iter().any(|file| path == Path::new(file));
+            locate_config(flag.map(Path::new), env.map(Path::new), &dirs, exists)
+        };
+
+        let location = locate(Some("/tmp/test.toml"), Some("/etc/voice-ptt.toml"), &all).unwrap();
+        assert_eq!(location.path, Some(PathBuf::from("/tmp/test.toml")));
+        assert_eq!(location.origin, ConfigOrigin::Flag);
+        let location = locate(None, Some("/etc/voice-ptt.toml"), &all).unwrap();
+        assert_eq!(location.origin, ConfigOrigin::Env);
+
+        let location = locate(None, None, &all).unwrap();
+        assert_eq!(location.origin, ConfigOrigin::Xdg);
+        assert_eq!(
+            location.data_dir,
+            PathBuf::from("/home/me/.local/share/voice-ptt")
+        );
+
+        // Portable install: data stays next to the binary
+        let location = locate(None, None, &["/opt/voice-ptt/config.toml"]).unwrap();
+        assert_eq!(location.origin, ConfigOrigin::ExeDir);
+        assert_eq!(location.data_dir, PathBuf::from("/opt/voice-ptt"));
+
+        let location = locate(None, None, &[]).unwrap();
+        assert_eq!(location.path, None);
+        assert_eq!(location.origin, ConfigOrigin::Defaults);
+
+        assert!(locate(Some("/tmp/typo.toml"), None, &all).is_err());
+        assert_eq!(
+            dirs.env_dirs(&locate(None, None, &all).unwrap()),
+            [
+                PathBuf::from("/home/me/.config/voice-ptt"),
+                PathBuf::from("/opt/voice-ptt")
+            ]
+        );
     }
 
     #[test]
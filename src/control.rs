@@ -0,0 +1,430 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// A command for the running instance, sent as one JSON line like `{"command": "status"}`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Reports whether it is idle, recording or processing, and whether it is paused.
+    Status,
+    /// Ignores the PTT, profile, repeat and retry keys until resumed; a recording in progress
+    /// ends.
+    Pause,
+    Resume,
+    /// Starts recording with ptt_key's settings until `stop` or a tap of the PTT key.
+    Start,
+    /// Ends a recording started with `start`, a toggle or a double tap, and transcribes it.
+    Stop,
+    /// Sends the last recording whose transcription failed again and types the result.
+    Retry,
+    /// Reads the config file again, like switching profiles (between recordings only).
+    ReloadConfig,
+    /// Shuts down like Ctrl+C.
+    Quit,
+    /// Keeps the connection open and answers with the status now and after every change,
+    /// for status bars; see `voice-ptt bar-status`.
+    #[value(skip)]
+    Subscribe,
+}
+
+/// What the instance is doing.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Idle,
+    Recording,
+    /// A transcription is being requested or typed.
+    Processing,
+}
+
+/// What `status` reports, and what status bars show.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Status {
+    pub state: State,
+    pub paused: bool,
+    /// The last transcription failed; cleared by the next recording.
+    #[serde(default)]
+    pub failed: bool,
+    pub profile: Option<String>,
+}
+
+/// The answer to a [`Request`], one JSON line: `{"ok": true}`, with `status` for `status`
+/// and `error` when the command failed.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            status: None,
+        }
+    }
+
+    pub fn status(status: Status) -> Self {
+        Self {
+            status: Some(status),
+            ..Self::ok()
+        }
+    }
+}
+
+/// The control socket, `$XDG_RUNTIME_DIR/voice-ptt.sock`.
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    crate::instance::runtime_path("sock")
+}
+
+/// Commands for the main loop, from the control socket or the tray icon, each with the
+/// sender for its response.
+pub type Requests = mpsc::UnboundedSender<(Request, oneshot::Sender<Response>)>;
+
+/// Accepts commands on the control socket in a background task and hands them to the main
+/// loop, which answers each through its sender. The socket is removed when this is dropped.
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Listens on `path`, readable and writable by the user only. A socket file nobody is
+    /// listening on, left by a crash, is replaced; a live one means another instance owns it.
+    /// Subscribers are answered from `statuses` without involving the main loop.
+    #[cfg(unix)]
+    pub fn bind(
+        path: &Path,
+        requests: Requests,
+        statuses: watch::Receiver<Status>,
+    ) -> Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                anyhow::bail!("Another instance is listening on {}", path.display());
+            }
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove the stale socket {}", path.display()))?;
+        }
+        let listener = tokio::net::UnixListener::bind(path)
+            .with_context(|| format!("Failed to listen on {}", path.display()))?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, requests.clone(), statuses.clone()));
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Answers the commands of one client, a line at a time, until it disconnects.
+#[cfg(unix)]
+async fn serve(
+    stream: tokio::net::UnixStream,
+    requests: Requests,
+    mut statuses: watch::Receiver<Status>,
+) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Subscribe) => loop {
+                let status = statuses.borrow_and_update().clone();
+                let mut line = serde_json::to_string(&Response::status(status)).unwrap_or_default();
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err()
+                    || statuses.changed().await.is_err()
+                {
+                    return;
+                }
+            },
+            Ok(request) => {
+                let (respond, response) = oneshot::channel();
+                let _ = requests.send((request, respond));
+                response
+                    .await
+                    .unwrap_or_else(|_| Response::error("voice-ptt is shutting down"))
+            }
+            Err(e) => Response::error(format!("Invalid command: {}", e)),
+        };
+        let mut line = serde_json::to_string(&response).unwrap_or_default();
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Sends `request` to the instance listening on `path` and returns its response.
+#[cfg(unix)]
+pub async fn send(path: &Path, request: Request) -> Result<Response> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .with_context(|| {
+            format!(
+                "voice-ptt doesn't seem to be running (no control socket at {})",
+                path.display()
+            )
+        })?;
+    let (reader, mut writer) = stream.into_split();
+    let mut line = serde_json::to_string(&request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    let response = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .context("voice-ptt closed the connection without answering")?;
+    serde_json::from_str(&response).context("Invalid response from voice-ptt")
+}
+
+/// Subscribes to the instance listening on `path` and passes its status, then every change,
+/// to `on_status` until the instance quits.
+#[cfg(unix)]
+pub async fn follow(path: &Path, mut on_status: impl FnMut(&Status)) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .with_context(|| format!("No control socket at {}", path.display()))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut line = serde_json::to_string(&Request::Subscribe)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response: Response =
+            serde_json::from_str(&line).context("Invalid response from voice-ptt")?;
+        match response.status {
+            Some(status) => on_status(&status),
+            None => anyhow::bail!(response.error.unwrap_or_default()),
+        }
+    }
+    Ok(())
+}
+
+/// One word for a status bar: `idle`, `recording`, `processing`, `paused` (idle but paused),
+/// `error` (idle after a failed transcription), or `stopped` when no instance is running.
+pub fn bar_class(status: Option<&Status>) -> &'static str {
+    match status {
+        None => "stopped",
+        Some(status) => match status.state {
+            State::Idle if status.paused => "paused",
+            State::Idle if status.failed => "error",
+            State::Idle => "idle",
+            State::Recording => "recording",
+            State::Processing => "processing",
+        },
+    }
+}
+
+/// A line for a waybar custom module with `"return-type": "json"`; `alt` picks the icon
+/// from `format-icons` and `class` the CSS class.
+pub fn waybar_line(status: Option<&Status>) -> String {
+    let class = bar_class(status);
+    let mut tooltip = format!("Voice PTT: {}", class);
+    if let Some(profile) = status.and_then(|status| status.profile.as_deref()) {
+        tooltip.push_str(&format!(" ({})", profile));
+    }
+    serde_json::json!({
+        "text": class,
+        "alt": class,
+        "class": class,
+        "tooltip": tooltip,
+    })
+    .to_string()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn idle() -> Status {
+        Status {
+            state: State::Idle,
+            paused: false,
+            failed: false,
+            profile: None,
+        }
+    }
+
+    #[test]
+    fn test_request_format() {
+        assert_eq!(
+            serde_json::to_string(&Request::ReloadConfig).unwrap(),
+            r#"{"command":"reload-config"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command": "pause"}"#).unwrap(),
+            Request::Pause
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command": "dance"}"#).is_err());
+        assert_eq!(
+            serde_json::to_string(&Response::ok()).unwrap(),
+            r#"{"ok":true}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commands_reach_the_main_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice-ptt.sock");
+        let (_statuses, received) = watch::channel(idle());
+        let (requests, mut incoming) = mpsc::unbounded_channel();
+        let server = ControlServer::bind(&path, requests.clone(), received.clone()).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let main_loop = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (request, respond) = incoming.recv().await.unwrap();
+                let response = match request {
+                    Request::Status => Response::status(Status {
+                        state: State::Recording,
+                        paused: false,
+                        failed: false,
+                        profile: Some("meetings".to_string()),
+                    }),
+                    _ => Response::error("not now"),
+                };
+                respond.send(response).unwrap();
+            }
+            server
+        });
+        let response = send(&path, Request::Status).await.unwrap();
+        assert_eq!(
+            response.status.unwrap(),
+            Status {
+                state: State::Recording,
+                paused: false,
+                failed: false,
+                profile: Some("meetings".to_string()),
+            }
+        );
+        let response = send(&path, Request::Stop).await.unwrap();
+        assert_eq!(response, Response::error("not now"));
+
+        // A second instance can't take over the socket while this one listens
+        assert!(ControlServer::bind(&path, requests, received).is_err());
+        drop(main_loop.await.unwrap());
+        assert!(!path.exists());
+        assert!(send(&path, Request::Status).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_lines_and_stale_sockets() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice-ptt.sock");
+        // A crashed instance leaves a socket nobody listens on
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let (requests, _incoming) = mpsc::unbounded_channel();
+        let _server = ControlServer::bind(&path, requests, watch::channel(idle()).1).unwrap();
+
+        let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        writer.write_all(b"status\n").await.unwrap();
+        let line = BufReader::new(reader)
+            .lines()
+            .next_line()
+            .await
+            .unwrap()
+            .unwrap();
+        let response: Response = serde_json::from_str(&line).unwrap();
+        assert!(!response.ok);
+        assert!(response.error.unwrap().starts_with("Invalid command"));
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_see_every_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice-ptt.sock");
+        let (statuses, received) = watch::channel(idle());
+        let (requests, _incoming) = mpsc::unbounded_channel();
+        let server = ControlServer::bind(&path, requests, received).unwrap();
+
+        let (seen, mut lines) = mpsc::unbounded_channel();
+        let follower = tokio::spawn({
+            let path = path.clone();
+            async move {
+                follow(&path, |status| {
+                    seen.send(waybar_line(Some(status))).unwrap()
+                })
+                .await
+            }
+        });
+        assert_eq!(
+            lines.recv().await.unwrap(),
+            r#"{"alt":"idle","class":"idle","text":"idle","tooltip":"Voice PTT: idle"}"#
+        );
+        statuses.send_replace(Status {
+            state: State::Recording,
+            paused: false,
+            failed: false,
+            profile: Some("meetings".to_string()),
+        });
+        assert_eq!(
+            lines.recv().await.unwrap(),
+            r#"{"alt":"recording","class":"recording","text":"recording","tooltip":"Voice PTT: recording (meetings)"}"#
+        );
+
+        // Quitting ends the subscription
+        drop(statuses);
+        follower.await.unwrap().unwrap();
+        drop(server);
+    }
+
+    #[test]
+    fn test_bar_class() {
+        let paused = Status {
+            paused: true,
+            ..idle()
+        };
+        assert_eq!(bar_class(Some(&paused)), "paused");
+        let failed = Status {
+            failed: true,
+            ..idle()
+        };
+        assert_eq!(bar_class(Some(&failed)), "error");
+        let processing = Status {
+            state: State::Processing,
+            ..paused
+        };
+        assert_eq!(bar_class(Some(&processing)), "processing");
+        assert_eq!(bar_class(None), "stopped");
+    }
+}
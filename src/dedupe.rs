@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::audio::RecordedAudio;
+
+/// How much of a recorder file is read at a time to hash it.
+const HASH_CHUNK_BYTES: usize = 64 * 1024;
+
+struct RecentTranscript {
+    hash: u64,
+    at: Instant,
+    text: String,
+}
+
+/// Remembers the last transcribed recording so an identical one right after it
+/// (e.g. from a bouncing key switch) doesn't cost a second request.
+#[derive(Default)]
+pub struct DedupeCache {
+    last: Option<RecentTranscript>,
+}
+
+impl DedupeCache {
+    /// Returns the cached transcript and its age if `hash` matches the last recording
+    /// and it was transcribed less than `window` ago.
+    pub fn lookup(&self, hash: u64, window: Duration) -> Option<(String, Duration)> {
+        let last = self.last.as_ref()?;
+        let age = last.at.elapsed();
+        (last.hash == hash && age < window).then(|| (last.text.clone(), age))
+    }
+
+    pub fn store(&mut self, hash: u64, text: String) {
+        self.last = Some(RecentTranscript {
+            hash,
+            at: Instant::now(),
+            text,
+        });
+    }
+}
+
+/// Hashes the recorded audio; recorder files are hashed including their WAV header, on a
+/// blocking thread since they can be many megabytes.
+pub async fn hash_recording(recording: &RecordedAudio) -> Result<u64> {
+    match recording {
+        RecordedAudio::Memory { samples, .. } => {
+            let mut hasher = DefaultHasher::new();
+            samples.hash(&mut hasher);
+            Ok(hasher.finish())
+        }
+        RecordedAudio::File(path) => {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || hash_file(&path))
+                .await
+                .context("Hashing the recording failed")?
+        }
+    }
+}
+
+/// Hashes a file a chunk at a time instead of reading it whole.
+fn hash_file(path: &Path) -> Result<u64> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut hasher = DefaultHasher::new();
+    let mut chunk = vec![0; HASH_CHUNK_BYTES];
+    loop {
+        let read = file
+            .read(&mut chunk)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&chunk[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(samples: Vec<i16>) -> RecordedAudio {
+        RecordedAudio::Memory {
+            samples,
+            spec: hound::WavSpec {
+                channels: 1,
+                sample_rate: 16000,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identical_audio_hashes_equal() {
+        let a = hash_recording(&memory(vec![1, 2, 3, 4])).await.unwrap();
+        let b = hash_recording(&memory(vec![1, 2, 3, 4])).await.unwrap();
+        let c = hash_recording(&memory(vec![1, 2, 3, 5])).await.unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[tokio::test]
+    async fn test_files_hash_in_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str, last: u8| {
+            let path = dir.path().join(name);
+            let mut bytes = vec![7; HASH_CHUNK_BYTES * 2 + 100];
+            *bytes.last_mut().unwrap() = last;
+            std::fs::write(&path, bytes).unwrap();
+            RecordedAudio::File(path)
+        };
+        let a = hash_recording(&file("a.wav", 1)).await.unwrap();
+        let b = hash_recording(&file("b.wav", 1)).await.unwrap();
+        let c = hash_recording(&file("c.wav", 2)).await.unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        let missing = RecordedAudio::File(dir.path().join("missing.wav"));
+        assert!(hash_recording(&missing).await.is_err());
+    }
+
+    #[test]
+    fn test_lookup_respects_hash_and_window() {
+        let mut cache = DedupeCache::default();
+        assert!(cache.lookup(7, Duration::from_secs(10)).is_none());
+
+        cache.store(7, "hello".to_string());
+        let (text, age) = cache.lookup(7, Duration::from_secs(10)).unwrap();
+        assert_eq!(text, "hello");
+        assert!(age < Duration::from_secs(10));
+
+        assert!(cache.lookup(8, Duration::from_secs(10)).is_none());
+        assert!(cache.lookup(7, Duration::ZERO).is_none());
+    }
+}
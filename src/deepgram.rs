@@ -0,0 +1,271 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::api::{self, Attempt, TranscriptionBackend};
+use crate::config::{AppConfig, TranscriptionMode};
+use crate::encode::EncodedAudio;
+
+const DEEPGRAM_LISTEN_URL: &str = "https://api.deepgram.com/v1/listen";
+
+/// Model used when `model` still holds the OpenAI default.
+const DEFAULT_DEEPGRAM_MODEL: &str = "nova-2";
+
+#[derive(Deserialize)]
+struct ListenResponse {
+    results: ListenResults,
+}
+
+#[derive(Deserialize)]
+struct ListenResults {
+    channels: Vec<ListenChannel>,
+}
+
+#[derive(Deserialize)]
+struct ListenChannel {
+    alternatives: Vec<ListenAlternative>,
+}
+
+#[derive(Deserialize)]
+struct ListenAlternative {
+    transcript: String,
+}
+
+impl ListenResponse {
+    /// Transcript of the first alternative of the first channel.
+    fn transcript(&self) -> Option<&str> {
+        let channel = self.results.channels.first()?;
+        Some(channel.alternatives.first()?.transcript.as_str())
+    }
+}
+
+/// Deepgram error body: `{"err_code": "...", "err_msg": "...", "request_id": "..."}`.
+#[derive(Deserialize)]
+struct DeepgramError {
+    err_msg: String,
+}
+
+fn error_message(body: &str) -> String {
+    match serde_json::from_str::<DeepgramError>(body) {
+        Ok(error) => error.err_msg,
+        Err(_) => body.trim().to_string(),
+    }
+}
+
+/// Client for Deepgram's prerecorded audio API, which takes the raw file as request body.
+pub struct DeepgramClient {
+    client: Client,
+    api_key: String,
+    url: String,
+    proxy: Option<String>,
+}
+
+impl DeepgramClient {
+    pub fn new(api_key: String, config: &AppConfig) -> Result<Self> {
+        let proxy = api::proxy_settings(config, |name| std::env::var(name).ok());
+        Ok(Self {
+            client: api::http_client(config, proxy.as_ref())?,
+            api_key,
+            url: DEEPGRAM_LISTEN_URL.to_string(),
+            proxy: proxy.map(|p| p.url),
+        })
+    }
+
+    async fn send_once(&self, audio: &EncodedAudio, config: &AppConfig) -> Result<Attempt> {
+        let model = if config.model == "whisper-1" {
+            DEFAULT_DEEPGRAM_MODEL
+        } else {
+            config.model.as_str()
+        };
+        let mut query = vec![("model", model), ("smart_format", "true")];
+        if let Some(lang) = &config.language {
+            query.push(("language", lang.as_str()));
+        }
+
+        let timeout = api::request_timeout(config, audio.duration);
+        let res = match self
+            .client
+            .post(&self.url)
+            .query(&query)
+            .header("Authorization", format!("Token {}", self.api_key))
+            .header("Content-Type", audio.format.mime_type())
+            .timeout(timeout)
+            .body(audio.bytes.clone())
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => {
+                return Ok(api::send_failure(
+                    "Deepgram",
+                    e,
+                    timeout,
+                    self.proxy.as_deref(),
+                ))
+            }
+        };
+
+        if !res.status().is_success() {
+            return api::error_response(res, "Deepgram", "DEEPGRAM_API_KEY", error_message).await;
+        }
+
+        let response: ListenResponse = res
+            .json()
+            .await
+            .context("Failed to parse Deepgram response")?;
+        let transcript = response
+            .transcript()
+            .context("Deepgram response contains no transcript")?;
+        Ok(Attempt::Done(transcript.trim().to_string()))
+    }
+}
+
+#[async_trait]
+impl TranscriptionBackend for DeepgramClient {
+    async fn transcribe_audio(
+        &self,
+        audio: EncodedAudio,
+        mode: TranscriptionMode,
+        config: &AppConfig,
+    ) -> Result<String> {
+        if mode == TranscriptionMode::Translate {
+            anyhow::bail!("The Deepgram backend does not support translate mode");
+        }
+        api::with_retries(config, || self.send_once(&audio, config)).await
+    }
+
+    async fn warm_up(&self, config: &AppConfig) -> Result<()> {
+        self.client
+            .head(&self.url)
+            .header("Authorization", format!("Token {}", self.api_key))
+            .timeout(std::time::Duration::from_secs(
+                config.api_connect_timeout_secs,
+            ))
+            .send()
+            .await
+            .context("Could not reach Deepgram")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UploadFormat;
+    use std::time::Duration;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Trimmed response of a real `POST /v1/listen?model=nova-2&smart_format=true` request.
+    const LISTEN_RESPONSE: &str = r#"{
+        "metadata": {
+            "transaction_key": "deprecated",
+            "request_id": "5d1c5a6e-3b4f-4c4e-9a53-0b6d1b0c2f11",
+            "sha256": "154e291ecfa8be6ab8343560bcc109008fa7853eb5372533e8efdefc9b504c33",
+            "created": "2024-05-12T18:57:13.426Z",
+            "duration": 4.2,
+            "channels": 1,
+            "models": ["30089e05-99d1-4376-b32e-c263170674af"]
+        },
+        "results": {
+            "channels": [{
+                "alternatives": [{
+                    "transcript": "Deploy the PostgreSQL migration tonight.",
+                    "confidence": 0.99609375,
+                    "words": [
+                        {"word": "deploy", "start": 0.24, "end": 0.64, "confidence": 0.99, "punctuated_word": "Deploy"}
+                    ]
+                }]
+            }]
+        }
+    }"#;
+
+    fn test_audio() -> EncodedAudio {
+        EncodedAudio {
+            bytes: b"RIFF0000WAVE".to_vec(),
+            format: UploadFormat::Wav,
+            duration: Duration::from_secs(1),
+        }
+    }
+
+    fn mock_client(server: &MockServer) -> DeepgramClient {
+        DeepgramClient {
+            url: format!("{}/v1/listen", server.uri()),
+            ..DeepgramClient::new("dg_test".to_string(), &AppConfig::default()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_parse_listen_response() {
+        let response: ListenResponse = serde_json::from_str(LISTEN_RESPONSE).unwrap();
+        assert_eq!(
+            response.transcript(),
+            Some("Deploy the PostgreSQL migration tonight.")
+        );
+
+        let empty: ListenResponse =
+            serde_json::from_str(r#"{"results": {"channels": []}}"#).unwrap();
+        assert_eq!(empty.transcript(), None);
+    }
+
+    #[test]
+    fn test_error_message() {
+        let body = r#"{"err_code": "INVALID_AUTH", "err_msg": "Invalid credentials.", "request_id": "abc"}"#;
+        assert_eq!(error_message(body), "Invalid credentials.");
+        assert_eq!(error_message("Bad Gateway"), "Bad Gateway");
+    }
+
+    #[tokio::test]
+    async fn test_listen_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/listen"))
+            .and(query_param("model", "nova-2"))
+            .and(query_param("language", "en"))
+            .and(header("authorization", "Token dg_test"))
+            .and(header("content-type", "audio/wav"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(LISTEN_RESPONSE))
+            .mount(&server)
+            .await;
+
+        let config = AppConfig {
+            backend: "deepgram".to_string(),
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        let text = mock_client(&server)
+            .transcribe_audio(test_audio(), TranscriptionMode::Transcribe, &config)
+            .await
+            .unwrap();
+        assert_eq!(text, "Deploy the PostgreSQL migration tonight.");
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].body, test_audio().bytes);
+    }
+
+    #[tokio::test]
+    async fn test_auth_error_is_reported() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string(
+                r#"{"err_code": "INVALID_AUTH", "err_msg": "Invalid credentials.", "request_id": "abc"}"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let error = mock_client(&server)
+            .transcribe_audio(
+                test_audio(),
+                TranscriptionMode::Transcribe,
+                &AppConfig::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "API key rejected — check DEEPGRAM_API_KEY"
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+}
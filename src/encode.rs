@@ -0,0 +1,378 @@
+use anyhow::{Context, Result};
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::UploadFormat;
+
+/// Audio bytes ready to be attached to an API request.
+pub struct EncodedAudio {
+    pub bytes: Vec<u8>,
+    pub format: UploadFormat,
+    /// Length of the recording, used to scale the request timeout.
+    pub duration: Duration,
+}
+
+impl UploadFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            UploadFormat::Wav => "recording.wav",
+            UploadFormat::Opus => "recording.ogg",
+            UploadFormat::Flac => "recording.flac",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            UploadFormat::Wav => "audio/wav",
+            UploadFormat::Opus => "audio/ogg",
+            UploadFormat::Flac => "audio/flac",
+        }
+    }
+}
+
+/// Encodes PCM samples in the requested format, falling back to WAV if encoding fails.
+pub fn encode_for_upload(
+    samples: &[i16],
+    spec: hound::WavSpec,
+    format: UploadFormat,
+) -> Result<EncodedAudio> {
+    let duration = audio_duration(samples.len(), spec);
+    let encoded = match format {
+        UploadFormat::Wav => encode_wav(samples, spec),
+        UploadFormat::Opus => encode_ogg_opus(samples, spec),
+        UploadFormat::Flac => encode_flac(samples, spec),
+    };
+    match encoded {
+        Ok(bytes) => Ok(EncodedAudio {
+            bytes,
+            format,
+            duration,
+        }),
+        Err(e) if format != UploadFormat::Wav => {
+            warn!(
+                "⚠️ {:?} encoding failed, uploading WAV instead: {}",
+                format, e
+            );
+            Ok(EncodedAudio {
+                bytes: encode_wav(samples, spec)?,
+                format: UploadFormat::Wav,
+                duration,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads a WAV file for upload, transcoding it when a compressed format is requested.
+pub fn encode_wav_file_for_upload(path: &Path, format: UploadFormat) -> Result<EncodedAudio> {
+    if format == UploadFormat::Wav {
+        let reader = hound::WavReader::open(path)
+            .with_context(|| format!("Failed to open WAV file at {}", path.display()))?;
+        let duration = audio_duration(reader.len() as usize, reader.spec());
+        drop(reader);
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read WAV file at {}", path.display()))?;
+        return Ok(EncodedAudio {
+            bytes,
+            format,
+            duration,
+        });
+    }
+    let (samples, spec) = read_wav(path)?;
+    encode_for_upload(&samples, spec, format)
+}
+
+/// Playback length of `sample_count` interleaved samples.
+pub fn audio_duration(sample_count: usize, spec: hound::WavSpec) -> Duration {
+    let frames_per_sec = spec.sample_rate as f64 * spec.channels.max(1) as f64;
+    if frames_per_sec == 0.0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(sample_count as f64 / frames_per_sec)
+}
+
+/// Loads all samples of a 16-bit PCM WAV file.
+pub fn read_wav(path: &Path) -> Result<(Vec<i16>, hound::WavSpec)> {
+    let mut reader = hound::WavReader::open(path)
+        .with_context(|| format!("Failed to open WAV file at {}", path.display()))?;
+    let spec = reader.spec();
+    let samples = reader
+        .samples::<i16>()
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read samples from {}", path.display()))?;
+    Ok((samples, spec))
+}
+
+/// Writes samples into an in-memory WAV file.
+pub fn encode_wav(samples: &[i16], spec: hound::WavSpec) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer =
+            hound::WavWriter::new(&mut cursor, spec).context("Failed to create WAV writer")?;
+        for &sample in samples {
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()?;
+    }
+    Ok(cursor.into_inner())
+}
+
+/// Losslessly compresses samples as a FLAC stream.
+pub fn encode_flac(samples: &[i16], spec: hound::WavSpec) -> Result<Vec<u8>> {
+    use flacenc::component::BitRepr;
+    use flacenc::error::Verify;
+
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| anyhow::anyhow!("Invalid FLAC encoder config: {:?}", e))?;
+    let samples: Vec<i32> = samples.iter().map(|&s| s as i32).collect();
+    let source = flacenc::source::MemSource::from_samples(
+        &samples,
+        spec.channels as usize,
+        16,
+        spec.sample_rate as usize,
+    );
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| anyhow::anyhow!("FLAC encoding failed: {:?}", e))?;
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| anyhow::anyhow!("Failed to serialize FLAC stream: {:?}", e))?;
+    Ok(sink.into_inner())
+}
+
+/// Sample rate used for Opus uploads; Whisper works on 16 kHz mono internally anyway.
+const OPUS_RATE: u32 = 16000;
+/// 20 ms frames at `OPUS_RATE`.
+const OPUS_FRAME: usize = 320;
+const OPUS_BITRATE: i32 = 24000;
+
+/// Encodes samples as a mono 16 kHz Ogg/Opus stream.
+pub fn encode_ogg_opus(samples: &[i16], spec: hound::WavSpec) -> Result<Vec<u8>> {
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+    let mono = downmix_to_mono(samples, spec.channels);
+    let mut pcm = resample_linear(&mono, spec.sample_rate, OPUS_RATE);
+    let original_len = pcm.len() as u64;
+
+    let mut encoder = OpusEncoder::new(OPUS_RATE as i32, OPUS_BITRATE)?;
+    let lookahead = encoder.lookahead()?;
+    // Pad so the encoder's lookahead is flushed and the last frame is complete
+    pcm.resize(pcm.len() + lookahead, 0);
+    let frames = pcm.len().div_ceil(OPUS_FRAME);
+    pcm.resize(frames * OPUS_FRAME, 0);
+
+    // Ogg Opus granule positions are always counted at 48 kHz
+    let to_48k = (48000 / OPUS_RATE) as u64;
+    let pre_skip = lookahead as u64 * to_48k;
+    let serial = 0x766f_6963; // "voic"
+    let mut writer = PacketWriter::new(Vec::new());
+
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    head.extend_from_slice(&spec.sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    writer.write_packet(head, serial, PacketWriteEndInfo::EndPage, 0)?;
+
+    let vendor = concat!("voice-ptt ", env!("CARGO_PKG_VERSION"));
+    let mut tags = Vec::new();
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes()); // no user comments
+    writer.write_packet(tags, serial, PacketWriteEndInfo::EndPage, 0)?;
+
+    let final_granule = pre_skip + original_len * to_48k;
+    for (index, frame) in pcm.chunks(OPUS_FRAME).enumerate() {
+        let packet = encoder.encode(frame)?;
+        let last = index + 1 == frames;
+        let granule = ((index as u64 + 1) * OPUS_FRAME as u64 * to_48k).min(final_granule);
+        let end_info = if last {
+            PacketWriteEndInfo::EndStream
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        writer.write_packet(packet, serial, end_info, granule)?;
+    }
+
+    Ok(writer.into_inner())
+}
+
+/// Averages interleaved channels into a single channel.
+pub fn downmix_to_mono(samples: &[i16], channels: u16) -> Vec<i16> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks(channels as usize)
+        .map(|frame| (frame.iter().map(|&s| s as i32).sum::<i32>() / frame.len() as i32) as i16)
+        .collect()
+}
+
+/// Resamples mono audio with linear interpolation.
+pub fn resample_linear(samples: &[i16], from: u32, to: u32) -> Vec<i16> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let out_len = (samples.len() as u64 * to as u64 / from as u64) as usize;
+    let step = from as f64 / to as f64;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let idx = pos as usize;
+            let frac = pos - idx as f64;
+            let a = samples[idx.min(samples.len() - 1)] as f64;
+            let b = samples[(idx + 1).min(samples.len() - 1)] as f64;
+            (a + (b - a) * frac).round() as i16
+        })
+        .collect()
+}
+
+/// Minimal owning wrapper around the libopus encoder state.
+struct OpusEncoder {
+    state: *mut unsafe_libopus::OpusEncoder,
+}
+
+impl OpusEncoder {
+    fn new(sample_rate: i32, bitrate: i32) -> Result<Self> {
+        let mut error = 0;
+        // SAFETY: plain constructor call; the returned pointer is owned by `Self` and freed in Drop
+        let state = unsafe {
+            unsafe_libopus::opus_encoder_create(
+                sample_rate,
+                1,
+                unsafe_libopus::OPUS_APPLICATION_VOIP,
+                &mut error,
+            )
+        };
+        if state.is_null() || error != unsafe_libopus::OPUS_OK {
+            anyhow::bail!("Failed to create Opus encoder (error {})", error);
+        }
+        let encoder = Self { state };
+        // SAFETY: `state` is a valid encoder created above
+        let ret = unsafe {
+            unsafe_libopus::opus_encoder_ctl!(
+                encoder.state,
+                unsafe_libopus::OPUS_SET_BITRATE_REQUEST,
+                bitrate
+            )
+        };
+        if ret != unsafe_libopus::OPUS_OK {
+            anyhow::bail!("Failed to set Opus bitrate (error {})", ret);
+        }
+        Ok(encoder)
+    }
+
+    /// Number of samples the encoder delays its output by.
+    fn lookahead(&mut self) -> Result<usize> {
+        let mut lookahead = 0i32;
+        // SAFETY: `state` is valid for the lifetime of `self`
+        let ret = unsafe {
+            unsafe_libopus::opus_encoder_ctl!(
+                self.state,
+                unsafe_libopus::OPUS_GET_LOOKAHEAD_REQUEST,
+                &mut lookahead
+            )
+        };
+        if ret != unsafe_libopus::OPUS_OK {
+            anyhow::bail!("Failed to query Opus lookahead (error {})", ret);
+        }
+        Ok(lookahead.max(0) as usize)
+    }
+
+    fn encode(&mut self, frame: &[i16]) -> Result<Vec<u8>> {
+        let mut packet = vec![0u8; 1500];
+        // SAFETY: `frame` holds exactly one frame of mono samples and `packet` is writable
+        // for the length we pass in
+        let len = unsafe {
+            unsafe_libopus::opus_encode(
+                self.state,
+                frame.as_ptr(),
+                frame.len() as i32,
+                packet.as_mut_ptr(),
+                packet.len() as i32,
+            )
+        };
+        if len < 0 {
+            anyhow::bail!("Opus encoding failed (error {})", len);
+        }
+        packet.truncate(len as usize);
+        Ok(packet)
+    }
+}
+
+impl Drop for OpusEncoder {
+    fn drop(&mut self) {
+        // SAFETY: `state` came from opus_encoder_create and is destroyed exactly once
+        unsafe { unsafe_libopus::opus_encoder_destroy(self.state) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(spec: hound::WavSpec, secs: f32) -> Vec<i16> {
+        let frames = (spec.sample_rate as f32 * secs) as usize;
+        (0..frames)
+            .flat_map(|i| {
+                let t = i as f32 / spec.sample_rate as f32;
+                let s = ((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16;
+                std::iter::repeat_n(s, spec.channels as usize)
+            })
+            .collect()
+    }
+
+    fn spec(channels: u16, sample_rate: u32) -> hound::WavSpec {
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn test_wav_encoding_round_trip() {
+        let spec = spec(1, 16000);
+        let samples = sine(spec, 0.1);
+        let bytes = encode_wav(&samples, spec).unwrap();
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        let decoded: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn test_opus_encoding_is_smaller_ogg() {
+        let spec = spec(2, 44100);
+        let samples = sine(spec, 1.0);
+        let audio = encode_for_upload(&samples, spec, UploadFormat::Opus).unwrap();
+        assert_eq!(audio.format, UploadFormat::Opus);
+        assert!((audio.duration.as_secs_f32() - 1.0).abs() < 0.01);
+        assert_eq!(&audio.bytes[..4], b"OggS");
+        assert!(audio.bytes.windows(8).any(|w| w == b"OpusHead"));
+        assert!(audio.bytes.len() < encode_wav(&samples, spec).unwrap().len() / 10);
+    }
+
+    #[test]
+    fn test_flac_encoding_has_magic_and_is_smaller() {
+        let spec = spec(1, 16000);
+        let samples = sine(spec, 1.0);
+        let audio = encode_for_upload(&samples, spec, UploadFormat::Flac).unwrap();
+        assert_eq!(audio.format, UploadFormat::Flac);
+        assert_eq!(&audio.bytes[..4], b"fLaC");
+        assert!(audio.bytes.len() < encode_wav(&samples, spec).unwrap().len());
+    }
+
+    #[test]
+    fn test_resample_and_downmix() {
+        assert_eq!(downmix_to_mono(&[100, 300, -50, 50], 2), vec![200, 0]);
+        assert_eq!(resample_linear(&[0; 48000], 48000, 16000).len(), 16000);
+    }
+}
@@ -0,0 +1,461 @@
+use device_query::Keycode;
+
+/// Linux input event key names (`KEY_*`, `BTN_*`) with their codes, and the device_query key
+/// for the ones it knows. The others are read from /dev/input.
+const EVDEV_KEYS: [(&str, u16, Option<Keycode>); 148] = [
+    ("KEY_ESC", 1, Some(Keycode::Escape)),
+    ("KEY_1", 2, Some(Keycode::Key1)),
+    ("KEY_2", 3, Some(Keycode::Key2)),
+    ("KEY_3", 4, Some(Keycode::Key3)),
+    ("KEY_4", 5, Some(Keycode::Key4)),
+    ("KEY_5", 6, Some(Keycode::Key5)),
+    ("KEY_6", 7, Some(Keycode::Key6)),
+    ("KEY_7", 8, Some(Keycode::Key7)),
+    ("KEY_8", 9, Some(Keycode::Key8)),
+    ("KEY_9", 10, Some(Keycode::Key9)),
+    ("KEY_0", 11, Some(Keycode::Key0)),
+    ("KEY_MINUS", 12, Some(Keycode::Minus)),
+    ("KEY_EQUAL", 13, Some(Keycode::Equal)),
+    ("KEY_BACKSPACE", 14, Some(Keycode::Backspace)),
+    ("KEY_TAB", 15, Some(Keycode::Tab)),
+    ("KEY_Q", 16, Some(Keycode::Q)),
+    ("KEY_W", 17, Some(Keycode::W)),
+    ("KEY_E", 18, Some(Keycode::E)),
+    ("KEY_R", 19, Some(Keycode::R)),
+    ("KEY_T", 20, Some(Keycode::T)),
+    ("KEY_Y", 21, Some(Keycode::Y)),
+    ("KEY_U", 22, Some(Keycode::U)),
+    ("KEY_I", 23, Some(Keycode::I)),
+    ("KEY_O", 24, Some(Keycode::O)),
+    ("KEY_P", 25, Some(Keycode::P)),
+    ("KEY_LEFTBRACE", 26, Some(Keycode::LeftBracket)),
+    ("KEY_RIGHTBRACE", 27, Some(Keycode::RightBracket)),
+    ("KEY_ENTER", 28, Some(Keycode::Enter)),
+    ("KEY_LEFTCTRL", 29, Some(Keycode::LControl)),
+    ("KEY_A", 30, Some(Keycode::A)),
+    ("KEY_S", 31, Some(Keycode::S)),
+    ("KEY_D", 32, Some(Keycode::D)),
+    ("KEY_F", 33, Some(Keycode::F)),
+    ("KEY_G", 34, Some(Keycode::G)),
+    ("KEY_H", 35, Some(Keycode::H)),
+    ("KEY_J", 36, Some(Keycode::J)),
+    ("KEY_K", 37, Some(Keycode::K)),
+    ("KEY_L", 38, Some(Keycode::L)),
+    ("KEY_SEMICOLON", 39, Some(Keycode::Semicolon)),
+    ("KEY_APOSTROPHE", 40, Some(Keycode::Apostrophe)),
+    ("KEY_GRAVE", 41, Some(Keycode::Grave)),
+    ("KEY_LEFTSHIFT", 42, Some(Keycode::LShift)),
+    ("KEY_BACKSLASH", 43, Some(Keycode::BackSlash)),
+    ("KEY_Z", 44, Some(Keycode::Z)),
+    ("KEY_X", 45, Some(Keycode::X)),
+    ("KEY_C", 46, Some(Keycode::C)),
+    ("KEY_V", 47, Some(Keycode::V)),
+    ("KEY_B", 48, Some(Keycode::B)),
+    ("KEY_N", 49, Some(Keycode::N)),
+    ("KEY_M", 50, Some(Keycode::M)),
+    ("KEY_COMMA", 51, Some(Keycode::Comma)),
+    ("KEY_DOT", 52, Some(Keycode::Dot)),
+    ("KEY_SLASH", 53, Some(Keycode::Slash)),
+    ("KEY_RIGHTSHIFT", 54, Some(Keycode::RShift)),
+    ("KEY_KPASTERISK", 55, Some(Keycode::NumpadMultiply)),
+    ("KEY_LEFTALT", 56, Some(Keycode::LAlt)),
+    ("KEY_SPACE", 57, Some(Keycode::Space)),
+    ("KEY_CAPSLOCK", 58, Some(Keycode::CapsLock)),
+    ("KEY_F1", 59, Some(Keycode::F1)),
+    ("KEY_F2", 60, Some(Keycode::F2)),
+    ("KEY_F3", 61, Some(Keycode::F3)),
+    ("KEY_F4", 62, Some(Keycode::F4)),
+    ("KEY_F5", 63, Some(Keycode::F5)),
+    ("KEY_F6", 64, Some(Keycode::F6)),
+    ("KEY_F7", 65, Some(Keycode::F7)),
+    ("KEY_F8", 66, Some(Keycode::F8)),
+    ("KEY_F9", 67, Some(Keycode::F9)),
+    ("KEY_F10", 68, Some(Keycode::F10)),
+    ("KEY_NUMLOCK", 69, None),
+    ("KEY_SCROLLLOCK", 70, None),
+    ("KEY_KP7", 71, Some(Keycode::Numpad7)),
+    ("KEY_KP8", 72, Some(Keycode::Numpad8)),
+    ("KEY_KP9", 73, Some(Keycode::Numpad9)),
+    ("KEY_KPMINUS", 74, Some(Keycode::NumpadSubtract)),
+    ("KEY_KP4", 75, Some(Keycode::Numpad4)),
+    ("KEY_KP5", 76, Some(Keycode::Numpad5)),
+    ("KEY_KP6", 77, Some(Keycode::Numpad6)),
+    ("KEY_KPPLUS", 78, Some(Keycode::NumpadAdd)),
+    ("KEY_KP1", 79, Some(Keycode::Numpad1)),
+    ("KEY_KP2", 80, Some(Keycode::Numpad2)),
+    ("KEY_KP3", 81, Some(Keycode::Numpad3)),
+    ("KEY_KP0", 82, Some(Keycode::Numpad0)),
+    ("KEY_KPDOT", 83, Some(Keycode::NumpadDecimal)),
+    ("KEY_102ND", 86, None),
+    ("KEY_F11", 87, Some(Keycode::F11)),
+    ("KEY_F12", 88, Some(Keycode::F12)),
+    ("KEY_KPENTER", 96, Some(Keycode::NumpadEnter)),
+    ("KEY_RIGHTCTRL", 97, Some(Keycode::RControl)),
+    ("KEY_KPSLASH", 98, Some(Keycode::NumpadDivide)),
+    ("KEY_SYSRQ", 99, None),
+    ("KEY_RIGHTALT", 100, Some(Keycode::RAlt)),
+    ("KEY_HOME", 102, Some(Keycode::Home)),
+    ("KEY_UP", 103, Some(Keycode::Up)),
+    ("KEY_PAGEUP", 104, Some(Keycode::PageUp)),
+    ("KEY_LEFT", 105, Some(Keycode::Left)),
+    ("KEY_RIGHT", 106, Some(Keycode::Right)),
+    ("KEY_END", 107, Some(Keycode::End)),
+    ("KEY_DOWN", 108, Some(Keycode::Down)),
+    ("KEY_PAGEDOWN", 109, Some(Keycode::PageDown)),
+    ("KEY_INSERT", 110, Some(Keycode::Insert)),
+    ("KEY_DELETE", 111, Some(Keycode::Delete)),
+    ("KEY_MUTE", 113, None),
+    ("KEY_VOLUMEDOWN", 114, None),
+    ("KEY_VOLUMEUP", 115, None),
+    ("KEY_KPEQUAL", 117, Some(Keycode::NumpadEquals)),
+    ("KEY_PAUSE", 119, None),
+    ("KEY_LEFTMETA", 125, Some(Keycode::LMeta)),
+    ("KEY_RIGHTMETA", 126, Some(Keycode::RMeta)),
+    ("KEY_COMPOSE", 127, None),
+    ("KEY_HELP", 138, None),
+    ("KEY_MENU", 139, None),
+    ("KEY_CALC", 140, None),
+    ("KEY_PROG1", 148, None),
+    ("KEY_PROG2", 149, None),
+    ("KEY_MAIL", 155, None),
+    ("KEY_NEXTSONG", 163, None),
+    ("KEY_PLAYPAUSE", 164, None),
+    ("KEY_PREVIOUSSONG", 165, None),
+    ("KEY_STOPCD", 166, None),
+    ("KEY_HOMEPAGE", 172, None),
+    ("KEY_F13", 183, Some(Keycode::F13)),
+    ("KEY_F14", 184, Some(Keycode::F14)),
+    ("KEY_F15", 185, Some(Keycode::F15)),
+    ("KEY_F16", 186, Some(Keycode::F16)),
+    ("KEY_F17", 187, Some(Keycode::F17)),
+    ("KEY_F18", 188, Some(Keycode::F18)),
+    ("KEY_F19", 189, Some(Keycode::F19)),
+    ("KEY_F20", 190, Some(Keycode::F20)),
+    ("KEY_F21", 191, None),
+    ("KEY_F22", 192, None),
+    ("KEY_F23", 193, None),
+    ("KEY_F24", 194, None),
+    ("KEY_PROG3", 202, None),
+    ("KEY_PROG4", 203, None),
+    ("KEY_PRINT", 210, None),
+    ("KEY_MICMUTE", 248, None),
+    ("BTN_MIDDLE", 274, None),
+    ("BTN_SIDE", 275, None),
+    ("BTN_EXTRA", 276, None),
+    ("BTN_FORWARD", 277, None),
+    ("BTN_BACK", 278, None),
+    ("KEY_FN", 464, None),
+    ("KEY_MACRO1", 656, None),
+    ("KEY_MACRO2", 657, None),
+    ("KEY_MACRO3", 658, None),
+    ("KEY_MACRO4", 659, None),
+    ("KEY_MACRO5", 660, None),
+    ("KEY_MACRO6", 661, None),
+];
+
+/// Highest key code the kernel reports (`KEY_MAX`).
+pub const KEY_MAX: u16 = 0x2ff;
+
+/// The code of a `KEY_*` or `BTN_*` name, compared case-insensitively.
+pub fn code(name: &str) -> Option<u16> {
+    EVDEV_KEYS
+        .iter()
+        .find(|(known, _, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, code, _)| *code)
+}
+
+/// The `KEY_*` name of a code, if it has one here.
+pub fn name(code: u16) -> Option<&'static str> {
+    EVDEV_KEYS
+        .iter()
+        .find(|(_, known, _)| *known == code)
+        .map(|(name, _, _)| *name)
+}
+
+/// The device_query key for a code, when it has one.
+pub fn keycode(code: u16) -> Option<Keycode> {
+    EVDEV_KEYS
+        .iter()
+        .find(|(_, known, _)| *known == code)
+        .and_then(|(_, _, keycode)| *keycode)
+}
+
+/// The names of keys device_query doesn't know, which are read from /dev/input.
+pub fn extra_names() -> impl Iterator<Item = &'static str> {
+    EVDEV_KEYS
+        .iter()
+        .filter(|(_, _, keycode)| keycode.is_none())
+        .map(|(name, _, _)| *name)
+}
+
+#[cfg(target_os = "linux")]
+pub use reader::{listen, EvdevKeys, KeyEvent};
+
+/// Key state and key events read straight from /dev/input, for keys device_query can't see
+/// and for `input_backend = "events"`.
+#[cfg(target_os = "linux")]
+mod reader {
+    use anyhow::Result;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::time::{Duration, Instant, SystemTime};
+    use tokio::sync::mpsc;
+
+    use super::KEY_MAX;
+
+    /// Bytes of the key state bitmap, one bit per code up to `KEY_MAX`.
+    const KEY_BYTES: usize = KEY_MAX as usize / 8 + 1;
+
+    /// `EVIOCGKEY(len)`: reads the state of every key of an input device as a bitmap.
+    const EVIOCGKEY: u64 = (2 << 30) | ((KEY_BYTES as u64) << 16) | ((b'E' as u64) << 8) | 0x18;
+
+    /// How often the devices are listed again, so a replugged keyboard is picked up.
+    const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// `EV_KEY`: the event type of key presses and releases.
+    const EV_KEY: u16 = 1;
+
+    /// Size of one `struct input_event` as read from a device.
+    const EVENT_SIZE: usize = std::mem::size_of::<libc::input_event>();
+
+    /// A key going down or up on one of the input devices.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct KeyEvent {
+        pub code: u16,
+        pub pressed: bool,
+        /// When the kernel saw it.
+        pub time: SystemTime,
+    }
+
+    /// Reads the key events of every input device on a thread of its own and sends them to
+    /// the returned channel. Fails like [`EvdevKeys::open`] when no device can be read.
+    pub fn listen() -> Result<mpsc::UnboundedReceiver<KeyEvent>> {
+        let devices = scan();
+        if devices.is_empty() {
+            anyhow::bail!("Can't read any device in /dev/input. Add yourself to the input group (sudo usermod -aG input $USER) and log in again");
+        }
+        let (events, received) = mpsc::unbounded_channel();
+        std::thread::spawn(move || read_events(devices, events));
+        Ok(received)
+    }
+
+    /// Blocks on the devices until one has events, until the receiving end is dropped.
+    fn read_events(mut devices: Vec<File>, events: mpsc::UnboundedSender<KeyEvent>) {
+        let mut scanned = Instant::now();
+        loop {
+            let mut fds: Vec<libc::pollfd> = devices
+                .iter()
+                .map(|device| libc::pollfd {
+                    fd: device.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            // SAFETY: `fds` holds `fds.len()` initialized pollfds
+            let ready = unsafe {
+                libc::poll(
+                    fds.as_mut_ptr(),
+                    fds.len() as libc::nfds_t,
+                    RESCAN_INTERVAL.as_millis() as libc::c_int,
+                )
+            };
+            if ready < 0
+                && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted
+            {
+                std::thread::sleep(RESCAN_INTERVAL);
+            }
+            for (device, fd) in devices.iter_mut().zip(&fds) {
+                if fd.revents & libc::POLLIN == 0 {
+                    continue;
+                }
+                for event in read_available(device) {
+                    if events.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+            // An unplugged device reports an error; list them again right away
+            let lost = fds
+                .iter()
+                .any(|fd| fd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0);
+            if lost || scanned.elapsed() >= RESCAN_INTERVAL {
+                devices = scan();
+                scanned = Instant::now();
+            }
+        }
+    }
+
+    /// The key events a device has ready, without blocking.
+    fn read_available(device: &mut File) -> Vec<KeyEvent> {
+        let mut buffer = [0u8; EVENT_SIZE * 64];
+        let mut events = Vec::new();
+        while let Ok(read @ 1..) = device.read(&mut buffer) {
+            for chunk in buffer[..read].chunks_exact(EVENT_SIZE) {
+                // SAFETY: the chunk is EVENT_SIZE bytes, and every bit pattern is valid
+                let event =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const libc::input_event) };
+                events.extend(key_event(&event));
+            }
+        }
+        events
+    }
+
+    /// The press or release an input event stands for; autorepeat and other events are none.
+    fn key_event(event: &libc::input_event) -> Option<KeyEvent> {
+        if event.type_ != EV_KEY || !matches!(event.value, 0 | 1) {
+            return None;
+        }
+        let time = SystemTime::UNIX_EPOCH
+            + Duration::from_secs(event.time.tv_sec as u64)
+            + Duration::from_micros(event.time.tv_usec as u64);
+        Some(KeyEvent {
+            code: event.code,
+            pressed: event.value == 1,
+            time,
+        })
+    }
+
+    /// The input devices in /dev/input, polled for held keys.
+    pub struct EvdevKeys {
+        devices: Vec<File>,
+        scanned: Instant,
+    }
+
+    impl EvdevKeys {
+        /// Opens every readable input device; fails when there is none, usually because the
+        /// user isn't in the `input` group.
+        pub fn open() -> Result<Self> {
+            let devices = scan();
+            if devices.is_empty() {
+                anyhow::bail!(
+                    "Can't read any device in /dev/input, which keys device_query doesn't know need. Add yourself to the input group (sudo usermod -aG input $USER) and log in again"
+                );
+            }
+            Ok(Self {
+                devices,
+                scanned: Instant::now(),
+            })
+        }
+
+        /// The codes held on any device right now.
+        pub fn pressed(&mut self) -> Vec<u16> {
+            if self.scanned.elapsed() >= RESCAN_INTERVAL {
+                self.devices = scan();
+                self.scanned = Instant::now();
+            }
+            let mut held = [0u8; KEY_BYTES];
+            for device in &self.devices {
+                let mut state = [0u8; KEY_BYTES];
+                // SAFETY: EVIOCGKEY writes at most KEY_BYTES bytes into `state`
+                let read =
+                    unsafe { libc::ioctl(device.as_raw_fd(), EVIOCGKEY as _, state.as_mut_ptr()) };
+                if read >= 0 {
+                    for (held, state) in held.iter_mut().zip(state) {
+                        *held |= state;
+                    }
+                }
+            }
+            codes_in(&held)
+        }
+    }
+
+    /// Opens /dev/input/event*, skipping the devices that can't be read.
+    fn scan() -> Vec<File> {
+        let Ok(entries) = fs::read_dir("/dev/input") else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+            .filter_map(|entry| {
+                OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(entry.path())
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// The codes whose bit is set in a key state bitmap.
+    fn codes_in(bits: &[u8]) -> Vec<u16> {
+        (0..bits.len() * 8)
+            .filter(|bit| bits[bit / 8] & (1 << (bit % 8)) != 0)
+            .map(|bit| bit as u16)
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_key_state_bitmap() {
+            assert_eq!(EVIOCGKEY, 0x8060_4518);
+            let mut bits = [0u8; KEY_BYTES];
+            assert!(codes_in(&bits).is_empty());
+            // KEY_ESC, KEY_MENU (139) and KEY_MAX
+            bits[0] = 0b10;
+            bits[139 / 8] |= 1 << (139 % 8);
+            bits[KEY_BYTES - 1] = 0x80;
+            assert_eq!(codes_in(&bits), [1, 139, KEY_MAX]);
+        }
+
+        #[test]
+        fn test_key_events() {
+            let event = |type_, value| libc::input_event {
+                time: libc::timeval {
+                    tv_sec: 1_700_000_000,
+                    tv_usec: 250_000,
+                },
+                type_,
+                code: 139,
+                value,
+            };
+            let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+            assert_eq!(
+                key_event(&event(EV_KEY, 1)),
+                Some(KeyEvent {
+                    code: 139,
+                    pressed: true,
+                    time
+                })
+            );
+            assert_eq!(key_event(&event(EV_KEY, 0)).map(|e| e.pressed), Some(false));
+            // Autorepeat and the sync event after every report
+            assert_eq!(key_event(&event(EV_KEY, 2)), None);
+            assert_eq!(key_event(&event(0, 0)), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evdev_names() {
+        assert_eq!(code("KEY_MENU"), Some(139));
+        assert_eq!(code("key_f21"), Some(191));
+        assert_eq!(code("KEY_NOPE"), None);
+        assert_eq!(name(183), Some("KEY_F13"));
+        assert_eq!(keycode(183), Some(Keycode::F13));
+        assert_eq!(keycode(139), None);
+        assert!(extra_names().any(|name| name == "KEY_COMPOSE"));
+        assert!(!extra_names().any(|name| name == "KEY_LEFTCTRL"));
+
+        // Names and codes are unique, and every code fits the key state bitmap
+        for (i, (name, code, _)) in EVDEV_KEYS.iter().enumerate() {
+            assert!(*code <= KEY_MAX, "{}", name);
+            assert!(
+                EVDEV_KEYS[i + 1..]
+                    .iter()
+                    .all(|(other, other_code, _)| other != name && other_code != code),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,718 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::config::AppConfig;
+
+/// Every config key in the order `init` writes them, with a one-line comment. Keys that are
+/// unset by default carry an example, written commented out; the others get their value
+/// from `AppConfig::default()`.
+const KEYS: &[(&str, &str, Option<&str>)] = &[
+    (
+        "ptt_key",
+        "Key to hold while speaking (RControl, LAlt, KEY_COMPOSE, code:183, ...) or a combination like LControl+Space",
+        None,
+    ),
+    (
+        "activation",
+        "\"hold\" records while the key is held, \"toggle\" starts and stops on a tap",
+        None,
+    ),
+    (
+        "hold_threshold_ms",
+        "In hold mode, ignore presses shorter than this many ms, e.g. when the key is also a modifier",
+        None,
+    ),
+    (
+        "double_tap_ms",
+        "In hold mode, a second press within this many ms locks recording on until the next tap (0 = off)",
+        None,
+    ),
+    (
+        "toggle_reminder_secs",
+        "Notify every N seconds that a toggled or locked recording is still on (0 = never)",
+        None,
+    ),
+    (
+        "input_backend",
+        "\"events\" waits for key events from /dev/input (Linux, falls back to polling), \"poll\" checks every 20 ms",
+        None,
+    ),
+    (
+        "min_recording_ms",
+        "Recordings shorter than this are dropped without a request (0 = keep all)",
+        None,
+    ),
+    (
+        "profile_key",
+        "Key or combination that switches to the next [profiles] entry, then back",
+        Some("\"F9\""),
+    ),
+    (
+        "repeat_key",
+        "Tap to type the last transcript again into the focused window",
+        Some("\"F8\""),
+    ),
+    (
+        "retry_key",
+        "Tap to send the last recording whose transcription failed again",
+        Some("\"F7\""),
+    ),
+    (
+        "keep_failed_recordings",
+        "How many failed recordings are kept in memory for retry_key (0 = none)",
+        None,
+    ),
+    (
+        "pause_key",
+        "Tap to ignore the other keys until tapped again, e.g. during a screen share",
+        Some("\"F10\""),
+    ),
+    (
+        "cancel_key",
+        "Press while recording to throw the recording away without transcribing it (\"\" = off)",
+        None,
+    ),
+    (
+        "typing_delay_ms",
+        "Between keystrokes with injection_method = \"type\"",
+        None,
+    ),
+    (
+        "initial_delay_ms",
+        "Pause before pasting, so the PTT key is released first",
+        None,
+    ),
+    (
+        "output_mode",
+        "\"inject\" (paste into the focused window), \"clipboard\" or \"stdout\"",
+        None,
+    ),
+    (
+        "append_suffix",
+        "Added after every transcript, e.g. \" \" or \"\\n\"",
+        None,
+    ),
+    (
+        "auto_space",
+        "Put a space between dictations when the previous one didn't end in one",
+        None,
+    ),
+    (
+        "injection_backend",
+        "\"auto\", \"x11\", \"wayland\" or \"native\" (no external tools)",
+        None,
+    ),
+    (
+        "injection_method",
+        "\"paste\" (clipboard + shortcut) or \"type\" (key by key)",
+        None,
+    ),
+    (
+        "clipboard_private",
+        "Keep transcripts out of clipboard manager history (X11, native)",
+        None,
+    ),
+    (
+        "after_keys_delay_ms",
+        "Pause before the `after` keys of a paste override",
+        None,
+    ),
+    (
+        "window_info",
+        "Focused window detection: \"auto\", \"x11\", \"hyprland\" or \"sway\"",
+        None,
+    ),
+    (
+        "backend",
+        "Transcription service: \"openai\", \"groq\", \"deepgram\", \"local\" or \"vosk\"",
+        None,
+    ),
+    ("model", "Model of the transcription service", None),
+    (
+        "api_base_url",
+        "OpenAI-compatible server instead of api.openai.com",
+        Some("\"http://localhost:8000/v1\""),
+    ),
+    (
+        "api_key_source",
+        "\"env\", or \"keyring\" to read the key stored with `voice-ptt set-key`",
+        None,
+    ),
+    (
+        "api_keys",
+        "Keys tried in turn when one hits a rate limit or its quota",
+        Some("[\"sk-personal...\", \"sk-project...\"]"),
+    ),
+    (
+        "api_key_file",
+        "File with the API key, when the environment variable isn't set",
+        Some("\"/home/me/.config/voice-ptt/openai-key\""),
+    ),
+    (
+        "api_key_command",
+        "Command that prints the API key",
+        Some("\"pass show openai\""),
+    ),
+    (
+        "model_path",
+        "whisper.cpp model for backend = \"local\"",
+        Some("\"/home/me/models/ggml-base.bin\""),
+    ),
+    (
+        "vosk_model_path",
+        "Vosk model directory for backend = \"vosk\"",
+        Some("\"/home/me/models/vosk-model-small-en-us-0.15\""),
+    ),
+    (
+        "mode",
+        "\"transcribe\" keeps the spoken language, \"translate\" produces English",
+        None,
+    ),
+    (
+        "language",
+        "Spoken language (ISO-639-1), detected when unset",
+        Some("\"en\""),
+    ),
+    (
+        "prompt",
+        "Free-form prompt that helps spell domain terms",
+        Some("\"Notes about Kubernetes and PostgreSQL migrations.\""),
+    ),
+    (
+        "vocabulary",
+        "Terms joined into a prompt when no prompt is set",
+        Some("[\"Kubernetes\", \"PostgreSQL\"]"),
+    ),
+    (
+        "temperature",
+        "Sampling temperature between 0.0 and 1.0",
+        Some("0.0"),
+    ),
+    (
+        "verbose",
+        "Log debug details: window classes, paste shortcuts, transcripts and request metadata",
+        None,
+    ),
+    (
+        "log_format",
+        "\"emoji\" (messages only) or \"full\" (with timestamp, level and module)",
+        None,
+    ),
+    (
+        "log_file",
+        "Also append log lines with timestamps to <log_file>.<date>, the last 7 days are kept",
+        Some("\"/home/me/.local/share/voice-ptt/voice-ptt.log\""),
+    ),
+    (
+        "transcript_format",
+        "\"text\", or \"srt\"/\"vtt\" to also save subtitles",
+        None,
+    ),
+    (
+        "subtitles_dir",
+        "Where subtitle files go",
+        Some("\"/home/me/voice-ptt-subtitles\""),
+    ),
+    (
+        "min_confidence",
+        "Below this confidence (0.0-1.0, needs verbose) nothing is typed",
+        None,
+    ),
+    (
+        "low_confidence_to_clipboard",
+        "Copy low-confidence transcripts instead of dropping them",
+        None,
+    ),
+    (
+        "hallucination_filters",
+        "Transcripts dropped as invented for silence; /.../ entries are regexes",
+        None,
+    ),
+    (
+        "strip_fillers",
+        "Remove filler words like \"um\", \"uh\" or \"эм\" from transcripts",
+        None,
+    ),
+    (
+        "filler_words",
+        "Fillers for strip_fillers; empty uses the defaults for English and language",
+        None,
+    ),
+    (
+        "auto_capitalize",
+        "Uppercase the first letter of the transcript and of every sentence",
+        None,
+    ),
+    (
+        "ensure_final_punctuation",
+        "Add this mark, e.g. \".\", when a transcript ends with a word (\"\" = none)",
+        None,
+    ),
+    (
+        "spoken_commands",
+        "Type spoken \"comma\", \"period\", \"new line\"... as characters",
+        None,
+    ),
+    (
+        "streaming",
+        "Stream audio to OpenAI's realtime API while the key is held",
+        None,
+    ),
+    ("streaming_model", "Model for streaming = true", None),
+    (
+        "cancel_previous",
+        "Drop a still-running transcription when a new recording starts",
+        None,
+    ),
+    (
+        "shutdown_grace_secs",
+        "On Ctrl+C, wait this many seconds for a transcription in flight to be typed (0 = quit right away)",
+        None,
+    ),
+    (
+        "dedupe_window_secs",
+        "Identical recordings within this many seconds aren't uploaded again (0 = off)",
+        None,
+    ),
+    (
+        "dedupe_action",
+        "\"reuse\" types the cached transcript, \"skip\" types nothing",
+        None,
+    ),
+    (
+        "postprocess_enabled",
+        "Clean up every transcript with a chat model before typing it",
+        None,
+    ),
+    ("postprocess_model", "Chat model for the clean-up", None),
+    ("postprocess_prompt", "Instructions for the clean-up", None),
+    (
+        "postprocess_timeout_secs",
+        "The raw transcript is used when the clean-up takes longer",
+        None,
+    ),
+    (
+        "api_retries",
+        "Retries for network errors, rate limits and server errors",
+        None,
+    ),
+    (
+        "api_retry_base_ms",
+        "First retry delay, doubled for every further retry",
+        None,
+    ),
+    (
+        "rate_limit_max_wait_secs",
+        "Longest wait a rate limit response may ask for",
+        None,
+    ),
+    (
+        "api_connect_timeout_secs",
+        "Timeout for connecting to the API",
+        None,
+    ),
+    (
+        "api_timeout_secs",
+        "Request timeout, plus api_timeout_per_minute_secs per minute of audio",
+        None,
+    ),
+    (
+        "api_timeout_per_minute_secs",
+        "Extra timeout per minute of audio",
+        None,
+    ),
+    (
+        "warmup_on_start",
+        "Connect to the API at startup so the first request is faster",
+        None,
+    ),
+    (
+        "keepalive_secs",
+        "Reconnect every N seconds to keep the connection warm (0 = off)",
+        None,
+    ),
+    (
+        "proxy_url",
+        "Proxy for API requests (http://, https:// or socks5://)",
+        Some("\"http://proxy.corp.example:3128\""),
+    ),
+    (
+        "no_proxy",
+        "Hosts that bypass the proxy",
+        Some("\"localhost,.corp.example\""),
+    ),
+    (
+        "upload_format",
+        "\"wav\", \"flac\" (lossless, smaller) or \"opus\" (much smaller, lossy)",
+        None,
+    ),
+    (
+        "max_upload_bytes",
+        "Longer recordings are split or refused (0 = no limit)",
+        None,
+    ),
+    (
+        "split_long_uploads",
+        "Split recordings above max_upload_bytes at pauses",
+        None,
+    ),
+    (
+        "capture_backend",
+        "\"auto\" (cpal, falling back to pw-record), \"cpal\" or \"pw-record\"",
+        None,
+    ),
+    (
+        "capture_source",
+        "\"mic\", or \"monitor\" to record what is playing",
+        None,
+    ),
+    (
+        "input_device",
+        "Part of the microphone's name (default input device when unset)",
+        Some("\"USB\""),
+    ),
+    (
+        "monitor_device",
+        "Device or sink to record with capture_source = \"monitor\"",
+        Some("\"Monitor of Built-in Audio\""),
+    ),
+    (
+        "preferred_sample_rate",
+        "Sample rate to request from the microphone (0 = device default)",
+        None,
+    ),
+    (
+        "pw_target",
+        "Node for the command-line recorders (pw-record, parec, arecord)",
+        Some("\"alsa_input.usb-headset\""),
+    ),
+    ("pw_rate", "Sample rate of the command-line recorders", None),
+    (
+        "pw_channels",
+        "Channels of the command-line recorders",
+        None,
+    ),
+    (
+        "clipping_warn_percent",
+        "Warn when more than this percentage is clipped (0 = never)",
+        None,
+    ),
+    (
+        "highpass_hz",
+        "High-pass filter cutoff against rumble (0 = disabled)",
+        None,
+    ),
+    (
+        "stats_enabled",
+        "Append the length and latency of every transcription to stats_file",
+        None,
+    ),
+    (
+        "stats_file",
+        "Where stats go",
+        Some("\"/home/me/voice-ptt-stats.jsonl\""),
+    ),
+    (
+        "price_per_minute",
+        "USD per minute, for the cost estimate of `voice-ptt stats`",
+        None,
+    ),
+    (
+        "offline_queue_enabled",
+        "Keep recordings made while offline and retry them",
+        None,
+    ),
+    (
+        "queue_dir",
+        "Where offline recordings are kept",
+        Some("\"/home/me/voice-ptt-queue\""),
+    ),
+    (
+        "queue_retry_secs",
+        "Retry interval of the offline queue (0 = only `voice-ptt flush`)",
+        None,
+    ),
+    (
+        "queue_max_items",
+        "Beyond this many queued recordings the oldest are dropped",
+        None,
+    ),
+    (
+        "keep_last_recording",
+        "Keep copies of uploaded recordings for debugging",
+        None,
+    ),
+    (
+        "recordings_dir",
+        "Where kept recordings go",
+        Some("\"/home/me/voice-ptt-recordings\""),
+    ),
+    ("keep_last_n", "How many kept recordings to keep", None),
+    (
+        "meter_enabled",
+        "Show a live input level meter while recording",
+        None,
+    ),
+    (
+        "status_notifications",
+        "Keep a \"Recording…\"/\"Processing…\" notification up (Linux)",
+        None,
+    ),
+    (
+        "spill_after_secs",
+        "Longer recordings go to a temp file instead of RAM (0 = never)",
+        None,
+    ),
+    (
+        "device_retry_interval_ms",
+        "Base delay between attempts to reopen a lost microphone",
+        None,
+    ),
+    ("sound_enabled", "Play feedback sounds", None),
+    (
+        "sound_backend",
+        "\"rodio\" (in-process), \"builtin\" (bundled tones) or \"external\"",
+        None,
+    ),
+    (
+        "sound_start_path",
+        "Played when recording starts; \"builtin\" plays the bundled tone",
+        None,
+    ),
+    ("sound_end_path", "Played when recording stops", None),
+    (
+        "sound_error_path",
+        "Played when the transcription or the paste fails",
+        None,
+    ),
+    (
+        "sound_cancel_path",
+        "Played when cancel_key throws a recording away",
+        None,
+    ),
+    (
+        "sound_done_path",
+        "Played after the text was pasted",
+        Some("\"/usr/share/sounds/freedesktop/stereo/complete.oga\""),
+    ),
+    (
+        "macos_sound_start_path",
+        "macOS version of sound_start_path",
+        None,
+    ),
+    (
+        "macos_sound_end_path",
+        "macOS version of sound_end_path",
+        None,
+    ),
+    (
+        "macos_sound_error_path",
+        "macOS version of sound_error_path",
+        None,
+    ),
+    (
+        "macos_sound_cancel_path",
+        "macOS version of sound_cancel_path",
+        None,
+    ),
+    (
+        "macos_sound_done_path",
+        "macOS version of sound_done_path",
+        Some("\"/System/Library/Sounds/Glass.aiff\""),
+    ),
+    (
+        "linux_sound_start_path",
+        "Linux version of sound_start_path",
+        None,
+    ),
+    (
+        "linux_sound_end_path",
+        "Linux version of sound_end_path",
+        None,
+    ),
+    (
+        "linux_sound_error_path",
+        "Linux version of sound_error_path",
+        None,
+    ),
+    (
+        "linux_sound_cancel_path",
+        "Linux version of sound_cancel_path",
+        None,
+    ),
+    (
+        "linux_sound_done_path",
+        "Linux version of sound_done_path",
+        Some("\"/usr/share/sounds/freedesktop/stereo/complete.oga\""),
+    ),
+    (
+        "macos_paste_delay_ms",
+        "How long a macOS paste waits before restoring the clipboard",
+        None,
+    ),
+    (
+        "macos_restore_clipboard",
+        "What a macOS paste restores: \"all\", \"text\" or \"never\"",
+        None,
+    ),
+    (
+        "macos_open_privacy_settings",
+        "Open System Settings when a macOS permission is missing",
+        None,
+    ),
+];
+
+/// Written last: tables have to follow the plain keys.
+const TABLE_EXAMPLES: &[(&str, &str)] = &[
+    ("replacements", REPLACEMENTS_EXAMPLE),
+    ("spoken_command_phrases", SPOKEN_COMMAND_PHRASES_EXAMPLE),
+    ("paste_overrides", PASTE_OVERRIDES_EXAMPLE),
+    ("language_overrides", LANGUAGE_OVERRIDES_EXAMPLE),
+    ("bindings", BINDINGS_EXAMPLE),
+    ("profiles", PROFILES_EXAMPLE),
+];
+
+const REPLACEMENTS_EXAMPLE: &str = "\
+# Fixes for words Whisper keeps getting wrong: whole words in any case, or \"re:<regex>\" keys
+# whose replacement can use $1
+[replacements]
+# \"cube control\" = \"kubectl\"
+# \"re:(\\\\d+) percent\" = \"$1%\"
+";
+
+const SPOKEN_COMMAND_PHRASES_EXAMPLE: &str = "\
+# More spoken_commands, or other text for built-in ones; \"\" turns a phrase off. A leading
+# space keeps the previous word apart, a trailing one the next
+[spoken_command_phrases]
+# smiley = \" :-) \"
+# dash = \"\"
+";
+
+const PASTE_OVERRIDES_EXAMPLE: &str = "\
+# Paste shortcut per window class (like ctrl+shift+v), or \"type\" to type into that window.
+# \"title:<regex>\" keys match the window title; a table can press keys after the text
+[paste_overrides]
+# Alacritty = \"ctrl+shift+v\"
+# remote-viewer = \"type\"
+# \"title:- Canva\" = \"type\"
+# telegram-desktop = { paste = \"ctrl+v\", after = \"Return\" }
+";
+
+const LANGUAGE_OVERRIDES_EXAMPLE: &str = "\
+# Language per window class (\"title:<regex>\" on the title) focused when a recording stops
+[language_overrides]
+# telegram-desktop = \"ru\"
+# \"title:Slack\" = \"en\"
+";
+
+const BINDINGS_EXAMPLE: &str = "\
+# More PTT keys or combinations, each with its own language, translate, model or prompt;
+# ptt_key keeps the settings above unless it is listed here
+[bindings]
+# RShift = { language = \"ru\" }
+# \"LControl+Space\" = { language = \"de\", translate = true }
+";
+
+const PROFILES_EXAMPLE: &str = "\
+# Settings that replace the ones above while a profile is active (--profile <name>, or
+# profile_key at runtime); tables like paste_overrides gain the profile's entries
+# [profiles.meetings]
+# mode = \"translate\"
+# output_mode = \"clipboard\"
+";
+
+/// An annotated config.toml with every key at its default value.
+pub fn template() -> Result<String> {
+    let defaults = toml::Table::try_from(AppConfig::default())
+        .context("Failed to serialize the default config")?;
+    let mut out = String::from("# voice-ptt configuration, written by `voice-ptt init`\n\n");
+    for (key, comment, example) in KEYS {
+        out.push_str(&format!("# {}\n", comment));
+        match (defaults.get(*key), example) {
+            (Some(value), _) => out.push_str(&format!("{} = {}\n", key, value)),
+            (None, Some(example)) => out.push_str(&format!("# {} = {}\n", key, example)),
+            (None, None) => anyhow::bail!("No default or example for config key '{}'", key),
+        }
+    }
+    for (_, example) in TABLE_EXAMPLES {
+        out.push('\n');
+        out.push_str(example);
+    }
+    Ok(out)
+}
+
+/// Writes [`template`] to `path`, creating its directory. An existing file is only replaced
+/// with `force`.
+pub fn run(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists, pass --force to overwrite it",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(path, template()?).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("📝 Wrote {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TranscriptionMode;
+
+    #[test]
+    fn test_template_covers_every_key() {
+        let defaults = toml::Table::try_from(AppConfig::default()).unwrap();
+        for key in defaults.keys() {
+            assert!(
+                TABLE_EXAMPLES.iter().any(|(name, _)| name == key)
+                    || KEYS.iter().any(|(name, _, _)| name == key),
+                "{} is missing from the init template",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn test_template_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice-ptt").join("config.toml");
+        run(&path, false).unwrap();
+        assert!(run(&path, false).is_err());
+        run(&path, true).unwrap();
+
+        let config = AppConfig::load(&path).unwrap();
+        let defaults = AppConfig::default();
+        assert_eq!(config.ptt_key, defaults.ptt_key);
+        assert_eq!(config.hallucination_filters, defaults.hallucination_filters);
+        assert_eq!(config.highpass_hz, defaults.highpass_hz);
+        assert_eq!(config.language, None);
+
+        // The commented examples are valid too
+        let mut examples: String = KEYS
+            .iter()
+            .filter_map(|(key, _, example)| Some(format!("{} = {}\n", key, (*example)?)))
+            .collect();
+        for (_, example) in TABLE_EXAMPLES {
+            let table = |line: &str| line.trim_start_matches("# ").starts_with('[');
+            for line in example.lines().skip_while(|line| !table(line)) {
+                examples.push_str(line.trim_start_matches("# "));
+                examples.push('\n');
+            }
+        }
+        fs::write(&path, examples).unwrap();
+        let config = AppConfig::load(&path).unwrap();
+        assert_eq!(config.language.as_deref(), Some("en"));
+        assert_eq!(config.paste_overrides.len(), 4);
+        assert_eq!(config.replacements.len(), 2);
+        assert_eq!(config.spoken_command_phrases.len(), 2);
+        assert_eq!(config.language_overrides.len(), 2);
+        assert_eq!(config.bindings.len(), 2);
+        let meetings = config.with_profile("meetings").unwrap();
+        assert_eq!(meetings.mode, TranscriptionMode::Translate);
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tracing::info;
+
+use crate::config::{AppConfig, OutputMode};
+use crate::injector::SystemInjector;
+use crate::text;
+
+/// Puts a transcript into the focused window.
+#[async_trait]
+pub trait Injector: Send + Sync {
+    async fn inject(&self, text: &str, config: &AppConfig) -> Result<()>;
+}
+
+/// Longest transcript excerpt shown in the `output_mode = "clipboard"` notification.
+const NOTIFY_PREVIEW_CHARS: usize = 80;
+
+/// Delivers transcripts according to `output_mode`: pasted or typed through
+/// [`SystemInjector::type_text`], copied to the clipboard, or printed.
+pub struct SystemInjection;
+
+#[async_trait]
+impl Injector for SystemInjection {
+    async fn inject(&self, text: &str, config: &AppConfig) -> Result<()> {
+        match config.output_mode {
+            OutputMode::Inject => {
+                SystemInjector::type_text(
+                    text,
+                    config.typing_delay_ms,
+                    config.initial_delay_ms,
+                    config,
+                )
+                .await
+            }
+            OutputMode::Clipboard => {
+                SystemInjector::copy_to_clipboard(text, config)?;
+                info!("📋 Copied to the clipboard");
+                SystemInjector::notify(
+                    "Voice PTT: copied",
+                    &text::preview(text, NOTIFY_PREVIEW_CHARS),
+                );
+                Ok(())
+            }
+            OutputMode::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                writeln!(stdout, "{}", text)?;
+                stdout.flush()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A repeat was asked for before anything was injected.
+#[derive(Debug)]
+pub struct NothingToRepeat;
+
+impl fmt::Display for NothingToRepeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Nothing to repeat yet")
+    }
+}
+
+impl std::error::Error for NothingToRepeat {}
+
+struct Job {
+    /// `None` injects the last transcript again.
+    text: Option<String>,
+    /// The config of the recording, whose profile decides how the text is output.
+    config: AppConfig,
+    done: oneshot::Sender<Result<Duration>>,
+}
+
+/// Hands transcripts to a single task that injects them one at a time, in the order they
+/// were submitted, so one paste can't race another's clipboard write. Anything that has to
+/// see every injection in order belongs in that task.
+#[derive(Clone)]
+pub struct InjectionQueue {
+    jobs: mpsc::UnboundedSender<Job>,
+    /// Last transcript, kept even if injecting it failed
+    last: Arc<Mutex<Option<String>>>,
+}
+
+impl InjectionQueue {
+    pub fn spawn(injector: Arc<dyn Injector>) -> Self {
+        let (jobs, mut queued) = mpsc::unbounded_channel::<Job>();
+        let last = Arc::new(Mutex::new(None));
+        let task_last = last.clone();
+        tokio::spawn(async move {
+            // Last character injected, for auto_space
+            let mut previous_end = None;
+            while let Some(job) = queued.recv().await {
+                let transcript = {
+                    let mut last = task_last.lock().unwrap_or_else(|e| e.into_inner());
+                    if job.text.is_some() {
+                        *last = job.text;
+                    }
+                    last.clone()
+                };
+                let Some(transcript) = transcript else {
+                    let _ = job.done.send(Err(NothingToRepeat.into()));
+                    continue;
+                };
+                let text = spaced(&transcript, previous_end, &job.config);
+                let started = Instant::now();
+                let result = injector.inject(&text, &job.config).await;
+                if result.is_ok() {
+                    previous_end = text.chars().last().or(previous_end);
+                }
+                let _ = job.done.send(result.map(|()| started.elapsed()));
+            }
+        });
+        Self { jobs, last }
+    }
+
+    /// The transcript most recently taken from the queue, as [`InjectionQueue::repeat`] would
+    /// type it.
+    pub fn last(&self) -> Option<String> {
+        self.last.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Queues `text` right away, to be output as `config` says; the returned future resolves
+    /// with how long injecting it took once its turn has come and gone.
+    pub fn inject(
+        &self,
+        text: String,
+        config: AppConfig,
+    ) -> impl Future<Output = Result<Duration>> {
+        self.submit(Some(text), config)
+    }
+
+    /// Queues the most recent transcript again, as of its turn, so a repeat can't overtake a
+    /// transcript submitted before it. Fails with [`NothingToRepeat`] before the first one.
+    pub fn repeat(&self, config: AppConfig) -> impl Future<Output = Result<Duration>> {
+        self.submit(None, config)
+    }
+
+    fn submit(
+        &self,
+        text: Option<String>,
+        config: AppConfig,
+    ) -> impl Future<Output = Result<Duration>> {
+        let (done, result) = oneshot::channel();
+        let sent = self.jobs.send(Job { text, config, done });
+        async move {
+            sent.ok().context("Injection queue stopped")?;
+            result.await.context("Injection queue stopped")?
+        }
+    }
+}
+
+/// Adds the `auto_space` separator and `append_suffix`; printed transcripts stay as they are.
+fn spaced(text: &str, previous_end: Option<char>, config: &AppConfig) -> String {
+    if config.output_mode == OutputMode::Stdout {
+        return text.to_string();
+    }
+    let space = config.auto_space && text::needs_space(previous_end, text);
+    format!(
+        "{}{}{}",
+        if space { " " } else { "" },
+        text,
+        config.append_suffix
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    /// Records what it was asked to inject and fails the test on overlapping calls.
+    #[derive(Default)]
+    struct MockInjector {
+        injected: Mutex<Vec<String>>,
+        busy: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Injector for MockInjector {
+        async fn inject(&self, text: &str, _config: &AppConfig) -> Result<()> {
+            assert!(
+                !self.busy.swap(true, Ordering::SeqCst),
+                "injections overlap"
+            );
+            // Earlier texts take longer, so a concurrent queue would finish them last
+            let delay = 40u64.saturating_sub(10 * self.injected.lock().unwrap().len() as u64);
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            self.injected.lock().unwrap().push(text.to_string());
+            self.busy.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auto_space_between_injections() {
+        let injector = Arc::new(MockInjector::default());
+        let config = AppConfig {
+            auto_space: true,
+            ..Default::default()
+        };
+        let queue = InjectionQueue::spawn(injector.clone());
+        for text in ["First one.", "Second", ", third"] {
+            queue
+                .inject(text.to_string(), config.clone())
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            *injector.injected.lock().unwrap(),
+            ["First one.", " Second", ", third"]
+        );
+    }
+
+    #[test]
+    fn test_append_suffix() {
+        let config = AppConfig {
+            append_suffix: "\n".to_string(),
+            auto_space: true,
+            ..Default::default()
+        };
+        // The suffix ends in whitespace, so no extra space is needed next time
+        assert_eq!(spaced("Done.", None, &config), "Done.\n");
+        assert_eq!(spaced("Next.", Some('\n'), &config), "Next.\n");
+    }
+
+    #[tokio::test]
+    async fn test_injections_keep_submission_order() {
+        let injector = Arc::new(MockInjector::default());
+        let queue = InjectionQueue::spawn(injector.clone());
+
+        let pending: Vec<_> = ["first", "second", "third"]
+            .into_iter()
+            .map(|text| tokio::spawn(queue.inject(text.to_string(), AppConfig::default())))
+            .collect();
+        for task in pending {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(
+            *injector.injected.lock().unwrap(),
+            ["first", "second", "third"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeat_injects_the_last_transcript_again() {
+        let injector = Arc::new(MockInjector::default());
+        let queue = InjectionQueue::spawn(injector.clone());
+        let error = queue.repeat(AppConfig::default()).await.unwrap_err();
+        assert!(error.is::<NothingToRepeat>());
+
+        // Queued behind a transcript, the repeat sees that one
+        let first = tokio::spawn(queue.inject("first".to_string(), AppConfig::default()));
+        let repeated = queue.repeat(AppConfig::default());
+        first.await.unwrap().unwrap();
+        repeated.await.unwrap();
+        assert_eq!(*injector.injected.lock().unwrap(), ["first", "first"]);
+        assert_eq!(queue.last().as_deref(), Some("first"));
+    }
+}
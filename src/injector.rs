@@ -1,11 +1,63 @@
 use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
 use std::process::Command;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{
+    AppConfig, CaptureBackend, InjectionBackend, InjectionMethod, Modifier, OutputMode,
+    PasteAction, PasteOverride, PttKey, Shortcut, ShortcutKey, SoundBackend, WindowInfo,
+};
+use crate::control::{State, Status};
+use crate::native;
+use crate::ptt::KeyReader;
+
+/// Longest wait for the keys of a PTT combination to be released before injecting.
+const CHORD_RELEASE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Rows of the US keyboard with the Linux input event code of their first key, for sending
+/// character keys with ydotool.
+const YDOTOOL_KEY_ROWS: [(&str, u16); 4] = [
+    ("1234567890", 2),
+    ("qwertyuiop", 16),
+    ("asdfghjkl", 30),
+    ("zxcvbnm", 44),
+];
+
+/// A sound player and the arguments that go before the file.
+pub type SoundPlayerCommand = (&'static str, &'static [&'static str]);
+
+/// External players for sound files, preferred first.
+#[cfg(target_os = "linux")]
+const SOUND_PLAYERS: [SoundPlayerCommand; 4] = [
+    ("pw-play", &[]),
+    ("paplay", &[]),
+    ("ffplay", &["-nodisp", "-autoexit", "-loglevel", "error"]),
+    ("aplay", &["-q"]),
+];
+#[cfg(not(target_os = "linux"))]
+const SOUND_PLAYERS: [SoundPlayerCommand; 1] = [("afplay", &[])];
+
+/// The first of [`SOUND_PLAYERS`] that `exists` finds.
+fn pick_sound_player<F>(exists: F) -> Option<SoundPlayerCommand>
+where
+    F: Fn(&str) -> bool,
+{
+    SOUND_PLAYERS
+        .into_iter()
+        .find(|(program, _)| exists(program))
+}
 
 pub struct SystemInjector;
 
 impl SystemInjector {
-    /// Plays an audio file using system tools.
+    /// Plays an audio file using system tools. Only the first failure per file is logged, so
+    /// a wrong path doesn't print an error on every beep.
     pub fn play_sound(enabled: bool, path: &str) {
         if !enabled {
             return;
@@ -13,58 +65,181 @@ impl SystemInjector {
 
         let path = path.to_string();
         std::thread::spawn(move || {
-            #[cfg(target_os = "linux")]
-            let result = Command::new("paplay").arg(&path).spawn();
-
-            #[cfg(target_os = "macos")]
-            let result = Command::new("afplay").arg(&path).spawn();
-
-            match result {
-                Ok(mut child) => { let _ = child.wait(); }
-                Err(e) => eprintln!("❌ Failed to play sound {}: {}", path, e),
+            if let Err(e) = Self::play_sound_blocking(&path) {
+                static FAILED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+                let mut failed = FAILED.lock().unwrap_or_else(|e| e.into_inner());
+                if !failed.contains(&path) {
+                    error!("❌ Failed to play sound {}: {:#}", path, e);
+                    failed.push(path);
+                }
             }
         });
     }
 
-    /// Sends a system notification.
+    /// Plays an audio file and waits until playback has finished.
+    pub fn play_sound_blocking(path: &str) -> Result<()> {
+        let (program, args) = Self::sound_player().with_context(|| {
+            format!(
+                "No sound player found, install one of: {}",
+                SOUND_PLAYERS.map(|(program, _)| program).join(", ")
+            )
+        })?;
+        let out = Command::new(program)
+            .args(args)
+            .arg(path)
+            .output()
+            .with_context(|| format!("Failed to execute {}", program))?;
+        if !out.status.success() {
+            anyhow::bail!(
+                "{} exited with {}: {}",
+                program,
+                out.status,
+                String::from_utf8_lossy(&out.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// The first installed entry of [`SOUND_PLAYERS`], looked up once.
+    pub fn sound_player() -> Option<SoundPlayerCommand> {
+        static PLAYER: OnceLock<Option<SoundPlayerCommand>> = OnceLock::new();
+        *PLAYER.get_or_init(|| pick_sound_player(tool_exists))
+    }
+
+    /// Sends a system notification, over D-Bus on Linux. Without a notification daemon the
+    /// failure is only logged.
     pub fn notify(title: &str, message: &str) {
         let title = title.to_string();
         let message = message.to_string();
         std::thread::spawn(move || {
             #[cfg(target_os = "linux")]
-            let result = Command::new("notify-send")
-                .arg(&title)
-                .arg(&message)
-                .arg("-t")
-                .arg("5000")
-                .spawn();
+            let result = notify_rust::Notification::new()
+                .summary(&title)
+                .body(&message)
+                .timeout(5000)
+                .show()
+                .map(|_| ())
+                .map_err(anyhow::Error::from);
 
             #[cfg(target_os = "macos")]
             let result = {
                 let script = format!(
-                    "display notification \"{}\" with title \"{}\"",
-                    message.replace('\\', "\\\\").replace('"', "\\\""),
-                    title.replace('\\', "\\\\").replace('"', "\\\"")
+                    "display notification {} with title {}",
+                    applescript_string(&message),
+                    applescript_string(&title)
                 );
                 Command::new("osascript")
                     .arg("-e")
                     .arg(script)
                     .spawn()
+                    .and_then(|mut child| child.wait())
+                    .map(|_| ())
+                    .map_err(anyhow::Error::from)
             };
 
-            match result {
-                Ok(mut child) => { let _ = child.wait(); }
-                Err(e) => eprintln!("❌ Failed to send notification: {}", e),
+            if let Err(e) = result {
+                error!("❌ Failed to send notification: {}", e);
             }
         });
     }
 
-    /// Injects text as keyboard input.
+    /// Puts text on the clipboard without pasting it.
+    pub fn copy_to_clipboard(text: &str, config: &AppConfig) -> Result<()> {
+        use std::io::Write;
+        #[cfg(target_os = "macos")]
+        let mut child = {
+            if config.injection_backend == InjectionBackend::Native {
+                return native::copy(text, config.clipboard_private);
+            }
+            Command::new("pbcopy")
+                .stdin(std::process::Stdio::piped())
+                .spawn()
+                .context("Failed to execute pbcopy")?
+        };
+        #[cfg(not(target_os = "macos"))]
+        #[allow(clippy::needless_borrows_for_generic_args)]
+        let mut child = match Self::injection_backend(config) {
+            InjectionBackend::Native => return native::copy(text, config.clipboard_private),
+            InjectionBackend::Wayland => Command::new("wl-copy")
+                .stdin(std::process::Stdio::piped())
+                .spawn()
+                .context("Failed to execute wl-copy. Is wl-clipboard installed?")?,
+            // xsel can only offer one target, the history hint needs a second one
+            _ if config.clipboard_private => return native::copy(text, true),
+            // Use xsel to set the clipboard selection
+            _ => Command::new("xsel")
+                .args(&["--clipboard", "--input"])
+                .stdin(std::process::Stdio::piped())
+                .spawn()
+                .context("Failed to execute xsel. Is it installed?")?,
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        // stdin is dropped here, closing the pipe.
+        // xsel and wl-copy read until EOF, then take over the clipboard and exit.
+        child.wait()?;
+        Ok(())
+    }
+
+    /// Detects the session from the environment, see [`detect_session`].
+    pub fn session() -> Session {
+        let var = |name| std::env::var(name).ok();
+        detect_session(
+            var("WAYLAND_DISPLAY").as_deref(),
+            var("XDG_SESSION_TYPE").as_deref(),
+            var("DISPLAY").as_deref(),
+        )
+    }
+
+    /// Resolves `injection_backend = "auto"` from the detected session, falling back to the
+    /// native injector on X11 when xsel or xdotool isn't installed.
+    pub fn injection_backend(config: &AppConfig) -> InjectionBackend {
+        match config.injection_backend {
+            InjectionBackend::Auto => match Self::session() {
+                Session::Wayland => InjectionBackend::Wayland,
+                Session::X11 | Session::Unknown => {
+                    let x11_tools = injection_tools(InjectionBackend::X11, OutputMode::Inject);
+                    if missing_tools(&x11_tools, tool_exists).is_empty() {
+                        InjectionBackend::X11
+                    } else {
+                        InjectionBackend::Native
+                    }
+                }
+            },
+            backend => backend,
+        }
+    }
+
+    /// Waits until a PTT combination is released, so a modifier that is still held (e.g. the
+    /// Ctrl of Ctrl+Space) can't turn the paste or the typed text into other shortcuts.
+    async fn wait_for_chord_release(keys: Vec<PttKey>) {
+        if keys.len() < 2 {
+            return;
+        }
+        // DeviceState isn't Send, so it polls on a blocking thread
+        let _ = tokio::task::spawn_blocking(move || {
+            let Ok(mut reader) = KeyReader::new(&keys) else {
+                return;
+            };
+            let started = Instant::now();
+            while started.elapsed() < CHORD_RELEASE_TIMEOUT
+                && reader.held().iter().any(|key| keys.contains(key))
+            {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        })
+        .await;
+    }
+
+    /// Injects text into the focused window, by pasting it through the clipboard or, with
+    /// `injection_method = "type"`, by typing it key by key `delay_ms` apart.
     pub async fn type_text(
         text: &str,
-        _delay_ms: u64,
+        delay_ms: u64,
         initial_delay_ms: u64,
-        config: &crate::config::AppConfig,
+        config: &AppConfig,
     ) -> Result<()> {
         if text.is_empty() {
             return Ok(());
@@ -72,95 +247,1677 @@ impl SystemInjector {
 
         // Wait a bit before typing to ensure the user has released the PTT key modifiers
         tokio::time::sleep(Duration::from_millis(initial_delay_ms)).await;
+        Self::wait_for_chord_release(config.get_ptt_keycodes()).await;
 
         #[cfg(target_os = "linux")]
         {
-            // Use xsel to set the clipboard selection
-            use std::io::Write;
-            let mut child = Command::new("xsel")
-                .args(&["--clipboard", "--input"])
-                .stdin(std::process::Stdio::piped())
-                .spawn()
-                .context("Failed to execute xsel. Is it installed?")?;
-
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(text.as_bytes())?;
-            }
-            // stdin is dropped here, closing the pipe.
-            // xsel reads until EOF, then takes over the clipboard and exits.
-            child.wait()?;
-
-            // Small delay to ensure the clipboard is ready before we simulate the paste command
-            tokio::time::sleep(Duration::from_millis(50)).await;
+            let backend = Self::injection_backend(config);
 
             // Detect if the active window has an override in the config
-            let mut paste_key = "ctrl+v".to_string();
-
-            let output = Command::new("xdotool")
-                .args(&["getactivewindow", "getwindowclassname"])
-                .output();
-
-            if let Ok(out) = output {
-                let window_class = String::from_utf8_lossy(&out.stdout).trim().to_string();
-                let lower_class = window_class.to_lowercase();
-                
-                println!("📌 Detected window class: '{}'", window_class);
-                
-                // Case-insensitive lookup in overrides map
-                for (name, shortcut) in &config.paste_overrides {
-                    if name.to_lowercase() == lower_class {
-                        paste_key = shortcut.clone();
-                        break;
+            let window = Self::focused_window(config);
+            let decision = insertion_for(
+                window.as_ref().map(|window| window.class.as_str()),
+                window.as_ref().and_then(|window| window.title.as_deref()),
+                &config.paste_overrides,
+                config.injection_method,
+            );
+            let source = decision.source;
+
+            match decision.insertion {
+                Insertion::Type => {
+                    debug!("⌨️ Typing {} characters ({})", text.chars().count(), source);
+                    match backend {
+                        InjectionBackend::Wayland => Self::wayland_type(text, delay_ms)?,
+                        InjectionBackend::Native => {
+                            native::type_text(&type_steps(text, TYPE_CHUNK_CHARS), delay_ms)?
+                        }
+                        _ => Self::x11_type(text, delay_ms)?,
                     }
                 }
+                Insertion::Paste(shortcut) => {
+                    debug!("⌨️ Using paste shortcut: '{}' ({})", shortcut, source);
+                    if backend == InjectionBackend::Native {
+                        native::paste(text, &shortcut, config.clipboard_private)?;
+                    } else {
+                        Self::copy_to_clipboard(text, config)?;
 
-                println!("⌨️ Using paste shortcut: '{}'", paste_key);
+                        // Small delay to ensure the clipboard is ready before we simulate the paste command
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+
+                        // Simulate the paste shortcut (either default ctrl+v or override from config)
+                        Self::send_chord(backend, &shortcut)?;
+                    }
+                }
             }
 
-            // Simulate the paste shortcut (either default ctrl+v or override from config)
-            Command::new("xdotool")
-                .args(&["key", "--clearmodifiers", &paste_key])
-                .status()
-                .context("Failed to execute xdotool for pasting")?;
+            if !decision.after.is_empty() {
+                tokio::time::sleep(Duration::from_millis(config.after_keys_delay_ms)).await;
+                let keys: Vec<String> = decision.after.iter().map(ToString::to_string).collect();
+                debug!("⌨️ Sending '{}' after the text", keys.join(" "));
+                for chord in &decision.after {
+                    Self::send_chord(backend, chord)?;
+                }
+            }
         }
 
         #[cfg(target_os = "macos")]
         {
+            let _ = delay_ms;
+            let entry = Self::focused_window(config)
+                .and_then(|window| window.lookup(&config.paste_overrides));
+            let default_paste = Shortcut::new(&[Modifier::Super], 'v');
+            let shortcut = match entry.and_then(|entry| entry.paste.as_ref()) {
+                Some(PasteAction::Type) => {
+                    warn!("⚠️ Typing isn't supported on macOS, pasting instead");
+                    &default_paste
+                }
+                Some(PasteAction::Shortcut(shortcut)) => shortcut,
+                None => &default_paste,
+            };
+            let source = if entry.is_some() {
+                InsertionSource::UserOverride
+            } else {
+                InsertionSource::Default
+            };
+            debug!("⌨️ Using paste shortcut: '{}' ({})", shortcut, source);
+            let after = entry.map_or(&[][..], |entry| &entry.after);
+
+            if config.injection_backend == InjectionBackend::Native {
+                native::paste(text, shortcut, config.clipboard_private)?;
+                if !after.is_empty() {
+                    tokio::time::sleep(Duration::from_millis(config.after_keys_delay_ms)).await;
+                    for chord in after {
+                        native::key_chord(chord)?;
+                    }
+                }
+                return Ok(());
+            }
+            let mut keystrokes = applescript_keystroke(shortcut)?;
+            if !after.is_empty() {
+                keystrokes.push_str(&format!(
+                    "\ndelay {:.3}",
+                    config.after_keys_delay_ms as f64 / 1000.0
+                ));
+                for chord in after {
+                    keystrokes.push('\n');
+                    keystrokes.push_str(&applescript_keystroke(chord)?);
+                }
+            }
+
             // For macOS, we use the clipboard to handle Unicode (like Russian) correctly.
-            // We save the current clipboard, set it to our text, paste it, and restore the old clipboard.
-            let script = format!(
-                "set oldClipboard to the clipboard\n\
-                 set the clipboard to \"{}\"\n\
-                 tell application \"System Events\"\n\
-                     keystroke \"v\" using command down\n\
-                 end tell\n\
-                 delay 0.1\n\
-                 set the clipboard to oldClipboard",
-                text.replace('\\', "\\\\").replace('"', "\\\"")
-            );
-            Command::new("osascript")
+            // The text goes through a temp file rather than the script, so quotes and line
+            // breaks reach the clipboard untouched. The old clipboard is held by the same
+            // script and restored after the paste.
+            let clipboard_info = Command::new("osascript")
+                .args(["-e", "clipboard info"])
+                .output()
+                .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+                .unwrap_or_default();
+            let non_text = clipboard_holds_non_text(&clipboard_info);
+            let restore = match config.macos_restore_clipboard {
+                crate::config::ClipboardRestore::All => true,
+                crate::config::ClipboardRestore::Text => !non_text,
+                crate::config::ClipboardRestore::Never => false,
+            };
+            if non_text && !restore {
+                info!("📋 The clipboard held non-text data, it is not restored");
+            }
+
+            let text_file =
+                std::env::temp_dir().join(format!("voice-ptt-paste-{}.txt", std::process::id()));
+            std::fs::write(&text_file, text).context("Failed to write the text to paste")?;
+            let status = Command::new("osascript")
                 .arg("-e")
-                .arg(script)
+                .arg(macos_paste_script(
+                    &text_file,
+                    &keystrokes,
+                    restore,
+                    config.macos_paste_delay_ms,
+                ))
+                .status();
+            let _ = std::fs::remove_file(&text_file);
+            status.context("Failed to execute osascript for typing via clipboard")?;
+        }
+
+        Ok(())
+    }
+
+    /// The focused window, asked from the `window_info` provider on Linux and from System
+    /// Events on macOS.
+    pub fn focused_window(config: &AppConfig) -> Option<FocusedWindow> {
+        #[cfg(target_os = "linux")]
+        {
+            let provider = window_info_provider(
+                config.window_info,
+                Self::injection_backend(config),
+                std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some(),
+                std::env::var_os("SWAYSOCK").is_some(),
+            );
+            let (class, title) = provider.and_then(Self::active_window)?;
+            debug!("📌 Detected window class: '{}', title: '{}'", class, title);
+            Some(FocusedWindow {
+                class,
+                title: Some(title),
+                bundle_id: None,
+            })
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let _ = config;
+            let (class, bundle_id) = Self::frontmost_app()?;
+            Some(FocusedWindow {
+                class,
+                title: None,
+                bundle_id,
+            })
+        }
+    }
+
+    /// Class and title of the focused window, asked from `provider`.
+    #[cfg(target_os = "linux")]
+    fn active_window(provider: WindowInfo) -> Option<(String, String)> {
+        let output = |program: &str, args: &[&str]| {
+            let out = Command::new(program).args(args).output().ok()?;
+            Some(String::from_utf8_lossy(&out.stdout).into_owned())
+        };
+        match provider {
+            WindowInfo::X11 => parse_xdotool_window(&output(
+                "xdotool",
+                &["getactivewindow", "getwindowclassname", "getwindowname"],
+            )?),
+            WindowInfo::Hyprland => {
+                parse_hyprctl_window(&output("hyprctl", &["activewindow", "-j"])?)
+            }
+            WindowInfo::Sway => parse_sway_tree(&output("swaymsg", &["-t", "get_tree"])?),
+            WindowInfo::Auto => None,
+        }
+    }
+
+    /// Name and bundle id of the frontmost macOS application.
+    #[cfg(target_os = "macos")]
+    fn frontmost_app() -> Option<(String, Option<String>)> {
+        let out = Command::new("osascript")
+            .args(["-e", FRONTMOST_APP_SCRIPT])
+            .output()
+            .ok()?;
+        let out = String::from_utf8_lossy(&out.stdout);
+        let mut lines = out.lines().map(str::trim);
+        let name = lines.next().filter(|name| !name.is_empty())?.to_string();
+        let bundle_id = lines.next().filter(|id| !id.is_empty()).map(str::to_string);
+        debug!(
+            "📌 Frontmost app: '{}' ({})",
+            name,
+            bundle_id.as_deref().unwrap_or("no bundle id")
+        );
+        Some((name, bundle_id))
+    }
+
+    /// Sends a chord like `ctrl+v` or `Return` through `backend`.
+    #[cfg(target_os = "linux")]
+    fn send_chord(backend: InjectionBackend, chord: &Shortcut) -> Result<()> {
+        match backend {
+            InjectionBackend::Wayland => Self::wayland_key(chord),
+            InjectionBackend::Native => native::key_chord(chord),
+            _ => {
+                #[allow(clippy::needless_borrows_for_generic_args)]
+                Command::new("xdotool")
+                    .args(&["key", "--clearmodifiers", &chord.to_string()])
+                    .status()
+                    .context("Failed to execute xdotool for sending keys")?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends a chord with wtype, or ydotool if wtype isn't installed.
+    #[cfg(target_os = "linux")]
+    fn wayland_key(chord: &Shortcut) -> Result<()> {
+        if tool_exists("wtype") {
+            Command::new("wtype")
+                .args(wtype_args(chord))
+                .status()
+                .context("Failed to execute wtype for sending keys")?;
+        } else {
+            Command::new("ydotool")
+                .arg("key")
+                .args(ydotool_args(chord)?)
                 .status()
-                .context("Failed to execute osascript for typing via clipboard")?;
+                .context("Failed to execute ydotool for sending keys")?;
         }
+        Ok(())
+    }
+
+    /// Types text with `xdotool type`, pressing Return for line breaks.
+    #[cfg(target_os = "linux")]
+    fn x11_type(text: &str, delay_ms: u64) -> Result<()> {
+        let delay = delay_ms.to_string();
+        for step in type_steps(text, TYPE_CHUNK_CHARS) {
+            let status = match &step {
+                TypeStep::Text(chunk) => Command::new("xdotool")
+                    .args(["type", "--clearmodifiers", "--delay", &delay, "--", chunk])
+                    .status(),
+                TypeStep::Return => Command::new("xdotool")
+                    .args(["key", "--clearmodifiers", "Return"])
+                    .status(),
+            };
+            status.context("Failed to execute xdotool for typing")?;
+        }
+        Ok(())
+    }
 
+    /// Types text with wtype, pressing Return for line breaks.
+    #[cfg(target_os = "linux")]
+    fn wayland_type(text: &str, delay_ms: u64) -> Result<()> {
+        if !tool_exists("wtype") {
+            anyhow::bail!("injection_method = \"type\" needs wtype on Wayland");
+        }
+        let delay = delay_ms.to_string();
+        for step in type_steps(text, TYPE_CHUNK_CHARS) {
+            let status = match &step {
+                TypeStep::Text(chunk) => Command::new("wtype")
+                    .args(["-d", &delay, "--", chunk])
+                    .status(),
+                TypeStep::Return => Command::new("wtype").args(["-k", "Return"]).status(),
+            };
+            status.context("Failed to execute wtype for typing")?;
+        }
         Ok(())
     }
 
-    /// Verifies that required system tools are available.
-    pub fn check_dependencies() -> Result<()> {
+    /// Prints the detected session and injection path, and refuses to start when that
+    /// session can't be served by the installed tools.
+    pub fn check_dependencies(config: &AppConfig) -> Result<()> {
         #[cfg(target_os = "linux")]
         {
-            if Command::new("xdotool").arg("--version").output().is_err() {
-                anyhow::bail!("'xdotool' is required but not found in PATH.");
+            let session = Self::session();
+            let backend = Self::injection_backend(config);
+            // Printing transcripts doesn't touch the clipboard or the focused window
+            if config.output_mode != OutputMode::Stdout {
+                if session == Session::Unknown && config.injection_backend == InjectionBackend::Auto
+                {
+                    anyhow::bail!("No graphical session found (WAYLAND_DISPLAY and DISPLAY are unset). Start voice-ptt from inside your desktop session, or set injection_backend in config.toml.");
+                }
+                let tools = match backend {
+                    InjectionBackend::Wayland => "wl-copy + wtype/ydotool",
+                    InjectionBackend::Native => "the native injector (no external tools)",
+                    _ => "xsel + xdotool",
+                };
+                info!("🖥️ {} session, pasting with {}", session, tools);
+                if backend == InjectionBackend::Native
+                    && config.injection_backend == InjectionBackend::Auto
+                {
+                    info!(
+                        "💡 xsel/xdotool not found, install them for per-window paste overrides."
+                    );
+                }
+                if config.clipboard_private && backend == InjectionBackend::Wayland {
+                    warn!("⚠️ clipboard_private has no effect on Wayland: wl-copy can't add the clipboard manager hint.");
+                }
+                if session == Session::Wayland && backend == InjectionBackend::X11 {
+                    warn!("⚠️ injection_backend = \"x11\" in a Wayland session: under XWayland xdotool keys often reach the wrong window.");
+                }
             }
-            if Command::new("xsel").arg("--version").output().is_err() {
-                anyhow::bail!("'xsel' is required for fast text injection. Please install it (e.g., sudo pacman -S xsel).");
+
+            let missing = missing_tools(&required_tools(config, backend), tool_exists);
+            if !missing.is_empty() {
+                let names: Vec<String> = missing.iter().map(|tool| tool.name()).collect();
+                anyhow::bail!(
+                    "Missing tools for this configuration: {}. Install them ({}) or change injection_backend, output_mode, sound_backend or capture_backend in config.toml.",
+                    names.join(", "),
+                    install_hint(&missing)
+                );
             }
         }
-        
-        // MacOS usually has osascript and afplay by default
+
+        // MacOS has osascript and afplay by default, but needs privacy permissions
+        #[cfg(target_os = "macos")]
+        crate::permissions::report(
+            &crate::permissions::missing(config),
+            config.macos_open_privacy_settings,
+        );
         Ok(())
     }
 }
+
+/// A change in what the status notification should say.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatusUpdate {
+    /// The key is held.
+    Recording,
+    /// A recording was handed off for transcription.
+    Processing,
+    /// The key was released but nothing is transcribed, e.g. an empty recording.
+    Discarded,
+    /// A transcription task ended, whether it typed anything or not.
+    Finished,
+    /// A transcription or its injection failed.
+    Failed,
+    /// Keys are ignored (`true`) or listened to again, through `voice-ptt ctl`.
+    Paused(bool),
+    /// Another profile is active.
+    Profile(Option<String>),
+}
+
+/// Recording state across overlapping transcriptions, reduced to one notification.
+#[derive(Debug, Default)]
+struct StatusState {
+    recording: bool,
+    processing: usize,
+    paused: bool,
+    failed: bool,
+    profile: Option<String>,
+}
+
+impl StatusState {
+    /// Applies an update and returns the text to show, or `None` to close the notification.
+    fn apply(&mut self, update: StatusUpdate) -> Option<&'static str> {
+        match update {
+            StatusUpdate::Recording => {
+                self.recording = true;
+                self.failed = false;
+            }
+            StatusUpdate::Processing => {
+                self.recording = false;
+                self.processing += 1;
+            }
+            StatusUpdate::Discarded => self.recording = false,
+            StatusUpdate::Finished => self.processing = self.processing.saturating_sub(1),
+            StatusUpdate::Failed => self.failed = true,
+            StatusUpdate::Paused(paused) => self.paused = paused,
+            StatusUpdate::Profile(profile) => self.profile = profile,
+        }
+        if self.recording {
+            Some("Recording…")
+        } else if self.processing > 0 {
+            Some("Processing…")
+        } else {
+            None
+        }
+    }
+
+    /// What `voice-ptt ctl status` and status bars report.
+    fn status(&self) -> Status {
+        Status {
+            state: if self.recording {
+                State::Recording
+            } else if self.processing > 0 {
+                State::Processing
+            } else {
+                State::Idle
+            },
+            paused: self.paused,
+            failed: self.failed,
+            profile: self.profile.clone(),
+        }
+    }
+}
+
+/// Broadcasts the recording state. It shows one persistent notification that says
+/// "Recording…", is replaced by "Processing…" and closes once the transcript is typed
+/// (`status_notifications`, Linux only), reports the same states to systemd when running as
+/// a service, and keeps the current [`Status`] for the control socket and its subscribers.
+/// Notifications are sent from a single thread, in order.
+#[derive(Clone)]
+pub struct StatusNotifier {
+    state: Arc<Mutex<StatusState>>,
+    statuses: Arc<watch::Sender<Status>>,
+    texts: Option<std::sync::mpsc::Sender<Option<&'static str>>>,
+}
+
+impl StatusNotifier {
+    pub fn new(config: &AppConfig) -> Self {
+        let state = StatusState::default();
+        let statuses = Arc::new(watch::Sender::new(state.status()));
+        let mut texts = None;
+        #[cfg(target_os = "linux")]
+        if config.status_notifications || crate::systemd::enabled() {
+            let desktop = config.status_notifications;
+            let (sender, received) = std::sync::mpsc::channel();
+            std::thread::spawn(move || show_status(received, desktop));
+            texts = Some(sender);
+        }
+        let _ = config;
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            statuses,
+            texts,
+        }
+    }
+
+    pub fn update(&self, update: StatusUpdate) {
+        // Applied and passed on under the lock, so concurrent updates arrive in order
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let text = state.apply(update);
+        let status = state.status();
+        self.statuses.send_if_modified(|current| {
+            let changed = *current != status;
+            *current = status;
+            changed
+        });
+        if let Some(texts) = &self.texts {
+            let _ = texts.send(text);
+        }
+    }
+
+    pub fn current(&self) -> Status {
+        self.statuses.borrow().clone()
+    }
+
+    /// Sees every change of the status from now on.
+    pub fn subscribe(&self) -> watch::Receiver<Status> {
+        self.statuses.subscribe()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn show_status(texts: std::sync::mpsc::Receiver<Option<&'static str>>, desktop: bool) {
+    let mut shown: Option<notify_rust::NotificationHandle> = None;
+    for text in texts {
+        crate::systemd::notify(&format!("STATUS={}", text.unwrap_or("Ready")));
+        if !desktop {
+            continue;
+        }
+        match (text, shown.as_mut()) {
+            (Some(text), Some(handle)) => {
+                if handle.body != text {
+                    handle.body(text);
+                    if let Err(e) = handle.update() {
+                        warn!("⚠️ Failed to update the status notification: {}", e);
+                    }
+                }
+            }
+            (Some(text), None) => {
+                match notify_rust::Notification::new()
+                    .summary("Voice PTT")
+                    .body(text)
+                    .timeout(notify_rust::Timeout::Never)
+                    .show()
+                {
+                    Ok(handle) => shown = Some(handle),
+                    Err(e) => warn!("⚠️ Failed to show the status notification: {}", e),
+                }
+            }
+            (None, _) => {
+                if let Some(handle) = shown.take() {
+                    handle.close();
+                }
+            }
+        }
+    }
+}
+
+/// Quotes text as an AppleScript string literal, escaping backslashes before quotes so the
+/// quote escapes aren't doubled.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn applescript_string(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Clipboard classes that hold plain or styled text, as listed by `clipboard info`.
+const TEXT_CLIPBOARD_CLASSES: [&str; 7] = [
+    "«class utf8»",
+    "«class ut16»",
+    "string",
+    "Unicode text",
+    "styled Clipboard text",
+    "«class RTF »",
+    "«class HTML»",
+];
+
+/// Whether `clipboard info` output (`«class PNGf», 3400, string, 12`) lists any class that
+/// isn't text, like an image or a copied file.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn clipboard_holds_non_text(info: &str) -> bool {
+    info.split(", ")
+        .step_by(2)
+        .map(str::trim)
+        .filter(|class| !class.is_empty())
+        .any(|class| !TEXT_CLIPBOARD_CLASSES.contains(&class))
+}
+
+/// Prints the name and bundle id of the frontmost application on two lines.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const FRONTMOST_APP_SCRIPT: &str = r#"tell application "System Events"
+    set frontApp to first process whose frontmost is true
+    set appName to name of frontApp
+    try
+        set bundleId to bundle identifier of frontApp
+    on error
+        set bundleId to ""
+    end try
+end tell
+return appName & linefeed & bundleId"#;
+
+/// System Events command for a chord: `cmd+shift+v` becomes
+/// `keystroke "v" using {command down, shift down}`, named keys are sent by key code.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn applescript_keystroke(chord: &Shortcut) -> Result<String> {
+    let modifiers: Vec<&str> = chord
+        .modifiers
+        .iter()
+        .map(|modifier| match modifier {
+            Modifier::Super => "command down",
+            Modifier::Ctrl => "control down",
+            Modifier::Alt => "option down",
+            Modifier::Shift => "shift down",
+        })
+        .collect();
+    // Named keys go by key code, which `keystroke` can't send
+    let mut command = match chord.key {
+        ShortcutKey::Char(c) => format!("keystroke {}", applescript_string(&c.to_string())),
+        ShortcutKey::Return => "key code 36".to_string(),
+        ShortcutKey::Tab => "key code 48".to_string(),
+        ShortcutKey::Space => "key code 49".to_string(),
+        ShortcutKey::Escape => "key code 53".to_string(),
+        ShortcutKey::Delete => "key code 117".to_string(),
+        ShortcutKey::BackSpace => "key code 51".to_string(),
+        ShortcutKey::Insert => anyhow::bail!("macOS has no Insert key, can't send '{}'", chord),
+    };
+    match modifiers.as_slice() {
+        [] => {}
+        [modifier] => command.push_str(&format!(" using {}", modifier)),
+        _ => command.push_str(&format!(" using {{{}}}", modifiers.join(", "))),
+    }
+    Ok(format!("tell application \"System Events\" to {}", command))
+}
+
+/// AppleScript that puts the contents of `text_file` on the clipboard, pastes them with
+/// `keystrokes` (see [`applescript_keystroke`]) and, with `restore`, puts the old contents
+/// back `delay_ms` later.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn macos_paste_script(text_file: &Path, keystrokes: &str, restore: bool, delay_ms: u64) -> String {
+    let mut script = String::new();
+    if restore {
+        script.push_str("set oldClipboard to the clipboard\n");
+    }
+    script.push_str(&format!(
+        "set the clipboard to (read POSIX file {} as «class utf8»)\n{}\n",
+        applescript_string(&text_file.to_string_lossy()),
+        keystrokes
+    ));
+    if restore {
+        script.push_str(&format!(
+            "delay {:.3}\nset the clipboard to oldClipboard\n",
+            delay_ms as f64 / 1000.0
+        ));
+    }
+    script
+}
+
+/// Display server the app runs under.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Session {
+    Wayland,
+    X11,
+    /// No graphical session, e.g. a text console or SSH.
+    Unknown,
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Session::Wayland => write!(f, "Wayland"),
+            Session::X11 => write!(f, "X11"),
+            Session::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Detects the session from `WAYLAND_DISPLAY`, `XDG_SESSION_TYPE` and `DISPLAY`. A Wayland
+/// session wins even when `DISPLAY` is set too: that is XWayland, where synthetic X11 key
+/// events often go to the wrong surface.
+pub fn detect_session(
+    wayland_display: Option<&str>,
+    session_type: Option<&str>,
+    display: Option<&str>,
+) -> Session {
+    let set = |value: Option<&str>| value.is_some_and(|v| !v.is_empty());
+    if set(wayland_display) || session_type.is_some_and(|t| t.eq_ignore_ascii_case("wayland")) {
+        Session::Wayland
+    } else if set(display) || session_type.is_some_and(|t| t.eq_ignore_ascii_case("x11")) {
+        Session::X11
+    } else {
+        Session::Unknown
+    }
+}
+
+/// Resolves `window_info = "auto"` from the injection backend and whether Hyprland's or
+/// Sway's socket variable is set. `None` means the focused window can't be asked for.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn window_info_provider(
+    setting: WindowInfo,
+    backend: InjectionBackend,
+    hyprland: bool,
+    sway: bool,
+) -> Option<WindowInfo> {
+    match setting {
+        WindowInfo::Auto if hyprland => Some(WindowInfo::Hyprland),
+        WindowInfo::Auto if sway => Some(WindowInfo::Sway),
+        WindowInfo::Auto if backend == InjectionBackend::Wayland => None,
+        WindowInfo::Auto => Some(WindowInfo::X11),
+        provider => Some(provider),
+    }
+}
+
+/// Output of `xdotool getactivewindow getwindowclassname getwindowname`: the class, then
+/// the title on the next line.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_xdotool_window(output: &str) -> Option<(String, String)> {
+    let (class, title) = output.split_once('\n').unwrap_or((output, ""));
+    let class = class.trim();
+    if class.is_empty() {
+        return None;
+    }
+    Some((class.to_string(), title.trim().to_string()))
+}
+
+/// `hyprctl activewindow -j` output; Hyprland prints `{}` when no window is focused.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_hyprctl_window(json: &str) -> Option<(String, String)> {
+    let window: serde_json::Value = serde_json::from_str(json).ok()?;
+    let class = window["class"].as_str()?;
+    let title = window["title"].as_str().unwrap_or_default();
+    Some((class.to_string(), title.to_string()))
+}
+
+/// The focused node of `swaymsg -t get_tree` output. Native Wayland windows have an
+/// `app_id`, XWayland ones only the X11 class in `window_properties`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_sway_tree(json: &str) -> Option<(String, String)> {
+    fn focused(node: &serde_json::Value) -> Option<&serde_json::Value> {
+        if node["focused"].as_bool() == Some(true) {
+            return Some(node);
+        }
+        ["nodes", "floating_nodes"]
+            .iter()
+            .filter_map(|children| node[children].as_array())
+            .flatten()
+            .find_map(focused)
+    }
+    let tree: serde_json::Value = serde_json::from_str(json).ok()?;
+    let node = focused(&tree)?;
+    let class = node["app_id"]
+        .as_str()
+        .or_else(|| node["window_properties"]["class"].as_str())?;
+    let title = node["name"].as_str().unwrap_or_default();
+    Some((class.to_string(), title.to_string()))
+}
+
+/// An external program voice-ptt runs on Linux, with the packages that provide it.
+#[derive(Debug, PartialEq)]
+struct Tool {
+    /// Executables that can each do the job, preferred first.
+    binaries: &'static [&'static str],
+    arch: &'static str,
+    debian: &'static str,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+impl Tool {
+    fn name(&self) -> String {
+        match self.binaries {
+            [binary] => binary.to_string(),
+            [binary, others @ ..] => format!("{} (or {})", binary, others.join(", ")),
+            [] => String::new(),
+        }
+    }
+}
+
+const XSEL: Tool = Tool {
+    binaries: &["xsel"],
+    arch: "xsel",
+    debian: "xsel",
+};
+const XDOTOOL: Tool = Tool {
+    binaries: &["xdotool"],
+    arch: "xdotool",
+    debian: "xdotool",
+};
+const WL_COPY: Tool = Tool {
+    binaries: &["wl-copy"],
+    arch: "wl-clipboard",
+    debian: "wl-clipboard",
+};
+const WTYPE: Tool = Tool {
+    binaries: &["wtype", "ydotool"],
+    arch: "wtype",
+    debian: "wtype",
+};
+const SOUND_PLAYER: Tool = Tool {
+    binaries: &["pw-play", "paplay", "ffplay", "aplay"],
+    arch: "pipewire",
+    debian: "pipewire-bin",
+};
+const PW_RECORD: Tool = Tool {
+    binaries: &["pw-record"],
+    arch: "pipewire",
+    debian: "pipewire-bin",
+};
+
+/// Tools the injection path needs: a clipboard setter, plus a key sender unless the text
+/// only goes to the clipboard.
+fn injection_tools(backend: InjectionBackend, output_mode: OutputMode) -> Vec<&'static Tool> {
+    let (clipboard, keys) = match backend {
+        InjectionBackend::Wayland => (&WL_COPY, &WTYPE),
+        InjectionBackend::Native => return Vec::new(),
+        _ => (&XSEL, &XDOTOOL),
+    };
+    match output_mode {
+        OutputMode::Inject => vec![clipboard, keys],
+        OutputMode::Clipboard => vec![clipboard],
+        OutputMode::Stdout => Vec::new(),
+    }
+}
+
+/// Every tool `config` needs, given the resolved injection `backend`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn required_tools(config: &AppConfig, backend: InjectionBackend) -> Vec<&'static Tool> {
+    let mut tools = injection_tools(backend, config.output_mode);
+    if config.clipboard_private {
+        // The private clipboard is set in-process instead of through xsel
+        tools.retain(|tool| **tool != XSEL);
+    }
+    if config.sound_enabled && config.sound_backend == SoundBackend::External {
+        tools.push(&SOUND_PLAYER);
+    }
+    if config.capture_backend == CaptureBackend::PwRecord {
+        tools.push(&PW_RECORD);
+    }
+    tools
+}
+
+/// Of `tools`, those with no executable `exists` can find.
+fn missing_tools<F>(tools: &[&'static Tool], exists: F) -> Vec<&'static Tool>
+where
+    F: Fn(&str) -> bool,
+{
+    tools
+        .iter()
+        .copied()
+        .filter(|tool| !tool.binaries.iter().any(|binary| exists(binary)))
+        .collect()
+}
+
+/// Install commands for `tools` on Arch and Debian-based distros.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn install_hint(tools: &[&Tool]) -> String {
+    let packages = |distro: fn(&Tool) -> &'static str| {
+        tools
+            .iter()
+            .map(|tool| distro(tool))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    format!(
+        "Arch: sudo pacman -S {}; Debian/Ubuntu: sudo apt install {}",
+        packages(|tool| tool.arch),
+        packages(|tool| tool.debian)
+    )
+}
+
+/// Whether an executable called `name` is on the PATH.
+fn tool_exists(name: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| Path::new(&dir).join(name).is_file())
+    })
+}
+
+/// Window classes of terminal emulators, where ctrl+v inserts a literal control character
+/// and ctrl+shift+v pastes.
+const TERMINAL_CLASSES: [&str; 8] = [
+    "alacritty",
+    "kitty",
+    "org.wezfurlong.wezterm",
+    "gnome-terminal-server",
+    "konsole",
+    "xterm",
+    "foot",
+    "st",
+];
+
+/// How text gets into the focused window.
+#[derive(Debug, PartialEq)]
+enum Insertion {
+    /// Through the clipboard, pasted with this shortcut.
+    Paste(Shortcut),
+    /// Key by key.
+    Type,
+}
+
+/// Where the insertion for a window came from, for the log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InsertionSource {
+    UserOverride,
+    TerminalDefault,
+    Default,
+}
+
+impl fmt::Display for InsertionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertionSource::UserOverride => write!(f, "user override"),
+            InsertionSource::TerminalDefault => write!(f, "builtin terminal default"),
+            InsertionSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Prefix of `paste_overrides` and `language_overrides` keys matched against the window title
+/// instead of the class.
+const TITLE_PREFIX: &str = "title:";
+
+/// The `title:<regex>` entries of an override table with their values, sorted by pattern so
+/// that the first match is stable.
+fn title_patterns<T>(overrides: &HashMap<String, T>) -> impl Iterator<Item = (&str, &T)> {
+    let mut patterns: Vec<(&str, &T)> = overrides
+        .iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix(TITLE_PREFIX)?, value)))
+        .collect();
+    patterns.sort_by_key(|(pattern, _)| *pattern);
+    patterns.into_iter()
+}
+
+/// Checks that every `title:<regex>` key of the `setting` override table compiles.
+pub fn validate_window_overrides<T>(setting: &str, overrides: &HashMap<String, T>) -> Result<()> {
+    for (pattern, _) in title_patterns(overrides) {
+        Regex::new(pattern)
+            .with_context(|| format!("Invalid regex in {} key \"title:{}\"", setting, pattern))?;
+    }
+    Ok(())
+}
+
+/// The window that override tables are matched against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusedWindow {
+    /// Window class on Linux, application name on macOS.
+    pub class: String,
+    /// Window title (Linux only).
+    pub title: Option<String>,
+    /// Application bundle id (macOS only).
+    pub bundle_id: Option<String>,
+}
+
+impl FocusedWindow {
+    /// The entry of an override table for this window, see [`window_override`].
+    pub fn lookup<'a, T>(&self, overrides: &'a HashMap<String, T>) -> Option<&'a T> {
+        window_override(
+            Some(&self.class),
+            self.title.as_deref(),
+            self.bundle_id.as_deref(),
+            overrides,
+        )
+    }
+}
+
+/// The entry of an override table for a window: the first `title:<regex>` key matching its
+/// title, else the key equal to its class or bundle id, compared case-insensitively.
+fn window_override<'a, T>(
+    class: Option<&str>,
+    title: Option<&str>,
+    bundle_id: Option<&str>,
+    overrides: &'a HashMap<String, T>,
+) -> Option<&'a T> {
+    let by_title = title.and_then(|title| {
+        title_patterns(overrides)
+            .find(|(pattern, _)| Regex::new(pattern).is_ok_and(|regex| regex.is_match(title)))
+            .map(|(_, value)| value)
+    });
+    by_title.or_else(|| {
+        overrides
+            .iter()
+            .find(|(key, _)| {
+                [class, bundle_id]
+                    .into_iter()
+                    .flatten()
+                    .any(|name| key.eq_ignore_ascii_case(name))
+            })
+            .map(|(_, value)| value)
+    })
+}
+
+/// What to do in the focused window.
+#[derive(Debug, PartialEq)]
+struct PasteDecision {
+    insertion: Insertion,
+    /// Keys to send after the text, e.g. Return.
+    after: Vec<Shortcut>,
+    source: InsertionSource,
+}
+
+/// Picks the insertion for the focused window: the first `title:<regex>` entry of
+/// `paste_overrides` matching its title, else its class entry (matched case-insensitively),
+/// where "type" means typing and "paste" the window's default shortcut; then the global
+/// `injection_method`, pasting with ctrl+shift+v in known terminals and ctrl+v elsewhere.
+fn insertion_for(
+    window_class: Option<&str>,
+    window_title: Option<&str>,
+    overrides: &HashMap<String, PasteOverride>,
+    method: InjectionMethod,
+) -> PasteDecision {
+    let entry = window_override(window_class, window_title, None, overrides);
+    let default_paste = || {
+        let terminal = window_class.is_some_and(|class| {
+            TERMINAL_CLASSES
+                .iter()
+                .any(|terminal| terminal.eq_ignore_ascii_case(class))
+        });
+        if terminal {
+            (
+                Shortcut::new(&[Modifier::Ctrl, Modifier::Shift], 'v'),
+                InsertionSource::TerminalDefault,
+            )
+        } else {
+            (
+                Shortcut::new(&[Modifier::Ctrl], 'v'),
+                InsertionSource::Default,
+            )
+        }
+    };
+    let Some(entry) = entry else {
+        let (insertion, source) = match method {
+            InjectionMethod::Type => (Insertion::Type, InsertionSource::Default),
+            InjectionMethod::Paste => {
+                let (shortcut, source) = default_paste();
+                (Insertion::Paste(shortcut), source)
+            }
+        };
+        return PasteDecision {
+            insertion,
+            after: Vec::new(),
+            source,
+        };
+    };
+    let insertion = match &entry.paste {
+        Some(PasteAction::Type) => Insertion::Type,
+        Some(PasteAction::Shortcut(shortcut)) => Insertion::Paste(shortcut.clone()),
+        None => Insertion::Paste(default_paste().0),
+    };
+    PasteDecision {
+        insertion,
+        after: entry.after.clone(),
+        source: InsertionSource::UserOverride,
+    }
+}
+
+/// Longest text handed to one `xdotool type` call.
+const TYPE_CHUNK_CHARS: usize = 200;
+
+/// One command of typed injection.
+#[derive(Debug, PartialEq)]
+pub enum TypeStep {
+    Text(String),
+    Return,
+}
+
+/// Splits text into chunks of at most `max_chars` characters, with a Return key press for
+/// every line break since xdotool types newlines inconsistently.
+pub fn type_steps(text: &str, max_chars: usize) -> Vec<TypeStep> {
+    let mut steps = Vec::new();
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            steps.push(TypeStep::Return);
+        }
+        let chars: Vec<char> = line.trim_end_matches('\r').chars().collect();
+        for chunk in chars.chunks(max_chars.max(1)) {
+            steps.push(TypeStep::Text(chunk.iter().collect()));
+        }
+    }
+    steps
+}
+
+/// wtype arguments for a chord: `ctrl+shift+v` becomes
+/// `-M ctrl -M shift -P v -p v -m shift -m ctrl`.
+fn wtype_args(chord: &Shortcut) -> Vec<String> {
+    let modifiers: Vec<&str> = chord
+        .modifiers
+        .iter()
+        .map(|modifier| match modifier {
+            Modifier::Ctrl => "ctrl",
+            Modifier::Shift => "shift",
+            Modifier::Alt => "alt",
+            Modifier::Super => "logo",
+        })
+        .collect();
+    let mut args = Vec::new();
+    for modifier in &modifiers {
+        args.extend(["-M".to_string(), modifier.to_string()]);
+    }
+    // Named keys are keysyms as they are
+    let key = chord.key.to_string();
+    args.extend(["-P".to_string(), key.clone(), "-p".to_string(), key]);
+    for modifier in modifiers.iter().rev() {
+        args.extend(["-m".to_string(), modifier.to_string()]);
+    }
+    args
+}
+
+/// `ydotool key` arguments for a chord: every key is pressed in order and released in
+/// reverse, e.g. `29:1 47:1 47:0 29:0` for `ctrl+v`.
+fn ydotool_args(chord: &Shortcut) -> Result<Vec<String>> {
+    let key = match chord.key {
+        ShortcutKey::Char(c) => YDOTOOL_KEY_ROWS
+            .iter()
+            .find_map(|(row, first)| Some(first + row.find(c)? as u16))
+            .with_context(|| format!("ydotool can't send '{}' from '{}'", c, chord))?,
+        ShortcutKey::Return => 28,
+        ShortcutKey::Tab => 15,
+        ShortcutKey::Space => 57,
+        ShortcutKey::Escape => 1,
+        ShortcutKey::Insert => 110,
+        ShortcutKey::Delete => 111,
+        ShortcutKey::BackSpace => 14,
+    };
+    let mut codes: Vec<u16> = chord
+        .modifiers
+        .iter()
+        .map(|modifier| match modifier {
+            Modifier::Ctrl => 29,
+            Modifier::Shift => 42,
+            Modifier::Alt => 56,
+            Modifier::Super => 125,
+        })
+        .collect();
+    codes.push(key);
+    let mut args: Vec<String> = codes.iter().map(|code| format!("{}:1", code)).collect();
+    args.extend(codes.iter().rev().map(|code| format!("{}:0", code)));
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_notification_states() {
+        let mut state = StatusState::default();
+        assert_eq!(state.apply(StatusUpdate::Recording), Some("Recording…"));
+        assert_eq!(state.apply(StatusUpdate::Processing), Some("Processing…"));
+        // A second dictation while the first is still transcribing
+        assert_eq!(state.apply(StatusUpdate::Recording), Some("Recording…"));
+        assert_eq!(state.apply(StatusUpdate::Finished), Some("Recording…"));
+        assert_eq!(state.apply(StatusUpdate::Processing), Some("Processing…"));
+        assert_eq!(state.apply(StatusUpdate::Finished), None);
+
+        assert_eq!(state.apply(StatusUpdate::Recording), Some("Recording…"));
+        assert_eq!(state.apply(StatusUpdate::Discarded), None);
+    }
+
+    #[test]
+    fn test_status_broadcast() {
+        let notifier = StatusNotifier::new(&AppConfig::default());
+        let mut statuses = notifier.subscribe();
+        notifier.update(StatusUpdate::Profile(Some("meetings".to_string())));
+        notifier.update(StatusUpdate::Recording);
+        notifier.update(StatusUpdate::Processing);
+        assert!(statuses.has_changed().unwrap());
+        assert_eq!(
+            *statuses.borrow_and_update(),
+            Status {
+                state: State::Processing,
+                paused: false,
+                failed: false,
+                profile: Some("meetings".to_string()),
+            }
+        );
+
+        // Only changes wake subscribers
+        notifier.update(StatusUpdate::Discarded);
+        assert!(!statuses.has_changed().unwrap());
+        notifier.update(StatusUpdate::Failed);
+        notifier.update(StatusUpdate::Finished);
+        notifier.update(StatusUpdate::Paused(true));
+        assert_eq!(notifier.current().state, State::Idle);
+        assert!(notifier.current().paused && notifier.current().failed);
+        notifier.update(StatusUpdate::Recording);
+        assert!(!notifier.current().failed);
+    }
+
+    #[test]
+    fn test_applescript_string() {
+        assert_eq!(
+            applescript_string(r#"She said "hi""#),
+            r#""She said \"hi\"""#
+        );
+        assert_eq!(applescript_string(r"C:\temp\"), r#""C:\\temp\\""#);
+        assert_eq!(applescript_string(r#"\""#), r#""\\\"""#);
+        assert_eq!(
+            applescript_string("Line one\nLine two\r\n"),
+            r#""Line one\nLine two\r\n""#
+        );
+        assert_eq!(applescript_string("Готово 🎉"), "\"Готово 🎉\"");
+    }
+
+    #[test]
+    fn test_clipboard_classes() {
+        assert!(!clipboard_holds_non_text(
+            "«class utf8», 12, «class ut16», 26, string, 12, Unicode text, 24"
+        ));
+        assert!(clipboard_holds_non_text(
+            "«class PNGf», 48213, TIFF picture, 90210"
+        ));
+        assert!(clipboard_holds_non_text("«class furl», 31, string, 12"));
+        assert!(!clipboard_holds_non_text(""));
+    }
+
+    #[test]
+    fn test_macos_paste_script() {
+        let file = Path::new("/tmp/voice \"ptt\".txt");
+        let paste = r#"tell application "System Events" to keystroke "v" using command down"#;
+        let script = macos_paste_script(file, paste, true, 400);
+        assert!(script.starts_with("set oldClipboard to the clipboard\n"));
+        assert!(script.contains(&format!("{}\ndelay", paste)));
+        assert!(script.contains(r#"read POSIX file "/tmp/voice \"ptt\".txt" as «class utf8»"#));
+        assert!(script.ends_with("delay 0.400\nset the clipboard to oldClipboard\n"));
+
+        let script = macos_paste_script(file, paste, false, 400);
+        assert!(!script.contains("oldClipboard"));
+        assert!(!script.contains("delay"));
+    }
+
+    #[test]
+    fn test_applescript_keystroke() {
+        let keystroke = |chord| {
+            applescript_keystroke(&Shortcut::parse(chord).unwrap())
+                .unwrap()
+                .strip_prefix(r#"tell application "System Events" to "#)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(keystroke("cmd+v"), r#"keystroke "v" using command down"#);
+        assert_eq!(
+            keystroke("cmd+shift+V"),
+            r#"keystroke "v" using {command down, shift down}"#
+        );
+        assert_eq!(
+            keystroke("ctrl+alt+v"),
+            r#"keystroke "v" using {control down, option down}"#
+        );
+        assert_eq!(
+            keystroke("Option+Shift+Cmd+y"),
+            r#"keystroke "y" using {option down, shift down, command down}"#
+        );
+        assert_eq!(keystroke("Return"), "key code 36");
+        assert_eq!(keystroke("cmd+Return"), "key code 36 using command down");
+        assert_eq!(keystroke("\""), r#"keystroke "\"""#);
+        assert_eq!(keystroke("BackSpace"), "key code 51");
+        assert!(applescript_keystroke(&Shortcut::parse("shift+Insert").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_macos_override() {
+        let overrides = shortcuts(&[
+            ("iTerm2", "cmd+shift+v"),
+            ("com.tinyspeck.slackmacgap", "type"),
+        ]);
+        let shortcut = |name, bundle_id| {
+            window_override(Some(name), None, bundle_id, &overrides)
+                .and_then(|entry| entry.paste.clone())
+        };
+        assert_eq!(
+            shortcut("iterm2", None),
+            Some(PasteAction::Shortcut(
+                Shortcut::parse("cmd+shift+v").unwrap()
+            ))
+        );
+        assert_eq!(
+            shortcut("Slack", Some("com.tinyspeck.slackmacgap")),
+            Some(PasteAction::Type)
+        );
+        assert_eq!(shortcut("Safari", Some("com.apple.Safari")), None);
+    }
+
+    #[test]
+    fn test_session_detection() {
+        assert_eq!(
+            detect_session(Some("wayland-1"), None, None),
+            Session::Wayland
+        );
+        assert_eq!(
+            detect_session(None, Some("wayland"), None),
+            Session::Wayland
+        );
+        // XWayland: DISPLAY is set as well, but keys have to go through Wayland
+        assert_eq!(
+            detect_session(Some("wayland-0"), Some("wayland"), Some(":0")),
+            Session::Wayland
+        );
+        assert_eq!(detect_session(None, Some("x11"), Some(":0")), Session::X11);
+        assert_eq!(detect_session(Some(""), None, Some(":1")), Session::X11);
+        assert_eq!(detect_session(None, Some("tty"), None), Session::Unknown);
+        assert_eq!(detect_session(None, None, None), Session::Unknown);
+    }
+
+    #[test]
+    fn test_missing_tools() {
+        let installed = |tools: &'static [&'static str]| move |name: &str| tools.contains(&name);
+        let config = AppConfig::default();
+        let missing = |backend, exists| missing_tools(&required_tools(&config, backend), exists);
+        assert!(missing(InjectionBackend::X11, installed(&["xsel", "xdotool"])).is_empty());
+        assert_eq!(
+            missing(InjectionBackend::X11, installed(&["xdotool"])),
+            [&XSEL]
+        );
+        assert!(missing(
+            InjectionBackend::Wayland,
+            installed(&["wl-copy", "ydotool"])
+        )
+        .is_empty());
+        assert_eq!(
+            missing(InjectionBackend::Wayland, installed(&["xsel", "xdotool"])),
+            [&WL_COPY, &WTYPE]
+        );
+        assert!(missing(InjectionBackend::Native, installed(&[])).is_empty());
+    }
+
+    #[test]
+    fn test_pick_sound_player() {
+        let (first, _) = SOUND_PLAYERS[0];
+        let (last, _) = SOUND_PLAYERS[SOUND_PLAYERS.len() - 1];
+        assert_eq!(pick_sound_player(|_| true), Some(SOUND_PLAYERS[0]));
+        assert_eq!(
+            pick_sound_player(|program| program == last).map(|(program, _)| program),
+            Some(last)
+        );
+        assert_eq!(
+            pick_sound_player(|program| program == first || program == last)
+                .map(|(program, _)| program),
+            Some(first)
+        );
+        assert_eq!(pick_sound_player(|_| false), None);
+    }
+
+    #[test]
+    fn test_required_tools_follow_config() {
+        let required = |config: AppConfig, backend| {
+            required_tools(&config, backend)
+                .iter()
+                .map(|tool| tool.name())
+                .collect::<Vec<_>>()
+        };
+        let config = AppConfig {
+            output_mode: OutputMode::Clipboard,
+            ..Default::default()
+        };
+        assert_eq!(required(config, InjectionBackend::Wayland), ["wl-copy"]);
+
+        let config = AppConfig {
+            output_mode: OutputMode::Stdout,
+            sound_backend: SoundBackend::External,
+            capture_backend: CaptureBackend::PwRecord,
+            ..Default::default()
+        };
+        assert_eq!(
+            required(config, InjectionBackend::X11),
+            ["pw-play (or paplay, ffplay, aplay)", "pw-record"]
+        );
+
+        let config = AppConfig {
+            sound_enabled: false,
+            sound_backend: SoundBackend::External,
+            ..Default::default()
+        };
+        assert_eq!(
+            required(config, InjectionBackend::Wayland),
+            ["wl-copy", "wtype (or ydotool)"]
+        );
+
+        let config = AppConfig {
+            clipboard_private: true,
+            ..Default::default()
+        };
+        assert_eq!(required(config, InjectionBackend::X11), ["xdotool"]);
+
+        assert_eq!(
+            install_hint(&[&WL_COPY, &SOUND_PLAYER]),
+            "Arch: sudo pacman -S wl-clipboard pipewire; Debian/Ubuntu: sudo apt install wl-clipboard pipewire-bin"
+        );
+    }
+
+    fn shortcuts(entries: &[(&str, &str)]) -> HashMap<String, PasteOverride> {
+        entries
+            .iter()
+            .map(|(key, value)| {
+                let entry = toml::Value::String(value.to_string()).try_into().unwrap();
+                (key.to_string(), entry)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_insertion_overrides() {
+        let overrides = shortcuts(&[
+            ("Alacritty", "ctrl+shift+v"),
+            ("remote-viewer", "type"),
+            ("Code", "paste"),
+        ]);
+        let paste = |key| Insertion::Paste(Shortcut::parse(key).unwrap());
+        let insertion =
+            |class: Option<&str>, method| insertion_for(class, None, &overrides, method).insertion;
+        assert_eq!(
+            insertion(Some("alacritty"), InjectionMethod::Paste),
+            paste("ctrl+shift+v")
+        );
+        assert_eq!(
+            insertion(Some("firefox"), InjectionMethod::Paste),
+            paste("ctrl+v")
+        );
+        assert_eq!(insertion(None, InjectionMethod::Paste), paste("ctrl+v"));
+        assert_eq!(
+            insertion(Some("Remote-Viewer"), InjectionMethod::Paste),
+            Insertion::Type
+        );
+        assert_eq!(
+            insertion(Some("firefox"), InjectionMethod::Type),
+            Insertion::Type
+        );
+        assert_eq!(
+            insertion(Some("code"), InjectionMethod::Type),
+            paste("ctrl+v")
+        );
+    }
+
+    #[test]
+    fn test_terminal_paste_defaults() {
+        let overrides = shortcuts(&[("kitty", "ctrl+v"), ("foot", "paste")]);
+        let insertion = |class| {
+            let decision = insertion_for(Some(class), None, &overrides, InjectionMethod::Paste);
+            (decision.insertion, decision.source)
+        };
+        let paste = |key, source| (Insertion::Paste(Shortcut::parse(key).unwrap()), source);
+
+        // A user override beats the builtin table, even when it asks for ctrl+v
+        assert_eq!(
+            insertion("kitty"),
+            paste("ctrl+v", InsertionSource::UserOverride)
+        );
+        assert_eq!(
+            insertion("foot"),
+            paste("ctrl+shift+v", InsertionSource::UserOverride)
+        );
+        assert_eq!(
+            insertion("Gnome-terminal-server"),
+            paste("ctrl+shift+v", InsertionSource::TerminalDefault)
+        );
+        assert_eq!(
+            insertion("fake-terminal"),
+            paste("ctrl+v", InsertionSource::Default)
+        );
+        assert_eq!(
+            insertion_for(Some("xterm"), None, &overrides, InjectionMethod::Type),
+            PasteDecision {
+                insertion: Insertion::Type,
+                after: Vec::new(),
+                source: InsertionSource::Default
+            }
+        );
+    }
+
+    #[test]
+    fn test_title_overrides() {
+        let overrides = shortcuts(&[
+            ("firefox", "ctrl+shift+v"),
+            ("title:- Canva( |$)", "type"),
+            ("title:(?i)^ssh ", "ctrl+alt+v"),
+        ]);
+        let insertion = |class, title| {
+            insertion_for(Some(class), Some(title), &overrides, InjectionMethod::Paste).insertion
+        };
+        // A title match beats the class entry
+        assert_eq!(
+            insertion("firefox", "Poster - Canva — Mozilla Firefox"),
+            Insertion::Type
+        );
+        assert_eq!(
+            insertion("firefox", "Inbox — Mozilla Firefox"),
+            Insertion::Paste(Shortcut::parse("ctrl+shift+v").unwrap())
+        );
+        assert_eq!(
+            insertion("xterm", "SSH build-box"),
+            Insertion::Paste(Shortcut::parse("ctrl+alt+v").unwrap())
+        );
+        assert_eq!(
+            insertion_for(Some("firefox"), None, &overrides, InjectionMethod::Paste).insertion,
+            Insertion::Paste(Shortcut::parse("ctrl+shift+v").unwrap())
+        );
+
+        assert!(validate_window_overrides("paste_overrides", &overrides).is_ok());
+        let invalid = shortcuts(&[("title:([a-z", "type")]);
+        assert!(validate_window_overrides("paste_overrides", &invalid).is_err());
+    }
+
+    #[test]
+    fn test_focused_window_lookup() {
+        let languages: HashMap<String, String> = [
+            ("telegram-desktop", "ru"),
+            ("title:Slack", "en"),
+            ("com.apple.Safari", "de"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let window = |class: &str, title: Option<&str>, bundle_id: Option<&str>| FocusedWindow {
+            class: class.to_string(),
+            title: title.map(str::to_string),
+            bundle_id: bundle_id.map(str::to_string),
+        };
+        let language = |window: FocusedWindow| window.lookup(&languages).map(String::as_str);
+
+        assert_eq!(
+            language(window("Telegram-Desktop", Some("Chat"), None)),
+            Some("ru")
+        );
+        // Title patterns win over the class
+        assert_eq!(
+            language(window("telegram-desktop", Some("Slack | general"), None)),
+            Some("en")
+        );
+        assert_eq!(
+            language(window("Safari", None, Some("com.apple.Safari"))),
+            Some("de")
+        );
+        assert_eq!(language(window("firefox", Some("Docs"), None)), None);
+
+        let invalid: HashMap<String, String> =
+            HashMap::from([("title:([a-z".to_string(), "ru".to_string())]);
+        let error = validate_window_overrides("language_overrides", &invalid).unwrap_err();
+        assert!(error.to_string().contains("language_overrides"));
+    }
+
+    #[test]
+    fn test_after_keys_overrides() {
+        let enter = Shortcut::parse("Return").unwrap();
+        let overrides = HashMap::from([
+            (
+                "telegram-desktop".to_string(),
+                PasteOverride {
+                    paste: Some(PasteAction::Shortcut(Shortcut::parse("ctrl+v").unwrap())),
+                    after: vec![enter.clone()],
+                },
+            ),
+            (
+                "kitty".to_string(),
+                PasteOverride {
+                    paste: None,
+                    after: vec![enter.clone()],
+                },
+            ),
+        ]);
+        let decision = |class| insertion_for(Some(class), None, &overrides, InjectionMethod::Paste);
+        assert_eq!(
+            decision("Telegram-Desktop"),
+            PasteDecision {
+                insertion: Insertion::Paste(Shortcut::parse("ctrl+v").unwrap()),
+                after: vec![enter],
+                source: InsertionSource::UserOverride
+            }
+        );
+        // Without a paste key the window keeps its default shortcut
+        assert_eq!(
+            decision("kitty").insertion,
+            Insertion::Paste(Shortcut::parse("ctrl+shift+v").unwrap())
+        );
+        assert!(decision("firefox").after.is_empty());
+    }
+
+    #[test]
+    fn test_window_info_provider() {
+        let auto = |backend, hyprland, sway| {
+            window_info_provider(WindowInfo::Auto, backend, hyprland, sway)
+        };
+        assert_eq!(
+            auto(InjectionBackend::Wayland, true, false),
+            Some(WindowInfo::Hyprland)
+        );
+        assert_eq!(
+            auto(InjectionBackend::Wayland, false, true),
+            Some(WindowInfo::Sway)
+        );
+        assert_eq!(auto(InjectionBackend::Wayland, false, false), None);
+        assert_eq!(
+            auto(InjectionBackend::Native, false, false),
+            Some(WindowInfo::X11)
+        );
+        assert_eq!(
+            window_info_provider(WindowInfo::Sway, InjectionBackend::X11, true, false),
+            Some(WindowInfo::Sway)
+        );
+    }
+
+    #[test]
+    fn test_parse_xdotool_window() {
+        assert_eq!(
+            parse_xdotool_window("Alacritty\nvim ~/notes.md\n"),
+            Some(("Alacritty".to_string(), "vim ~/notes.md".to_string()))
+        );
+        assert_eq!(parse_xdotool_window(""), None);
+    }
+
+    #[test]
+    fn test_parse_hyprctl_window() {
+        let json = r#"{
+            "address": "0x55d0c8a1e2b0",
+            "mapped": true,
+            "workspace": { "id": 2, "name": "2" },
+            "floating": false,
+            "class": "org.telegram.desktop",
+            "title": "Saved Messages",
+            "initialClass": "org.telegram.desktop",
+            "pid": 4242,
+            "xwayland": false
+        }"#;
+        assert_eq!(
+            parse_hyprctl_window(json),
+            Some((
+                "org.telegram.desktop".to_string(),
+                "Saved Messages".to_string()
+            ))
+        );
+        assert_eq!(parse_hyprctl_window("{}"), None);
+        assert_eq!(parse_hyprctl_window("Invalid"), None);
+    }
+
+    #[test]
+    fn test_parse_sway_tree() {
+        let tree = |app_id: &str, window_properties: &str| {
+            format!(
+                r#"{{
+                "id": 1, "type": "root", "name": "root", "focused": false,
+                "nodes": [{{
+                    "id": 3, "type": "output", "name": "eDP-1", "focused": false,
+                    "nodes": [{{
+                        "id": 4, "type": "workspace", "name": "1", "focused": false,
+                        "nodes": [{{
+                            "id": 6, "type": "con", "name": "Mozilla Firefox",
+                            "focused": false, "app_id": "firefox", "nodes": []
+                        }}],
+                        "floating_nodes": [{{
+                            "id": 7, "type": "floating_con", "name": "~/src",
+                            "focused": true, "app_id": {}, "window_properties": {},
+                            "nodes": []
+                        }}]
+                    }}]
+                }}]
+            }}"#,
+                app_id, window_properties
+            )
+        };
+        assert_eq!(
+            parse_sway_tree(&tree(r#""foot""#, "{}")),
+            Some(("foot".to_string(), "~/src".to_string()))
+        );
+        // XWayland windows have no app_id
+        assert_eq!(
+            parse_sway_tree(&tree(
+                "null",
+                r#"{ "class": "XTerm", "instance": "xterm" }"#
+            )),
+            Some(("XTerm".to_string(), "~/src".to_string()))
+        );
+        assert_eq!(
+            parse_sway_tree(r#"{ "focused": false, "nodes": [] }"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_type_steps() {
+        assert_eq!(
+            type_steps("Dear team,\r\n\nthanks.", 200),
+            [
+                TypeStep::Text("Dear team,".to_string()),
+                TypeStep::Return,
+                TypeStep::Return,
+                TypeStep::Text("thanks.".to_string()),
+            ]
+        );
+        assert_eq!(
+            type_steps("Привет мир", 4),
+            [
+                TypeStep::Text("Прив".to_string()),
+                TypeStep::Text("ет м".to_string()),
+                TypeStep::Text("ир".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wayland_paste_chords() {
+        let wtype_args = |chord| wtype_args(&Shortcut::parse(chord).unwrap());
+        assert_eq!(
+            wtype_args("ctrl+v"),
+            ["-M", "ctrl", "-P", "v", "-p", "v", "-m", "ctrl"]
+        );
+        assert_eq!(
+            wtype_args("ctrl+shift+V"),
+            ["-M", "ctrl", "-M", "shift", "-P", "v", "-p", "v", "-m", "shift", "-m", "ctrl"]
+        );
+        assert_eq!(wtype_args("Return"), ["-P", "Return", "-p", "Return"]);
+        assert_eq!(
+            wtype_args("super+v"),
+            ["-M", "logo", "-P", "v", "-p", "v", "-m", "logo"]
+        );
+
+        let ydotool_args = |chord| ydotool_args(&Shortcut::parse(chord).unwrap());
+        assert_eq!(
+            ydotool_args("ctrl+shift+v").unwrap(),
+            ["29:1", "42:1", "47:1", "47:0", "42:0", "29:0"]
+        );
+        assert_eq!(
+            ydotool_args("shift+Insert").unwrap(),
+            ["42:1", "110:1", "110:0", "42:0"]
+        );
+        assert_eq!(ydotool_args("Return").unwrap(), ["28:1", "28:0"]);
+        assert_eq!(
+            ydotool_args("ctrl+a").unwrap(),
+            ["29:1", "30:1", "30:0", "29:0"]
+        );
+        assert_eq!(ydotool_args("1").unwrap(), ["2:1", "2:0"]);
+        assert!(ydotool_args("ctrl+,").is_err());
+    }
+}
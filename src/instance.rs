@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How often `replace` checks whether the old instance has let go of the lock.
+const REPLACE_POLL: Duration = Duration::from_millis(100);
+
+/// `$XDG_RUNTIME_DIR/voice-ptt.<extension>`, or a per-user file in the temp directory
+/// where there is no runtime directory (macOS).
+pub fn runtime_path(extension: &str) -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(format!("voice-ptt.{}", extension)),
+        None => {
+            // SAFETY: getuid(2) can't fail and touches no memory
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("voice-ptt-{}.{}", uid, extension))
+        }
+    }
+}
+
+/// The lock file, `$XDG_RUNTIME_DIR/voice-ptt.lock`.
+pub fn lock_path() -> PathBuf {
+    runtime_path("lock")
+}
+
+/// Another instance holds the lock.
+#[derive(Debug)]
+pub struct AlreadyRunning {
+    /// The PID it wrote into the lock file, if it could be read.
+    pub pid: Option<u32>,
+}
+
+impl fmt::Display for AlreadyRunning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "voice-ptt is already running (PID {})", pid),
+            None => write!(f, "voice-ptt is already running"),
+        }
+    }
+}
+
+impl std::error::Error for AlreadyRunning {}
+
+/// An exclusive advisory lock (flock) on the lock file, held as long as this value lives.
+///
+/// The kernel releases it when the process ends, crashes included, so a lock left by a
+/// crashed instance is free again; only the PID it wrote stays behind. The file is emptied
+/// on a clean exit but never deleted, since a process that opened it just before the
+/// deletion would lock a file nobody else can see.
+pub struct InstanceLock {
+    file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock and writes our PID into the file. Fails with [`AlreadyRunning`] when
+    /// another instance holds it.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open the lock file {}", path.display()))?;
+        let previous = read_pid(&mut file);
+        // SAFETY: flock(2) on a descriptor `file` owns
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() == std::io::ErrorKind::WouldBlock {
+                return Err(AlreadyRunning { pid: previous }.into());
+            }
+            return Err(error).with_context(|| format!("Failed to lock {}", path.display()));
+        }
+        if let Some(pid) = previous {
+            info!(
+                "🧹 Took over the lock of PID {}, which exited without releasing it",
+                pid
+            );
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        Ok(Self { file })
+    }
+
+    /// Takes the lock, asking the instance holding it to exit (SIGTERM) and waiting up to
+    /// `timeout` for it to do so.
+    pub fn replace(path: &Path, timeout: Duration) -> Result<Self> {
+        let pid = match Self::acquire(path) {
+            Ok(lock) => return Ok(lock),
+            Err(e) => match e.downcast_ref::<AlreadyRunning>() {
+                Some(running) => running.pid.context(
+                    "Another instance holds the lock but didn't write its PID, stop it yourself",
+                )?,
+                None => return Err(e),
+            },
+        };
+        info!("🔁 Asking the running instance (PID {}) to exit...", pid);
+        // SAFETY: kill(2) only sends a signal
+        if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("Failed to signal PID {}", pid));
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            match Self::acquire(path) {
+                Err(e) if e.is::<AlreadyRunning>() && Instant::now() < deadline => {
+                    std::thread::sleep(REPLACE_POLL)
+                }
+                Err(e) if e.is::<AlreadyRunning>() => anyhow::bail!(
+                    "PID {} didn't exit within {}s, stop it yourself",
+                    pid,
+                    timeout.as_secs()
+                ),
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // An empty file tells the next instance this one exited cleanly
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// The PID in the lock file, if it holds one.
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_instance_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice-ptt.lock");
+        let lock = InstanceLock::acquire(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        // flock locks belong to the open file, so a second open conflicts even in-process
+        let error = InstanceLock::acquire(&path).err().unwrap();
+        let running = error.downcast_ref::<AlreadyRunning>().unwrap();
+        assert_eq!(running.pid, Some(std::process::id()));
+        assert!(error.to_string().contains("already running (PID"));
+
+        drop(lock);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        InstanceLock::acquire(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice-ptt.lock");
+        // A crashed instance leaves its PID behind, but not the lock
+        std::fs::write(&path, "4194305\n").unwrap();
+        let _lock = InstanceLock::acquire(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        // Nothing to replace when no other instance runs
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voice-ptt.lock");
+        InstanceLock::replace(&path, Duration::ZERO).unwrap();
+    }
+
+    #[test]
+    fn test_missing_runtime_dir_fails_clearly() {
+        let error = InstanceLock::acquire(Path::new("/nonexistent/voice-ptt.lock"))
+            .err()
+            .unwrap();
+        assert!(!error.is::<AlreadyRunning>());
+        assert!(error.to_string().contains("Failed to open the lock file"));
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use std::path::Path;
+use std::process::Command;
+use tracing::warn;
+
+use crate::config::{ApiKeySource, AppConfig};
+
+/// Service name under which keys are stored in the desktop keyring.
+const KEYRING_SERVICE: &str = "voice-ptt";
+
+/// Resolves an API key, trying in order: the keyring (with `api_key_source = "keyring"`),
+/// the `env_var` environment variable, `api_key_file` and `api_key_command`.
+///
+/// `get_env` is injected so the lookup order can be tested without touching the environment.
+/// The key itself never appears in errors or log output.
+pub fn resolve_api_key<F>(env_var: &str, config: &AppConfig, get_env: F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if config.api_key_source == ApiKeySource::Keyring {
+        match read_keyring(&config.backend) {
+            Ok(key) => return Ok(key),
+            Err(e) => warn!(
+                "⚠️ {}. Falling back to {}.",
+                describe_keyring_error(&e, &config.backend),
+                env_var
+            ),
+        }
+    }
+
+    let mut tried = Vec::new();
+
+    match get_env(env_var).map(|key| key.trim().to_string()) {
+        Some(key) if !key.is_empty() => return Ok(key),
+        _ => tried.push(format!("{} environment variable (not set)", env_var)),
+    }
+
+    if let Some(path) = &config.api_key_file {
+        match read_key_file(path) {
+            Ok(key) => return Ok(key),
+            Err(e) => tried.push(format!("api_key_file {} ({})", path.display(), e)),
+        }
+    }
+
+    if let Some(command) = &config.api_key_command {
+        match run_key_command(command) {
+            Ok(key) => return Ok(key),
+            Err(e) => tried.push(format!("api_key_command `{}` ({})", command, e)),
+        }
+    }
+
+    anyhow::bail!("No API key found. Tried:\n  - {}", tried.join("\n  - "))
+}
+
+/// Resolves one or more API keys: `api_keys` from the config when set, otherwise the result
+/// of [`resolve_api_key`] split at commas, so `OPENAI_API_KEY=sk-a,sk-b` gives two keys.
+pub fn resolve_api_keys<F>(env_var: &str, config: &AppConfig, get_env: F) -> Result<Vec<String>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let keys = if config.api_keys.is_empty() {
+        split_keys(&resolve_api_key(env_var, config, get_env)?)
+    } else {
+        split_keys(&config.api_keys.join(","))
+    };
+    if keys.is_empty() {
+        anyhow::bail!("The configured API key list for {} is empty", env_var);
+    }
+    Ok(keys)
+}
+
+fn split_keys(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn read_keyring(account: &str) -> keyring::Result<String> {
+    keyring::Entry::new(KEYRING_SERVICE, account)?.get_password()
+}
+
+/// Stores the key for `account` (the backend name) in the desktop keyring.
+pub fn store_in_keyring(account: &str, key: &str) -> Result<()> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+        .and_then(|entry| entry.set_password(key))
+        .map_err(|e| anyhow::anyhow!("{}", describe_keyring_error(&e, account)))
+}
+
+/// Turns keyring/D-Bus errors into a message that says what to do about them.
+fn describe_keyring_error(error: &keyring::Error, account: &str) -> String {
+    match error {
+        keyring::Error::NoEntry => format!(
+            "No API key for '{}' in the keyring. Run `voice-ptt set-key` to store one",
+            account
+        ),
+        keyring::Error::NoStorageAccess(_) => {
+            "The keyring is locked or access was denied. Unlock your login keyring (e.g. with Seahorse or KWallet) and try again".to_string()
+        }
+        keyring::Error::PlatformFailure(e) => format!(
+            "No keyring service is reachable ({}). In a headless or SSH session start gnome-keyring-daemon or use an environment variable",
+            e
+        ),
+        other => format!("Keyring error: {}", other),
+    }
+}
+
+fn read_key_file(path: &Path) -> Result<String, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let key = content.trim();
+    if key.is_empty() {
+        return Err("file is empty".to_string());
+    }
+    Ok(key.to_string())
+}
+
+fn run_key_command(command: &str) -> Result<String, String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| format!("failed to run sh: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}: {}", output.status, stderr.trim()));
+    }
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if key.is_empty() {
+        return Err("printed nothing".to_string());
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_env_var_takes_priority() {
+        let config = AppConfig {
+            api_key_command: Some("echo sk-from-command".to_string()),
+            ..Default::default()
+        };
+        let key = resolve_api_key("OPENAI_API_KEY", &config, |_| Some(" sk-env\n".to_string()));
+        assert_eq!(key.unwrap(), "sk-env");
+    }
+
+    #[test]
+    fn test_key_lists() {
+        let env = |_: &str| Some("sk-personal, sk-project,".to_string());
+        let keys = resolve_api_keys("OPENAI_API_KEY", &AppConfig::default(), env).unwrap();
+        assert_eq!(keys, ["sk-personal", "sk-project"]);
+
+        let single = resolve_api_keys("OPENAI_API_KEY", &AppConfig::default(), |_| {
+            Some("sk-only".to_string())
+        });
+        assert_eq!(single.unwrap(), ["sk-only"]);
+
+        let config = AppConfig {
+            api_keys: vec!["sk-a".to_string(), " sk-b ".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_api_keys("OPENAI_API_KEY", &config, env).unwrap(),
+            ["sk-a", "sk-b"]
+        );
+
+        let empty = resolve_api_keys("OPENAI_API_KEY", &AppConfig::default(), |_| {
+            Some(" , ".to_string())
+        });
+        assert!(empty.is_err());
+    }
+
+    #[test]
+    fn test_key_file_is_trimmed() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "  sk-from-file  ").unwrap();
+        let config = AppConfig {
+            api_key_file: Some(file.path().to_path_buf()),
+            api_key_command: Some("echo sk-from-command".to_string()),
+            ..Default::default()
+        };
+        let key = resolve_api_key("OPENAI_API_KEY", &config, no_env);
+        assert_eq!(key.unwrap(), "sk-from-file");
+    }
+
+    #[test]
+    fn test_key_command_fallback() {
+        let config = AppConfig {
+            api_key_file: Some("/nonexistent/openai-key".into()),
+            api_key_command: Some("printf 'sk-from-command\\n'".to_string()),
+            ..Default::default()
+        };
+        let key = resolve_api_key("OPENAI_API_KEY", &config, no_env);
+        assert_eq!(key.unwrap(), "sk-from-command");
+    }
+
+    #[test]
+    fn test_keyring_errors_are_actionable() {
+        let message = describe_keyring_error(&keyring::Error::NoEntry, "openai");
+        assert!(message.contains("voice-ptt set-key"));
+
+        let locked =
+            keyring::Error::NoStorageAccess("org.freedesktop.Secret.Error.IsLocked".into());
+        assert!(describe_keyring_error(&locked, "openai").contains("Unlock your login keyring"));
+
+        let no_dbus = keyring::Error::PlatformFailure(
+            "org.freedesktop.DBus.Error.ServiceUnknown: The name org.freedesktop.secrets was not provided".into(),
+        );
+        assert!(describe_keyring_error(&no_dbus, "openai").contains("headless"));
+    }
+
+    #[test]
+    fn test_error_lists_tried_sources() {
+        let config = AppConfig {
+            api_key_file: Some("/nonexistent/openai-key".into()),
+            api_key_command: Some("echo 'pass: store locked' >&2; exit 1".to_string()),
+            ..Default::default()
+        };
+        let message = resolve_api_key("GROQ_API_KEY", &config, no_env)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("GROQ_API_KEY environment variable"));
+        assert!(message.contains("api_key_file /nonexistent/openai-key"));
+        assert!(message.contains("pass: store locked"));
+    }
+}
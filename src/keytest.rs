@@ -0,0 +1,256 @@
+use anyhow::Result;
+use std::io::Write;
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::config::{self, PttKey};
+#[cfg(target_os = "linux")]
+use crate::evdev;
+use crate::ptt::KeyReader;
+
+/// The device_query key names `ptt_key`, `[bindings]` and `profile_key` accept, in its order.
+pub const KEY_NAMES: [&str; 112] = [
+    "Key0",
+    "Key1",
+    "Key2",
+    "Key3",
+    "Key4",
+    "Key5",
+    "Key6",
+    "Key7",
+    "Key8",
+    "Key9",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "F1",
+    "F2",
+    "F3",
+    "F4",
+    "F5",
+    "F6",
+    "F7",
+    "F8",
+    "F9",
+    "F10",
+    "F11",
+    "F12",
+    "F13",
+    "F14",
+    "F15",
+    "F16",
+    "F17",
+    "F18",
+    "F19",
+    "F20",
+    "Escape",
+    "Space",
+    "LControl",
+    "RControl",
+    "LShift",
+    "RShift",
+    "LAlt",
+    "RAlt",
+    "Command",
+    "RCommand",
+    "LOption",
+    "ROption",
+    "LMeta",
+    "RMeta",
+    "Enter",
+    "Up",
+    "Down",
+    "Left",
+    "Right",
+    "Backspace",
+    "CapsLock",
+    "Tab",
+    "Home",
+    "End",
+    "PageUp",
+    "PageDown",
+    "Insert",
+    "Delete",
+    "Numpad0",
+    "Numpad1",
+    "Numpad2",
+    "Numpad3",
+    "Numpad4",
+    "Numpad5",
+    "Numpad6",
+    "Numpad7",
+    "Numpad8",
+    "Numpad9",
+    "NumpadSubtract",
+    "NumpadAdd",
+    "NumpadDivide",
+    "NumpadMultiply",
+    "NumpadEquals",
+    "NumpadEnter",
+    "NumpadDecimal",
+    "Grave",
+    "Minus",
+    "Equal",
+    "LeftBracket",
+    "RightBracket",
+    "BackSlash",
+    "Semicolon",
+    "Apostrophe",
+    "Comma",
+    "Dot",
+    "Slash",
+];
+
+/// How often `detect-key` looks at the keyboard.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Prints every accepted key name, one per line: device_query's, then on Linux the evdev
+/// names of the keys it doesn't know.
+pub fn list() {
+    let mut out = std::io::stdout().lock();
+    #[cfg(target_os = "linux")]
+    let names = KEY_NAMES.into_iter().chain(evdev::extra_names());
+    #[cfg(not(target_os = "linux"))]
+    let names = KEY_NAMES;
+    for name in names {
+        // Stop quietly when piped into e.g. `head`
+        if writeln!(out, "{}", name).is_err() {
+            break;
+        }
+    }
+}
+
+/// Collects the keys of the first press: everything held together until all are released.
+/// Keys already down when it starts, like the Enter that ran the command, are ignored until
+/// they come up.
+struct Detector {
+    ignored: Vec<PttKey>,
+    pressed: Vec<PttKey>,
+}
+
+impl Detector {
+    fn new(held: Vec<PttKey>) -> Self {
+        Self {
+            ignored: held,
+            pressed: Vec::new(),
+        }
+    }
+
+    /// Feeds the keys held right now; returns the keys in press order once they are all
+    /// released, and calls `on_press` for every new one.
+    fn poll(&mut self, keys: &[PttKey], mut on_press: impl FnMut(PttKey)) -> Option<Vec<PttKey>> {
+        self.ignored.retain(|key| keys.contains(key));
+        let mut held = keys
+            .iter()
+            .filter(|key| !self.ignored.contains(key))
+            .peekable();
+        if held.peek().is_none() && !self.pressed.is_empty() {
+            return Some(std::mem::take(&mut self.pressed));
+        }
+        for &key in held {
+            if !self.pressed.contains(&key) {
+                self.pressed.push(key);
+                on_press(key);
+            }
+        }
+        None
+    }
+
+    fn is_pressing(&self) -> bool {
+        !self.pressed.is_empty()
+    }
+}
+
+/// Waits up to `timeout` for a key or combination and prints its name as a `ptt_key` line.
+///
+/// Returns `false` when nothing was pressed in time.
+pub async fn detect(timeout: Duration) -> Result<bool> {
+    let mut key_reader = KeyReader::new(&[])?;
+    if !key_reader.watch_all() && cfg!(target_os = "linux") {
+        println!(
+            "ℹ️ /dev/input isn't readable, so keys like Menu can't be seen (join the input group)"
+        );
+    }
+    println!(
+        "⌨️ Press the key or combination to use within {}s...",
+        timeout.as_secs()
+    );
+    let started = Instant::now();
+    let mut detector = Detector::new(key_reader.held());
+    // A press that has begun is always finished, even past the timeout
+    while started.elapsed() < timeout || detector.is_pressing() {
+        let keys = key_reader.held();
+        if let Some(pressed) = detector.poll(&keys, |key| println!("   {}", key)) {
+            println!("✅ ptt_key = \"{}\"", config::keys_label(&pressed));
+            return Ok(true);
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+    println!(
+        "⌛ No key pressed within {}s. On Wayland, key state may only be readable with access to /dev/input.",
+        timeout.as_secs()
+    );
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use device_query::Keycode;
+    use std::str::FromStr;
+
+    const ENTER: PttKey = PttKey::Key(Keycode::Enter);
+    const CONTROL: PttKey = PttKey::Key(Keycode::LControl);
+    const SPACE: PttKey = PttKey::Key(Keycode::Space);
+
+    #[test]
+    fn test_key_names_parse_and_round_trip() {
+        for name in KEY_NAMES {
+            let key = Keycode::from_str(name).unwrap();
+            assert_eq!(key.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_detector_collects_a_combination() {
+        let mut detector = Detector::new(vec![ENTER]);
+        let mut printed = Vec::new();
+        let mut poll = |keys: &[PttKey]| detector.poll(keys, |key| printed.push(key));
+
+        // The Enter that started the command doesn't count, even after a key goes down
+        assert_eq!(poll(&[ENTER]), None);
+        assert_eq!(poll(&[ENTER, CONTROL]), None);
+        assert_eq!(poll(&[CONTROL, SPACE]), None);
+        assert_eq!(poll(&[SPACE]), None);
+        assert_eq!(poll(&[]), Some(vec![CONTROL, SPACE]));
+        assert_eq!(printed, [CONTROL, SPACE]);
+
+        // Once released, a key held at the start counts like any other
+        let mut detector = Detector::new(vec![ENTER]);
+        assert_eq!(detector.poll(&[], |_| {}), None);
+        assert_eq!(detector.poll(&[ENTER], |_| {}), None);
+        assert_eq!(detector.poll(&[], |_| {}), Some(vec![ENTER]));
+    }
+}
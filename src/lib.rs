@@ -0,0 +1,44 @@
+//! Push-to-talk dictation: records while a key is held, transcribes the recording and types
+//! the text into the focused window. The `voice-ptt` binary is a thin loop around
+//! [`recorder::Recorder`]; the transcription backend and the injector are trait objects, so
+//! the pipeline can be driven without a microphone, a network or a display.
+
+pub mod api;
+mod api_error;
+pub mod api_stream;
+pub mod audio;
+pub mod batch;
+pub mod capture;
+pub mod cli;
+pub mod config;
+pub mod control;
+mod dedupe;
+mod deepgram;
+pub mod encode;
+mod evdev;
+pub mod init;
+pub mod inject_queue;
+pub mod injector;
+#[cfg(unix)]
+pub mod instance;
+pub mod keys;
+pub mod keytest;
+#[cfg(feature = "local-whisper")]
+mod local_whisper;
+pub mod logging;
+pub mod mictest;
+mod native;
+mod permissions;
+pub mod pipeline;
+pub mod ptt;
+pub mod queue;
+pub mod recorder;
+mod recordings;
+pub mod sound;
+pub mod stats;
+pub mod systemd;
+pub mod text;
+#[cfg(all(feature = "tray", target_os = "linux"))]
+pub mod tray;
+#[cfg(feature = "vosk")]
+mod vosk_backend;
@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::io::Cursor;
+use std::sync::Arc;
+use tracing::info;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use crate::api::TranscriptionBackend;
+use crate::config::{AppConfig, TranscriptionMode, UploadFormat};
+use crate::encode::{self, EncodedAudio};
+
+/// whisper.cpp only accepts 16 kHz mono input.
+const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// Offline transcription with whisper.cpp; no audio leaves the machine.
+pub struct LocalWhisperBackend {
+    context: Arc<WhisperContext>,
+}
+
+impl LocalWhisperBackend {
+    /// Loads the model from `config.model_path`.
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        let path = config.model_path.as_ref().context(
+            "backend = \"local\" requires model_path to point at a whisper.cpp model file",
+        )?;
+        if !path.is_file() {
+            anyhow::bail!(
+                "Whisper model file not found at {}. Download one, e.g. ggml-base.bin from https://huggingface.co/ggerganov/whisper.cpp",
+                path.display()
+            );
+        }
+        let path_str = path.to_str().context("model_path must be valid UTF-8")?;
+        info!("Loading local Whisper model from {}...", path.display());
+        let context =
+            WhisperContext::new_with_params(path_str, WhisperContextParameters::default())
+                .map_err(|e| {
+                    anyhow::anyhow!("Failed to load Whisper model {}: {}", path.display(), e)
+                })?;
+        Ok(Self {
+            context: Arc::new(context),
+        })
+    }
+}
+
+/// Converts a WAV file to the 16 kHz mono f32 samples whisper.cpp expects.
+fn wav_to_whisper_input(bytes: &[u8]) -> Result<Vec<f32>> {
+    let mut reader =
+        hound::WavReader::new(Cursor::new(bytes)).context("Failed to read recorded WAV")?;
+    let spec = reader.spec();
+    let samples = reader
+        .samples::<i16>()
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read recorded samples")?;
+    let mono = encode::downmix_to_mono(&samples, spec.channels);
+    let resampled = encode::resample_linear(&mono, spec.sample_rate, WHISPER_SAMPLE_RATE);
+    Ok(resampled
+        .into_iter()
+        .map(|s| s as f32 / i16::MAX as f32)
+        .collect())
+}
+
+#[async_trait]
+impl TranscriptionBackend for LocalWhisperBackend {
+    /// whisper.cpp needs raw PCM, so skip compressed upload formats.
+    fn upload_format(&self, _configured: UploadFormat) -> UploadFormat {
+        UploadFormat::Wav
+    }
+
+    async fn transcribe_audio(
+        &self,
+        audio: EncodedAudio,
+        mode: TranscriptionMode,
+        config: &AppConfig,
+    ) -> Result<String> {
+        let context = self.context.clone();
+        let language = config.language.clone();
+        let prompt = config.whisper_prompt();
+        let temperature = config.temperature;
+
+        // Inference takes seconds of CPU time, keep it off the async runtime threads
+        tokio::task::spawn_blocking(move || {
+            let input = wav_to_whisper_input(&audio.bytes)?;
+
+            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            params.set_language(Some(language.as_deref().unwrap_or("auto")));
+            params.set_translate(mode == TranscriptionMode::Translate);
+            if let Some(prompt) = &prompt {
+                params.set_initial_prompt(prompt);
+            }
+            if let Some(temperature) = temperature {
+                params.set_temperature(temperature);
+            }
+            params.set_print_special(false);
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+            params.set_print_timestamps(false);
+
+            let mut state = context
+                .create_state()
+                .map_err(|e| anyhow::anyhow!("Failed to create Whisper state: {}", e))?;
+            state
+                .full(params, &input)
+                .map_err(|e| anyhow::anyhow!("Local transcription failed: {}", e))?;
+
+            let segments = state
+                .full_n_segments()
+                .map_err(|e| anyhow::anyhow!("Failed to read transcription: {}", e))?;
+            let mut text = String::new();
+            for i in 0..segments {
+                let segment = state
+                    .full_get_segment_text(i)
+                    .map_err(|e| anyhow::anyhow!("Failed to read segment {}: {}", i, e))?;
+                text.push_str(&segment);
+            }
+            Ok(text.trim().to_string())
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_to_whisper_input() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let samples = vec![i16::MAX; 48000 * 2];
+        let bytes = encode::encode_wav(&samples, spec).unwrap();
+        let input = wav_to_whisper_input(&bytes).unwrap();
+        assert_eq!(input.len(), 16000);
+        assert!((input[100] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_missing_model_file() {
+        let config = AppConfig {
+            model_path: Some("/nonexistent/ggml-base.bin".into()),
+            ..Default::default()
+        };
+        match LocalWhisperBackend::new(&config) {
+            Err(e) => assert!(e.to_string().contains("not found")),
+            Ok(_) => panic!("missing model must be an error"),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use anyhow::{Context, Result};
+use std::fmt::{self, Write as _};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::config::{AppConfig, LogFormat};
+
+/// How many daily log files are kept next to `log_file`.
+const LOG_FILES_KEPT: usize = 7;
+
+/// Set by [`init`], see [`live_lines`].
+static LIVE_LINES: AtomicBool = AtomicBool::new(false);
+
+/// Whether lines redrawn in place, like the level meter, can be drawn on stderr: only on a
+/// terminal, so their carriage returns stay out of the journal and piped output, and not with
+/// `--quiet`.
+pub fn live_lines() -> bool {
+    LIVE_LINES.load(Ordering::Relaxed)
+}
+
+/// The `RUST_LOG` directives to use: `RUST_LOG` itself if set, otherwise voice-ptt's own
+/// messages at info level (debug with `verbose`, none below warnings with `quiet`) and
+/// warnings from libraries.
+fn filter_directives(rust_log: Option<&str>, verbose: bool, quiet: bool) -> &str {
+    match rust_log.filter(|spec| !spec.trim().is_empty()) {
+        Some(spec) => spec,
+        None if quiet => "warn",
+        None if verbose => "warn,voice_ptt=debug",
+        None => "warn,voice_ptt=info",
+    }
+}
+
+/// The message of an event followed by its other fields as `name=value`.
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.insert_str(0, value);
+        } else {
+            let _ = write!(self.0, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Fields for timestamped lines: the message without the blank lines the console output
+/// uses as spacing, then the other fields.
+struct TrimmedFields;
+
+impl<'writer> FormatFields<'writer> for TrimmedFields {
+    fn format_fields<R: tracing_subscriber::field::RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut message = Message::default();
+        fields.record(&mut message);
+        write!(writer, "{}", message.0.trim())
+    }
+}
+
+/// Just the message, as the console shows it with `log_format = "emoji"`.
+struct EmojiFormat;
+
+impl<S, N> FormatEvent<S, N> for EmojiFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut message = Message::default();
+        event.record(&mut message);
+        writeln!(writer, "{}", message.0)
+    }
+}
+
+/// Routes log events to stderr, leaving stdout to the transcripts of `output_mode = "stdout"`,
+/// and to `log_file`, rotated daily. The level comes from `RUST_LOG` when it is set,
+/// otherwise from `verbose` and `--quiet`.
+pub fn init(config: &AppConfig, quiet: bool) -> Result<()> {
+    LIVE_LINES.store(
+        !quiet && std::io::stdout().is_terminal() && std::io::stderr().is_terminal(),
+        Ordering::Relaxed,
+    );
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let directives = filter_directives(rust_log.as_deref(), config.verbose, quiet);
+    let filter = EnvFilter::try_new(directives)
+        .with_context(|| format!("Invalid log filter '{}'", directives))?;
+
+    let console = match config.log_format {
+        LogFormat::Emoji => tracing_subscriber::fmt::layer()
+            .event_format(EmojiFormat)
+            .with_writer(std::io::stderr)
+            .boxed(),
+        LogFormat::Full => tracing_subscriber::fmt::layer()
+            .fmt_fields(TrimmedFields)
+            .with_writer(std::io::stderr)
+            .boxed(),
+    };
+    let file = match &config.log_file {
+        Some(path) => {
+            let appender = log_file_appender(path)?;
+            let layer = tracing_subscriber::fmt::layer()
+                .fmt_fields(TrimmedFields)
+                .with_ansi(false)
+                .with_writer(appender)
+                // A full disk would otherwise print an error for every line
+                .log_internal_errors(false);
+            Some(layer)
+        }
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(file)
+        .try_init()
+        .context("Logging was already set up")
+}
+
+/// Appends to `<log_file>.<date>`, keeping the last [`LOG_FILES_KEPT`] days.
+fn log_file_appender(path: &std::path::Path) -> Result<RollingFileAppender> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("log_file {} has no file name", path.display()))?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(name)
+        .max_log_files(LOG_FILES_KEPT)
+        .build(dir)
+        .with_context(|| format!("Failed to open the log file {}", path.display()))
+}
+
+/// Transcript text for a log line: the text itself at debug level, otherwise only its
+/// length, so transcripts stay out of logs and the journal by default.
+pub fn transcript(text: &str) -> String {
+    if tracing::enabled!(Level::DEBUG) {
+        format!("'{}'", text)
+    } else {
+        format!("{} characters", text.chars().count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_filter_directives() {
+        assert_eq!(filter_directives(None, false, false), "warn,voice_ptt=info");
+        assert_eq!(filter_directives(None, true, false), "warn,voice_ptt=debug");
+        assert_eq!(filter_directives(Some(" "), true, true), "warn");
+        assert_eq!(
+            filter_directives(Some("voice_ptt::injector=debug"), false, true),
+            "voice_ptt::injector=debug"
+        );
+    }
+
+    /// Logs a line through `layer` and returns what it wrote.
+    fn written<L>(layer: impl FnOnce(Arc<Mutex<Vec<u8>>>) -> L) -> String
+    where
+        L: Layer<tracing_subscriber::Registry> + Send + Sync,
+    {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(layer(output.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "voice_ptt::recorder", "\n✅ Ready! Hold RControl.");
+        });
+        let output = output.lock().unwrap();
+        String::from_utf8(output.clone()).unwrap()
+    }
+
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        let emoji = written(|output| {
+            tracing_subscriber::fmt::layer()
+                .event_format(EmojiFormat)
+                .with_writer(move || Buffer(output.clone()))
+        });
+        assert_eq!(emoji, "\n✅ Ready! Hold RControl.\n");
+
+        let full = written(|output| {
+            tracing_subscriber::fmt::layer()
+                .fmt_fields(TrimmedFields)
+                .with_ansi(false)
+                .with_writer(move || Buffer(output.clone()))
+        });
+        assert!(
+            full.ends_with("  INFO voice_ptt::recorder: ✅ Ready! Hold RControl.\n"),
+            "{}",
+            full
+        );
+        assert!(full.starts_with("20"), "{}", full);
+    }
+
+    /// Set in the child process of [`test_stdout_holds_only_transcripts`].
+    const STDOUT_CHILD: &str = "VOICE_PTT_TEST_STDOUT_CHILD";
+
+    #[test]
+    fn test_stdout_holds_only_transcripts() {
+        use crate::config::OutputMode;
+        use crate::inject_queue::{Injector, SystemInjection};
+
+        // Logging is set up once per process, so the run happens in a child process
+        if std::env::var_os(STDOUT_CHILD).is_some() {
+            let config = AppConfig {
+                output_mode: OutputMode::Stdout,
+                ..AppConfig::default()
+            };
+            init(&config, false).unwrap();
+            tracing::info!("🎙️ Recording...");
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            runtime
+                .block_on(SystemInjection.inject("hello world", &config))
+                .unwrap();
+            tracing::warn!("⚠️ Done");
+            return;
+        }
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "logging::tests::test_stdout_holds_only_transcripts",
+                "--exact",
+                "--nocapture",
+            ])
+            .env(STDOUT_CHILD, "1")
+            .env_remove("RUST_LOG")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        // The rest of stdout is the test harness reporting on the child
+        assert!(stdout.contains("hello world\n"), "{}", stdout);
+        assert!(
+            !stdout.contains("Recording") && !stdout.contains("Done"),
+            "{}",
+            stdout
+        );
+        assert!(stderr.contains("🎙️ Recording...\n"), "{}", stderr);
+        assert!(stderr.contains("⚠️ Done\n"), "{}", stderr);
+    }
+
+    #[test]
+    fn test_transcript_hidden_without_debug() {
+        assert_eq!(transcript("hello wörld"), "11 characters");
+        let subscriber = tracing_subscriber::registry().with(EnvFilter::new("debug"));
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(transcript("hello"), "'hello'");
+        });
+    }
+}
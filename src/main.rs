@@ -1,282 +1,611 @@
-mod api;
-mod audio;
-mod config;
-mod injector;
-
 use anyhow::{Context, Result};
-use cpal::traits::DeviceTrait;
-use cpal::traits::StreamTrait;
-use device_query::{DeviceQuery, DeviceState};
+use clap::Parser;
 use dotenvy::dotenv;
 use std::env;
-use std::path::PathBuf;
-use std::process::{Child, Command};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::time::{sleep, Duration};
-
-use crate::api::WhisperClient;
-use crate::audio::AudioSystem;
-use crate::config::AppConfig;
-use crate::injector::SystemInjector;
-
-enum CaptureMode {
-    Cpal {
-        audio_buffer: Arc<Mutex<Vec<i16>>>,
-        is_recording: Arc<AtomicBool>,
-        wav_spec: hound::WavSpec,
-        _stream: cpal::Stream,
-    },
-    PwRecord {
-        recorder: Option<Child>,
-        current_file: Option<PathBuf>,
-    },
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use voice_ptt::capture::CaptureMode;
+use voice_ptt::cli::{Cli, Command, ConfiguredCommand, StandaloneCommand};
+use voice_ptt::config::{self, AppConfig, AppDirs, ConfigLocation, PttBinding, PttKey, CONFIG_ENV};
+use voice_ptt::control::{self, ControlServer, Request, Response};
+use voice_ptt::inject_queue::SystemInjection;
+use voice_ptt::injector::{StatusUpdate, SystemInjector};
+#[cfg(unix)]
+use voice_ptt::instance::{self, AlreadyRunning, InstanceLock};
+use voice_ptt::pipeline::{self, Services};
+use voice_ptt::ptt::{self, KeyReader};
+use voice_ptt::recorder::Recorder;
+use voice_ptt::{
+    api, api_stream, audio, batch, init, keys, keytest, logging, mictest, queue, stats, systemd,
+};
+
+/// The settings of `profile` on top of `base`, with the command-line flags still winning;
+/// `base` itself without a profile.
+fn profile_config(base: &AppConfig, profile: Option<&str>, cli: &Cli) -> Result<AppConfig> {
+    let Some(name) = profile else {
+        return Ok(base.clone());
+    };
+    let mut config = base.with_profile(name)?;
+    config.apply_overrides(cli)?;
+    Ok(config)
 }
 
-fn start_pw_recording() -> Result<(Child, PathBuf)> {
-    let ts = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis();
-    let out_file = PathBuf::from(format!("/tmp/voice-ptt-{}.wav", ts));
-
-    let child = Command::new("pw-record")
-        .arg("--rate")
-        .arg("16000")
-        .arg("--channels")
-        .arg("1")
-        .arg("--format")
-        .arg("s16")
-        .arg(&out_file)
-        .spawn()
-        .context(
-            "Failed to start pw-record. Install pipewire tools and ensure PipeWire is running.",
-        )?;
-
-    Ok((child, out_file))
+/// What to press to speak, naming the active profile.
+fn profile_hint(config: &AppConfig, bindings: &[PttBinding], profile: Option<&str>) -> String {
+    let labels: Vec<String> = bindings.iter().map(PttBinding::label).collect();
+    let hint = ptt::hint(config.activation, &labels.join("] or ["));
+    match profile {
+        Some(name) => format!("{} (profile {})", hint, name),
+        None => hint,
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // 1. Initialization
-    SystemInjector::check_dependencies()?;
+/// Logs how long after the key press the recording started, at debug level.
+fn log_start_latency(key_reader: &KeyReader) {
+    let Some(latency) = key_reader
+        .last_press()
+        .and_then(|pressed| pressed.elapsed().ok())
+    else {
+        return;
+    };
+    let polled = match key_reader.reads_events() {
+        true => String::new(),
+        false => format!(
+            ", plus up to {}ms until the poll saw it",
+            ptt::POLL_INTERVAL.as_millis()
+        ),
+    };
+    debug!(
+        "⏱️ Recording started {:.1}ms after the key press{}",
+        latency.as_secs_f64() * 1000.0,
+        polled
+    );
+}
+
+/// Exit status when another instance is already running, so scripts can tell it apart
+/// from a failure.
+#[cfg(unix)]
+const ALREADY_RUNNING_EXIT: i32 = 3;
 
-    let exe_path = env::current_exe()?;
-    let exe_dir = exe_path
-        .parent()
-        .context("Failed to get executable directory")?;
+/// How often `bar-status` looks for an instance while none is running.
+#[cfg(unix)]
+const BAR_STATUS_RETRY: Duration = Duration::from_secs(2);
 
-    // Load .env
-    let env_path = exe_dir.join(".env");
-    if env_path.exists() {
-        dotenvy::from_path(&env_path).ok();
+/// Takes the single-instance lock, replacing the running instance with `--replace`. The old
+/// one gets its shutdown grace plus a few seconds to exit. Exits with
+/// `ALREADY_RUNNING_EXIT` when another instance holds the lock.
+#[cfg(unix)]
+fn lock_instance(cli: &Cli, grace: Duration) -> Result<Option<InstanceLock>> {
+    if cli.allow_multiple {
+        return Ok(None);
+    }
+    let path = instance::lock_path();
+    let locked = if cli.replace {
+        InstanceLock::replace(&path, grace + Duration::from_secs(5))
     } else {
-        let _ = dotenv();
+        InstanceLock::acquire(&path)
+    };
+    match locked {
+        Ok(lock) => Ok(Some(lock)),
+        Err(e) => match e.downcast_ref::<AlreadyRunning>() {
+            Some(running) => {
+                error!(
+                    "❌ {}. Quit it first, or start with --replace to take over.",
+                    running
+                );
+                std::process::exit(ALREADY_RUNNING_EXIT);
+            }
+            None => Err(e),
+        },
     }
+}
 
-    let api_key =
-        env::var("OPENAI_API_KEY").context("OPENAI_API_KEY environment variable must be set")?;
+/// Resolves on Ctrl+C, or on SIGTERM on Unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let mut terminate =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok();
+    #[cfg(unix)]
+    let terminated = async {
+        match &mut terminate {
+            Some(terminate) => terminate.recv().await,
+            None => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminated = std::future::pending::<Option<()>>();
+    tokio::select! {
+        Ok(()) = tokio::signal::ctrl_c() => {}
+        _ = terminated => {}
+    }
+}
 
-    // Load config.toml
-    let config_path = exe_dir.join("config.toml");
-    let app_config = AppConfig::load(&config_path)?;
-    let ptt_key = app_config.get_ptt_keycode();
+/// Pauses or resumes listening to the keys and confirms it with a notification. Pausing ends
+/// a toggled or started recording; a PTT key still held when resuming records only once it is
+/// pressed again.
+fn set_paused(paused: bool, recorder: &mut Recorder, services: &Services, ready_hint: &str) {
+    services.status.update(StatusUpdate::Paused(paused));
+    if paused {
+        recorder.stop_latched();
+        info!("⏸️ Paused, keys are ignored until resumed.");
+        SystemInjector::notify("Voice PTT", "Paused, the PTT keys do nothing until resumed");
+    } else {
+        recorder.ignore_held_keys();
+        info!("▶️ Resumed. {}.", ready_hint);
+        SystemInjector::notify("Voice PTT", "Resumed");
+    }
+}
 
-    // 2. Audio Setup with fallback
-    println!("Init audio...");
-    let mut capture_mode = match AudioSystem::new() {
-        Ok(audio_system) => {
-            let device_name = audio_system
-                .device
-                .name()
-                .unwrap_or_else(|_| "default".to_string());
-            println!("Using input device: {}", device_name);
-
-            let wav_spec = audio_system.get_wav_spec();
-            let audio_buffer = Arc::new(Mutex::new(Vec::new()));
-            let is_recording = Arc::new(AtomicBool::new(false));
-            let stream = audio_system.build_stream(audio_buffer.clone(), is_recording.clone())?;
-            stream.play()?;
-
-            CaptureMode::Cpal {
-                audio_buffer,
-                is_recording,
-                wav_spec,
-                _stream: stream,
-            }
+/// Loads the config file with the VOICE_PTT_* variables and the flags on top.
+fn load_config(location: &ConfigLocation, cli: &Cli) -> Result<AppConfig> {
+    let mut config = AppConfig::load_located(location)?;
+    config.apply_env(env::vars())?;
+    config.apply_overrides(cli)?;
+    Ok(config)
+}
+
+/// The keys of the active config besides the PTT bindings.
+struct ControlKeys {
+    profile: Vec<PttKey>,
+    repeat: Vec<PttKey>,
+    retry: Vec<PttKey>,
+    cancel: Vec<PttKey>,
+    pause: Vec<PttKey>,
+}
+
+impl ControlKeys {
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            profile: config.get_profile_keycodes(),
+            repeat: config.get_repeat_keycodes(),
+            retry: config.get_retry_keycodes(),
+            cancel: config.get_cancel_keycodes(),
+            pause: config.get_pause_keycodes(),
         }
-        Err(e) => {
-            eprintln!("⚠️ cpal capture init failed: {}", e);
-            eprintln!("⚠️ Falling back to PipeWire recorder (pw-record).");
-            CaptureMode::PwRecord {
-                recorder: None,
-                current_file: None,
+    }
+
+    /// The keys of the bindings and these, for the key reader to watch.
+    fn watched(&self, bindings: &[PttBinding]) -> Vec<PttKey> {
+        bindings
+            .iter()
+            .flat_map(|binding| binding.keys.iter())
+            .chain(&self.profile)
+            .chain(&self.repeat)
+            .chain(&self.retry)
+            .chain(&self.pause)
+            .chain(&self.cancel)
+            .copied()
+            .collect()
+    }
+}
+
+/// Switches to `config` between recordings: the recorder and the sounds use it from now on,
+/// and its bindings, other keys and ready hint are returned, making sure the key reader can
+/// see the keys.
+fn use_config(
+    config: &AppConfig,
+    profile: Option<&str>,
+    key_reader: &mut KeyReader,
+    recorder: &mut Recorder,
+) -> (Vec<PttBinding>, ControlKeys, String) {
+    let bindings = config.ptt_bindings();
+    let controls = ControlKeys::new(config);
+    if let Err(e) = key_reader.watch(&controls.watched(&bindings)) {
+        error!("❌ {:#}", e);
+    }
+    recorder.reconfigure(config);
+    let ready_hint = profile_hint(config, &bindings, profile);
+    (bindings, controls, ready_hint)
+}
+
+/// Loads the first `.env` found in `dirs`, or from the working directory.
+fn load_env(dirs: &[PathBuf]) {
+    match dirs
+        .iter()
+        .map(|dir| dir.join(".env"))
+        .find(|path| path.exists())
+    {
+        Some(env_path) => {
+            dotenvy::from_path(&env_path).ok();
+        }
+        None => {
+            let _ = dotenv();
+        }
+    }
+}
+
+/// Runs a command that doesn't need the config, the API key or the audio setup.
+async fn run_standalone(command: StandaloneCommand, cli: &Cli) -> Result<()> {
+    match command {
+        StandaloneCommand::ListKeys => {
+            keytest::list();
+            Ok(())
+        }
+        StandaloneCommand::DetectKey { timeout } => {
+            let detected = keytest::detect(Duration::from_secs(timeout)).await?;
+            std::process::exit(if detected { 0 } else { 1 });
+        }
+        #[cfg(unix)]
+        StandaloneCommand::Ctl { command } => {
+            let response = control::send(&control::socket_path(), command).await?;
+            println!("{}", serde_json::to_string(&response)?);
+            std::process::exit(if response.ok { 0 } else { 1 });
+        }
+        #[cfg(unix)]
+        StandaloneCommand::BarStatus { plain } => {
+            let mut shown = String::new();
+            let mut show = |status: Option<&control::Status>| {
+                let line = match plain {
+                    true => control::bar_class(status).to_string(),
+                    false => control::waybar_line(status),
+                };
+                if line != shown {
+                    println!("{}", line);
+                    shown = line;
+                }
+            };
+            // Outlives restarts of the instance, showing it as stopped in between
+            loop {
+                let _ = control::follow(&control::socket_path(), |status| show(Some(status))).await;
+                show(None);
+                tokio::time::sleep(BAR_STATUS_RETRY).await;
             }
         }
+        #[cfg(not(unix))]
+        StandaloneCommand::Ctl { .. } | StandaloneCommand::BarStatus { .. } => {
+            anyhow::bail!("voice-ptt ctl and bar-status need Unix sockets")
+        }
+        StandaloneCommand::Init { force } => {
+            let dirs = AppDirs::detect()?;
+            let path = match (&cli.config, &dirs.config) {
+                (Some(path), _) => path.clone(),
+                (None, Some(dir)) => dir.join("config.toml"),
+                (None, None) => dirs.exe.join("config.toml"),
+            };
+            init::run(&path, force)
+        }
+        StandaloneCommand::InstallService { force } => {
+            let dirs = AppDirs::detect()?;
+            let config = cli.config.as_deref().map(std::path::absolute).transpose()?;
+            let env_file = dirs
+                .config
+                .as_ref()
+                .unwrap_or(&dirs.exe)
+                .join("service.env");
+            let unit = systemd::unit_file(&env::current_exe()?, config.as_deref(), &env_file);
+            systemd::install(&systemd::unit_path()?, &unit, force)
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let command = match cli.command.clone() {
+        Some(Command::Standalone(command)) => return run_standalone(command, &cli).await,
+        Some(Command::Configured(command)) => Some(command),
+        None => None,
     };
 
+    let dirs = AppDirs::detect()?;
+    let config_env = env::var_os(CONFIG_ENV).map(PathBuf::from);
+    let location = config::locate_config(
+        cli.config.as_deref(),
+        config_env.as_deref(),
+        &dirs,
+        Path::exists,
+    )?;
+    load_env(&dirs.env_dirs(&location));
+    let mut app_config = load_config(&location, &cli)?;
+    let mut base_config = app_config.clone();
+    let mut profile = cli.profile.clone();
+    app_config = profile_config(&base_config, profile.as_deref(), &cli)?;
+    logging::init(&app_config, cli.quiet)?;
+    if cli.print_config {
+        print!("{}", app_config.to_toml()?);
+        return Ok(());
+    }
+
+    match command {
+        Some(ConfiguredCommand::TestMic) => {
+            let heard_something = mictest::run(&app_config).await?;
+            std::process::exit(if heard_something { 0 } else { 1 });
+        }
+        Some(ConfiguredCommand::SetKey) => {
+            let key = rpassword::prompt_password(format!(
+                "API key for '{}' (input is hidden): ",
+                app_config.backend
+            ))
+            .context("Failed to read the API key")?;
+            let key = key.trim();
+            if key.is_empty() {
+                anyhow::bail!("No API key entered, nothing stored.");
+            }
+            keys::store_in_keyring(&app_config.backend, key)?;
+            println!(
+                "🔑 Stored the API key for '{}' in the keyring. Set api_key_source = \"keyring\" to use it.",
+                app_config.backend
+            );
+            return Ok(());
+        }
+        Some(ConfiguredCommand::Transcribe { paths, json, out }) => {
+            let all_ok = batch::run(&app_config, &paths, json, out.as_deref()).await?;
+            std::process::exit(if all_ok { 0 } else { 1 });
+        }
+        Some(ConfiguredCommand::Stats) => {
+            stats::print_report(&app_config.stats_path(), app_config.price_per_minute)?;
+            return Ok(());
+        }
+        Some(ConfiguredCommand::Flush) => {
+            let backend = api::create_backend(&app_config)?;
+            let recovered = queue::flush(backend.as_ref(), &app_config, |meta, text| {
+                queue::deliver(meta, text, &app_config)
+            })
+            .await?;
+            let left =
+                queue::OfflineQueue::new(app_config.queue_path(), app_config.queue_max_items)
+                    .items()?
+                    .len();
+            println!(
+                "📬 Recovered {} recordings, {} left in {}",
+                recovered,
+                left,
+                app_config.queue_path().display()
+            );
+            std::process::exit(if left == 0 { 0 } else { 1 });
+        }
+        None => {}
+    }
+
+    // 1. Initialization
+    #[cfg(unix)]
+    let _instance_lock = lock_instance(&cli, Duration::from_secs(app_config.shutdown_grace_secs))?;
+    match &location.path {
+        Some(path) => info!("⚙️ Config: {} ({})", path.display(), location.origin),
+        None => info!("⚙️ Config: {}", location.origin),
+    }
+    SystemInjector::check_dependencies(&app_config)?;
+    let mut bindings = app_config.ptt_bindings();
+    let mut ready_hint = profile_hint(&app_config, &bindings, profile.as_deref());
+    let mut controls = ControlKeys::new(&app_config);
+    let mut profile_key_held = false;
+    let mut repeat_key_held = false;
+    let mut retry_key_held = false;
+    let mut pause_key_held = false;
+    // The settings of the active profile, for repeats
+    let mut active_config = app_config.clone();
+
+    let swept = audio::sweep_temp_files();
+    if swept > 0 {
+        info!(
+            "🧹 Removed {} temp recordings left by an earlier crash",
+            swept
+        );
+    }
+
+    // 2. Audio Setup with fallback
+    info!("Init audio...");
+    let capture_mode = CaptureMode::init(&app_config)?;
+
     // 3. Components
-    let whisper_client = Arc::new(WhisperClient::new(api_key));
-    let device_state = DeviceState::new();
-    let (sound_start, sound_end) = app_config.get_sound_paths();
+    let backend = api::create_backend(&app_config)?;
+    api::spawn_warmup(backend.clone(), &app_config);
+    queue::spawn_flusher(backend.clone(), &app_config);
+    let streamer = api_stream::create_streamer(&app_config)?;
+    let mut key_reader =
+        KeyReader::for_backend(app_config.input_backend, &controls.watched(&bindings))?;
+    let services = Services::new(backend.clone(), Arc::new(SystemInjection), &app_config);
+    let mut recorder = Recorder::new(&app_config, capture_mode, services.clone(), streamer);
+    services
+        .status
+        .update(StatusUpdate::Profile(profile.clone()));
 
     let version_info = "v0.1.2 (dynamic-paste)";
-    println!("🚀 Voice PTT {} is ready! Hold [{:?}] to speak.", version_info, ptt_key);
+    info!("🚀 Voice PTT {} is ready! {}.", version_info, ready_hint);
+    systemd::notify("READY=1\nSTATUS=Ready");
+    for binding in bindings
+        .iter()
+        .filter(|binding| !binding.summary.is_empty())
+    {
+        info!("   [{}] {}", binding.label(), binding.summary);
+    }
+
+    // Commands from the control socket and the tray icon
+    let (requests, mut incoming) = mpsc::unbounded_channel();
+    #[cfg(all(feature = "tray", target_os = "linux"))]
+    voice_ptt::tray::spawn(services.clone(), requests.clone(), location.path.clone());
+    #[cfg(unix)]
+    let _control = match ControlServer::bind(
+        &control::socket_path(),
+        requests,
+        services.status.subscribe(),
+    ) {
+        Ok(server) => Some(server),
+        Err(e) => {
+            warn!(
+                "⚠️ No control socket, voice-ptt ctl won't reach this instance: {:#}",
+                e
+            );
+            None
+        }
+    };
+    #[cfg(not(unix))]
+    drop(requests);
+    let mut paused = false;
 
     // 4. Main Event Loop
+    let mut shutdown = std::pin::pin!(shutdown_signal());
     loop {
-        let keys = device_state.get_keys();
-
-        if keys.contains(&ptt_key) {
-            match &mut capture_mode {
-                CaptureMode::Cpal {
-                    audio_buffer,
-                    is_recording,
-                    ..
-                } => {
-                    if !is_recording.load(Ordering::Relaxed) {
-                        SystemInjector::play_sound(app_config.sound_enabled, &sound_start);
-                        println!("🎙️ Recording...");
-
-                        if let Ok(mut buffer) = audio_buffer.lock() {
-                            buffer.clear();
-                        }
-                        is_recording.store(true, Ordering::Relaxed);
-                    }
+        recorder.check_capture();
+        let keys = key_reader.held();
+        // While paused only the pause key counts: nothing records, plays or clears a buffer
+        let pause_key_down =
+            !controls.pause.is_empty() && controls.pause.iter().all(|key| keys.contains(key));
+        if pause_key_down && !pause_key_held {
+            paused = !paused;
+            set_paused(paused, &mut recorder, &services, &ready_hint);
+        }
+        pause_key_held = pause_key_down;
+        let keys = match paused {
+            true => Vec::new(),
+            false => keys,
+        };
+        let was_recording = recorder.is_recording();
+
+        // The cancel key only counts while recording, so it keeps its use everywhere else
+        if was_recording
+            && !controls.cancel.is_empty()
+            && controls.cancel.iter().all(|key| keys.contains(key))
+        {
+            recorder.cancel(&ready_hint);
+        }
+
+        // A repeat or retry key that contains a PTT key holds that key off while it is down,
+        // unless it was already recording
+        let repeat_key_down =
+            !controls.repeat.is_empty() && controls.repeat.iter().all(|key| keys.contains(key));
+        let repeating = repeat_key_down && !was_recording;
+        let retry_key_down =
+            !controls.retry.is_empty() && controls.retry.iter().all(|key| keys.contains(key));
+        let retrying = retry_key_down && !was_recording;
+        let ptt_keys: Vec<_> = keys
+            .iter()
+            .filter(|key| !(repeating && controls.repeat.contains(key)))
+            .filter(|key| !(retrying && controls.retry.contains(key)))
+            .copied()
+            .collect();
+        recorder.update(&bindings, &ptt_keys, &ready_hint);
+        if !was_recording && recorder.is_recording() {
+            log_start_latency(&key_reader);
+        }
+        if repeating && !repeat_key_held {
+            pipeline::spawn_repeat(&services, active_config.clone());
+        }
+        repeat_key_held = repeat_key_down;
+        if retrying && !retry_key_held && !recorder.retry(&ready_hint) {
+            info!("🔁 No failed recording to retry");
+            SystemInjector::notify("Voice PTT", "Nothing to retry");
+        }
+        retry_key_held = retry_key_down;
+
+        // Profiles change the bindings, so they are only switched between recordings
+        let profile_key_down =
+            !controls.profile.is_empty() && controls.profile.iter().all(|key| keys.contains(key));
+        if profile_key_down && !profile_key_held && recorder.is_idle() {
+            let next = base_config
+                .next_profile(profile.as_deref())
+                .map(str::to_string);
+            match profile_config(&base_config, next.as_deref(), &cli) {
+                Ok(config) => {
+                    profile = next;
+                    services
+                        .status
+                        .update(StatusUpdate::Profile(profile.clone()));
+                    (bindings, controls, ready_hint) =
+                        use_config(&config, profile.as_deref(), &mut key_reader, &mut recorder);
+                    let message = match &profile {
+                        Some(name) => format!("Profile: {}", name),
+                        None => "Profile: none (base settings)".to_string(),
+                    };
+                    info!("🎛️ {}", message);
+                    SystemInjector::notify("Voice PTT", &message);
+                    info!("\n✅ Ready! {}.", ready_hint);
+                    active_config = config;
                 }
-                CaptureMode::PwRecord {
-                    recorder,
-                    current_file,
-                } => {
-                    if recorder.is_none() {
-                        SystemInjector::play_sound(app_config.sound_enabled, &sound_start);
-                        println!("🎙️ Recording...");
-
-                        match start_pw_recording() {
-                            Ok((child, wav_path)) => {
-                                *recorder = Some(child);
-                                *current_file = Some(wav_path);
-                            }
-                            Err(e) => {
-                                eprintln!("❌ Recorder start error: {}", e);
-                                SystemInjector::notify("Voice PTT Error", &e.to_string());
-                            }
-                        }
-                    }
+                Err(e) => error!("❌ {:#}", e),
+            }
+        }
+        profile_key_held = profile_key_down;
+
+        // Between recordings nothing changes until a key does, or a command comes in
+        let busy = !recorder.is_idle();
+        let request = tokio::select! {
+            _ = key_reader.wait(busy) => None,
+            _ = recorder.reconnected(&ready_hint) => None,
+            Some(request) = incoming.recv() => Some(request),
+            _ = &mut shutdown => break,
+        };
+        let Some((request, respond)) = request else {
+            continue;
+        };
+        let response = match request {
+            Request::Status => Response::status(services.status.current()),
+            Request::Pause | Request::Resume => {
+                let pause = request == Request::Pause;
+                if pause != paused {
+                    paused = pause;
+                    set_paused(paused, &mut recorder, &services, &ready_hint);
                 }
+                Response::ok()
             }
-        } else {
-            match &mut capture_mode {
-                CaptureMode::Cpal {
-                    audio_buffer,
-                    is_recording,
-                    wav_spec,
-                    ..
-                } => {
-                    if is_recording.load(Ordering::Relaxed) {
-                        is_recording.store(false, Ordering::Relaxed);
-                        SystemInjector::play_sound(app_config.sound_enabled, &sound_end);
-                        println!("⚙️ Processing...");
-
-                        let buffer_snapshot: Vec<i16> = match audio_buffer.lock() {
-                            Ok(buf) => buf.clone(),
-                            Err(e) => {
-                                eprintln!("❌ Failed to lock audio buffer: {}", e);
-                                continue;
-                            }
-                        };
-
-                        if !buffer_snapshot.is_empty() {
-                            let client_clone = whisper_client.clone();
-                            let app_config_clone = app_config.clone();
-                            let wav_spec_clone = *wav_spec;
-
-                            tokio::spawn(async move {
-                                match client_clone
-                                    .transcribe(buffer_snapshot, wav_spec_clone, &app_config_clone)
-                                    .await
-                                {
-                                    Ok(text) => {
-                                        println!("📝 Transcribed: '{}'", text);
-                                        if let Err(e) = SystemInjector::type_text(
-                                            &text,
-                                            app_config_clone.typing_delay_ms,
-                                            app_config_clone.initial_delay_ms,
-                                            &app_config_clone,
-                                        )
-                                        .await
-                                        {
-                                            eprintln!("❌ Injection error: {}", e);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        eprintln!("❌ API Error: {}", e);
-                                        SystemInjector::notify("Voice PTT Error", &e.to_string());
-                                    }
-                                }
-                                println!("\n✅ Ready! Hold [{:?}] to speak.", ptt_key);
-                            });
-                        }
-                    }
+            Request::Start if paused => Response::error("Paused, resume first"),
+            Request::Start if !recorder.is_idle() => Response::error("Already recording"),
+            Request::Start => {
+                let ptt = active_config.get_ptt_keycodes();
+                let binding = bindings
+                    .iter()
+                    .position(|binding| binding.keys == ptt)
+                    .unwrap_or(0);
+                recorder.start_latched(binding);
+                Response::ok()
+            }
+            Request::Stop => match recorder.stop_latched() {
+                true => Response::ok(),
+                false => Response::error("No recording to stop"),
+            },
+            Request::Retry if recorder.is_recording() => {
+                Response::error("Recording, try again once it has stopped")
+            }
+            Request::Retry => match recorder.retry(&ready_hint) {
+                true => Response::ok(),
+                false => Response::error("No failed recording to retry"),
+            },
+            Request::ReloadConfig if !recorder.is_idle() => {
+                Response::error("Recording, try again once it has stopped")
+            }
+            Request::ReloadConfig => match load_config(&location, &cli).and_then(|base| {
+                let config = profile_config(&base, profile.as_deref(), &cli)?;
+                let fixed = config.changed_startup_keys(&app_config)?;
+                if !fixed.is_empty() {
+                    anyhow::bail!(
+                        "Not reloaded, restart voice-ptt to change {}",
+                        fixed.join(", ")
+                    );
                 }
-                CaptureMode::PwRecord {
-                    recorder,
-                    current_file,
-                } => {
-                    if recorder.is_some() {
-                        if let Some(mut proc) = recorder.take() {
-                            let _ = proc.kill();
-                            let _ = proc.wait();
-                        }
-                        SystemInjector::play_sound(app_config.sound_enabled, &sound_end);
-                        println!("⚙️ Processing...");
-
-                        if let Some(recorded_file) = current_file.take() {
-                            let size_ok = std::fs::metadata(&recorded_file)
-                                .map(|m| m.len() > 44)
-                                .unwrap_or(false);
-
-                            if size_ok {
-                                let client_clone = whisper_client.clone();
-                                let app_config_clone = app_config.clone();
-
-                                tokio::spawn(async move {
-                                    match client_clone
-                                        .transcribe_wav_file(&recorded_file, &app_config_clone)
-                                        .await
-                                    {
-                                        Ok(text) => {
-                                            println!("📝 Transcribed: '{}'", text);
-                                            if let Err(e) = SystemInjector::type_text(
-                                                &text,
-                                                app_config_clone.typing_delay_ms,
-                                                app_config_clone.initial_delay_ms,
-                                                &app_config_clone,
-                                            )
-                                            .await
-                                            {
-                                                eprintln!("❌ Injection error: {}", e);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            eprintln!("❌ API Error: {}", e);
-                                            SystemInjector::notify("Voice PTT Error", &e.to_string());
-                                        }
-                                    }
-                                    let _ = tokio::fs::remove_file(&recorded_file).await;
-                                    println!("\n✅ Ready! Hold [{:?}] to speak.", ptt_key);
-                                });
-                            } else {
-                                eprintln!("⚠️ Recorded audio file is empty.");
-                            }
-                        }
-                    }
+                Ok((base, config))
+            }) {
+                Ok((base, config)) => {
+                    base_config = base;
+                    (bindings, controls, ready_hint) =
+                        use_config(&config, profile.as_deref(), &mut key_reader, &mut recorder);
+                    active_config = config;
+                    info!("🔄 Reloaded the config. {}.", ready_hint);
+                    Response::ok()
                 }
+                Err(e) => Response::error(format!("{:#}", e)),
+            },
+            // Answered by the control server itself
+            Request::Subscribe => Response::error("Not a command for the main loop"),
+            Request::Quit => {
+                let _ = respond.send(Response::ok());
+                break;
             }
-        }
+        };
+        let _ = respond.send(response);
+    }
 
-        // Use async sleep to be friendly to the tokio runtime
-        sleep(Duration::from_millis(20)).await;
+    info!("\n🛑 Shutting down...");
+    systemd::notify("STOPPING=1\nSTATUS=Shutting down");
+    let grace = Duration::from_secs(app_config.shutdown_grace_secs);
+    tokio::select! {
+        _ = recorder.shutdown(grace) => {}
+        _ = shutdown_signal() => {}
     }
+    info!("👋 Bye!");
+    Ok(())
 }
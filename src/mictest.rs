@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use ringbuf::traits::Consumer;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use tokio::time::{sleep, Duration};
+
+use crate::audio::{self, RecordedAudio};
+use crate::capture::{start_cli_recording, stop_cli_recording, CaptureMode};
+use crate::config::AppConfig;
+use crate::encode;
+use crate::permissions;
+use crate::sound;
+
+/// Length of the test recording.
+const TEST_DURATION: Duration = Duration::from_secs(3);
+
+/// Records a short clip through the normal capture path, reports its level and plays it back.
+///
+/// Returns `false` when the recording was essentially silent.
+pub async fn run(app_config: &AppConfig) -> Result<bool> {
+    let mut capture_mode = CaptureMode::init(app_config)?;
+
+    println!(
+        "🎙️ Recording {} seconds, say something...",
+        TEST_DURATION.as_secs()
+    );
+    let wav_path = record(&mut capture_mode, app_config).await?;
+    drop(capture_mode);
+
+    let (samples, spec) = encode::read_wav(&wav_path)
+        .with_context(|| format!("Failed to read test recording {:?}", wav_path))?;
+    let seconds = samples.len() as f32 / (spec.sample_rate as f32 * spec.channels as f32);
+    let peak = audio::peak_level(&samples);
+    let rms = audio::rms_level(&samples);
+    println!("💾 Saved test recording to {}", wav_path.display());
+    println!(
+        "📊 {:.1}s at {} Hz, {} ch: peak {:.1} dBFS, RMS {:.1} dBFS",
+        seconds,
+        spec.sample_rate,
+        spec.channels,
+        audio::to_dbfs(peak),
+        audio::to_dbfs(rms)
+    );
+
+    println!("🔈 Playing back...");
+    if let Err(e) = sound::play_file_blocking(app_config.sound_backend, &wav_path) {
+        eprintln!("⚠️ Playback failed: {}", e);
+    }
+
+    if rms < audio::SILENCE_RMS {
+        eprintln!(
+            "❌ The recording is essentially silent. Check that the right input device is selected and not muted."
+        );
+        permissions::report(&permissions::missing(app_config), false);
+        return Ok(false);
+    }
+    println!("✅ Microphone works.");
+    Ok(true)
+}
+
+/// Records `TEST_DURATION` of audio with the given capture mode into a WAV file.
+async fn record(capture_mode: &mut CaptureMode, app_config: &AppConfig) -> Result<PathBuf> {
+    match capture_mode {
+        CaptureMode::Cpal {
+            audio_buffer,
+            consumer,
+            is_recording,
+            ..
+        } => {
+            audio_buffer.clear();
+            consumer.clear();
+            is_recording.store(true, Ordering::Relaxed);
+            let started = Instant::now();
+            while started.elapsed() < TEST_DURATION {
+                sleep(Duration::from_millis(20)).await;
+                audio_buffer.extend(consumer.pop_iter());
+            }
+            is_recording.store(false, Ordering::Relaxed);
+            audio_buffer.extend(consumer.pop_iter());
+
+            match audio_buffer.take()? {
+                RecordedAudio::File(path) => Ok(path),
+                RecordedAudio::Memory { samples, spec } => {
+                    let path = audio::temp_wav_path("-mic-test");
+                    std::fs::write(&path, encode::encode_wav(&samples, spec)?)
+                        .with_context(|| format!("Failed to write {:?}", path))?;
+                    Ok(path)
+                }
+            }
+        }
+        CaptureMode::Cli { tool, .. } => {
+            let (process, path) = start_cli_recording(*tool, app_config)?;
+            sleep(TEST_DURATION).await;
+            let tool = *tool;
+            let recorder_stderr =
+                tokio::task::spawn_blocking(move || stop_cli_recording(tool, process)).await?;
+            if !recorder_stderr.is_empty() {
+                eprintln!("{}: {}", tool.binary(), recorder_stderr);
+            }
+            Ok(path)
+        }
+    }
+}
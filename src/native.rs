@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use arboard::Clipboard;
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::{Modifier, Shortcut, ShortcutKey};
+use crate::injector::TypeStep;
+
+/// Kept for the lifetime of the process: on X11 the clipboard contents are served by
+/// their owner, so dropping it right after a paste could lose the text.
+static CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+/// Puts text on the clipboard through the OS API. With `private` on Linux it is marked
+/// with `x-kde-passwordManagerHint` so Klipper and compatible managers keep it out of their
+/// history.
+pub fn copy(text: &str, private: bool) -> Result<()> {
+    let mut clipboard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new().context("Failed to open the clipboard")?);
+    }
+    if let Some(clipboard) = clipboard.as_mut() {
+        let set = clipboard.set();
+        #[cfg(target_os = "linux")]
+        let set = if private {
+            arboard::SetExtLinux::exclude_from_history(set)
+        } else {
+            set
+        };
+        #[cfg(not(target_os = "linux"))]
+        let _ = private;
+        set.text(text).context("Failed to set the clipboard")?;
+    }
+    Ok(())
+}
+
+/// Sets the clipboard and sends the paste chord, e.g. `ctrl+shift+v`.
+pub fn paste(text: &str, chord: &Shortcut, private: bool) -> Result<()> {
+    let keys = chord_keys(chord)?;
+    copy(text, private)?;
+    // Small delay to ensure the clipboard is ready before we simulate the paste command
+    std::thread::sleep(Duration::from_millis(50));
+
+    press_keys(&keys)
+}
+
+/// Sends a chord such as `Return` or `ctrl+Return`.
+pub fn key_chord(chord: &Shortcut) -> Result<()> {
+    press_keys(&chord_keys(chord)?)
+}
+
+/// Presses the keys in order and releases them in reverse.
+fn press_keys(keys: &[Key]) -> Result<()> {
+    let mut enigo = connect()?;
+    for key in keys {
+        enigo
+            .key(*key, Direction::Press)
+            .context("Failed to send the key chord")?;
+    }
+    for key in keys.iter().rev() {
+        enigo
+            .key(*key, Direction::Release)
+            .context("Failed to send the key chord")?;
+    }
+    Ok(())
+}
+
+/// Types the steps from [`crate::injector::type_steps`], one character every `delay_ms`.
+pub fn type_text(steps: &[TypeStep], delay_ms: u64) -> Result<()> {
+    let mut enigo = connect()?;
+    for step in steps {
+        match step {
+            TypeStep::Text(chunk) => {
+                for c in chunk.chars() {
+                    enigo
+                        .text(&c.to_string())
+                        .context("Failed to type the text")?;
+                    std::thread::sleep(Duration::from_millis(delay_ms));
+                }
+            }
+            TypeStep::Return => enigo
+                .key(Key::Return, Direction::Click)
+                .context("Failed to type the text")?,
+        }
+    }
+    Ok(())
+}
+
+fn connect() -> Result<Enigo> {
+    Enigo::new(&Settings::default()).context("Failed to connect to the display for key input")
+}
+
+/// Maps a chord to enigo keys, modifiers first.
+fn chord_keys(chord: &Shortcut) -> Result<Vec<Key>> {
+    let mut keys: Vec<Key> = chord
+        .modifiers
+        .iter()
+        .map(|modifier| match modifier {
+            Modifier::Ctrl => Key::Control,
+            Modifier::Shift => Key::Shift,
+            Modifier::Alt => Key::Alt,
+            Modifier::Super => Key::Meta,
+        })
+        .collect();
+    keys.push(match chord.key {
+        ShortcutKey::Char(c) => Key::Unicode(c),
+        ShortcutKey::Return => Key::Return,
+        ShortcutKey::Tab => Key::Tab,
+        ShortcutKey::Space => Key::Space,
+        ShortcutKey::Escape => Key::Escape,
+        #[cfg(not(target_os = "macos"))]
+        ShortcutKey::Insert => Key::Insert,
+        #[cfg(target_os = "macos")]
+        ShortcutKey::Insert => anyhow::bail!("macOS has no Insert key, can't send '{}'", chord),
+        ShortcutKey::Delete => Key::Delete,
+        ShortcutKey::BackSpace => Key::Backspace,
+    });
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chord_keys() {
+        let keys = |chord| chord_keys(&Shortcut::parse(chord).unwrap()).unwrap();
+        assert_eq!(
+            keys("ctrl+shift+v"),
+            [Key::Control, Key::Shift, Key::Unicode('v')]
+        );
+        assert_eq!(keys("Super+V"), [Key::Meta, Key::Unicode('v')]);
+        assert_eq!(keys("ctrl+Return"), [Key::Control, Key::Return]);
+        assert_eq!(keys("BackSpace"), [Key::Backspace]);
+    }
+}
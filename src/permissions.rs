@@ -0,0 +1,81 @@
+use std::process::Command;
+use tracing::warn;
+
+use crate::config::AppConfig;
+
+/// macOS privacy permissions voice-ptt needs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub enum Permission {
+    /// Sending the paste keystroke to other apps.
+    Accessibility,
+    /// Recording anything but silence.
+    Microphone,
+}
+
+impl Permission {
+    /// System Settings pane where the permission is granted.
+    fn settings_url(self) -> &'static str {
+        match self {
+            Permission::Accessibility => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility"
+            }
+            Permission::Microphone => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Microphone"
+            }
+        }
+    }
+
+    fn instructions(self) -> &'static str {
+        match self {
+            Permission::Accessibility => "Accessibility permission is missing, so pasting does nothing. Open System Settings → Privacy & Security → Accessibility and add your terminal (or IDE), then restart voice-ptt.",
+            Permission::Microphone => "The microphone only delivers silence, Microphone permission is probably missing. Open System Settings → Privacy & Security → Microphone and enable your terminal (or IDE), then restart voice-ptt.",
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+}
+
+/// Permissions that look missing: Accessibility trust, and a microphone that delivers
+/// more than digital silence.
+#[cfg(target_os = "macos")]
+pub fn missing(config: &AppConfig) -> Vec<Permission> {
+    let mut missing = Vec::new();
+    // Only pasting sends keystrokes to other apps.
+    // SAFETY: AXIsProcessTrusted takes no arguments and only reads the TCC database.
+    if config.output_mode == crate::config::OutputMode::Inject && !unsafe { AXIsProcessTrusted() } {
+        missing.push(Permission::Accessibility);
+    }
+    match crate::capture::probe_microphone(config) {
+        Ok(true) => {}
+        Ok(false) => missing.push(Permission::Microphone),
+        Err(e) => warn!(
+            "⚠️ Couldn't open the microphone to check its permission: {}",
+            e
+        ),
+    }
+    missing
+}
+
+/// Only macOS gates input and recording behind privacy permissions.
+#[cfg(not(target_os = "macos"))]
+pub fn missing(_config: &AppConfig) -> Vec<Permission> {
+    Vec::new()
+}
+
+/// Prints how to grant each missing permission and, with `open_settings`, opens the
+/// matching System Settings pane.
+pub fn report(missing: &[Permission], open_settings: bool) {
+    for permission in missing {
+        warn!("🔒 {}", permission.instructions());
+        if open_settings {
+            if let Err(e) = Command::new("open").arg(permission.settings_url()).status() {
+                warn!("⚠️ Failed to open System Settings: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,560 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::api::{self, LowConfidence, TranscriptionBackend};
+use crate::api_stream::StreamingSession;
+use crate::audio::{self, RecordedAudio};
+use crate::config::{AppConfig, DedupeAction, PttBinding, TranscriptionMode};
+use crate::dedupe::{self, DedupeCache};
+use crate::encode;
+use crate::inject_queue::{InjectionQueue, Injector, NothingToRepeat};
+use crate::injector::{StatusNotifier, StatusUpdate, SystemInjector};
+use crate::logging;
+use crate::sound::{Cue, SoundPlayer};
+use crate::stats::{self, TranscriptionRecord};
+use crate::text;
+
+/// Announces that a recording is being sent off, naming translations and the language
+/// explicitly.
+pub fn print_processing(app_config: &AppConfig) {
+    let language = match &app_config.language {
+        Some(language) => format!(" (language {})", language),
+        None => String::new(),
+    };
+    match app_config.mode {
+        TranscriptionMode::Transcribe => info!("⚙️ Processing{}...", language),
+        TranscriptionMode::Translate => info!("🌐 Translating{}...", language),
+    }
+}
+
+/// The config a finished recording is transcribed with: its binding's, with the language of
+/// the focused window's `language_overrides` entry unless the binding sets one itself.
+pub fn recording_config(binding: &PttBinding) -> AppConfig {
+    let mut config = binding.config.clone();
+    if binding.sets_language || config.language_overrides.is_empty() {
+        return config;
+    }
+    let language = SystemInjector::focused_window(&config)
+        .and_then(|window| window.lookup(&config.language_overrides).cloned());
+    if let Some(language) = language {
+        config.language = (!language.is_empty()).then_some(language);
+    }
+    config
+}
+
+/// Checks a finished recording before upload.
+///
+/// Warns about clipping (transcription proceeds regardless) and returns `false` if the
+/// recording is shorter than `min_recording_ms` or the microphone appears muted, in which
+/// case the recording must not be uploaded.
+async fn check_recording(recording: &RecordedAudio, app_config: &AppConfig) -> bool {
+    let duration = recording.duration();
+    if duration < Duration::from_millis(app_config.min_recording_ms) {
+        info!(
+            "🤏 Recording too short ({:.2}s < min_recording_ms), not sent.",
+            duration.as_secs_f64()
+        );
+        return false;
+    }
+
+    let file_samples;
+    let samples = match recording {
+        RecordedAudio::Memory { samples, .. } => samples.as_slice(),
+        RecordedAudio::File(path) => {
+            let path = path.clone();
+            match tokio::task::spawn_blocking(move || encode::read_wav(&path)).await {
+                Ok(Ok((samples, _))) => {
+                    file_samples = samples;
+                    file_samples.as_slice()
+                }
+                _ => return true,
+            }
+        }
+    };
+
+    if audio::is_muted(samples) {
+        warn!("⚠️ Microphone appears muted, skipping transcription.");
+        SystemInjector::notify(
+            "Voice PTT",
+            "Microphone appears muted. Nothing was recorded, so nothing was sent.",
+        );
+        return false;
+    }
+
+    warn_if_clipped(samples, app_config);
+    true
+}
+
+/// Warns the user when a recording is heavily clipped.
+fn warn_if_clipped(samples: &[i16], app_config: &AppConfig) {
+    if app_config.clipping_warn_percent <= 0.0 {
+        return;
+    }
+    let percent = audio::clipping_ratio(samples) * 100.0;
+    if percent > app_config.clipping_warn_percent {
+        warn!(
+            "⚠️ {:.1}% of the recording is clipped. Consider lowering the microphone input gain.",
+            percent
+        );
+        SystemInjector::notify(
+            "Voice PTT",
+            &format!(
+                "Recording is clipping ({:.1}%). Lower your microphone input gain for better accuracy.",
+                percent
+            ),
+        );
+    }
+}
+
+/// Prints the timing of a transcription and appends it to the stats file if enabled.
+fn record_stats(app_config: &AppConfig, audio: Duration, api: Duration, inject: Duration) {
+    let record =
+        TranscriptionRecord::new(&app_config.backend, &app_config.model, audio, api, inject);
+    info!("{}", record.summary());
+    if app_config.stats_enabled {
+        if let Err(e) = stats::append(&app_config.stats_path(), &record) {
+            warn!("⚠️ Failed to record stats: {}", e);
+        }
+    }
+}
+
+/// A background transcription that a newer recording can supersede until it starts typing.
+pub struct PendingTranscription {
+    handle: tokio::task::JoinHandle<()>,
+    /// Set by whichever comes first: the task starting injection, or `supersede`.
+    claimed: Arc<AtomicBool>,
+}
+
+impl PendingTranscription {
+    /// Aborts the task unless it already finished or started typing.
+    pub fn supersede(self) {
+        if self.handle.is_finished() || self.claimed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.handle.abort();
+        info!("⏭️ Previous transcription superseded by the new recording.");
+    }
+
+    /// Whether the task has ended.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits until the transcript is typed, or the task ends without typing anything.
+    pub async fn finish(self) {
+        let _ = self.handle.await;
+    }
+
+    /// Waits up to `grace` like [`PendingTranscription::finish`], then aborts the task.
+    /// Returns whether it finished in time.
+    pub async fn finish_within(mut self, grace: Duration) -> bool {
+        if tokio::time::timeout(grace, &mut self.handle).await.is_ok() {
+            return true;
+        }
+        self.handle.abort();
+        // Its temp file is deleted as the task is dropped
+        let _ = self.handle.await;
+        false
+    }
+}
+
+/// Deletes a recorder's temp file when the transcription task ends, including on abort.
+struct TempRecording(Option<PathBuf>);
+
+impl Drop for TempRecording {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Transcribes a recording, preferring the streamed transcript when there is one, and runs
+/// the optional post-processing step. A failed upload hands the recording back, see
+/// [`api::transcribe_returning_recording`].
+async fn transcribe_text(
+    backend: &dyn TranscriptionBackend,
+    recording: RecordedAudio,
+    stream: Option<StreamingSession>,
+    app_config: &AppConfig,
+) -> Result<String, (anyhow::Error, Option<RecordedAudio>)> {
+    let streamed = match stream {
+        Some(stream) => match stream.finish().await {
+            Ok(text) => Some(text),
+            Err(e) => {
+                warn!(
+                    "⚠️ Streaming failed, uploading the recording instead: {}",
+                    e
+                );
+                None
+            }
+        },
+        None => None,
+    };
+    let text = match streamed {
+        Some(text) => text,
+        None => {
+            api::transcribe_returning_recording(backend, recording, app_config.mode, app_config)
+                .await?
+        }
+    };
+    info!("📝 Transcribed: {}", logging::transcript(&text));
+    Ok(api::postprocess_transcript(backend, text, app_config).await)
+}
+
+/// Hash used to spot a repeated recording, or `None` when deduplication is off.
+async fn recording_hash(recording: &RecordedAudio, app_config: &AppConfig) -> Option<u64> {
+    if app_config.dedupe_window_secs == 0 {
+        return None;
+    }
+    match dedupe::hash_recording(recording).await {
+        Ok(hash) => Some(hash),
+        Err(e) => {
+            warn!("⚠️ Failed to hash recording: {}", e);
+            None
+        }
+    }
+}
+
+/// A recording whose transcription failed, with the config it was made with.
+pub struct FailedRecording {
+    pub recording: RecordedAudio,
+    pub config: AppConfig,
+}
+
+/// Long-lived parts every transcription task uses.
+#[derive(Clone)]
+pub struct Services {
+    backend: Arc<dyn TranscriptionBackend>,
+    dedupe: Arc<Mutex<DedupeCache>>,
+    injections: InjectionQueue,
+    /// Newest last, at most `keep_failed_recordings` of them
+    failed: Arc<Mutex<VecDeque<FailedRecording>>>,
+    pub sounds: Arc<SoundPlayer>,
+    pub status: StatusNotifier,
+}
+
+impl Services {
+    /// Transcribes with `backend` and hands the transcripts to `injector`, one at a time.
+    pub fn new(
+        backend: Arc<dyn TranscriptionBackend>,
+        injector: Arc<dyn Injector>,
+        config: &AppConfig,
+    ) -> Self {
+        Self {
+            backend,
+            dedupe: Arc::new(Mutex::new(DedupeCache::default())),
+            injections: InjectionQueue::spawn(injector),
+            failed: Arc::new(Mutex::new(VecDeque::new())),
+            sounds: Arc::new(SoundPlayer::new(config)),
+            status: StatusNotifier::new(config),
+        }
+    }
+
+    /// The transcript typed last, or being typed; see [`InjectionQueue::last`].
+    pub fn last_transcript(&self) -> Option<String> {
+        self.injections.last()
+    }
+
+    /// Takes the most recent recording whose transcription failed, to send it again; it is
+    /// kept again if that fails too.
+    pub fn take_failed(&self) -> Option<FailedRecording> {
+        self.failed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_back()
+    }
+}
+
+/// Keeps a recording whose transcription failed for a retry, in memory since a recorder's
+/// temp file is deleted when its task ends. Returns whether it was kept.
+async fn keep_failed(
+    failed: &Mutex<VecDeque<FailedRecording>>,
+    recording: RecordedAudio,
+    config: &AppConfig,
+) -> bool {
+    let (recording, config) = match recording {
+        RecordedAudio::File(path) => {
+            match tokio::task::spawn_blocking(move || encode::read_wav(&path)).await {
+                Ok(Ok((samples, spec))) => {
+                    (RecordedAudio::Memory { samples, spec }, config.clone())
+                }
+                Ok(Err(e)) => {
+                    warn!("⚠️ Failed to keep the recording for a retry: {}", e);
+                    return false;
+                }
+                Err(e) => {
+                    warn!("⚠️ Failed to keep the recording for a retry: {}", e);
+                    return false;
+                }
+            }
+        }
+        // Samples come back high-passed from the upload, filtering them again would dull them
+        memory => (
+            memory,
+            AppConfig {
+                highpass_hz: 0.0,
+                ..config.clone()
+            },
+        ),
+    };
+    let keep = config.keep_failed_recordings;
+    let mut failed = failed.lock().unwrap_or_else(|e| e.into_inner());
+    failed.push_back(FailedRecording { recording, config });
+    while failed.len() > keep {
+        failed.pop_front();
+    }
+    true
+}
+
+/// How to send a kept recording again, for the failure notification.
+fn retry_hint(config: &AppConfig) -> String {
+    match &config.retry_key {
+        Some(key) => format!(
+            "press {} or run `voice-ptt ctl retry` to send it again",
+            key
+        ),
+        None => "run `voice-ptt ctl retry` to send it again".to_string(),
+    }
+}
+
+/// Reports the end of a transcription task to the status notification, including on abort.
+struct FinishedStatus(StatusNotifier);
+
+impl Drop for FinishedStatus {
+    fn drop(&mut self) {
+        self.0.update(StatusUpdate::Finished);
+    }
+}
+
+/// Injects the last transcript again through the injection queue, e.g. after the paste went
+/// to the wrong window; the paste shortcut is picked for the window focused now.
+pub fn spawn_repeat(services: &Services, config: AppConfig) -> tokio::task::JoinHandle<()> {
+    let repeated = services.injections.repeat(config);
+    let sounds = services.sounds.clone();
+    tokio::spawn(async move {
+        match repeated.await {
+            Ok(_) => {
+                info!("🔁 Typed the last transcript again");
+                sounds.play(Cue::Done);
+            }
+            Err(e) if e.is::<NothingToRepeat>() => {
+                info!("🔁 Nothing to repeat yet");
+                SystemInjector::notify("Voice PTT", "Nothing to repeat");
+            }
+            Err(e) => {
+                sounds.play(Cue::Error);
+                error!("❌ Injection error: {}", e);
+            }
+        }
+    })
+}
+
+/// Transcribes a finished recording and injects the result in a background task.
+pub fn spawn_transcription(
+    services: Services,
+    app_config: AppConfig,
+    recording: RecordedAudio,
+    ready_hint: String,
+    previous: Option<PendingTranscription>,
+    stream: Option<StreamingSession>,
+) -> PendingTranscription {
+    spawn_transcription_when(
+        services,
+        app_config,
+        async move { Some(recording) },
+        ready_hint,
+        previous,
+        stream,
+    )
+}
+
+/// Like [`spawn_transcription`] for a recording that is still being finished, e.g. while a
+/// command-line recorder writes its WAV header; nothing is sent if it comes to `None`.
+pub fn spawn_transcription_when(
+    services: Services,
+    app_config: AppConfig,
+    recording: impl Future<Output = Option<RecordedAudio>> + Send + 'static,
+    ready_hint: String,
+    previous: Option<PendingTranscription>,
+    stream: Option<StreamingSession>,
+) -> PendingTranscription {
+    let claimed = Arc::new(AtomicBool::new(false));
+    let task_claimed = claimed.clone();
+    let Services {
+        backend,
+        dedupe,
+        injections,
+        failed,
+        sounds,
+        status,
+    } = services;
+    status.update(StatusUpdate::Processing);
+    let finished = FinishedStatus(status);
+    let handle = tokio::spawn(async move {
+        let finished = finished;
+        let Some(recording) = recording.await else {
+            info!("\n✅ Ready! {}.", ready_hint);
+            return;
+        };
+        if !check_recording(&recording, &app_config).await {
+            if let RecordedAudio::File(path) = recording {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+            info!("\n✅ Ready! {}.", ready_hint);
+            return;
+        }
+
+        let _temp_file = TempRecording(match &recording {
+            RecordedAudio::File(path) => Some(path.clone()),
+            RecordedAudio::Memory { .. } => None,
+        });
+        let audio_length = recording.duration();
+        let hash = recording_hash(&recording, &app_config).await;
+        let window = Duration::from_secs(app_config.dedupe_window_secs);
+        let cached = hash.and_then(|hash| {
+            dedupe
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .lookup(hash, window)
+        });
+        let api_started = Instant::now();
+        // Handed back by a failed upload, a recorder's temp file is still there until the end
+        let mut retained = None;
+        let result = match cached {
+            Some((text, age)) => {
+                info!(
+                    "♻️ Same audio as {:.1}s ago, no request sent.",
+                    age.as_secs_f64()
+                );
+                if app_config.dedupe_action == DedupeAction::Skip {
+                    info!("♻️ Nothing typed (dedupe_action = \"skip\").");
+                    info!("\n✅ Ready! {}.", ready_hint);
+                    return;
+                }
+                Ok(text)
+            }
+            None => {
+                let result = transcribe_text(backend.as_ref(), recording, stream, &app_config)
+                    .await
+                    .map_err(|(error, recording)| {
+                        retained = recording;
+                        error
+                    });
+                if let (Ok(text), Some(hash)) = (&result, hash) {
+                    dedupe
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .store(hash, text.clone());
+                }
+                result
+            }
+        };
+
+        match result {
+            Ok(text) => {
+                let api_time = api_started.elapsed();
+                if let Some(reason) =
+                    text::rejection_reason(&text, &app_config.hallucination_filters)
+                {
+                    info!("🔇 Not typing {}: {}", logging::transcript(&text), reason);
+                    info!("\n✅ Ready! {}.", ready_hint);
+                    return;
+                }
+                // Fillers go first, so "comma, um, new line" still reads as two commands;
+                // replacements come last and see the text as it will be typed
+                let mut edited = text.clone();
+                if app_config.strip_fillers {
+                    let fillers = match app_config.filler_words.is_empty() {
+                        true => text::default_fillers(app_config.language.as_deref()),
+                        false => app_config.filler_words.clone(),
+                    };
+                    edited = text::strip_fillers(&edited, &fillers);
+                }
+                if app_config.spoken_commands {
+                    edited = text::apply_spoken_commands(
+                        &edited,
+                        app_config.language.as_deref(),
+                        &app_config.spoken_command_phrases,
+                    );
+                }
+                if app_config.auto_capitalize {
+                    edited = text::capitalize_sentences(&edited);
+                }
+                edited =
+                    text::ensure_final_punctuation(&edited, &app_config.ensure_final_punctuation);
+                edited = text::apply_replacements(&edited, &app_config.replacements);
+                if edited.trim().is_empty() {
+                    info!(
+                        "🔇 Not typing {}: nothing left after editing",
+                        logging::transcript(&text)
+                    );
+                    info!("\n✅ Ready! {}.", ready_hint);
+                    return;
+                }
+                if edited != text {
+                    info!("✏️ Edited: {}", logging::transcript(&edited));
+                }
+                let text = edited;
+                // Without cancel_previous, transcripts are typed in recording order
+                if let Some(previous) = previous {
+                    let _ = previous.handle.await;
+                }
+                if task_claimed.swap(true, Ordering::SeqCst) {
+                    info!("⏭️ Superseded, dropping: {}", logging::transcript(&text));
+                    return;
+                }
+                match injections.inject(text, app_config.clone()).await {
+                    Ok(inject_time) => {
+                        sounds.play(Cue::Done);
+                        record_stats(&app_config, audio_length, api_time, inject_time)
+                    }
+                    Err(e) => {
+                        sounds.play(Cue::Error);
+                        finished.0.update(StatusUpdate::Failed);
+                        error!("❌ Injection error: {}", e);
+                    }
+                }
+            }
+            Err(e) => match e.downcast_ref::<LowConfidence>() {
+                Some(low) => {
+                    info!("🤷 {}: {}", low, logging::transcript(&low.text));
+                    let mut message = "Low confidence transcription discarded".to_string();
+                    if app_config.low_confidence_to_clipboard {
+                        match SystemInjector::copy_to_clipboard(&low.text, &app_config) {
+                            Ok(()) => message.push_str(", copied to the clipboard"),
+                            Err(e) => error!("❌ Clipboard error: {}", e),
+                        }
+                    }
+                    SystemInjector::notify("Voice PTT", &message);
+                }
+                None => {
+                    sounds.play(Cue::Error);
+                    finished.0.update(StatusUpdate::Failed);
+                    error!("❌ API Error: {}", e);
+                    let mut message = e.to_string();
+                    // Not handed back when the offline queue took it
+                    let keep = app_config.keep_failed_recordings > 0;
+                    if let Some(recording) = retained.filter(|_| keep) {
+                        if keep_failed(&failed, recording, &app_config).await {
+                            let hint = retry_hint(&app_config);
+                            info!("🔁 Recording kept, {}", hint);
+                            message = format!("{}\nRecording kept, {}", message, hint);
+                        }
+                    }
+                    SystemInjector::notify("Voice PTT Error", &message);
+                }
+            },
+        }
+
+        info!("\n✅ Ready! {}.", ready_hint);
+    });
+    PendingTranscription { handle, claimed }
+}
@@ -0,0 +1,685 @@
+use anyhow::{Context, Result};
+use device_query::{DeviceQuery, DeviceState};
+use std::time::{Duration, Instant, SystemTime};
+#[cfg(target_os = "linux")]
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::info;
+
+use crate::config::{self, Activation, InputBackend, PttBinding, PttKey};
+#[cfg(target_os = "linux")]
+use crate::evdev::{self, EvdevKeys, KeyEvent};
+
+/// What to do with the PTT keys, e.g. "Hold [RControl] to speak".
+pub fn hint(activation: Activation, label: &str) -> String {
+    match activation {
+        Activation::Hold => format!("Hold [{}] to speak", label),
+        Activation::Toggle => format!("Tap [{}] to start and stop recording", label),
+    }
+}
+
+/// How often the keys are polled, and how often the main loop runs while recording.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long the main loop waits for a key event while nothing is recording.
+const IDLE_WAIT: Duration = Duration::from_secs(1);
+
+/// Reads the held keys from device_query and, on Linux, from /dev/input for the keys only
+/// readable there. With key events, /dev/input is the only source.
+pub struct KeyReader {
+    /// `None` when the keys come from /dev/input alone.
+    device_state: Option<DeviceState>,
+    #[cfg(target_os = "linux")]
+    evdev: Option<EvdevKeys>,
+    #[cfg(target_os = "linux")]
+    events: Option<mpsc::UnboundedReceiver<KeyEvent>>,
+    /// When the latest key went down, for the start latency log.
+    last_press: Option<SystemTime>,
+    previous: Vec<PttKey>,
+}
+
+impl KeyReader {
+    /// A reader for `keys`; fails when one of them needs /dev/input and it can't be read.
+    pub fn new(keys: &[PttKey]) -> Result<Self> {
+        let device_state = DeviceState::checked_new().context(
+            "Can't read the keyboard (no X display, or no accessibility permission on macOS)",
+        )?;
+        let mut reader = Self {
+            device_state: Some(device_state),
+            #[cfg(target_os = "linux")]
+            evdev: None,
+            #[cfg(target_os = "linux")]
+            events: None,
+            last_press: None,
+            previous: Vec::new(),
+        };
+        reader.watch(keys)?;
+        Ok(reader)
+    }
+
+    /// A reader for `keys` that waits for key events with `InputBackend::Events`, and polls
+    /// when they can't be read.
+    pub fn for_backend(backend: InputBackend, keys: &[PttKey]) -> Result<Self> {
+        #[cfg(target_os = "linux")]
+        if backend == InputBackend::Events {
+            match EvdevKeys::open().and_then(|evdev| Ok((evdev, evdev::listen()?))) {
+                Ok((evdev, events)) => {
+                    return Ok(Self {
+                        device_state: None,
+                        evdev: Some(evdev),
+                        events: Some(events),
+                        last_press: None,
+                        previous: Vec::new(),
+                    })
+                }
+                Err(_) => info!(
+                    "ℹ️ Key events need read access to /dev/input (the input group), polling the keyboard instead. Set input_backend = \"poll\" to skip this check."
+                ),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = backend;
+        Self::new(keys)
+    }
+
+    /// Makes sure `keys` can be read, opening /dev/input when one of them needs it.
+    pub fn watch(&mut self, keys: &[PttKey]) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        if self.evdev.is_none() && keys.iter().any(|key| matches!(key, PttKey::Code(_))) {
+            self.evdev = Some(EvdevKeys::open()?);
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = keys;
+        Ok(())
+    }
+
+    /// Also reads /dev/input when it is readable, so every key can be seen; returns whether
+    /// it is.
+    pub fn watch_all(&mut self) -> bool {
+        #[cfg(target_os = "linux")]
+        if self.evdev.is_none() {
+            self.evdev = EvdevKeys::open().ok();
+        }
+        #[cfg(target_os = "linux")]
+        return self.evdev.is_some();
+        #[cfg(not(target_os = "linux"))]
+        false
+    }
+
+    /// Whether key events wake [`KeyReader::wait`], rather than a poll timer.
+    pub fn reads_events(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        return self.events.is_some();
+        #[cfg(not(target_os = "linux"))]
+        false
+    }
+
+    /// When the latest key went down: the kernel's time with key events, otherwise the poll
+    /// that first saw it.
+    pub fn last_press(&self) -> Option<SystemTime> {
+        self.last_press
+    }
+
+    /// The keys held right now.
+    pub fn held(&mut self) -> Vec<PttKey> {
+        let mut held: Vec<PttKey> = match &self.device_state {
+            Some(device_state) => device_state
+                .get_keys()
+                .into_iter()
+                .map(PttKey::Key)
+                .collect(),
+            None => Vec::new(),
+        };
+        #[cfg(target_os = "linux")]
+        if let Some(evdev) = &mut self.evdev {
+            for key in evdev.pressed().into_iter().map(PttKey::from_code) {
+                if !held.contains(&key) {
+                    held.push(key);
+                }
+            }
+        }
+        if !self.reads_events() && held.iter().any(|key| !self.previous.contains(key)) {
+            self.last_press = Some(SystemTime::now());
+        }
+        self.previous.clone_from(&held);
+        held
+    }
+
+    /// Waits until the held keys may have changed: for the next key event, at most
+    /// `POLL_INTERVAL` while `busy` (something is recording or timing a press) and a second
+    /// otherwise, or for the next poll.
+    pub async fn wait(&mut self, busy: bool) {
+        #[cfg(target_os = "linux")]
+        if let Some(events) = &mut self.events {
+            let timeout = if busy { POLL_INTERVAL } else { IDLE_WAIT };
+            match tokio::time::timeout(timeout, events.recv()).await {
+                Ok(Some(event)) => {
+                    // Take in everything that arrived together
+                    let mut event = Some(event);
+                    while let Some(KeyEvent { pressed, time, .. }) = event {
+                        if pressed {
+                            self.last_press = Some(time);
+                        }
+                        event = events.try_recv().ok();
+                    }
+                }
+                // The reader thread is gone; /dev/input is still polled for the key state
+                Ok(None) => self.events = None,
+                Err(_) => {}
+            }
+            return;
+        }
+        let _ = busy;
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Tracks the PTT keys between polls and decides which binding is recording.
+///
+/// Hold mode records while the keys are down, once they have been held for `hold_threshold`;
+/// shorter presses are ignored. A double tap (the second press within `double_tap` of the
+/// first) locks the recording on until the next tap. Toggle mode starts on the release of a
+/// tap and stops on the release of the next one.
+pub struct PttState {
+    activation: Activation,
+    /// Zero disables the double-tap lock.
+    double_tap: Duration,
+    hold_threshold: Duration,
+    /// The keys are down but haven't been held for `hold_threshold` yet.
+    arming: bool,
+    /// Binding whose keys were all held at the last poll.
+    held: Option<usize>,
+    /// When the keys of `held` went down.
+    pressed_at: Instant,
+    /// Hold mode: a tap shorter than `double_tap` that keeps recording in case a second
+    /// press follows.
+    tapped: Option<usize>,
+    /// The binding of a toggled or locked recording, which runs without holding the keys.
+    latched: Option<usize>,
+    latched_at: Instant,
+    /// The lock's own second press is still down; only the tap after it unlocks.
+    lock_pending_release: bool,
+    /// The recording was aborted while its keys were held; they do nothing until released.
+    aborted: bool,
+    /// "Still recording" reminders shown for the latched recording.
+    reminders: u32,
+}
+
+impl PttState {
+    pub fn new(activation: Activation, double_tap: Duration, hold_threshold: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            activation,
+            double_tap,
+            hold_threshold,
+            arming: false,
+            held: None,
+            pressed_at: now,
+            tapped: None,
+            latched: None,
+            latched_at: now,
+            lock_pending_release: false,
+            aborted: false,
+            reminders: 0,
+        }
+    }
+
+    /// Updates the state with the keys held right now and returns the binding that should be
+    /// recording.
+    pub fn update(&mut self, bindings: &[PttBinding], keys: &[PttKey]) -> Option<usize> {
+        self.update_at(bindings, keys, Instant::now())
+    }
+
+    fn update_at(
+        &mut self,
+        bindings: &[PttBinding],
+        keys: &[PttKey],
+        now: Instant,
+    ) -> Option<usize> {
+        let previous = self.held;
+        // A combination only counts while all of its keys are held; a recording ends when
+        // any key of the binding that started it is released
+        self.held = match self.held {
+            Some(i) if bindings[i].is_held(keys) => Some(i),
+            Some(_) => None,
+            None => config::held_binding(bindings, keys),
+        };
+        if self.aborted {
+            self.aborted = self.held.is_some();
+            self.arming = false;
+            return None;
+        }
+        match (previous, self.held) {
+            (None, Some(_)) => self.on_press(now),
+            (Some(released), None) => self.on_release(released, now),
+            _ => {}
+        }
+        // A tap that wasn't followed by a second press in time ends like a short hold
+        if self.tapped.is_some() && now.duration_since(self.pressed_at) > self.double_tap {
+            self.tapped = None;
+        }
+        let recording = match self.activation {
+            Activation::Hold => self.latched.or(self.tapped),
+            Activation::Toggle => return self.latched,
+        };
+        let threshold_passed = now.duration_since(self.pressed_at) >= self.hold_threshold;
+        self.arming = recording.is_none() && self.held.is_some() && !threshold_passed;
+        recording.or(self.held.filter(|_| threshold_passed))
+    }
+
+    fn on_press(&mut self, now: Instant) {
+        if let Some(tapped) = self.tapped.take() {
+            if now.duration_since(self.pressed_at) <= self.double_tap {
+                self.latch(tapped, now);
+                self.lock_pending_release = true;
+                return;
+            }
+        }
+        self.pressed_at = now;
+    }
+
+    fn on_release(&mut self, released: usize, now: Instant) {
+        match (self.activation, self.latched) {
+            (Activation::Hold, Some(_)) if self.lock_pending_release => {
+                self.lock_pending_release = false;
+            }
+            (_, Some(_)) => self.latched = None,
+            (Activation::Toggle, None) => self.latch(released, now),
+            (Activation::Hold, None) => {
+                let held_for = now.duration_since(self.pressed_at);
+                // Presses below the hold threshold never recorded, so they can't be a tap
+                if held_for >= self.hold_threshold && held_for < self.double_tap {
+                    self.tapped = Some(released);
+                }
+            }
+        }
+    }
+
+    fn latch(&mut self, binding: usize, now: Instant) {
+        self.latched = Some(binding);
+        self.latched_at = now;
+        self.reminders = 0;
+    }
+
+    /// Whether the keys are down but not yet held long enough to record, i.e. the audio
+    /// captured now is pre-roll that is kept if the press turns into a recording.
+    pub fn is_arming(&self) -> bool {
+        self.arming
+    }
+
+    /// Whether no key is held and nothing is recording or waiting for a second tap.
+    pub fn is_idle(&self) -> bool {
+        self.held.is_none() && self.tapped.is_none() && self.latched.is_none()
+    }
+
+    /// Whether a double tap has locked a hold-mode recording on.
+    pub fn is_locked(&self) -> bool {
+        self.activation == Activation::Hold && self.latched.is_some()
+    }
+
+    /// Latches a recording of `binding` without a key press, as if it had been toggled on.
+    pub fn start(&mut self, binding: usize) {
+        self.tapped = None;
+        self.lock_pending_release = false;
+        self.latch(binding, Instant::now());
+    }
+
+    /// Whether a toggled, locked or started recording runs without its keys held.
+    pub fn is_latched(&self) -> bool {
+        self.latched.is_some()
+    }
+
+    /// Stops a latched recording without a tap, e.g. when the recorder failed to start.
+    pub fn cancel(&mut self) {
+        self.latched = None;
+        self.tapped = None;
+    }
+
+    /// Ends any recording for good: unlike [`PttState::cancel`], keys still held don't start
+    /// another one, and their release isn't a tap.
+    pub fn abort(&mut self) {
+        self.cancel();
+        self.arming = false;
+        self.lock_pending_release = false;
+        self.aborted = self.held.is_some();
+    }
+
+    /// Keys already down at the next update do nothing until released, e.g. a PTT key held
+    /// while the keys are listened to again after a pause.
+    pub fn ignore_held_keys(&mut self) {
+        self.aborted = true;
+    }
+
+    /// Whether a toggled or locked recording has run for another `interval` since the last
+    /// reminder (never with a zero interval).
+    pub fn reminder_due(&mut self, interval: Duration) -> bool {
+        if self.latched.is_none() || interval.is_zero() {
+            return false;
+        }
+        let intervals = (self.latched_at.elapsed().as_secs_f64() / interval.as_secs_f64()) as u32;
+        if intervals > self.reminders {
+            self.reminders = intervals;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// How long the toggled or locked recording has been running.
+    pub fn latched_for(&self) -> Duration {
+        self.latched_at.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AppConfig, Binding};
+    use device_query::Keycode;
+
+    const DOUBLE_TAP: Duration = Duration::from_millis(350);
+
+    fn bindings() -> Vec<PttBinding> {
+        let mut config = AppConfig {
+            ptt_key: "LControl+Space".to_string(),
+            ..Default::default()
+        };
+        config
+            .bindings
+            .insert("RShift".to_string(), Binding::default());
+        config.ptt_bindings()
+    }
+
+    fn held(keys: &[Keycode]) -> Vec<PttKey> {
+        keys.iter().copied().map(PttKey::Key).collect()
+    }
+
+    /// Feeds `(milliseconds, held keys)` events and returns what every poll recorded with.
+    fn run(state: &mut PttState, events: &[(u64, &[Keycode])]) -> Vec<Option<usize>> {
+        let bindings = bindings();
+        let start = Instant::now();
+        events
+            .iter()
+            .map(|(ms, keys)| {
+                state.update_at(&bindings, &held(keys), start + Duration::from_millis(*ms))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hold_records_while_held() {
+        let chord: &[Keycode] = &[Keycode::LControl, Keycode::Space];
+        let mut state = PttState::new(Activation::Hold, Duration::ZERO, Duration::ZERO);
+        let polls = run(
+            &mut state,
+            &[
+                (0, &[]),
+                (20, &[Keycode::LControl]),
+                (40, chord),
+                (60, chord),
+                // Releasing one key of the combination stops the recording
+                (80, &[Keycode::LControl]),
+                (100, &[Keycode::RShift]),
+                (120, &[]),
+            ],
+        );
+        assert_eq!(polls, [None, None, Some(0), Some(0), None, Some(1), None]);
+    }
+
+    #[test]
+    fn test_double_tap_locks_until_the_next_tap() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        let polls = run(
+            &mut state,
+            &[
+                (0, key),
+                // The first short tap keeps recording while a second press may follow
+                (100, &[]),
+                (200, key),
+                (300, &[]),
+                (5000, &[]),
+                (6000, key),
+                (6050, key),
+                (6100, &[]),
+                (6120, &[]),
+            ],
+        );
+        assert_eq!(
+            polls,
+            [
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                None,
+                None
+            ]
+        );
+        assert!(!state.is_locked());
+    }
+
+    #[test]
+    fn test_short_tap_without_second_press_stops() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        let polls = run(
+            &mut state,
+            &[(0, key), (100, &[]), (340, &[]), (360, &[]), (500, key)],
+        );
+        assert_eq!(polls, [Some(1), Some(1), Some(1), None, Some(1)]);
+        assert!(!state.is_locked());
+    }
+
+    #[test]
+    fn test_is_idle() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        assert!(state.is_idle());
+        run(&mut state, &[(0, key)]);
+        assert!(!state.is_idle());
+        // A tap waits for a second press before the state is idle again
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        run(&mut state, &[(0, key), (100, &[])]);
+        assert!(!state.is_idle());
+        run(&mut state, &[(500, &[])]);
+        assert!(state.is_idle());
+    }
+
+    #[test]
+    fn test_start_latches_until_a_tap() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        state.start(1);
+        assert!(state.is_latched() && !state.is_idle());
+        let polls = run(&mut state, &[(0, &[]), (100, key), (150, &[])]);
+        assert_eq!(polls, [Some(1), Some(1), None]);
+        assert!(!state.is_latched());
+
+        let mut state = PttState::new(Activation::Toggle, DOUBLE_TAP, Duration::ZERO);
+        state.start(0);
+        assert_eq!(run(&mut state, &[(0, &[])]), [Some(0)]);
+        state.cancel();
+        assert_eq!(run(&mut state, &[(20, &[])]), [None]);
+    }
+
+    #[test]
+    fn test_abort_ignores_the_keys_until_released() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        assert_eq!(run(&mut state, &[(0, key)]), [Some(1)]);
+        state.abort();
+        // The quick release isn't a tap that keeps recording, the next press records again
+        let polls = run(&mut state, &[(0, key), (50, &[]), (100, key)]);
+        assert_eq!(polls, [None, None, Some(1)]);
+
+        // Aborted while holding the key that would stop a toggled recording
+        let mut state = PttState::new(Activation::Toggle, DOUBLE_TAP, Duration::ZERO);
+        let polls = run(&mut state, &[(0, key), (50, &[]), (300, key)]);
+        assert_eq!(polls, [None, Some(1), Some(1)]);
+        state.abort();
+        assert_eq!(run(&mut state, &[(0, key), (50, &[])]), [None, None]);
+        assert!(state.is_idle());
+    }
+
+    #[test]
+    fn test_ignore_held_keys() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        state.ignore_held_keys();
+        let polls = run(&mut state, &[(0, key), (500, key), (600, &[]), (700, key)]);
+        assert_eq!(polls, [None, None, None, Some(1)]);
+
+        // Nothing held, so the next press records
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        state.ignore_held_keys();
+        assert_eq!(run(&mut state, &[(0, &[]), (50, key)]), [None, Some(1)]);
+    }
+
+    #[test]
+    fn test_long_hold_stops_on_release() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, Duration::ZERO);
+        let polls = run(
+            &mut state,
+            &[(0, key), (2000, &[]), (2100, key), (2200, &[])],
+        );
+        assert_eq!(polls, [Some(1), None, Some(1), Some(1)]);
+        assert!(!state.is_locked());
+
+        // Without double_tap_ms a tap stops right away
+        let mut state = PttState::new(Activation::Hold, Duration::ZERO, Duration::ZERO);
+        let polls = run(&mut state, &[(0, key), (100, &[]), (200, key)]);
+        assert_eq!(polls, [Some(1), None, Some(1)]);
+    }
+
+    #[test]
+    fn test_hold_threshold_ignores_short_presses() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let threshold = Duration::from_millis(200);
+        let mut state = PttState::new(Activation::Hold, Duration::ZERO, threshold);
+        let bindings = bindings();
+        let start = Instant::now();
+        let mut poll = |ms: u64, keys: &[Keycode]| {
+            let recording =
+                state.update_at(&bindings, &held(keys), start + Duration::from_millis(ms));
+            (recording, state.is_arming())
+        };
+
+        assert_eq!(poll(0, key), (None, true));
+        assert_eq!(poll(150, key), (None, true));
+        assert_eq!(poll(180, &[]), (None, false));
+        assert_eq!(poll(300, key), (None, true));
+        assert_eq!(poll(500, key), (Some(1), false));
+        assert_eq!(poll(3000, key), (Some(1), false));
+        assert_eq!(poll(3020, &[]), (None, false));
+    }
+
+    #[test]
+    fn test_hold_threshold_with_double_tap() {
+        let key: &[Keycode] = &[Keycode::RShift];
+        let threshold = Duration::from_millis(100);
+        let mut state = PttState::new(Activation::Hold, DOUBLE_TAP, threshold);
+        let polls = run(
+            &mut state,
+            &[
+                // Too short to count as the first tap
+                (0, key),
+                (50, &[]),
+                (150, key),
+                // Long enough: recording starts and the release waits for a second press
+                (260, key),
+                (300, &[]),
+                (400, key),
+                (450, &[]),
+                (2000, &[]),
+            ],
+        );
+        assert_eq!(
+            polls,
+            [
+                None,
+                None,
+                None,
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1)
+            ]
+        );
+        assert!(state.is_locked());
+        assert!(!state.is_arming());
+    }
+
+    #[test]
+    fn test_toggle_starts_and_stops_on_taps() {
+        let chord: &[Keycode] = &[Keycode::LControl, Keycode::Space];
+        let mut state = PttState::new(Activation::Toggle, DOUBLE_TAP, Duration::ZERO);
+        let polls = run(
+            &mut state,
+            &[
+                (0, &[Keycode::RShift]),
+                // The release of the first tap starts recording, and it keeps going
+                (100, &[]),
+                (200, &[]),
+                (300, &[Keycode::A]),
+                // The second tap stops it, whichever binding it is
+                (400, chord),
+                (500, &[Keycode::LControl]),
+                (600, &[]),
+                (700, chord),
+                (800, &[]),
+            ],
+        );
+        assert_eq!(
+            polls,
+            [
+                None,
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                None,
+                None,
+                None,
+                Some(0)
+            ]
+        );
+        state.cancel();
+        assert_eq!(run(&mut state, &[(900, &[])]), [None]);
+    }
+
+    #[test]
+    fn test_reminder_due() {
+        let mut state = PttState::new(Activation::Toggle, Duration::ZERO, Duration::ZERO);
+        let interval = Duration::from_secs(60);
+        assert!(!state.reminder_due(interval));
+
+        run(&mut state, &[(0, &[Keycode::RShift]), (100, &[])]);
+        assert!(!state.reminder_due(interval));
+        assert!(!state.reminder_due(Duration::ZERO));
+
+        state.latched_at = Instant::now() - Duration::from_secs(150);
+        assert!(state.reminder_due(interval));
+        assert!(!state.reminder_due(interval));
+        state.latched_at = Instant::now() - Duration::from_secs(185);
+        assert!(state.reminder_due(interval));
+        assert!(!state.reminder_due(interval));
+    }
+
+    #[test]
+    fn test_hint() {
+        assert_eq!(
+            hint(Activation::Hold, "RControl"),
+            "Hold [RControl] to speak"
+        );
+        assert_eq!(
+            hint(Activation::Toggle, "RControl] or [RShift"),
+            "Tap [RControl] or [RShift] to start and stop recording"
+        );
+    }
+}
@@ -0,0 +1,543 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, error, info, warn};
+
+use crate::api::TranscriptionBackend;
+use crate::api_error::ApiError;
+use crate::audio::RecordedAudio;
+use crate::batch;
+use crate::config::{AppConfig, TranscriptionMode};
+use crate::encode;
+use crate::injector::SystemInjector;
+use crate::logging;
+
+/// Settings a queued recording was made with, stored next to its WAV file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueuedMeta {
+    /// Unix time in seconds.
+    pub timestamp: u64,
+    /// Only flushed while this is still the configured backend, `model` may not exist on others
+    pub backend: String,
+    pub model: String,
+    pub language: Option<String>,
+    /// Missing in items queued by older versions, which only transcribed
+    #[serde(default)]
+    pub mode: TranscriptionMode,
+}
+
+impl QueuedMeta {
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            backend: config.backend.clone(),
+            model: config.model.clone(),
+            language: config.language.clone(),
+            mode: config.mode,
+        }
+    }
+}
+
+/// A recording waiting in the queue.
+#[derive(Debug)]
+pub struct QueuedItem {
+    pub wav: PathBuf,
+    pub meta: QueuedMeta,
+}
+
+impl QueuedItem {
+    fn meta_path(&self) -> PathBuf {
+        self.wav.with_extension("json")
+    }
+
+    /// Moves the recording and its metadata to the `failed` directory next to them, out of
+    /// the queue, and returns where the recording went.
+    pub fn set_aside(&self) -> Result<PathBuf> {
+        let dir = self
+            .wav
+            .parent()
+            .context("Queued recording has no directory")?
+            .join("failed");
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let wav = dir.join(
+            self.wav
+                .file_name()
+                .context("Queued recording has no name")?,
+        );
+        fs::rename(&self.wav, &wav)
+            .with_context(|| format!("Failed to move {}", self.wav.display()))?;
+        let _ = fs::rename(self.meta_path(), wav.with_extension("json"));
+        Ok(wav)
+    }
+
+    /// Deletes the recording and its metadata.
+    pub fn remove(&self) -> Result<()> {
+        fs::remove_file(&self.wav)
+            .with_context(|| format!("Failed to remove {}", self.wav.display()))?;
+        let _ = fs::remove_file(self.meta_path());
+        Ok(())
+    }
+}
+
+/// Recordings whose upload failed because the network was down, oldest first.
+pub struct OfflineQueue {
+    dir: PathBuf,
+    max_items: usize,
+}
+
+impl OfflineQueue {
+    pub fn new(dir: PathBuf, max_items: usize) -> Self {
+        Self { dir, max_items }
+    }
+
+    /// Adds a recording, dropping the oldest ones beyond `max_items`.
+    pub fn push(&self, recording: &RecordedAudio, meta: &QueuedMeta) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let wav = self.dir.join(format!("queued-{}.wav", ts));
+        match recording {
+            RecordedAudio::Memory { samples, spec } => {
+                fs::write(&wav, encode::encode_wav(samples, *spec)?)
+                    .with_context(|| format!("Failed to write {}", wav.display()))?;
+            }
+            RecordedAudio::File(path) => {
+                fs::copy(path, &wav)
+                    .with_context(|| format!("Failed to copy to {}", wav.display()))?;
+            }
+        }
+        let meta_path = wav.with_extension("json");
+        fs::write(&meta_path, serde_json::to_string(meta)?)
+            .with_context(|| format!("Failed to write {}", meta_path.display()))?;
+        self.evict()?;
+        Ok(wav)
+    }
+
+    /// Queued recordings, oldest first. Files without readable metadata are skipped.
+    pub fn items(&self) -> Result<Vec<QueuedItem>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut wavs: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read {}", self.dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("queued-") && n.ends_with(".wav"))
+            })
+            .collect();
+        // Names embed a millisecond timestamp of equal width, so lexical order is chronological
+        wavs.sort();
+        Ok(wavs
+            .into_iter()
+            .filter_map(|wav| {
+                let meta = fs::read_to_string(wav.with_extension("json")).ok()?;
+                let meta = serde_json::from_str(&meta).ok()?;
+                Some(QueuedItem { wav, meta })
+            })
+            .collect())
+    }
+
+    fn evict(&self) -> Result<()> {
+        let items = self.items()?;
+        if items.len() <= self.max_items {
+            return Ok(());
+        }
+        for old in &items[..items.len() - self.max_items] {
+            warn!(
+                "🗑️ Offline queue is full, dropping the recording from {}",
+                old.wav.display()
+            );
+            old.remove()?;
+        }
+        Ok(())
+    }
+}
+
+fn offline_queue(config: &AppConfig) -> OfflineQueue {
+    OfflineQueue::new(config.queue_path(), config.queue_max_items.max(1))
+}
+
+/// Whether the request never reached the service, so retrying later may succeed.
+pub fn is_network_error(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<ApiError>(), Some(ApiError::Network(_)))
+}
+
+/// Whether the service asked to slow down, so the rest of the queue should wait too.
+fn is_rate_limited(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<ApiError>(),
+        Some(ApiError::RateLimited { .. })
+    )
+}
+
+/// Saves a recording whose upload failed for a later retry and says so in the returned error.
+pub async fn save_for_later(
+    error: anyhow::Error,
+    recording: RecordedAudio,
+    config: &AppConfig,
+) -> anyhow::Error {
+    let queue = offline_queue(config);
+    let meta = QueuedMeta::new(config);
+    let saved = tokio::task::spawn_blocking(move || queue.push(&recording, &meta)).await;
+    match saved {
+        Ok(Ok(_)) => {
+            let message = format!(
+                "{}; saved for later, it is retried when the network is back",
+                error
+            );
+            error.context(message)
+        }
+        Ok(Err(e)) => {
+            warn!("⚠️ Failed to queue the recording: {}", e);
+            error
+        }
+        Err(e) => {
+            warn!("⚠️ Failed to queue the recording: {}", e);
+            error
+        }
+    }
+}
+
+/// Retries queued recordings in order and hands each recovered transcript to `recovered_text`.
+/// Stops at the first network error or rate limit; recordings that fail otherwise are moved to
+/// `failed/` so they aren't sent again, and those queued for another backend are skipped.
+/// Returns how many were recovered.
+pub async fn flush<F>(
+    backend: &dyn TranscriptionBackend,
+    config: &AppConfig,
+    mut recovered_text: F,
+) -> Result<usize>
+where
+    F: FnMut(&QueuedMeta, &str),
+{
+    let mut recovered = 0;
+    for item in offline_queue(config).items()? {
+        if item.meta.backend != config.backend {
+            debug!(
+                "⏭️ Skipping {}, it was queued for the {} backend",
+                item.wav.display(),
+                item.meta.backend
+            );
+            continue;
+        }
+        let item_config = AppConfig {
+            model: item.meta.model.clone(),
+            language: item.meta.language.clone(),
+            mode: item.meta.mode,
+            ..config.clone()
+        };
+        match batch::transcribe_file(backend, &item.wav, &item_config).await {
+            Ok(text) => {
+                recovered_text(&item.meta, &text);
+                item.remove()?;
+                recovered += 1;
+            }
+            Err(e) if is_network_error(&e) => {
+                warn!(
+                    "📴 Still offline, {} left in the queue: {}",
+                    item.wav.display(),
+                    e
+                );
+                break;
+            }
+            Err(e) if is_rate_limited(&e) => {
+                warn!(
+                    "⏳ Rate limited, {} left in the queue: {}",
+                    item.wav.display(),
+                    e
+                );
+                break;
+            }
+            Err(e) => match item.set_aside() {
+                Ok(failed) => error!(
+                    "❌ Queued recording failed, moved it to {}: {}",
+                    failed.display(),
+                    e
+                ),
+                Err(move_error) => error!(
+                    "❌ Queued recording {} failed: {}; {:#}",
+                    item.wav.display(),
+                    e,
+                    move_error
+                ),
+            },
+        }
+    }
+    Ok(recovered)
+}
+
+/// Prints a recovered transcript and copies it to the clipboard. It isn't typed: the focused
+/// window is probably not the one it was dictated into.
+pub fn deliver(meta: &QueuedMeta, text: &str, config: &AppConfig) {
+    let age = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .saturating_sub(meta.timestamp);
+    info!(
+        "📬 Recovered dictation from {} min ago: {}",
+        age / 60,
+        logging::transcript(text)
+    );
+    if let Err(e) = SystemInjector::copy_to_clipboard(text, config) {
+        error!("❌ Clipboard error: {}", e);
+    }
+    SystemInjector::notify(
+        "Voice PTT",
+        "Recovered a dictation saved while offline, it is on the clipboard",
+    );
+}
+
+/// Retries the queue every `queue_retry_secs` in the background (0 = only `voice-ptt flush`).
+pub fn spawn_flusher(backend: Arc<dyn TranscriptionBackend>, config: &AppConfig) {
+    if !config.offline_queue_enabled || config.queue_retry_secs == 0 {
+        return;
+    }
+    let config = config.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.queue_retry_secs));
+        loop {
+            interval.tick().await;
+            let queue_empty = offline_queue(&config)
+                .items()
+                .map(|items| items.is_empty())
+                .unwrap_or(true);
+            if queue_empty {
+                continue;
+            }
+            if let Err(e) = flush(backend.as_ref(), &config, |meta, text| {
+                deliver(meta, text, &config)
+            })
+            .await
+            {
+                error!("❌ Offline queue error: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TranscriptionMode;
+    use crate::encode::EncodedAudio;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    fn spec() -> hound::WavSpec {
+        hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        }
+    }
+
+    fn meta(timestamp: u64) -> QueuedMeta {
+        QueuedMeta {
+            timestamp,
+            backend: "openai".to_string(),
+            model: "whisper-1".to_string(),
+            language: Some("de".to_string()),
+            mode: TranscriptionMode::Transcribe,
+        }
+    }
+
+    #[test]
+    fn test_queue_keeps_order_and_evicts_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let queue = OfflineQueue::new(dir.path().to_path_buf(), 2);
+        for i in 0..3 {
+            let recording = RecordedAudio::Memory {
+                samples: vec![i as i16; 160],
+                spec: spec(),
+            };
+            queue.push(&recording, &meta(i)).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        let items = queue.items().unwrap();
+        let timestamps: Vec<u64> = items.iter().map(|item| item.meta.timestamp).collect();
+        assert_eq!(timestamps, [1, 2]);
+        assert_eq!(encode::read_wav(&items[0].wav).unwrap().0, vec![1; 160]);
+
+        items[0].remove().unwrap();
+        assert_eq!(queue.items().unwrap().len(), 1);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    /// Succeeds for the first `offline_from` calls, then fails with a network error.
+    struct FlakyBackend {
+        calls: Mutex<Vec<Option<String>>>,
+        offline_from: usize,
+    }
+
+    #[async_trait]
+    impl TranscriptionBackend for FlakyBackend {
+        async fn transcribe_audio(
+            &self,
+            _audio: EncodedAudio,
+            _mode: TranscriptionMode,
+            config: &AppConfig,
+        ) -> Result<String> {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(config.language.clone());
+            if calls.len() > self.offline_from {
+                return Err(ApiError::Network("Could not reach OpenAI".to_string()).into());
+            }
+            Ok(format!("dictation {}", calls.len()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_recovers_in_order_until_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            queue_dir: Some(dir.path().to_path_buf()),
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        let queue = offline_queue(&config);
+        for i in 0..3 {
+            let recording = RecordedAudio::Memory {
+                samples: vec![1000; 16000],
+                spec: spec(),
+            };
+            queue.push(&recording, &meta(i)).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        let backend = FlakyBackend {
+            calls: Mutex::new(Vec::new()),
+            offline_from: 1,
+        };
+        let mut texts = Vec::new();
+        let recovered = flush(&backend, &config, |meta, text| {
+            texts.push((meta.timestamp, text.to_string()))
+        })
+        .await
+        .unwrap();
+        assert_eq!(recovered, 1);
+        assert_eq!(texts, [(0, "dictation 1".to_string())]);
+        // Queued items are retried with the language they were recorded with
+        assert_eq!(
+            *backend.calls.lock().unwrap(),
+            [Some("de".to_string()), Some("de".to_string())]
+        );
+        let left: Vec<u64> = queue
+            .items()
+            .unwrap()
+            .iter()
+            .map(|item| item.meta.timestamp)
+            .collect();
+        assert_eq!(left, [1, 2]);
+    }
+
+    /// Answers with `results` in turn and records the mode of each call.
+    struct ScriptedBackend {
+        results: Mutex<Vec<Result<String>>>,
+        modes: Mutex<Vec<TranscriptionMode>>,
+    }
+
+    #[async_trait]
+    impl TranscriptionBackend for ScriptedBackend {
+        async fn transcribe_audio(
+            &self,
+            _audio: EncodedAudio,
+            mode: TranscriptionMode,
+            _config: &AppConfig,
+        ) -> Result<String> {
+            self.modes.lock().unwrap().push(mode);
+            self.results.lock().unwrap().remove(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_sets_failures_aside_and_waits_out_rate_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            queue_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let queue = offline_queue(&config);
+        let metas = [
+            QueuedMeta {
+                backend: "groq".to_string(),
+                ..meta(0)
+            },
+            QueuedMeta {
+                mode: TranscriptionMode::Translate,
+                ..meta(1)
+            },
+            meta(2),
+            meta(3),
+        ];
+        for meta in &metas {
+            let recording = RecordedAudio::Memory {
+                samples: vec![1000; 16000],
+                spec: spec(),
+            };
+            queue.push(&recording, meta).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        let backend = ScriptedBackend {
+            results: Mutex::new(vec![
+                Err(ApiError::InvalidRequest("Invalid file format".to_string()).into()),
+                Err(ApiError::RateLimited { retry_after: None }.into()),
+            ]),
+            modes: Mutex::new(Vec::new()),
+        };
+        let recovered = flush(&backend, &config, |_, _| {}).await.unwrap();
+        assert_eq!(recovered, 0);
+        // The groq item is skipped, the translated one is sent as such, the rate limit ends
+        // the pass
+        assert_eq!(
+            *backend.modes.lock().unwrap(),
+            [TranscriptionMode::Translate, TranscriptionMode::Transcribe]
+        );
+        let left: Vec<u64> = queue
+            .items()
+            .unwrap()
+            .iter()
+            .map(|item| item.meta.timestamp)
+            .collect();
+        assert_eq!(left, [0, 2, 3]);
+        let failed = OfflineQueue::new(dir.path().join("failed"), 10)
+            .items()
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].meta.mode, TranscriptionMode::Translate);
+    }
+
+    #[tokio::test]
+    async fn test_network_failure_is_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            queue_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let error: anyhow::Error = ApiError::Network("Could not reach OpenAI".to_string()).into();
+        assert!(is_network_error(&error));
+        let recording = RecordedAudio::Memory {
+            samples: vec![0; 160],
+            spec: spec(),
+        };
+        let error = save_for_later(error, recording, &config).await;
+        assert!(error.to_string().contains("saved for later"));
+        let items = offline_queue(&config).items().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].meta.model, "whisper-1");
+    }
+}
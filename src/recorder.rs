@@ -0,0 +1,585 @@
+use ringbuf::traits::Consumer;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::api_stream::{StreamingClient, StreamingSession};
+use crate::audio::{self, CaptureBuffer, RecordedAudio};
+use crate::capture::{
+    start_cli_recording, stop_cli_recording, CaptureMode, CliRecorder, Reconnect,
+};
+use crate::config::{Activation, AppConfig, PttBinding, PttKey};
+use crate::injector::{StatusUpdate, SystemInjector};
+use crate::logging;
+use crate::pipeline::{
+    print_processing, recording_config, spawn_transcription, spawn_transcription_when,
+    PendingTranscription, Services,
+};
+use crate::ptt::PttState;
+use crate::sound::Cue;
+
+/// How often the live level meter is redrawn while recording.
+const METER_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Whether the level meter is drawn, see [`logging::live_lines`].
+fn meter(config: &AppConfig) -> bool {
+    config.meter_enabled && logging::live_lines()
+}
+
+/// Redraws the level meter line from the tail of the capture buffer.
+fn print_level_meter(buffer: &CaptureBuffer) {
+    let samples_per_sec = buffer.samples_per_sec();
+    let recent = buffer.recent(samples_per_sec / 10);
+    let last_second = buffer.recent(samples_per_sec);
+    // Only judge silence once a full second has been captured
+    let silent =
+        buffer.len() >= samples_per_sec && audio::rms_level(last_second) < audio::SILENCE_RMS;
+    let level = audio::rms_level(recent);
+    eprint!("\r{}   ", audio::format_level_meter(level, silent));
+    let _ = std::io::stderr().flush();
+}
+
+/// Redraws the elapsed recording time line (used when no sample buffer is available).
+fn print_elapsed(started: Instant) {
+    eprint!("\r⏺️ {:.1}s   ", started.elapsed().as_secs_f32());
+    let _ = std::io::stderr().flush();
+}
+
+/// "🎙️ Recording...", naming the binding when there are several, and how to stop a toggled
+/// recording.
+fn print_recording(bindings: &[PttBinding], binding: usize, activation: Activation) {
+    let label = bindings[binding].label();
+    let name = match bindings.len() {
+        1 => String::new(),
+        _ => format!(" [{}]", label),
+    };
+    match activation {
+        Activation::Hold => info!("🎙️ Recording{}...", name),
+        Activation::Toggle => info!("🎙️ Recording{}... tap [{}] again to stop", name, label),
+    }
+}
+
+/// The key state for the `activation` and timings of `config`.
+fn ptt_state(config: &AppConfig) -> PttState {
+    PttState::new(
+        config.activation,
+        Duration::from_millis(config.double_tap_ms),
+        Duration::from_millis(config.hold_threshold_ms),
+    )
+}
+
+/// Records while the PTT keys say so: starts the capture when a binding's keys go down and
+/// hands the recording to a transcription task when they come up.
+pub struct Recorder {
+    config: AppConfig,
+    capture: CaptureMode,
+    ptt: PttState,
+    services: Services,
+    streamer: Option<StreamingClient>,
+    stream: Option<StreamingSession>,
+    pending: Option<PendingTranscription>,
+    /// Reopens the input device after its stream died, see [`Recorder::check_capture`]
+    reconnecting: Option<Reconnect>,
+    /// cpal capture that already runs while the keys are held below hold_threshold_ms
+    pre_rolling: bool,
+    /// The binding the current recording is transcribed with
+    binding: usize,
+    started: Instant,
+    last_meter_update: Instant,
+}
+
+impl Recorder {
+    pub fn new(
+        config: &AppConfig,
+        capture: CaptureMode,
+        services: Services,
+        streamer: Option<StreamingClient>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            capture,
+            ptt: ptt_state(config),
+            services,
+            streamer,
+            stream: None,
+            pending: None,
+            reconnecting: None,
+            pre_rolling: false,
+            binding: 0,
+            started: Instant::now(),
+            last_meter_update: Instant::now(),
+        }
+    }
+
+    /// Records with `config` from now on, e.g. its `activation` and timings after a reload;
+    /// only while [idle](Recorder::is_idle). The capture keeps its startup settings.
+    pub fn reconfigure(&mut self, config: &AppConfig) {
+        self.config = config.clone();
+        self.ptt = ptt_state(config);
+        self.services.sounds.reconfigure(config);
+    }
+
+    /// Starts reopening the input device in the background when its stream stopped, e.g.
+    /// because it was unplugged; [`Recorder::reconnected`] switches to it. A recording in
+    /// progress is lost, and the keys do nothing until the device is back.
+    pub fn check_capture(&mut self) {
+        if self.reconnecting.is_some() {
+            return;
+        }
+        let CaptureMode::Cpal { stream_dead, .. } = &self.capture else {
+            return;
+        };
+        if !stream_dead.load(Ordering::Relaxed) {
+            return;
+        }
+        error!("❌ Audio input stream stopped, the device may have been disconnected.");
+        let lost = self.discard_recording();
+        self.ptt.abort();
+        if lost {
+            warn!("🗑️ The recording in progress was lost.");
+        }
+        SystemInjector::notify(
+            "Voice PTT Error",
+            match lost {
+                true => {
+                    "Microphone disconnected, the recording in progress was lost. Reconnecting..."
+                }
+                false => "Microphone disconnected, reconnecting...",
+            },
+        );
+        // Drop the dead stream before trying to open the device again
+        if let CaptureMode::Cpal {
+            _stream: stream, ..
+        } = &mut self.capture
+        {
+            stream.take();
+        }
+        self.reconnecting = Some(Reconnect::new(&self.config));
+    }
+
+    /// Resolves once a reconnect started by [`Recorder::check_capture`] has reopened the
+    /// input device, or fallen back to a command-line recorder, and records from it from now
+    /// on. Never resolves while there is no reconnect.
+    pub async fn reconnected(&mut self, ready_hint: &str) {
+        let Some(reconnect) = &mut self.reconnecting else {
+            return std::future::pending().await;
+        };
+        let capture = loop {
+            if let Some(capture) = reconnect.attempt(&self.config).await {
+                break capture;
+            }
+        };
+        self.reconnecting = None;
+        self.capture = capture;
+        self.pre_rolling = false;
+        info!("\n✅ Ready! {}.", ready_hint);
+    }
+
+    /// Whether no key is held and nothing is recording, so the bindings can change.
+    pub fn is_idle(&self) -> bool {
+        self.ptt.is_idle()
+    }
+
+    /// Whether audio is being recorded, not counting the pre-roll of a press that hasn't
+    /// reached hold_threshold_ms.
+    pub fn is_recording(&self) -> bool {
+        match &self.capture {
+            CaptureMode::Cpal { is_recording, .. } => {
+                is_recording.load(Ordering::Relaxed) && !self.pre_rolling
+            }
+            CaptureMode::Cli { recorder, .. } => recorder.is_some(),
+        }
+    }
+
+    /// Starts recording with `binding` until [`Recorder::stop_latched`] or a tap of its keys,
+    /// like a toggled recording; it begins at the next update.
+    pub fn start_latched(&mut self, binding: usize) {
+        self.ptt.start(binding);
+    }
+
+    /// Keys already down at the next update don't record until released and pressed again.
+    pub fn ignore_held_keys(&mut self) {
+        self.ptt.ignore_held_keys();
+    }
+
+    /// Ends a toggled, locked or started recording at the next update, which transcribes it.
+    /// Returns whether one was running.
+    pub fn stop_latched(&mut self) -> bool {
+        let latched = self.ptt.is_latched();
+        self.ptt.cancel();
+        latched
+    }
+
+    /// Throws the recording in progress away without transcribing it; keys still held don't
+    /// record again until released. Returns whether anything was recording.
+    pub fn cancel(&mut self, ready_hint: &str) -> bool {
+        if !self.is_recording() {
+            return false;
+        }
+        self.ptt.abort();
+        self.discard_recording();
+        self.services.sounds.play(Cue::Cancel);
+        if meter(&self.config) {
+            // Terminate the meter line
+            eprintln!();
+        }
+        info!("🚫 Cancelled");
+        info!("\n✅ Ready! {}.", ready_hint);
+        true
+    }
+
+    /// Sends the last recording whose transcription failed again, typed after any transcript
+    /// still in flight. Returns whether one was kept.
+    pub fn retry(&mut self, ready_hint: &str) -> bool {
+        let Some(failed) = self.services.take_failed() else {
+            return false;
+        };
+        info!("🔁 Retrying the recording that failed");
+        print_processing(&failed.config);
+        self.pending = Some(spawn_transcription(
+            self.services.clone(),
+            failed.config,
+            failed.recording,
+            ready_hint.to_string(),
+            self.pending.take(),
+            None,
+        ));
+        true
+    }
+
+    /// Waits until the last recording handed off is typed, or its transcription gave up.
+    pub async fn finish(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.finish().await;
+        }
+    }
+
+    /// Stops for good: discards the recording in progress and gives the transcription in
+    /// flight up to `grace` to be typed.
+    pub async fn shutdown(&mut self, grace: Duration) {
+        if self.discard_recording() {
+            info!("🗑️ Discarded the recording in progress.");
+        }
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        if !pending.is_finished() && !grace.is_zero() {
+            info!(
+                "⏳ Waiting up to {}s for the transcription in flight (Ctrl+C again to quit now)...",
+                grace.as_secs()
+            );
+        }
+        if !pending.finish_within(grace).await {
+            info!("⏭️ Dropped the transcription in flight.");
+        }
+    }
+
+    /// Stops the recording in progress without transcribing it: the capture stops, and a
+    /// recorder child is killed and reaped and its file deleted. Returns whether anything
+    /// was recording.
+    fn discard_recording(&mut self) -> bool {
+        self.stream = None;
+        let was_recording = self.is_recording();
+        match &mut self.capture {
+            CaptureMode::Cpal {
+                audio_buffer,
+                consumer,
+                is_recording,
+                ..
+            } => {
+                is_recording.store(false, Ordering::Relaxed);
+                consumer.clear();
+                audio_buffer.clear();
+                self.pre_rolling = false;
+            }
+            CaptureMode::Cli {
+                recorder,
+                current_file,
+                ..
+            } => {
+                if let Some(process) = recorder.take() {
+                    process.kill();
+                }
+                if let Some(path) = current_file.take() {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+        }
+        if was_recording {
+            self.services.status.update(StatusUpdate::Discarded);
+        }
+        was_recording
+    }
+
+    /// Updates the recording with the keys held right now.
+    pub fn update(&mut self, bindings: &[PttBinding], keys: &[PttKey], ready_hint: &str) {
+        let was_locked = self.ptt.is_locked();
+        let active = self.ptt.update(bindings, keys);
+        if self.reconnecting.is_some() {
+            // Nothing to record from until the device is back
+            self.ptt.abort();
+            return;
+        }
+        if self.ptt.is_locked() && !was_locked {
+            info!(
+                "🔒 Recording locked, tap [{}] to stop",
+                bindings[self.binding].label()
+            );
+        }
+        if self
+            .ptt
+            .reminder_due(Duration::from_secs(self.config.toggle_reminder_secs))
+        {
+            let message = format!(
+                "Still recording ({}s), tap [{}] to stop",
+                self.ptt.latched_for().as_secs(),
+                bindings[self.binding].label()
+            );
+            info!("\n⏺️ {}", message);
+            SystemInjector::notify("Voice PTT", &message);
+        }
+        // Capture during the hold threshold, so a press that becomes a recording keeps its
+        // first words; the command-line recorders only start once it has passed
+        if self.ptt.is_arming() && !self.pre_rolling {
+            if let CaptureMode::Cpal {
+                consumer,
+                is_recording,
+                dropped_samples,
+                ..
+            } = &mut self.capture
+            {
+                if !is_recording.load(Ordering::Relaxed) {
+                    consumer.clear();
+                    dropped_samples.store(0, Ordering::Relaxed);
+                    is_recording.store(true, Ordering::Relaxed);
+                    self.pre_rolling = true;
+                }
+            }
+        }
+
+        match active {
+            Some(binding) => self.record(bindings, binding),
+            None => self.stop(bindings, ready_hint),
+        }
+    }
+
+    /// Announces a new recording with `binding`, superseding the previous transcription if
+    /// `cancel_previous` is set.
+    fn begin(&mut self, bindings: &[PttBinding], binding: usize) {
+        self.services.sounds.play(Cue::Start);
+        if self.config.cancel_previous {
+            if let Some(previous) = self.pending.take() {
+                previous.supersede();
+            }
+        }
+        self.binding = binding;
+        print_recording(bindings, binding, self.config.activation);
+        self.services.status.update(StatusUpdate::Recording);
+    }
+
+    /// Starts or continues the recording of `binding`.
+    fn record(&mut self, bindings: &[PttBinding], binding: usize) {
+        let starting = match &self.capture {
+            CaptureMode::Cpal { is_recording, .. } => {
+                !is_recording.load(Ordering::Relaxed) || self.pre_rolling
+            }
+            CaptureMode::Cli { recorder, .. } => recorder.is_none(),
+        };
+        if starting {
+            self.begin(bindings, binding);
+        }
+        match &mut self.capture {
+            CaptureMode::Cpal {
+                audio_buffer,
+                consumer,
+                is_recording,
+                dropped_samples,
+                ..
+            } => {
+                if starting {
+                    audio_buffer.clear();
+                    if !self.pre_rolling {
+                        consumer.clear();
+                        dropped_samples.store(0, Ordering::Relaxed);
+                    }
+                    self.pre_rolling = false;
+                    is_recording.store(true, Ordering::Relaxed);
+                    self.last_meter_update = Instant::now();
+                    self.stream = self.streamer.as_ref().map(|streamer| {
+                        streamer.start(audio_buffer.spec(), &bindings[binding].config)
+                    });
+                } else {
+                    let chunk: Vec<i16> = consumer.pop_iter().collect();
+                    if let Some(stream) = &self.stream {
+                        stream.push(&chunk);
+                    }
+                    audio_buffer.extend(chunk);
+                    if meter(&self.config) && self.last_meter_update.elapsed() >= METER_INTERVAL {
+                        print_level_meter(audio_buffer);
+                        self.last_meter_update = Instant::now();
+                    }
+                }
+            }
+            CaptureMode::Cli {
+                tool,
+                recorder,
+                current_file,
+            } => {
+                if starting {
+                    match start_cli_recording(*tool, &self.config) {
+                        Ok((child, wav_path)) => {
+                            *recorder = Some(child);
+                            *current_file = Some(wav_path);
+                            self.started = Instant::now();
+                            self.last_meter_update = Instant::now();
+                        }
+                        Err(e) => {
+                            error!("❌ Recorder start error: {}", e);
+                            // Don't retry on every poll until the keys are pressed again
+                            self.ptt.abort();
+                            self.services.status.update(StatusUpdate::Discarded);
+                            SystemInjector::notify("Voice PTT Error", &e.to_string());
+                        }
+                    }
+                } else if meter(&self.config) && self.last_meter_update.elapsed() >= METER_INTERVAL
+                {
+                    print_elapsed(self.started);
+                    self.last_meter_update = Instant::now();
+                }
+            }
+        }
+    }
+
+    /// Ends a recording once its keys are released and hands it off for transcription.
+    fn stop(&mut self, bindings: &[PttBinding], ready_hint: &str) {
+        match &mut self.capture {
+            CaptureMode::Cpal {
+                audio_buffer,
+                consumer,
+                is_recording,
+                dropped_samples,
+                ..
+            } => {
+                if self.pre_rolling {
+                    if !self.ptt.is_arming() {
+                        // Released before hold_threshold_ms: drop the audio, no sound
+                        is_recording.store(false, Ordering::Relaxed);
+                        consumer.clear();
+                        self.pre_rolling = false;
+                    }
+                } else if is_recording.load(Ordering::Relaxed) {
+                    is_recording.store(false, Ordering::Relaxed);
+                    self.services.sounds.play(Cue::End);
+                    if meter(&self.config) {
+                        // Terminate the meter line
+                        eprintln!();
+                    }
+                    let config = recording_config(&bindings[self.binding]);
+                    print_processing(&config);
+
+                    let chunk: Vec<i16> = consumer.pop_iter().collect();
+                    if let Some(stream) = &self.stream {
+                        stream.push(&chunk);
+                    }
+                    audio_buffer.extend(chunk);
+                    let dropped = dropped_samples.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        warn!(
+                            "⚠️ Dropped {} samples during recording (capture buffer overflow).",
+                            dropped
+                        );
+                    }
+                    if audio_buffer.is_empty() {
+                        self.stream = None;
+                        self.services.status.update(StatusUpdate::Discarded);
+                        return;
+                    }
+                    match audio_buffer.take() {
+                        Ok(recording) => {
+                            self.pending = Some(spawn_transcription(
+                                self.services.clone(),
+                                config,
+                                recording,
+                                ready_hint.to_string(),
+                                self.pending.take(),
+                                self.stream.take(),
+                            ))
+                        }
+                        Err(e) => {
+                            error!("❌ Failed to finish recording: {}", e);
+                            self.services.status.update(StatusUpdate::Discarded);
+                        }
+                    }
+                }
+            }
+            CaptureMode::Cli {
+                tool,
+                recorder,
+                current_file,
+            } => {
+                if let Some(process) = recorder.take() {
+                    self.services.sounds.play(Cue::End);
+                    if meter(&self.config) {
+                        eprintln!();
+                    }
+                    let config = recording_config(&bindings[self.binding]);
+                    print_processing(&config);
+
+                    let tool = *tool;
+                    let recorded_file = current_file.take();
+                    // The recorder gets a grace period to finalize the file, which would stall
+                    // the main loop
+                    let recording = async move {
+                        let stopped =
+                            tokio::task::spawn_blocking(move || stop_cli_recording(tool, process));
+                        let recorder_stderr = stopped.await.unwrap_or_default();
+                        finished_cli_recording(tool, recorded_file?, &recorder_stderr)
+                    };
+                    self.pending = Some(spawn_transcription_when(
+                        self.services.clone(),
+                        config,
+                        recording,
+                        ready_hint.to_string(),
+                        self.pending.take(),
+                        None,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// The file of a stopped command-line recorder, or `None` after reporting why it is empty.
+fn finished_cli_recording(
+    tool: CliRecorder,
+    recorded_file: PathBuf,
+    recorder_stderr: &str,
+) -> Option<RecordedAudio> {
+    let size_ok = std::fs::metadata(&recorded_file)
+        .map(|m| m.len() > 44)
+        .unwrap_or(false);
+    if size_ok {
+        return Some(RecordedAudio::File(recorded_file));
+    }
+    warn!("⚠️ Recorded audio file is empty.");
+    let _ = std::fs::remove_file(&recorded_file);
+    if !recorder_stderr.is_empty() {
+        error!("❌ {}: {}", tool.binary(), recorder_stderr);
+        SystemInjector::notify(
+            "Voice PTT Error",
+            &format!("{} failed: {}", tool.binary(), recorder_stderr),
+        );
+    }
+    None
+}
+
+/// Cleans up after a panic or an early return too: no recorder child or temp file outlives
+/// the recorder.
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.discard_recording();
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::encode;
+
+/// Keeps copies of uploaded recordings so they can be listened to afterwards.
+pub struct RecordingArchive {
+    dir: PathBuf,
+    keep_last_n: usize,
+}
+
+impl RecordingArchive {
+    pub fn new(dir: PathBuf, keep_last_n: usize) -> Self {
+        Self { dir, keep_last_n }
+    }
+
+    /// Writes in-memory samples as a timestamped WAV file.
+    pub fn save_samples(&self, samples: &[i16], spec: hound::WavSpec) -> Result<PathBuf> {
+        let bytes = encode::encode_wav(samples, spec)?;
+        let path = self.next_path()?;
+        fs::write(&path, bytes).with_context(|| format!("Failed to write {}", path.display()))?;
+        self.finish(path)
+    }
+
+    /// Copies an existing WAV file into the archive.
+    pub fn save_file(&self, source: &Path) -> Result<PathBuf> {
+        let path = self.next_path()?;
+        fs::copy(source, &path).with_context(|| format!("Failed to copy to {}", path.display()))?;
+        self.finish(path)
+    }
+
+    fn next_path(&self) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        Ok(self.dir.join(format!("recording-{}.wav", ts)))
+    }
+
+    fn finish(&self, path: PathBuf) -> Result<PathBuf> {
+        info!("💾 Saved recording to {}", path.display());
+        self.prune()?;
+        Ok(path)
+    }
+
+    /// Deletes the oldest archived recordings beyond `keep_last_n`.
+    fn prune(&self) -> Result<()> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("recording-") && n.ends_with(".wav"))
+            })
+            .collect();
+        if files.len() <= self.keep_last_n {
+            return Ok(());
+        }
+        // Names embed a millisecond timestamp of equal width, so lexical order is chronological
+        files.sort();
+        for old in &files[..files.len() - self.keep_last_n] {
+            fs::remove_file(old).with_context(|| format!("Failed to remove {}", old.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_prunes_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = RecordingArchive::new(dir.path().to_path_buf(), 2);
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut saved = Vec::new();
+        for _ in 0..4 {
+            saved.push(archive.save_samples(&[1, 2, 3], spec).unwrap());
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let mut remaining: Vec<PathBuf> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, saved[2..]);
+        assert_eq!(hound::WavReader::open(&saved[3]).unwrap().len(), 3);
+    }
+}
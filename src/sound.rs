@@ -0,0 +1,312 @@
+use anyhow::{Context, Result};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use tracing::{error, warn};
+
+use crate::config::{AppConfig, SoundBackend, SoundPaths};
+use crate::injector::SystemInjector;
+
+const BUILTIN_START: &[u8] = include_bytes!("../assets/sounds/start.wav");
+const BUILTIN_END: &[u8] = include_bytes!("../assets/sounds/end.wav");
+const BUILTIN_ERROR: &[u8] = include_bytes!("../assets/sounds/error.wav");
+const BUILTIN_DONE: &[u8] = include_bytes!("../assets/sounds/done.wav");
+const BUILTIN_CANCEL: &[u8] = include_bytes!("../assets/sounds/cancel.wav");
+
+/// Sound path that always selects the bundled tone.
+pub const BUILTIN_PATH: &str = "builtin";
+
+/// Which feedback sound to play.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cue {
+    Start,
+    End,
+    /// Transcribing or injecting failed.
+    Error,
+    /// `cancel_key` threw the recording away.
+    Cancel,
+    /// The transcript was injected; only with a `sound_done_path`.
+    Done,
+}
+
+impl Cue {
+    fn builtin(self) -> &'static [u8] {
+        match self {
+            Cue::Start => BUILTIN_START,
+            Cue::End => BUILTIN_END,
+            Cue::Error => BUILTIN_ERROR,
+            Cue::Cancel => BUILTIN_CANCEL,
+            Cue::Done => BUILTIN_DONE,
+        }
+    }
+}
+
+/// Plays the feedback sounds with the configured backend.
+pub struct SoundPlayer {
+    /// `sound_enabled`, switched at runtime from the tray menu.
+    enabled: AtomicBool,
+    /// `sound_backend` and the sound files, replaced by [`SoundPlayer::reconfigure`]
+    settings: Mutex<(SoundBackend, SoundPaths)>,
+}
+
+impl SoundPlayer {
+    pub fn new(app_config: &AppConfig) -> Self {
+        prepare(app_config);
+        Self {
+            enabled: AtomicBool::new(app_config.sound_enabled),
+            settings: Mutex::new((app_config.sound_backend, app_config.get_sound_paths())),
+        }
+    }
+
+    /// Plays the sounds `app_config` configures from now on, after a reload or a profile
+    /// switch.
+    pub fn reconfigure(&self, app_config: &AppConfig) {
+        prepare(app_config);
+        self.set_enabled(app_config.sound_enabled);
+        *self.settings.lock().unwrap_or_else(|e| e.into_inner()) =
+            (app_config.sound_backend, app_config.get_sound_paths());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Starts playing `cue` and returns immediately.
+    pub fn play(&self, cue: Cue) {
+        if !self.is_enabled() {
+            return;
+        }
+        let (backend, path) = {
+            let settings = self.settings.lock().unwrap_or_else(|e| e.into_inner());
+            let (backend, paths) = &*settings;
+            let path = match cue {
+                Cue::Start => &paths.start,
+                Cue::End => &paths.end,
+                Cue::Error => &paths.error,
+                Cue::Cancel => &paths.cancel,
+                Cue::Done => match &paths.done {
+                    Some(path) => path,
+                    None => return,
+                },
+            };
+            (*backend, path.clone())
+        };
+        let sound = resolve(backend, cue, &path);
+        if backend == SoundBackend::External && matches!(sound, Sound::File(_)) {
+            return SystemInjector::play_sound(true, &path);
+        }
+        match player() {
+            Some(player) => {
+                let _ = player.send(PlayRequest {
+                    sound,
+                    finished: None,
+                });
+            }
+            None => {
+                if let Sound::File(_) = sound {
+                    SystemInjector::play_sound(true, &path);
+                }
+            }
+        }
+    }
+}
+
+/// Gets the configured sound backend ready before its first beep.
+fn prepare(app_config: &AppConfig) {
+    if app_config.sound_enabled && app_config.sound_backend != SoundBackend::External {
+        // Open the output device now so the first beep isn't delayed
+        player();
+    } else if app_config.sound_enabled {
+        // Look for the external player once, before the first beep
+        SystemInjector::sound_player();
+    }
+}
+
+/// Plays an audio file with `backend` and waits until it has finished.
+pub fn play_file_blocking(backend: SoundBackend, path: &Path) -> Result<()> {
+    if backend != SoundBackend::External {
+        if let Some(player) = player() {
+            let (tx, rx) = mpsc::channel();
+            let request = PlayRequest {
+                sound: Sound::File(path.to_path_buf()),
+                finished: Some(tx),
+            };
+            if player.send(request).is_ok() {
+                match rx.recv() {
+                    Ok(Ok(sink)) => {
+                        sink.sleep_until_end();
+                        return Ok(());
+                    }
+                    Ok(Err(e)) => warn!(
+                        "⚠️ Cannot play {} in-process ({}), using the external player.",
+                        path.display(),
+                        e
+                    ),
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+    SystemInjector::play_sound_blocking(&path.to_string_lossy())
+}
+
+#[derive(Debug, PartialEq)]
+enum Sound {
+    Embedded(&'static [u8]),
+    File(PathBuf),
+}
+
+/// The configured file for `cue`, or its bundled tone with the builtin backend, for the
+/// path "builtin", and when the file doesn't exist.
+fn resolve(backend: SoundBackend, cue: Cue, path: &str) -> Sound {
+    if backend == SoundBackend::Builtin || path == BUILTIN_PATH || !Path::new(path).exists() {
+        Sound::Embedded(cue.builtin())
+    } else {
+        Sound::File(PathBuf::from(path))
+    }
+}
+
+struct PlayRequest {
+    sound: Sound,
+    /// Receives the playing sink when the caller wants to wait for the end of playback.
+    finished: Option<mpsc::Sender<Result<rodio::Sink>>>,
+}
+
+/// Returns the channel of the playback thread, starting it on first use.
+///
+/// `None` means no output device could be opened; callers then fall back to the
+/// external player.
+fn player() -> Option<&'static mpsc::Sender<PlayRequest>> {
+    static PLAYER: OnceLock<Option<mpsc::Sender<PlayRequest>>> = OnceLock::new();
+    PLAYER
+        .get_or_init(|| {
+            let (tx, rx) = mpsc::channel::<PlayRequest>();
+            let (ready_tx, ready_rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                // The output stream is not Send, so it lives on this thread for the whole run
+                let (_stream, handle) = match rodio::OutputStream::try_default() {
+                    Ok(output) => {
+                        let _ = ready_tx.send(Ok(()));
+                        output
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                for request in rx {
+                    handle_request(&handle, request);
+                }
+            });
+            match ready_rx.recv() {
+                Ok(Ok(())) => Some(tx),
+                Ok(Err(e)) => {
+                    warn!(
+                        "⚠️ Audio output unavailable ({}), falling back to the external sound player.",
+                        e
+                    );
+                    None
+                }
+                Err(_) => None,
+            }
+        })
+        .as_ref()
+}
+
+fn handle_request(handle: &rodio::OutputStreamHandle, request: PlayRequest) {
+    let result = start_playback(handle, &request.sound);
+    match (result, request.finished) {
+        (Ok(sink), Some(finished)) => {
+            let _ = finished.send(Ok(sink));
+        }
+        (Ok(sink), None) => sink.detach(),
+        (Err(e), Some(finished)) => {
+            let _ = finished.send(Err(e));
+        }
+        (Err(e), None) => match request.sound {
+            // e.g. AIFF files, which rodio can't decode
+            Sound::File(path) => {
+                let path = path.to_string_lossy();
+                warn!(
+                    "⚠️ Cannot play {} in-process ({}), using the external player.",
+                    path, e
+                );
+                SystemInjector::play_sound(true, &path);
+            }
+            Sound::Embedded(_) => error!("❌ Failed to play sound: {}", e),
+        },
+    }
+}
+
+fn start_playback(handle: &rodio::OutputStreamHandle, sound: &Sound) -> Result<rodio::Sink> {
+    let source = decode(sound)?;
+    let sink = rodio::Sink::try_new(handle).context("Failed to open audio output")?;
+    sink.append(source);
+    Ok(sink)
+}
+
+fn decode(sound: &Sound) -> Result<rodio::Decoder<Cursor<Vec<u8>>>> {
+    let bytes = match sound {
+        Sound::Embedded(bytes) => bytes.to_vec(),
+        Sound::File(path) => std::fs::read(path)
+            .with_context(|| format!("Failed to read sound file {}", path.display()))?,
+    };
+    rodio::Decoder::new(Cursor::new(bytes)).context("Unsupported sound format")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::Source;
+
+    #[test]
+    fn test_builtin_sounds_decode() {
+        for cue in [Cue::Start, Cue::End, Cue::Error, Cue::Done] {
+            let source = decode(&Sound::Embedded(cue.builtin())).unwrap();
+            assert_eq!(source.channels(), 1);
+            assert!(source.count() > 1000);
+        }
+        assert_ne!(Cue::Start.builtin(), Cue::End.builtin());
+        assert_ne!(Cue::Error.builtin(), Cue::Done.builtin());
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_builtin() {
+        let file = std::env::temp_dir().join(format!("voice-ptt-beep-{}.oga", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        let path = file.to_string_lossy();
+
+        assert_eq!(
+            resolve(SoundBackend::Rodio, Cue::Start, &path),
+            Sound::File(file.clone())
+        );
+        assert_eq!(
+            resolve(SoundBackend::External, Cue::End, &path),
+            Sound::File(file.clone())
+        );
+        assert_eq!(
+            resolve(SoundBackend::Builtin, Cue::Start, &path),
+            Sound::Embedded(BUILTIN_START)
+        );
+        assert_eq!(
+            resolve(SoundBackend::Rodio, Cue::End, BUILTIN_PATH),
+            Sound::Embedded(BUILTIN_END)
+        );
+        assert_eq!(
+            resolve(SoundBackend::External, Cue::Error, "/nonexistent/beep.oga"),
+            Sound::Embedded(BUILTIN_ERROR)
+        );
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_missing_file() {
+        let sound = Sound::File(PathBuf::from("/nonexistent/beep.oga"));
+        assert!(decode(&sound).is_err());
+    }
+}
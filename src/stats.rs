@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Timing of one transcription, appended as a JSON line to the stats file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TranscriptionRecord {
+    /// Unix time in seconds.
+    pub timestamp: u64,
+    pub backend: String,
+    pub model: String,
+    pub audio_secs: f64,
+    pub api_ms: u64,
+    pub inject_ms: u64,
+}
+
+impl TranscriptionRecord {
+    pub fn new(
+        backend: &str,
+        model: &str,
+        audio: Duration,
+        api: Duration,
+        inject: Duration,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            backend: backend.to_string(),
+            model: model.to_string(),
+            audio_secs: audio.as_secs_f64(),
+            api_ms: api.as_millis() as u64,
+            inject_ms: inject.as_millis() as u64,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "⏱️ {:.1}s audio, API {:.2}s, typing {:.2}s",
+            self.audio_secs,
+            self.api_ms as f64 / 1000.0,
+            self.inject_ms as f64 / 1000.0
+        )
+    }
+}
+
+/// Appends `record` to the JSONL stats file, creating it if needed.
+pub fn append(path: &Path, record: &TranscriptionRecord) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open stats file at {}", path.display()))?;
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{}", line).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Sums over all recorded transcriptions.
+#[derive(Debug, Default, PartialEq)]
+pub struct Totals {
+    pub requests: usize,
+    pub audio_secs: f64,
+    pub api_ms: u64,
+    pub inject_ms: u64,
+}
+
+impl Totals {
+    pub fn minutes(&self) -> f64 {
+        self.audio_secs / 60.0
+    }
+
+    pub fn average_api_secs(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.api_ms as f64 / 1000.0 / self.requests as f64
+    }
+
+    pub fn report(&self, price_per_minute: f64) -> String {
+        format!(
+            "📊 {} requests, {:.1} minutes transcribed\n💰 Estimated cost: ${:.2} (at ${} per minute)\n⏱️ Average API latency: {:.2}s",
+            self.requests,
+            self.minutes(),
+            self.minutes() * price_per_minute,
+            price_per_minute,
+            self.average_api_secs()
+        )
+    }
+}
+
+/// Adds up the records in a stats file's contents. Lines that don't parse (e.g. cut off
+/// by a crash) are skipped and counted in the second value.
+pub fn totals(content: &str) -> (Totals, usize) {
+    let mut totals = Totals::default();
+    let mut skipped = 0;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<TranscriptionRecord>(line) {
+            Ok(record) => {
+                totals.requests += 1;
+                totals.audio_secs += record.audio_secs;
+                totals.api_ms += record.api_ms;
+                totals.inject_ms += record.inject_ms;
+            }
+            Err(_) => skipped += 1,
+        }
+    }
+    (totals, skipped)
+}
+
+/// Prints the totals of the stats file for `voice-ptt stats`.
+pub fn print_report(path: &Path, price_per_minute: f64) -> Result<()> {
+    if !path.exists() {
+        println!(
+            "No stats recorded yet at {}. Set stats_enabled = true to start.",
+            path.display()
+        );
+        return Ok(());
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read stats file at {}", path.display()))?;
+    let (totals, skipped) = totals(&content);
+    println!("{}", totals.report(price_per_minute));
+    if skipped > 0 {
+        eprintln!(
+            "⚠️ Skipped {} unreadable lines in {}",
+            skipped,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_round_trip_and_add_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.jsonl");
+        let first = TranscriptionRecord::new(
+            "openai",
+            "whisper-1",
+            Duration::from_secs(90),
+            Duration::from_millis(1200),
+            Duration::from_millis(300),
+        );
+        let second = TranscriptionRecord::new(
+            "openai",
+            "whisper-1",
+            Duration::from_secs(30),
+            Duration::from_millis(800),
+            Duration::from_millis(100),
+        );
+        append(&path, &first).unwrap();
+        append(&path, &second).unwrap();
+
+        let mut content = fs::read_to_string(&path).unwrap();
+        content.push_str("{\"timestamp\": 17");
+        let (totals, skipped) = totals(&content);
+        assert_eq!(skipped, 1);
+        assert_eq!(totals.requests, 2);
+        assert!((totals.minutes() - 2.0).abs() < 1e-9);
+        assert!((totals.average_api_secs() - 1.0).abs() < 1e-9);
+
+        let report = totals.report(0.006);
+        assert!(report.contains("2 requests, 2.0 minutes"));
+        assert!(report.contains("$0.01"));
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let record = TranscriptionRecord::new(
+            "groq",
+            "whisper-large-v3-turbo",
+            Duration::from_millis(4200),
+            Duration::from_millis(650),
+            Duration::from_millis(120),
+        );
+        assert_eq!(record.summary(), "⏱️ 4.2s audio, API 0.65s, typing 0.12s");
+    }
+}
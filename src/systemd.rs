@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Whether voice-ptt runs as a `Type=notify` systemd service.
+pub fn enabled() -> bool {
+    cfg!(target_os = "linux") && std::env::var_os("NOTIFY_SOCKET").is_some()
+}
+
+/// Sends `state` (e.g. `READY=1` or `STATUS=Recording…`, one assignment per line) to systemd
+/// as a datagram to the socket in `$NOTIFY_SOCKET`, the sd_notify protocol. Does nothing
+/// when not started by systemd.
+pub fn notify(state: &str) {
+    #[cfg(target_os = "linux")]
+    if let Some(socket) = std::env::var_os("NOTIFY_SOCKET") {
+        if let Err(e) = send(Path::new(&socket), state) {
+            warn!("⚠️ Failed to notify systemd: {}", e);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = state;
+}
+
+/// Sends one notification to `socket`, an abstract socket when it starts with `@`.
+#[cfg(target_os = "linux")]
+fn send(socket: &Path, state: &str) -> std::io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let address = match socket.as_os_str().as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address)?;
+    Ok(())
+}
+
+/// `~/.config/systemd/user/voice-ptt.service`.
+pub fn unit_path() -> Result<PathBuf> {
+    let dirs = directories::BaseDirs::new().context("Failed to find the home directory")?;
+    Ok(dirs.config_dir().join("systemd/user/voice-ptt.service"))
+}
+
+/// Quotes a command line word for a unit file: `%` and `$` would be expanded by systemd,
+/// and words with spaces need quotes.
+fn quote(word: &str) -> String {
+    let escaped = word.replace('%', "%%").replace('$', "$$");
+    if escaped.is_empty() || escaped.contains([' ', '\t', '"', '\'', '\\']) {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+/// The user unit started by `voice-ptt install-service`: a notify service, so it only counts
+/// as started once the microphone and keys are ready, restarted after failures but not so
+/// often that every attempt hits the API key check. `env_file` is read when it exists.
+pub fn unit_file(exe: &Path, config: Option<&Path>, env_file: &Path) -> String {
+    let mut command = quote(&exe.to_string_lossy());
+    if let Some(config) = config {
+        command.push_str(" --config ");
+        command.push_str(&quote(&config.to_string_lossy()));
+    }
+    format!(
+        r#"[Unit]
+Description=Voice PTT push-to-talk dictation
+PartOf=graphical-session.target
+After=graphical-session.target
+StartLimitIntervalSec=300
+StartLimitBurst=5
+
+[Service]
+Type=notify
+ExecStart={command}
+# DISPLAY, XAUTHORITY or WAYLAND_DISPLAY if the session doesn't import them into the
+# user manager, and any VOICE_PTT_* overrides, one NAME=value per line
+EnvironmentFile=-{env_file}
+Restart=on-failure
+RestartSec=10
+# 3: another instance is already running
+RestartPreventExitStatus=3
+
+[Install]
+WantedBy=graphical-session.target
+"#,
+        command = command,
+        env_file = quote(&env_file.to_string_lossy()),
+    )
+}
+
+/// Writes the unit to `path` without enabling it; an existing one is only replaced with
+/// `force`.
+pub fn install(path: &Path, unit: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists, pass --force to overwrite it",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(path, unit).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("📝 Wrote {}", path.display());
+    println!("   Start it with: systemctl --user daemon-reload && systemctl --user enable --now voice-ptt");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_file() {
+        let unit = unit_file(
+            Path::new("/opt/voice ptt/voice-ptt"),
+            Some(Path::new("/home/me/100%.toml")),
+            Path::new("/home/me/.config/voice-ptt/service.env"),
+        );
+        assert!(unit.contains("Type=notify\n"));
+        assert!(
+            unit.contains("ExecStart=\"/opt/voice ptt/voice-ptt\" --config /home/me/100%%.toml\n")
+        );
+        assert!(unit.contains("EnvironmentFile=-/home/me/.config/voice-ptt/service.env\n"));
+        assert!(unit.contains("RestartPreventExitStatus=3\n"));
+
+        let unit = unit_file(
+            Path::new("/usr/bin/voice-ptt"),
+            None,
+            Path::new("/tmp/service.env"),
+        );
+        assert!(unit.contains("ExecStart=/usr/bin/voice-ptt\n"));
+    }
+
+    #[test]
+    fn test_install_keeps_existing_unit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("systemd/user/voice-ptt.service");
+        install(&path, "first", false).unwrap();
+        assert!(install(&path, "second", false).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        install(&path, "second", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_send_notification() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let systemd = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        send(&path, "READY=1\nSTATUS=Ready").unwrap();
+        let mut buffer = [0; 64];
+        let len = systemd.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"READY=1\nSTATUS=Ready");
+
+        assert!(send(&dir.path().join("missing.sock"), "READY=1").is_err());
+    }
+}
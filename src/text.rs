@@ -0,0 +1,858 @@
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// Phrases Whisper tends to return for silence, breathing or background noise.
+pub const DEFAULT_HALLUCINATION_FILTERS: [&str; 9] = [
+    "thank you",
+    "thanks for watching",
+    "thank you for watching",
+    "thank you so much for watching",
+    "please subscribe",
+    "you",
+    "/^subtitles by /",
+    "продолжение следует",
+    "/^редактор субтитров/",
+];
+
+/// One `hallucination_filters` entry: `/regex/`, or a phrase that must match the whole
+/// transcript, compared case-insensitively and ignoring surrounding punctuation.
+enum Filter {
+    Phrase(String),
+    Pattern(Regex),
+}
+
+impl Filter {
+    fn parse(entry: &str) -> Result<Self> {
+        match entry
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            Some(pattern) => RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map(Filter::Pattern)
+                .with_context(|| format!("Invalid hallucination filter {}", entry)),
+            None => Ok(Filter::Phrase(normalize(entry))),
+        }
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            Filter::Phrase(phrase) => normalize(text) == *phrase,
+            Filter::Pattern(regex) => regex.is_match(text),
+        }
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Checks that every regex in `filters` compiles.
+pub fn validate_filters(filters: &[String]) -> Result<()> {
+    for entry in filters {
+        Filter::parse(entry)?;
+    }
+    Ok(())
+}
+
+/// Returns why a transcript shouldn't be typed: it has no letters or digits, or it matches
+/// one of `filters`. Entries that don't parse are skipped, `validate_filters` reports them.
+pub fn rejection_reason(text: &str, filters: &[String]) -> Option<String> {
+    let text = text.trim();
+    if !text.chars().any(char::is_alphanumeric) {
+        return Some("empty transcript".to_string());
+    }
+    filters
+        .iter()
+        .find(|entry| Filter::parse(entry).is_ok_and(|filter| filter.matches(text)))
+        .map(|entry| format!("matches hallucination filter \"{}\"", entry))
+}
+
+/// Prefix of `replacements` keys that are regexes rather than words to replace.
+const REGEX_PREFIX: &str = "re:";
+
+/// One `replacements` entry: `re:<regex>` with `$1`-style references in the replacement, or
+/// a word or phrase matched case-insensitively as a whole word and replaced literally.
+struct Replacement {
+    pattern: Regex,
+    with: String,
+    expand: bool,
+}
+
+impl Replacement {
+    fn parse(key: &str, with: &str) -> Result<Self> {
+        let (pattern, expand) = match key.strip_prefix(REGEX_PREFIX) {
+            Some(pattern) => (pattern.to_string(), true),
+            None => {
+                // \b only where the phrase starts or ends with a word character, so "c++"
+                // still matches
+                let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+                let boundary = |c| if word(c) { r"\b" } else { "" };
+                let pattern = format!(
+                    "(?i){}{}{}",
+                    boundary(key.chars().next()),
+                    regex::escape(key),
+                    boundary(key.chars().last())
+                );
+                (pattern, false)
+            }
+        };
+        let pattern = Regex::new(&pattern)
+            .with_context(|| format!("Invalid replacements key \"{}\"", key))?;
+        Ok(Self {
+            pattern,
+            with: with.to_string(),
+            expand,
+        })
+    }
+}
+
+/// Checks that every `re:` key in `replacements` compiles.
+pub fn validate_replacements(replacements: &BTreeMap<String, String>) -> Result<()> {
+    for (key, with) in replacements {
+        Replacement::parse(key, with)?;
+    }
+    Ok(())
+}
+
+/// Applies `replacements` to `text` in one pass: at each point the earliest match wins, the
+/// longest among those starting together, and replaced text isn't matched again. Entries
+/// that don't parse are skipped, `validate_replacements` reports them.
+pub fn apply_replacements(text: &str, replacements: &BTreeMap<String, String>) -> String {
+    let rules: Vec<Replacement> = replacements
+        .iter()
+        .filter_map(|(key, with)| Replacement::parse(key, with).ok())
+        .collect();
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    while pos <= text.len() {
+        let next = rules
+            .iter()
+            .filter_map(|rule| Some((rule, rule.pattern.captures_at(text, pos)?)))
+            .min_by_key(|(_, captures)| {
+                let found = captures.get(0).unwrap();
+                (found.start(), Reverse(found.len()))
+            });
+        let Some((rule, captures)) = next else {
+            break;
+        };
+        let found = captures.get(0).unwrap();
+        out.push_str(&text[pos..found.start()]);
+        if rule.expand {
+            captures.expand(&rule.with, &mut out);
+        } else {
+            out.push_str(&rule.with);
+        }
+        pos = found.end();
+        // After an empty match, move on by a character so it isn't found again
+        if found.is_empty() {
+            match text[pos..].chars().next() {
+                Some(c) => {
+                    out.push(c);
+                    pos += c.len_utf8();
+                }
+                None => return out,
+            }
+        }
+    }
+    out.push_str(&text[pos.min(text.len())..]);
+    out
+}
+
+/// Spoken commands known in every language, and what they are typed as. A value starting
+/// with a space keeps the previous word apart and sticks to the next; one ending in a space
+/// sticks to the previous word.
+const SPOKEN_COMMANDS_EN: [(&str, &str); 17] = [
+    ("new line", "\n"),
+    ("newline", "\n"),
+    ("new paragraph", "\n\n"),
+    ("comma", ", "),
+    ("period", ". "),
+    ("full stop", ". "),
+    ("question mark", "? "),
+    ("exclamation mark", "! "),
+    ("exclamation point", "! "),
+    ("colon", ": "),
+    ("semicolon", "; "),
+    ("open quote", " \""),
+    ("close quote", "\" "),
+    ("end quote", "\" "),
+    ("open paren", " ("),
+    ("close paren", ") "),
+    ("dash", " - "),
+];
+
+/// Spoken commands added when `language` is set to one of these.
+const SPOKEN_COMMANDS_BY_LANGUAGE: [(&str, &[(&str, &str)]); 2] = [
+    (
+        "ru",
+        &[
+            ("новая строка", "\n"),
+            ("новый абзац", "\n\n"),
+            ("запятая", ", "),
+            ("точка", ". "),
+            ("вопросительный знак", "? "),
+            ("восклицательный знак", "! "),
+            ("двоеточие", ": "),
+            ("открыть кавычки", " \""),
+            ("закрыть кавычки", "\" "),
+        ],
+    ),
+    (
+        "de",
+        &[
+            ("neue zeile", "\n"),
+            ("neuer absatz", "\n\n"),
+            ("komma", ", "),
+            ("punkt", ". "),
+            ("fragezeichen", "? "),
+            ("ausrufezeichen", "! "),
+            ("doppelpunkt", ": "),
+        ],
+    ),
+];
+
+/// Punctuation Whisper adds itself, dropped where a spoken punctuation mark replaces it.
+const SENTENCE_PUNCTUATION: [char; 6] = [',', '.', ';', ':', '!', '?'];
+
+/// The spoken commands for `language`: the English ones, the language's own, then
+/// `spoken_command_phrases` from the config, where an empty value turns a phrase off.
+/// Longest phrases come first so "new paragraph" isn't read as "new" + something.
+fn spoken_commands(
+    language: Option<&str>,
+    custom: &BTreeMap<String, String>,
+) -> Vec<(Vec<String>, String)> {
+    let own = SPOKEN_COMMANDS_BY_LANGUAGE
+        .iter()
+        .filter(|(code, _)| Some(*code) == language)
+        .flat_map(|(_, commands)| commands.iter());
+    let mut phrases: BTreeMap<String, String> = SPOKEN_COMMANDS_EN
+        .iter()
+        .chain(own)
+        .map(|(phrase, typed)| (phrase.to_string(), typed.to_string()))
+        .collect();
+    for (phrase, typed) in custom {
+        phrases.insert(phrase.to_lowercase(), typed.clone());
+    }
+    let mut commands: Vec<(Vec<String>, String)> = phrases
+        .into_iter()
+        .filter(|(phrase, typed)| !phrase.trim().is_empty() && !typed.is_empty())
+        .map(|(phrase, typed)| {
+            (
+                phrase.split_whitespace().map(str::to_string).collect(),
+                typed,
+            )
+        })
+        .collect();
+    commands.sort_by_key(|(words, _)| Reverse(words.len()));
+    commands
+}
+
+/// A word without the punctuation Whisper put around it, lowercased for comparing.
+fn bare_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Uppercases the first letter of `word`.
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Turns spoken punctuation and formatting like "comma" or "new line" into the characters,
+/// for `spoken_commands`. Only whole words count, so "commander" stays; the word after a
+/// sentence end or line break is capitalized. Text without commands is returned unchanged.
+pub fn apply_spoken_commands(
+    text: &str,
+    language: Option<&str>,
+    custom: &BTreeMap<String, String>,
+) -> String {
+    let commands = spoken_commands(language, custom);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let bare: Vec<String> = words.iter().map(|word| bare_word(word)).collect();
+    let mut out = String::with_capacity(text.len());
+    let mut matched = false;
+    // Right after a command the next word follows without a space of its own
+    let mut attached = false;
+    let mut capitalize = false;
+    let mut i = 0;
+    while i < words.len() {
+        let command = commands.iter().find(|(phrase, _)| {
+            bare.get(i..i + phrase.len())
+                .is_some_and(|window| window == phrase.as_slice())
+        });
+        let Some((phrase, typed)) = command else {
+            if !out.is_empty() && !attached && !out.ends_with(char::is_whitespace) {
+                out.push(' ');
+            }
+            if capitalize {
+                out.push_str(&capitalized(words[i]));
+            } else {
+                out.push_str(words[i]);
+            }
+            attached = false;
+            capitalize = false;
+            i += 1;
+            continue;
+        };
+        matched = true;
+        if typed.trim_start().starts_with(SENTENCE_PUNCTUATION) {
+            let kept = out.trim_end_matches(SENTENCE_PUNCTUATION).len();
+            out.truncate(kept);
+        }
+        let typed = if out.is_empty() || out.ends_with(char::is_whitespace) {
+            typed.trim_start_matches(' ')
+        } else {
+            typed
+        };
+        out.push_str(typed);
+        attached = true;
+        let end = typed.trim_end_matches(' ');
+        capitalize = end.ends_with(['.', '!', '?', '\n']);
+        i += phrase.len();
+    }
+    if !matched {
+        return text.to_string();
+    }
+    out.trim_matches(' ').to_string()
+}
+
+/// Default `filler_words` per language.
+const DEFAULT_FILLER_WORDS: [(&str, &[&str]); 3] = [
+    (
+        "en",
+        &[
+            "um", "umm", "uh", "uhh", "er", "erm", "hmm", "mhm", "you know", "i mean",
+        ],
+    ),
+    (
+        "ru",
+        &["эм", "эмм", "ээ", "эээ", "мм", "ммм", "как бы", "типа"],
+    ),
+    ("de", &["äh", "ähm", "öhm", "hm"]),
+];
+
+/// The filler words stripped when `filler_words` is empty: English and `language`'s, or
+/// every language's when the language is detected.
+pub fn default_fillers(language: Option<&str>) -> Vec<String> {
+    DEFAULT_FILLER_WORDS
+        .iter()
+        .filter(|(code, _)| language.is_none_or(|language| *code == "en" || *code == language))
+        .flat_map(|(_, words)| words.iter().map(|word| word.to_string()))
+        .collect()
+}
+
+/// Removes standalone `fillers` from `text` with the commas Whisper put around them, so
+/// "so, um, yes" becomes "so, yes". Single words go wherever they stand; phrases like
+/// "you know" only between commas or at the start or end of a sentence, so "do you know"
+/// stays. A sentence end after a filler moves to the word before it.
+pub fn strip_fillers(text: &str, fillers: &[String]) -> String {
+    let fillers: Vec<Vec<String>> = fillers
+        .iter()
+        .map(|filler| filler.split_whitespace().map(bare_word).collect())
+        .filter(|words: &Vec<String>| !words.is_empty())
+        .collect();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let bare: Vec<String> = words.iter().map(|word| bare_word(word)).collect();
+    let set_off = |word: &str| word.ends_with(|c: char| c.is_ascii_punctuation());
+    let mut kept: Vec<String> = Vec::with_capacity(words.len());
+    let mut stripped = false;
+    let mut capitalize = false;
+    let mut i = 0;
+    while i < words.len() {
+        let filler = fillers.iter().find(|filler| {
+            let end = i + filler.len();
+            let matches = bare
+                .get(i..end)
+                .is_some_and(|window| window == filler.as_slice());
+            matches
+                && (filler.len() == 1
+                    || ((i == 0 || set_off(words[i - 1]))
+                        && (end == words.len() || set_off(words[end - 1]))))
+        });
+        let Some(filler) = filler else {
+            let word = words[i];
+            kept.push(if capitalize {
+                capitalized(word)
+            } else {
+                word.to_string()
+            });
+            capitalize = false;
+            i += 1;
+            continue;
+        };
+        stripped = true;
+        let last = words[i + filler.len() - 1];
+        let sentence_end: String = last
+            .chars()
+            .rev()
+            .take_while(|c| c.is_ascii_punctuation())
+            .filter(|c| ['.', '!', '?'].contains(c))
+            .collect();
+        match kept.last_mut() {
+            Some(previous) if !sentence_end.is_empty() => {
+                let trimmed = previous.trim_end_matches([',', ';', ':']).len();
+                previous.truncate(trimmed);
+                if !previous.ends_with(['.', '!', '?']) {
+                    previous.extend(sentence_end.chars().rev());
+                }
+            }
+            // A filler that started a sentence hands its capital letter on
+            None => capitalize = words[i].starts_with(char::is_uppercase),
+            Some(previous) => {
+                capitalize =
+                    previous.ends_with(['.', '!', '?']) && words[i].starts_with(char::is_uppercase);
+            }
+        }
+        i += filler.len();
+    }
+    if !stripped {
+        return text.to_string();
+    }
+    let mut out = kept.join(" ");
+    let trimmed = out.trim_end_matches([',', ';', ':']).len();
+    out.truncate(trimmed);
+    out
+}
+
+/// Abbreviations whose period doesn't end a sentence for `auto_capitalize`, lowercase.
+const ABBREVIATIONS: [&str; 19] = [
+    "e.g.",
+    "i.e.",
+    "vs.",
+    "cf.",
+    "approx.",
+    "mr.",
+    "mrs.",
+    "ms.",
+    "dr.",
+    "prof.",
+    "st.",
+    "т.е.",
+    "т.к.",
+    "т.н.",
+    "напр.",
+    "см.",
+    "z.b.",
+    "d.h.",
+    "bzw.",
+];
+
+/// What may follow a sentence end inside the same word, like the quote in `"Stop."`.
+const CLOSING_MARKS: [char; 8] = ['"', '\'', ')', ']', '}', '»', '”', '’'];
+
+/// Uppercases the first letter or digit of `word`, skipping a leading quote or bracket; a
+/// word starting with a digit stays as it is.
+fn capitalize_first_letter(word: &str) -> String {
+    match word.char_indices().find(|(_, c)| c.is_alphanumeric()) {
+        Some((i, c)) if c.is_lowercase() => {
+            format!(
+                "{}{}{}",
+                &word[..i],
+                c.to_uppercase(),
+                &word[i + c.len_utf8()..]
+            )
+        }
+        _ => word.to_string(),
+    }
+}
+
+/// Whether `word` ends a sentence: it ends with ".", "!" or "?" (before any closing quote or
+/// bracket) and isn't an abbreviation like "e.g.". An ellipsis trails off mid-sentence.
+fn ends_sentence(word: &str) -> bool {
+    let word = word.trim_end_matches(CLOSING_MARKS);
+    if word.ends_with("...") || word.ends_with('…') {
+        return false;
+    }
+    if word.ends_with(['!', '?']) {
+        return true;
+    }
+    let bare = word
+        .trim_start_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase();
+    word.ends_with('.') && !ABBREVIATIONS.contains(&bare.as_str())
+}
+
+/// Uppercases the first letter of `text` and of every sentence after ".", "!" or "?", for
+/// `auto_capitalize`. Works for any script with case, like Cyrillic or accented letters;
+/// abbreviations like "e.g." or "т.е." don't start a new sentence.
+pub fn capitalize_sentences(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut capitalize = true;
+    for word in text.split_inclusive(char::is_whitespace) {
+        if !word.contains(char::is_alphanumeric) {
+            out.push_str(word);
+            continue;
+        }
+        if capitalize {
+            out.push_str(&capitalize_first_letter(word));
+        } else {
+            out.push_str(word);
+        }
+        capitalize = ends_sentence(word.trim_end());
+    }
+    out
+}
+
+/// Appends `mark` to `text` when it ends with a letter or digit, for
+/// `ensure_final_punctuation`; text already ending with punctuation is left alone. The mark
+/// goes after a closing quote or bracket and before trailing whitespace.
+pub fn ensure_final_punctuation(text: &str, mark: &str) -> String {
+    let content = text.trim_end();
+    let ends_with_word = content
+        .trim_end_matches(CLOSING_MARKS)
+        .ends_with(char::is_alphanumeric);
+    if mark.is_empty() || !ends_with_word {
+        return text.to_string();
+    }
+    format!("{}{}{}", content, mark, &text[content.len()..])
+}
+
+/// Whether `next` needs a leading space to follow an injection that ended with
+/// `previous_end`: not before the first injection, after whitespace or an opening bracket,
+/// or when `next` starts with whitespace or closing punctuation.
+pub fn needs_space(previous_end: Option<char>, next: &str) -> bool {
+    let Some(previous_end) = previous_end else {
+        return false;
+    };
+    let Some(next_start) = next.chars().next() else {
+        return false;
+    };
+    !previous_end.is_whitespace()
+        && !"([{«\"'/-".contains(previous_end)
+        && !next_start.is_whitespace()
+        && !".,;:!?)]}»…".contains(next_start)
+}
+
+/// The first `max_chars` characters of `text`, with an ellipsis if it was cut.
+pub fn preview(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_HALLUCINATION_FILTERS
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_default_filters() {
+        let filters = defaults();
+        for junk in [
+            "Thank you.",
+            " Thanks for watching! ",
+            "THANK YOU SO MUCH FOR WATCHING.",
+            "you",
+            "Subtitles by the Amara.org community",
+            "Продолжение следует...",
+            "Редактор субтитров А.Семкин Корректор А.Егорова",
+        ] {
+            assert!(rejection_reason(junk, &filters).is_some(), "{}", junk);
+        }
+        for speech in [
+            "Thank you for the review, merging now.",
+            "Can you check the logs?",
+            "Привет, как дела?",
+        ] {
+            assert_eq!(rejection_reason(speech, &filters), None, "{}", speech);
+        }
+    }
+
+    #[test]
+    fn test_punctuation_only_is_empty() {
+        for empty in ["", "   ", ".", " ... ", "?!", "—"] {
+            assert_eq!(
+                rejection_reason(empty, &[]).as_deref(),
+                Some("empty transcript"),
+                "{:?}",
+                empty
+            );
+        }
+        assert_eq!(rejection_reason("42.", &[]), None);
+    }
+
+    #[test]
+    fn test_user_regex_filters() {
+        let filters = vec!["/^(um+|uh+)[.!]*$/".to_string(), "Okay.".to_string()];
+        assert!(rejection_reason("Ummm.", &filters)
+            .unwrap()
+            .contains("/^(um+|uh+)[.!]*$/"));
+        assert!(rejection_reason("okay", &filters).is_some());
+        assert_eq!(rejection_reason("Um, okay, let's start.", &filters), None);
+
+        assert!(validate_filters(&filters).is_ok());
+        let broken = vec!["/(unclosed/".to_string()];
+        let message = validate_filters(&broken).unwrap_err().to_string();
+        assert!(message.contains("/(unclosed/"), "{}", message);
+    }
+
+    fn replacements(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, with)| (key.to_string(), with.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_word_replacements() {
+        let map = replacements(&[("cube control", "kubectl"), ("smyth", "Smith")]);
+        assert_eq!(
+            apply_replacements("Run Cube Control get pods, Mr. SMYTH.", &map),
+            "Run kubectl get pods, Mr. Smith."
+        );
+        // Only whole words
+        assert_eq!(
+            apply_replacements("Smythe uses a rubiks cube controller", &map),
+            "Smythe uses a rubiks cube controller"
+        );
+        assert_eq!(
+            apply_replacements("Use c++ or C++17", &replacements(&[("c++", "C++")])),
+            "Use C++ or C++17"
+        );
+        // Replacements are literal
+        assert_eq!(
+            apply_replacements("costs five", &replacements(&[("five", "$5")])),
+            "costs $5"
+        );
+    }
+
+    #[test]
+    fn test_overlapping_replacements() {
+        let map = replacements(&[
+            ("cube", "Kube"),
+            ("cube control", "kubectl"),
+            ("control", "ctl"),
+            ("kubectl", "never applied to replaced text"),
+        ]);
+        // The longest match at a position wins, and output isn't matched again
+        assert_eq!(
+            apply_replacements("cube control and a cube, control", &map),
+            "kubectl and a Kube, ctl"
+        );
+    }
+
+    #[test]
+    fn test_unicode_replacements() {
+        let map = replacements(&[("кубер", "Kubernetes"), ("straße", "Strasse")]);
+        assert_eq!(
+            apply_replacements("Деплой в Кубер и кубернетес", &map),
+            "Деплой в Kubernetes и кубернетес"
+        );
+        assert_eq!(
+            apply_replacements("STRASSE, Straße", &map),
+            "STRASSE, Strasse"
+        );
+    }
+
+    #[test]
+    fn test_regex_replacements() {
+        let map = replacements(&[
+            (r"re:(\d+) percent", "$1%"),
+            (r"re:^note (\w+)", "NOTE(${1})"),
+        ]);
+        assert_eq!(
+            apply_replacements("note todo: 50 percent done", &map),
+            "NOTE(todo): 50% done"
+        );
+        // Empty matches don't loop forever
+        assert_eq!(
+            apply_replacements("ab", &replacements(&[("re:x*", "-")])),
+            "-a-b-"
+        );
+
+        assert!(validate_replacements(&map).is_ok());
+        let broken = replacements(&[("re:(unclosed", "x")]);
+        let message = validate_replacements(&broken).unwrap_err().to_string();
+        assert!(message.contains("re:(unclosed"), "{}", message);
+    }
+
+    #[test]
+    fn test_spoken_commands() {
+        let spoken = |text| apply_spoken_commands(text, None, &BTreeMap::new());
+        // Middle, with and without Whisper's own punctuation around the command
+        assert_eq!(
+            spoken("Hello comma how are you question mark"),
+            "Hello, how are you?"
+        );
+        assert_eq!(
+            spoken("Hello, comma, how are you? Question mark."),
+            "Hello, how are you?"
+        );
+        assert_eq!(
+            spoken("first line new line second new paragraph third"),
+            "first line\nSecond\n\nThird"
+        );
+        assert_eq!(
+            spoken("he said open quote hi close quote and left period"),
+            "he said \"hi\" and left."
+        );
+        // Beginning and end
+        assert_eq!(spoken("New line. Dear Anna comma"), "\nDear Anna,");
+        assert_eq!(spoken("done period next one"), "done. Next one");
+        assert_eq!(spoken("Signed new line"), "Signed\n");
+    }
+
+    #[test]
+    fn test_spoken_commands_need_whole_words() {
+        let spoken = |text| apply_spoken_commands(text, None, &BTreeMap::new());
+        for text in [
+            "The commander left periodically",
+            "A comma-separated list",
+            "Newlines in  a colonial   house",
+        ] {
+            assert_eq!(spoken(text), text);
+        }
+    }
+
+    #[test]
+    fn test_spoken_commands_per_language() {
+        let none = BTreeMap::new();
+        assert_eq!(
+            apply_spoken_commands("Привет запятая мир точка", Some("ru"), &none),
+            "Привет, мир."
+        );
+        // Another language's words only count when it is configured
+        assert_eq!(
+            apply_spoken_commands("Привет запятая мир", Some("de"), &none),
+            "Привет запятая мир"
+        );
+        assert_eq!(
+            apply_spoken_commands("Hallo Komma Welt", Some("de"), &none),
+            "Hallo, Welt"
+        );
+
+        let custom = replacements(&[("Smiley", " :-) "), ("dash", ""), ("tab key", "\t")]);
+        assert_eq!(
+            apply_spoken_commands("nice smiley a dash b tab key c", None, &custom),
+            "nice :-) a dash b\tc"
+        );
+    }
+
+    fn fillers() -> Vec<String> {
+        default_fillers(Some("en"))
+    }
+
+    #[test]
+    fn test_strip_fillers() {
+        let strip = |text| strip_fillers(text, &fillers());
+        assert_eq!(strip("so, um, yes"), "so, yes");
+        assert_eq!(strip("Um, I think uh we should go"), "I think we should go");
+        assert_eq!(strip("We should go, um."), "We should go.");
+        assert_eq!(strip("Fine. Uh, let's start"), "Fine. Let's start");
+        assert_eq!(strip("it works, you know, mostly"), "it works, mostly");
+        assert_eq!(strip("Uhh… right"), "Right");
+        assert_eq!(strip("Um."), "");
+        // Nothing to strip keeps the text as it was
+        assert_eq!(strip("Hello  world"), "Hello  world");
+    }
+
+    #[test]
+    fn test_fillers_only_as_standalone_words() {
+        let strip = |text| strip_fillers(text, &fillers());
+        for text in [
+            "Do you know the umbrella man?",
+            "I mean it when I say the herb is hmmable",
+            "Uhura said erase it",
+        ] {
+            assert_eq!(strip(text), text);
+        }
+        // "you know" mid-sentence is left alone
+        assert_eq!(strip("Um, do you know him?"), "Do you know him?");
+    }
+
+    #[test]
+    fn test_default_fillers() {
+        assert!(default_fillers(Some("en")).contains(&"um".to_string()));
+        assert!(!default_fillers(Some("en")).contains(&"эм".to_string()));
+        let russian = default_fillers(Some("ru"));
+        assert!(russian.contains(&"эм".to_string()) && russian.contains(&"um".to_string()));
+        assert!(default_fillers(None).contains(&"äh".to_string()));
+        assert_eq!(
+            strip_fillers("Ну, эм, давай начнём", &russian),
+            "Ну, давай начнём"
+        );
+    }
+
+    #[test]
+    fn test_capitalize_sentences() {
+        for (text, expected) in [
+            ("hello world", "Hello world"),
+            ("done. next one? yes! ok", "Done. Next one? Yes! Ok"),
+            ("привет. как дела", "Привет. Как дела"),
+            ("élan vital. über alles", "Élan vital. Über alles"),
+            ("\"quoted.\" then more", "\"Quoted.\" Then more"),
+            (
+                "see (e.g. this) and i.e. that",
+                "See (e.g. this) and i.e. that",
+            ),
+            ("ask Dr. smith. then go", "Ask Dr. smith. Then go"),
+            ("то есть т.е. так", "То есть т.е. так"),
+            ("wait... what", "Wait... what"),
+            ("first\n\nsecond. third", "First\n\nsecond. Third"),
+            ("version 2.0 is out. 3 more", "Version 2.0 is out. 3 more"),
+            ("  padded  text", "  Padded  text"),
+            ("", ""),
+        ] {
+            assert_eq!(capitalize_sentences(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_ensure_final_punctuation() {
+        for (text, mark, expected) in [
+            ("Hello world", ".", "Hello world."),
+            ("Hello world.", ".", "Hello world."),
+            ("Really?", ".", "Really?"),
+            ("Wait…", ".", "Wait…"),
+            ("So,", ".", "So,"),
+            ("He said \"stop\"", ".", "He said \"stop\"."),
+            ("Line\n", ".", "Line.\n"),
+            ("Привет", ".", "Привет."),
+            ("Hello world", "", "Hello world"),
+            ("", ".", ""),
+        ] {
+            assert_eq!(ensure_final_punctuation(text, mark), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("Short note.", 80), "Short note.");
+        assert_eq!(preview("Привет, как дела", 7), "Привет,…");
+        assert_eq!(preview("one two three", 4), "one…");
+    }
+
+    #[test]
+    fn test_needs_space() {
+        // Nothing typed yet this session
+        assert!(!needs_space(None, "Hello."));
+        // Sentence-final punctuation and plain words both need a separator
+        assert!(needs_space(Some('.'), "Next sentence."));
+        assert!(needs_space(Some('?'), "Yes."));
+        assert!(needs_space(Some('d'), "and more"));
+        // Existing whitespace on either side
+        assert!(!needs_space(Some(' '), "Next."));
+        assert!(!needs_space(Some('\n'), "Next."));
+        assert!(!needs_space(Some('.'), " Next."));
+        // Punctuation that attaches to the previous word, or text after an opening bracket
+        assert!(!needs_space(Some('d'), ", and then"));
+        assert!(!needs_space(Some('('), "aside)"));
+        assert!(!needs_space(Some('.'), ""));
+    }
+}
@@ -0,0 +1,454 @@
+//! Tray icon over the StatusNotifierItem D-Bus protocol, with its menu over
+//! `com.canonical.dbusmenu`, as KDE, waybar's tray, GNOME's AppIndicator extension and most
+//! other panels expect.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedValue, Structure};
+
+use crate::control::{self, Request, Requests, State, Status};
+use crate::injector::SystemInjector;
+use crate::pipeline::Services;
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+
+/// Starts the tray icon on its own thread. Without a session bus or a panel that shows
+/// StatusNotifier items, it logs why and the app runs on without it.
+pub fn spawn(services: Services, requests: Requests, config_path: Option<PathBuf>) {
+    let runtime = tokio::runtime::Handle::current();
+    let spawned = std::thread::Builder::new()
+        .name("tray".to_string())
+        .spawn(move || {
+            if let Err(e) = runtime.block_on(run(services, requests, config_path)) {
+                info!("ℹ️ No tray icon: {:#}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("⚠️ Failed to start the tray icon: {}", e);
+    }
+}
+
+async fn run(services: Services, requests: Requests, config_path: Option<PathBuf>) -> Result<()> {
+    let mut statuses = services.status.subscribe();
+    let item = Item {
+        status: statuses.borrow_and_update().clone(),
+    };
+    let menu = Menu {
+        services,
+        requests,
+        config_path,
+        revision: 1,
+    };
+    let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+    let connection = zbus::connection::Builder::session()?
+        .name(name.as_str())?
+        .serve_at(ITEM_PATH, item)?
+        .serve_at(MENU_PATH, menu)?
+        .build()
+        .await
+        .context("Failed to connect to the session bus")?;
+    connection
+        .call_method(
+            Some("org.kde.StatusNotifierWatcher"),
+            "/StatusNotifierWatcher",
+            Some("org.kde.StatusNotifierWatcher"),
+            "RegisterStatusNotifierItem",
+            &name,
+        )
+        .await
+        .context("No system tray (StatusNotifierWatcher) on the session bus")?;
+
+    let item = connection
+        .object_server()
+        .interface::<_, Item>(ITEM_PATH)
+        .await?;
+    let menu = connection
+        .object_server()
+        .interface::<_, Menu>(MENU_PATH)
+        .await?;
+    // Lives as long as the app, following its status
+    while statuses.changed().await.is_ok() {
+        let status = statuses.borrow_and_update().clone();
+        let needs_attention = status.state == State::Recording;
+        item.get_mut().await.status = status;
+        Item::new_icon(item.signal_emitter()).await?;
+        Item::new_tool_tip(item.signal_emitter()).await?;
+        Item::new_status(item.signal_emitter(), item_status(needs_attention)).await?;
+        let revision = menu.get_mut().await.bump();
+        Menu::layout_updated(menu.signal_emitter(), revision, 0).await?;
+    }
+    Ok(())
+}
+
+/// Freedesktop icon name for a status, by its [`control::bar_class`].
+fn icon_name(status: &Status) -> &'static str {
+    match control::bar_class(Some(status)) {
+        "recording" => "media-record",
+        "processing" => "view-refresh",
+        "paused" => "media-playback-pause",
+        "error" => "dialog-error",
+        _ => "audio-input-microphone",
+    }
+}
+
+fn item_status(needs_attention: bool) -> &'static str {
+    match needs_attention {
+        true => "NeedsAttention",
+        false => "Active",
+    }
+}
+
+/// Icon name, pixmaps (width, height, ARGB data), title and text.
+type ToolTip = (String, Vec<(i32, i32, Vec<u8>)>, String, String);
+
+/// The icon itself.
+struct Item {
+    status: Status,
+}
+
+#[zbus::interface(name = "org.kde.StatusNotifierItem")]
+impl Item {
+    #[zbus(property)]
+    fn category(&self) -> String {
+        "ApplicationStatus".to_string()
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        "voice-ptt".to_string()
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        "Voice PTT".to_string()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        item_status(self.status.state == State::Recording).to_string()
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        icon_name(&self.status).to_string()
+    }
+
+    #[zbus(property)]
+    fn tool_tip(&self) -> ToolTip {
+        let mut text = control::bar_class(Some(&self.status)).to_string();
+        if let Some(profile) = &self.status.profile {
+            text.push_str(&format!(", profile {}", profile));
+        }
+        (
+            icon_name(&self.status).to_string(),
+            Vec::new(),
+            "Voice PTT".to_string(),
+            text,
+        )
+    }
+
+    /// Clicks open the menu rather than calling `Activate`.
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> ObjectPath<'static> {
+        ObjectPath::from_static_str_unchecked(MENU_PATH)
+    }
+
+    fn activate(&self, _x: i32, _y: i32) {}
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {}
+
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    fn scroll(&self, _delta: i32, _orientation: String) {}
+
+    #[zbus(signal)]
+    async fn new_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_tool_tip(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_status(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Pause,
+    Sounds,
+    OpenConfig,
+    ShowLast,
+    Quit,
+}
+
+/// The menu entries in order, `None` being a separator. An entry's id is its index plus one,
+/// 0 being the root.
+const MENU: [Option<Action>; 7] = [
+    Some(Action::Pause),
+    Some(Action::Sounds),
+    None,
+    Some(Action::OpenConfig),
+    Some(Action::ShowLast),
+    None,
+    Some(Action::Quit),
+];
+
+/// What the menu entries show.
+struct MenuView {
+    paused: bool,
+    sounds: bool,
+    has_config: bool,
+}
+
+/// The dbusmenu properties of an entry.
+fn entry_properties(entry: Option<Action>, view: &MenuView) -> HashMap<String, OwnedValue> {
+    let mut properties = HashMap::new();
+    let mut set = |key: &str, value: OwnedValue| {
+        properties.insert(key.to_string(), value);
+    };
+    let Some(action) = entry else {
+        set("type", zbus::zvariant::Str::from("separator").into());
+        return properties;
+    };
+    let label = match action {
+        Action::Pause => "Paused",
+        Action::Sounds => "Sounds",
+        Action::OpenConfig => "Open config",
+        Action::ShowLast => "Show last transcription",
+        Action::Quit => "Quit",
+    };
+    set("label", zbus::zvariant::Str::from(label).into());
+    let toggled = match action {
+        Action::Pause => Some(view.paused),
+        Action::Sounds => Some(view.sounds),
+        _ => None,
+    };
+    if let Some(toggled) = toggled {
+        set("toggle-type", zbus::zvariant::Str::from("checkmark").into());
+        set("toggle-state", i32::from(toggled).into());
+    }
+    if action == Action::OpenConfig && !view.has_config {
+        set("enabled", false.into());
+    }
+    properties
+}
+
+/// The menu, whose entries act through the same paths as `voice-ptt ctl`.
+struct Menu {
+    services: Services,
+    requests: Requests,
+    config_path: Option<PathBuf>,
+    revision: u32,
+}
+
+impl Menu {
+    fn view(&self) -> MenuView {
+        MenuView {
+            paused: self.services.status.current().paused,
+            sounds: self.services.sounds.is_enabled(),
+            has_config: self.config_path.is_some(),
+        }
+    }
+
+    fn bump(&mut self) -> u32 {
+        self.revision += 1;
+        self.revision
+    }
+
+    /// Hands `request` to the main loop and logs a refusal.
+    async fn send(&self, request: Request) {
+        let (respond, response) = oneshot::channel();
+        if self.requests.send((request, respond)).is_err() {
+            return;
+        }
+        if let Ok(response) = response.await {
+            if let Some(error) = response.error {
+                warn!("⚠️ {}", error);
+            }
+        }
+    }
+
+    async fn act(&mut self, action: Action) -> Option<u32> {
+        match action {
+            Action::Pause => match self.services.status.current().paused {
+                true => self.send(Request::Resume).await,
+                false => self.send(Request::Pause).await,
+            },
+            Action::Sounds => {
+                let enabled = !self.services.sounds.is_enabled();
+                self.services.sounds.set_enabled(enabled);
+                info!("🔔 Sounds {}", if enabled { "on" } else { "off" });
+                // The status doesn't change, so the menu has to say so itself
+                return Some(self.bump());
+            }
+            Action::OpenConfig => {
+                if let Some(path) = &self.config_path {
+                    if let Err(e) = std::process::Command::new("xdg-open").arg(path).spawn() {
+                        warn!("⚠️ Failed to open {}: {}", path.display(), e);
+                    }
+                }
+            }
+            Action::ShowLast => match self.services.last_transcript() {
+                Some(text) => SystemInjector::notify("Voice PTT: last transcription", &text),
+                None => SystemInjector::notify("Voice PTT", "Nothing transcribed yet"),
+            },
+            Action::Quit => self.send(Request::Quit).await,
+        }
+        None
+    }
+}
+
+type Layout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+#[zbus::interface(name = "com.canonical.dbusmenu")]
+impl Menu {
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> String {
+        "ltr".to_string()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        "normal".to_string()
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The whole menu; it is small enough to ignore the depth and property filters.
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> zbus::fdo::Result<(u32, Layout)> {
+        let view = self.view();
+        let mut children = Vec::new();
+        if parent_id == 0 {
+            for (index, entry) in MENU.into_iter().enumerate() {
+                let child: Layout = (index as i32 + 1, entry_properties(entry, &view), Vec::new());
+                let child = OwnedValue::try_from(Structure::from(child))
+                    .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+                children.push(child);
+            }
+        }
+        let mut properties = HashMap::new();
+        if parent_id == 0 {
+            properties.insert(
+                "children-display".to_string(),
+                zbus::zvariant::Str::from("submenu").into(),
+            );
+        } else if let Some(entry) = Self::entry(parent_id) {
+            properties = entry_properties(entry, &view);
+        }
+        Ok((self.revision, (parent_id, properties, children)))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        let view = self.view();
+        ids.into_iter()
+            .filter_map(|id| Some((id, entry_properties(Self::entry(id)?, &view))))
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: String) -> zbus::fdo::Result<OwnedValue> {
+        let entry = Self::entry(id)
+            .ok_or_else(|| zbus::fdo::Error::InvalidArgs(format!("No menu entry {}", id)))?;
+        entry_properties(entry, &self.view())
+            .remove(&name)
+            .ok_or_else(|| zbus::fdo::Error::InvalidArgs(format!("No property {}", name)))
+    }
+
+    async fn event(
+        &mut self,
+        id: i32,
+        event_id: String,
+        _data: OwnedValue,
+        _timestamp: u32,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) {
+        let Some(Some(action)) = Self::entry(id) else {
+            return;
+        };
+        if event_id != "clicked" {
+            return;
+        }
+        if let Some(revision) = self.act(action).await {
+            let _ = Self::layout_updated(&emitter, revision, 0).await;
+        }
+    }
+
+    fn event_group(&self, _events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        Vec::new()
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        (Vec::new(), Vec::new())
+    }
+
+    #[zbus(signal)]
+    async fn layout_updated(
+        emitter: &SignalEmitter<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+impl Menu {
+    /// The entry with `id`, `Some(None)` being a separator.
+    fn entry(id: i32) -> Option<Option<Action>> {
+        let index = usize::try_from(id).ok()?.checked_sub(1)?;
+        MENU.get(index).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_menu_entries() {
+        let view = MenuView {
+            paused: true,
+            sounds: false,
+            has_config: false,
+        };
+        let paused = entry_properties(Some(Action::Pause), &view);
+        assert_eq!(paused["toggle-state"], OwnedValue::from(1i32));
+        let sounds = entry_properties(Some(Action::Sounds), &view);
+        assert_eq!(sounds["toggle-state"], OwnedValue::from(0i32));
+        // Nothing to open without a config file
+        let open = entry_properties(Some(Action::OpenConfig), &view);
+        assert_eq!(open["enabled"], OwnedValue::from(false));
+
+        assert_eq!(Menu::entry(1), Some(Some(Action::Pause)));
+        assert_eq!(Menu::entry(3), Some(None));
+        assert_eq!(Menu::entry(7), Some(Some(Action::Quit)));
+        assert_eq!(Menu::entry(0), None);
+        assert_eq!(Menu::entry(8), None);
+    }
+}
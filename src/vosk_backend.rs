@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+use vosk::{Model, Recognizer};
+
+use crate::api::TranscriptionBackend;
+use crate::config::{AppConfig, TranscriptionMode, UploadFormat};
+use crate::encode::{self, EncodedAudio};
+
+/// Sample rate the recording is converted to before recognition.
+const VOSK_SAMPLE_RATE: u32 = 16000;
+
+/// Offline transcription with a Vosk model, light enough for real time on old laptops.
+pub struct VoskBackend {
+    model: Arc<Model>,
+}
+
+impl VoskBackend {
+    /// Loads the model from `config.vosk_model_path`. This takes a few seconds, so it is
+    /// done once at startup.
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        let path = config.vosk_model_path.as_ref().context(
+            "backend = \"vosk\" requires vosk_model_path to point at a Vosk model directory",
+        )?;
+        check_model_dir(path)?;
+        let path_str = path
+            .to_str()
+            .context("vosk_model_path must be valid UTF-8")?;
+
+        let started = Instant::now();
+        let model = Model::new(path_str)
+            .with_context(|| format!("Vosk failed to load the model at {}", path.display()))?;
+        info!(
+            "🧠 Loaded Vosk model in {:.1}s",
+            started.elapsed().as_secs_f32()
+        );
+        Ok(Self {
+            model: Arc::new(model),
+        })
+    }
+}
+
+/// Fails with a hint when `path` isn't an unpacked Vosk model directory.
+fn check_model_dir(path: &Path) -> Result<()> {
+    if !path.is_dir() {
+        anyhow::bail!(
+            "Vosk model directory not found at {}. Download and unpack a model from https://alphacephei.com/vosk/models",
+            path.display()
+        );
+    }
+    for required in ["am/final.mdl", "conf/model.conf"] {
+        if !path.join(required).is_file() {
+            anyhow::bail!(
+                "{} doesn't look like a Vosk model (missing {}). Point vosk_model_path at the unpacked model's top-level folder, e.g. vosk-model-small-en-us-0.15",
+                path.display(),
+                required
+            );
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl TranscriptionBackend for VoskBackend {
+    /// The recognizer is fed raw PCM, so skip compressed upload formats.
+    fn upload_format(&self, _configured: UploadFormat) -> UploadFormat {
+        UploadFormat::Wav
+    }
+
+    async fn transcribe_audio(
+        &self,
+        audio: EncodedAudio,
+        mode: TranscriptionMode,
+        _config: &AppConfig,
+    ) -> Result<String> {
+        if mode == TranscriptionMode::Translate {
+            anyhow::bail!("The Vosk backend does not support translate mode");
+        }
+        let model = self.model.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut reader = hound::WavReader::new(Cursor::new(audio.bytes))
+                .context("Failed to read recorded WAV")?;
+            let spec = reader.spec();
+            let samples = reader
+                .samples::<i16>()
+                .collect::<Result<Vec<_>, _>>()
+                .context("Failed to read recorded samples")?;
+            let mono = encode::downmix_to_mono(&samples, spec.channels);
+            let input = encode::resample_linear(&mono, spec.sample_rate, VOSK_SAMPLE_RATE);
+
+            let mut recognizer = Recognizer::new(&model, VOSK_SAMPLE_RATE as f32)
+                .context("Failed to create Vosk recognizer")?;
+            recognizer
+                .accept_waveform(&input)
+                .map_err(|e| anyhow::anyhow!("Vosk recognition failed: {:?}", e))?;
+            let text = recognizer
+                .final_result()
+                .single()
+                .map(|result| result.text.trim().to_string())
+                .unwrap_or_default();
+            Ok(text)
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_model_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = check_model_dir(dir.path()).unwrap_err();
+        assert!(error.to_string().contains("missing am/final.mdl"));
+
+        std::fs::create_dir_all(dir.path().join("am")).unwrap();
+        std::fs::create_dir_all(dir.path().join("conf")).unwrap();
+        std::fs::write(dir.path().join("am/final.mdl"), b"").unwrap();
+        std::fs::write(dir.path().join("conf/model.conf"), b"").unwrap();
+        assert!(check_model_dir(dir.path()).is_ok());
+
+        assert!(check_model_dir(&dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ringbuf::traits::Producer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use voice_ptt::api::TranscriptionBackend;
+use voice_ptt::capture::CaptureMode;
+use voice_ptt::config::{AppConfig, TranscriptionMode, UploadFormat};
+use voice_ptt::encode::EncodedAudio;
+use voice_ptt::inject_queue::Injector;
+use voice_ptt::pipeline::{self, Services};
+use voice_ptt::recorder::Recorder;
+
+#[derive(Default)]
+struct FakeBackend {
+    uploads: Mutex<Vec<EncodedAudio>>,
+    /// How many of the next uploads fail
+    failures: Mutex<usize>,
+}
+
+#[async_trait]
+impl TranscriptionBackend for FakeBackend {
+    async fn transcribe_audio(
+        &self,
+        audio: EncodedAudio,
+        _mode: TranscriptionMode,
+        _config: &AppConfig,
+    ) -> Result<String> {
+        self.uploads.lock().unwrap().push(audio);
+        let mut failures = self.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            anyhow::bail!("server error");
+        }
+        Ok("hello world".to_string())
+    }
+}
+
+#[derive(Default)]
+struct FakeInjector {
+    injected: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl Injector for FakeInjector {
+    async fn inject(&self, text: &str, _config: &AppConfig) -> Result<()> {
+        self.injected.lock().unwrap().push(text.to_string());
+        Ok(())
+    }
+}
+
+const SPEC: hound::WavSpec = hound::WavSpec {
+    channels: 1,
+    sample_rate: 16000,
+    bits_per_sample: 16,
+    sample_format: hound::SampleFormat::Int,
+};
+
+/// Half a second of a quiet 400 Hz tone, so the recording isn't taken for a muted mic.
+fn speech() -> Vec<i16> {
+    (0..8000)
+        .map(|i| ((i as f32 * 400.0 / 16000.0 * std::f32::consts::TAU).sin() * 3000.0) as i16)
+        .collect()
+}
+
+/// No sounds or meter, and no double-tap lock holding a quick press open.
+fn quiet_config() -> AppConfig {
+    AppConfig {
+        double_tap_ms: 0,
+        sound_enabled: false,
+        meter_enabled: false,
+        offline_queue_enabled: false,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_press_and_release_types_the_transcript() {
+    let config = quiet_config();
+    let (capture, mut microphone) = CaptureMode::fed(SPEC);
+    let backend = Arc::new(FakeBackend::default());
+    let injector = Arc::new(FakeInjector::default());
+    let services = Services::new(backend.clone(), injector.clone(), &config);
+    let mut recorder = Recorder::new(&config, capture, services, None);
+    let bindings = config.ptt_bindings();
+    let ptt_key = config.get_ptt_keycodes();
+
+    recorder.update(&bindings, &[], "ready");
+    assert!(recorder.is_idle());
+    recorder.update(&bindings, &ptt_key, "ready");
+    assert!(!recorder.is_idle());
+    assert!(recorder.is_recording());
+    assert_eq!(microphone.push_slice(&speech()), 8000);
+    recorder.update(&bindings, &ptt_key, "ready");
+    recorder.update(&bindings, &[], "ready");
+    recorder.finish().await;
+
+    assert!(recorder.is_idle());
+    assert_eq!(*injector.injected.lock().unwrap(), ["hello world"]);
+    let uploads = backend.uploads.lock().unwrap();
+    assert_eq!(uploads.len(), 1);
+    assert_eq!(uploads[0].format, UploadFormat::Wav);
+    assert_eq!(uploads[0].duration.as_millis(), 500);
+}
+
+#[tokio::test]
+async fn test_cancel_sends_nothing() {
+    let config = quiet_config();
+    let (capture, mut microphone) = CaptureMode::fed(SPEC);
+    let backend = Arc::new(FakeBackend::default());
+    let injector = Arc::new(FakeInjector::default());
+    let services = Services::new(backend.clone(), injector.clone(), &config);
+    let mut recorder = Recorder::new(&config, capture, services, None);
+    let bindings = config.ptt_bindings();
+    let ptt_key = config.get_ptt_keycodes();
+
+    assert!(!recorder.cancel("ready"));
+    recorder.update(&bindings, &ptt_key, "ready");
+    assert_eq!(microphone.push_slice(&speech()), 8000);
+    recorder.update(&bindings, &ptt_key, "ready");
+    assert!(recorder.cancel("ready"));
+    assert!(!recorder.is_recording());
+    // Still holding the PTT key doesn't start over
+    recorder.update(&bindings, &ptt_key, "ready");
+    assert!(!recorder.is_recording());
+    recorder.update(&bindings, &[], "ready");
+    recorder.finish().await;
+
+    assert!(recorder.is_idle());
+    assert!(backend.uploads.lock().unwrap().is_empty());
+    assert!(injector.injected.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_press_below_hold_threshold_sends_nothing() {
+    let config = AppConfig {
+        hold_threshold_ms: 60_000,
+        ..quiet_config()
+    };
+    let (capture, mut microphone) = CaptureMode::fed(SPEC);
+    let backend = Arc::new(FakeBackend::default());
+    let injector = Arc::new(FakeInjector::default());
+    let services = Services::new(backend.clone(), injector.clone(), &config);
+    let mut recorder = Recorder::new(&config, capture, services, None);
+    let bindings = config.ptt_bindings();
+    let ptt_key = config.get_ptt_keycodes();
+
+    recorder.update(&bindings, &ptt_key, "ready");
+    assert!(!recorder.is_recording());
+    microphone.push_slice(&speech());
+    recorder.update(&bindings, &ptt_key, "ready");
+    recorder.update(&bindings, &[], "ready");
+    recorder.finish().await;
+
+    assert!(recorder.is_idle());
+    assert!(injector.injected.lock().unwrap().is_empty());
+    assert!(backend.uploads.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_shutdown_discards_the_recording_in_progress() {
+    let config = quiet_config();
+    let (capture, mut microphone) = CaptureMode::fed(SPEC);
+    let backend = Arc::new(FakeBackend::default());
+    let injector = Arc::new(FakeInjector::default());
+    let services = Services::new(backend.clone(), injector.clone(), &config);
+    let mut recorder = Recorder::new(&config, capture, services, None);
+    let bindings = config.ptt_bindings();
+    let ptt_key = config.get_ptt_keycodes();
+
+    recorder.update(&bindings, &ptt_key, "ready");
+    microphone.push_slice(&speech());
+    recorder.update(&bindings, &ptt_key, "ready");
+    recorder.shutdown(Duration::from_secs(1)).await;
+
+    assert!(!recorder.is_recording());
+    assert!(backend.uploads.lock().unwrap().is_empty());
+    assert!(injector.injected.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_repeat_types_the_last_transcript_again() {
+    let config = quiet_config();
+    let (capture, mut microphone) = CaptureMode::fed(SPEC);
+    let backend = Arc::new(FakeBackend::default());
+    let injector = Arc::new(FakeInjector::default());
+    let services = Services::new(backend.clone(), injector.clone(), &config);
+    let mut recorder = Recorder::new(&config, capture, services.clone(), None);
+    let bindings = config.ptt_bindings();
+    let ptt_key = config.get_ptt_keycodes();
+
+    recorder.update(&bindings, &ptt_key, "ready");
+    microphone.push_slice(&speech());
+    recorder.update(&bindings, &ptt_key, "ready");
+    recorder.update(&bindings, &[], "ready");
+    recorder.finish().await;
+    pipeline::spawn_repeat(&services, config.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *injector.injected.lock().unwrap(),
+        ["hello world", "hello world"]
+    );
+    assert_eq!(backend.uploads.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_retry_sends_the_failed_recording_again() {
+    let config = quiet_config();
+    let (capture, mut microphone) = CaptureMode::fed(SPEC);
+    let backend = Arc::new(FakeBackend {
+        failures: Mutex::new(2),
+        ..Default::default()
+    });
+    let injector = Arc::new(FakeInjector::default());
+    let services = Services::new(backend.clone(), injector.clone(), &config);
+    let mut recorder = Recorder::new(&config, capture, services, None);
+    let bindings = config.ptt_bindings();
+    let ptt_key = config.get_ptt_keycodes();
+
+    assert!(!recorder.retry("ready"));
+    recorder.update(&bindings, &ptt_key, "ready");
+    microphone.push_slice(&speech());
+    recorder.update(&bindings, &ptt_key, "ready");
+    recorder.update(&bindings, &[], "ready");
+    recorder.finish().await;
+    assert!(injector.injected.lock().unwrap().is_empty());
+
+    // A failed retry keeps it for the next one, a successful one forgets it
+    assert!(recorder.retry("ready"));
+    recorder.finish().await;
+    assert!(injector.injected.lock().unwrap().is_empty());
+    assert!(recorder.retry("ready"));
+    recorder.finish().await;
+    assert!(!recorder.retry("ready"));
+
+    assert_eq!(*injector.injected.lock().unwrap(), ["hello world"]);
+    let uploads = backend.uploads.lock().unwrap();
+    assert_eq!(uploads.len(), 3);
+    assert!(uploads
+        .iter()
+        .all(|upload| upload.bytes == uploads[0].bytes));
+}
+
+#[tokio::test]
+async fn test_dead_stream_drops_the_recording_until_reconnected() {
+    let config = quiet_config();
+    let (capture, mut microphone) = CaptureMode::fed(SPEC);
+    let CaptureMode::Cpal { stream_dead, .. } = &capture else {
+        unreachable!()
+    };
+    let stream_dead = stream_dead.clone();
+    let backend = Arc::new(FakeBackend::default());
+    let injector = Arc::new(FakeInjector::default());
+    let services = Services::new(backend.clone(), injector.clone(), &config);
+    let mut recorder = Recorder::new(&config, capture, services, None);
+    let bindings = config.ptt_bindings();
+    let ptt_key = config.get_ptt_keycodes();
+
+    recorder.update(&bindings, &ptt_key, "ready");
+    microphone.push_slice(&speech());
+    recorder.update(&bindings, &ptt_key, "ready");
+    stream_dead.store(true, std::sync::atomic::Ordering::Relaxed);
+    recorder.check_capture();
+    assert!(!recorder.is_recording());
+    // Presses do nothing while the device is being reopened
+    recorder.update(&bindings, &[], "ready");
+    recorder.update(&bindings, &ptt_key, "ready");
+    assert!(!recorder.is_recording());
+    recorder.update(&bindings, &[], "ready");
+    recorder.finish().await;
+
+    assert!(backend.uploads.lock().unwrap().is_empty());
+    assert!(injector.injected.lock().unwrap().is_empty());
+}